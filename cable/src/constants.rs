@@ -6,6 +6,34 @@ pub const INFO_POST: u64 = 2;
 pub const TOPIC_POST: u64 = 3;
 pub const JOIN_POST: u64 = 4;
 pub const LEAVE_POST: u64 = 5;
+pub const ROLE_POST: u64 = 6;
+pub const MODERATION_POST: u64 = 7;
+pub const BLOCK_POST: u64 = 8;
+pub const UNBLOCK_POST: u64 = 9;
+
+/* ROLE FIELD VALUES */
+
+pub const USER_ROLE: u64 = 0;
+pub const ADMIN_ROLE: u64 = 1;
+pub const MODERATOR_ROLE: u64 = 2;
+
+/* MODERATION ACTION FIELD VALUES */
+
+pub const HIDE_USER: u64 = 0;
+pub const UNHIDE_USER: u64 = 1;
+pub const HIDE_POST: u64 = 2;
+pub const UNHIDE_POST: u64 = 3;
+pub const DROP_POST: u64 = 4;
+pub const UNDROP_POST: u64 = 5;
+pub const DROP_CHANNEL: u64 = 6;
+pub const UNDROP_CHANNEL: u64 = 7;
+
+/* INFO KEY VALUES */
+
+/// The `post/info` key of a user's display name.
+pub const INFO_NAME_KEY: &str = "name";
+/// The `post/info` key indicating whether a user accepts assigned roles.
+pub const INFO_ACCEPT_ROLE_KEY: &str = "accept-role";
 
 /* RESPONSE FIELD VALUES */
 
@@ -24,3 +52,6 @@ pub const CHANNEL_LIST_REQUEST: u64 = 6;
 /* MISC FIELD VALUES */
 
 pub const NO_CIRCUIT: [u8; 4] = [0, 0, 0, 0];
+
+/// The maximum number of times a request may be forwarded.
+pub const MAX_TTL: u8 = 16;
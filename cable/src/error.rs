@@ -30,7 +30,11 @@ pub enum CableErrorKind {
     ChannelLengthIncorrect { channel: String, len: usize },
     TextLengthIncorrect { text: String, len: usize },
     TopicLengthIncorrect { topic: String, len: usize },
+    ReasonLengthIncorrect { reason: String, len: usize },
     UsernameLengthIncorrect { name: String, len: usize },
+    InfoKeyLengthIncorrect { key: String, len: usize },
+    InfoValueLengthIncorrect { key: String, len: usize },
+    AcceptRoleValueIncorrect { val: String },
 }
 
 impl CableErrorKind {
@@ -117,6 +121,13 @@ impl std::fmt::Display for CableError {
                     topic, len
                 ]
             }
+            CableErrorKind::ReasonLengthIncorrect { reason, len } => {
+                write![
+                    f,
+                    "expected reason between 0 and 128 codepoints; reason `{}` is {} codepoints",
+                    reason, len
+                ]
+            }
             CableErrorKind::UsernameLengthIncorrect { name, len } => {
                 write![
                     f,
@@ -124,6 +135,27 @@ impl std::fmt::Display for CableError {
                     name, len
                 ]
             }
+            CableErrorKind::InfoKeyLengthIncorrect { key, len } => {
+                write![
+                    f,
+                    "expected info key between 1 and 128 codepoints; key `{}` is {} codepoints",
+                    key, len
+                ]
+            }
+            CableErrorKind::InfoValueLengthIncorrect { key, len } => {
+                write![
+                    f,
+                    "expected info value of 4096 bytes or less; value of key `{}` is {} bytes",
+                    key, len
+                ]
+            }
+            CableErrorKind::AcceptRoleValueIncorrect { val } => {
+                write![
+                    f,
+                    "expected accept-role value of `0` or `1`; value is `{}`",
+                    val
+                ]
+            }
         }
     }
 }
@@ -11,7 +11,7 @@ pub mod validation;
 // Public exports for library user convenience.
 pub use crate::{error::Error, message::Message, post::Post};
 
-use crate::error::CableErrorKind;
+use crate::constants::{INFO_ACCEPT_ROLE_KEY, INFO_NAME_KEY};
 
 /// The name of a channel.
 pub type Channel = String;
@@ -85,18 +85,22 @@ impl UserInfo {
     /// Create an instance of `UserInfo` to set a user's display name.
     pub fn name<T: Into<String>>(username: T) -> Result<Self, Error> {
         let name = username.into();
-        // Determine the length of the given username in UTF-8 codepoints.
-        let name_len = name.chars().count();
         // The name must be between 1 and 32 codepoints.
-        if !(1..=32).contains(&name_len) {
-            return CableErrorKind::UsernameLengthIncorrect {
-                name,
-                len: name_len,
-            }
-            .raise();
-        }
+        validation::validate_name(&name)?;
+
+        Ok(UserInfo::new(INFO_NAME_KEY, name))
+    }
+
+    /// Create an instance of `UserInfo` to set whether a user accepts roles
+    /// assigned to them.
+    pub fn accept_role(accept: bool) -> Self {
+        UserInfo::new(INFO_ACCEPT_ROLE_KEY, if accept { "1" } else { "0" })
+    }
 
-        Ok(UserInfo::new("name", name))
+    /// Validate the key and value, including any constraints on the value of
+    /// a known key.
+    pub fn validate(&self) -> Result<(), Error> {
+        validation::validate_info(self)
     }
 }
 
@@ -19,7 +19,10 @@ use sodiumoxide::{
 };
 
 use crate::{
-    constants::{DELETE_POST, INFO_POST, JOIN_POST, LEAVE_POST, TEXT_POST, TOPIC_POST},
+    constants::{
+        BLOCK_POST, DELETE_POST, INFO_POST, JOIN_POST, LEAVE_POST, MODERATION_POST, ROLE_POST,
+        TEXT_POST, TOPIC_POST, UNBLOCK_POST,
+    },
     error::{CableErrorKind, Error},
     validation, Channel, Hash, Text, Topic, UserInfo,
 };
@@ -114,8 +117,69 @@ pub enum PostBody {
         /// Channel name (UTF-8).
         channel: Channel,
     },
+    /// Assign a role to a user, either for a single channel or for the whole
+    /// cabal.
+    Role {
+        /// Channel name (UTF-8); empty if the role applies to the whole cabal.
+        channel: Channel,
+        /// Public key of the user to whom the role is assigned.
+        recipient: [u8; 32],
+        /// The assigned role (user, admin or moderator).
+        role: u64,
+        /// The reason for the assignment (UTF-8).
+        reason: String,
+        /// Whether the post is private (1) or public (0).
+        privacy: u64,
+    },
+    /// Apply a moderation action to users, posts or a channel.
+    Moderation {
+        /// Channel name (UTF-8); empty if the action applies to the whole
+        /// cabal.
+        channel: Channel,
+        /// Public keys of users or hashes of posts to which the action applies.
+        recipients: Vec<Hash>,
+        /// The moderation action (e.g. hide user, drop post).
+        action: u64,
+        /// The reason for the action (UTF-8).
+        reason: String,
+        /// Whether the post is private (1) or public (0).
+        privacy: u64,
+    },
+    /// Block users from the cabal.
+    Block {
+        /// Public keys of the blocked users.
+        recipients: Vec<[u8; 32]>,
+        /// Whether the posts of the blocked users are dropped (1) or not (0).
+        drop: u64,
+        /// Whether the blocked users are notified (1) or not (0).
+        notify: u64,
+        /// The reason for the block (UTF-8).
+        reason: String,
+        /// Whether the post is private (1) or public (0).
+        privacy: u64,
+    },
+    /// Remove a block from users.
+    Unblock {
+        /// Public keys of the unblocked users.
+        recipients: Vec<[u8; 32]>,
+        /// Whether the posts of the unblocked users are undropped (1) or not
+        /// (0).
+        undrop: u64,
+        /// The reason for the unblock (UTF-8).
+        reason: String,
+        /// Whether the post is private (1) or public (0).
+        privacy: u64,
+    },
     /// A post type which is not recognised as part of the cable specification.
-    Unrecognized { post_type: u64 },
+    ///
+    /// The encoded body is retained so that the post can be stored and relayed
+    /// to other peers unchanged.
+    Unrecognized {
+        /// Post type.
+        post_type: u64,
+        /// The encoded post body.
+        body: Vec<u8>,
+    },
 }
 
 /// Print a post body with byte arrays formatted as hex strings.
@@ -141,8 +205,66 @@ impl fmt::Display for PostBody {
             PostBody::Leave { channel } => {
                 write!(f, "channel: {:?}", channel)
             }
-            PostBody::Unrecognized { post_type: _ } => {
-                write!(f, "post_type: unrecognized")
+            PostBody::Role {
+                channel,
+                recipient,
+                role,
+                reason,
+                privacy,
+            } => {
+                write!(
+                    f,
+                    "channel: {:?}, recipient: {:?}, role: {}, reason: {:?}, privacy: {}",
+                    channel,
+                    hex::encode(recipient),
+                    role,
+                    reason,
+                    privacy
+                )
+            }
+            PostBody::Moderation {
+                channel,
+                recipients,
+                action,
+                reason,
+                privacy,
+            } => {
+                let recipients_hex: Vec<String> = recipients.iter().map(hex::encode).collect();
+                write!(
+                    f,
+                    "channel: {:?}, recipients: {:?}, action: {}, reason: {:?}, privacy: {}",
+                    channel, recipients_hex, action, reason, privacy
+                )
+            }
+            PostBody::Block {
+                recipients,
+                drop,
+                notify,
+                reason,
+                privacy,
+            } => {
+                let recipients_hex: Vec<String> = recipients.iter().map(hex::encode).collect();
+                write!(
+                    f,
+                    "recipients: {:?}, drop: {}, notify: {}, reason: {:?}, privacy: {}",
+                    recipients_hex, drop, notify, reason, privacy
+                )
+            }
+            PostBody::Unblock {
+                recipients,
+                undrop,
+                reason,
+                privacy,
+            } => {
+                let recipients_hex: Vec<String> = recipients.iter().map(hex::encode).collect();
+                write!(
+                    f,
+                    "recipients: {:?}, undrop: {}, reason: {:?}, privacy: {}",
+                    recipients_hex, undrop, reason, privacy
+                )
+            }
+            PostBody::Unrecognized { post_type: _, body } => {
+                write!(f, "post_type: unrecognized, body: {:?}", hex::encode(body))
             }
         }
     }
@@ -231,6 +353,97 @@ impl Post {
         Post { header, body }
     }
 
+    /// Construct an unsigned public role `Post` with the given parameters.
+    ///
+    /// An empty channel name assigns the role for the whole cabal.
+    pub fn role(
+        public_key: [u8; 32],
+        links: Vec<Hash>,
+        timestamp: u64,
+        channel: Channel,
+        recipient: [u8; 32],
+        role: u64,
+        reason: String,
+    ) -> Self {
+        let header = PostHeader::new(public_key, [0; 64], links, ROLE_POST, timestamp);
+        let body = PostBody::Role {
+            channel,
+            recipient,
+            role,
+            reason,
+            privacy: 0,
+        };
+
+        Post { header, body }
+    }
+
+    /// Construct an unsigned public moderation `Post` with the given
+    /// parameters.
+    ///
+    /// An empty channel name applies the action to the whole cabal.
+    pub fn moderation(
+        public_key: [u8; 32],
+        links: Vec<Hash>,
+        timestamp: u64,
+        channel: Channel,
+        recipients: Vec<Hash>,
+        action: u64,
+        reason: String,
+    ) -> Self {
+        let header = PostHeader::new(public_key, [0; 64], links, MODERATION_POST, timestamp);
+        let body = PostBody::Moderation {
+            channel,
+            recipients,
+            action,
+            reason,
+            privacy: 0,
+        };
+
+        Post { header, body }
+    }
+
+    /// Construct an unsigned public block `Post` with the given parameters.
+    pub fn block(
+        public_key: [u8; 32],
+        links: Vec<Hash>,
+        timestamp: u64,
+        recipients: Vec<[u8; 32]>,
+        drop: bool,
+        notify: bool,
+        reason: String,
+    ) -> Self {
+        let header = PostHeader::new(public_key, [0; 64], links, BLOCK_POST, timestamp);
+        let body = PostBody::Block {
+            recipients,
+            drop: drop as u64,
+            notify: notify as u64,
+            reason,
+            privacy: 0,
+        };
+
+        Post { header, body }
+    }
+
+    /// Construct an unsigned public unblock `Post` with the given parameters.
+    pub fn unblock(
+        public_key: [u8; 32],
+        links: Vec<Hash>,
+        timestamp: u64,
+        recipients: Vec<[u8; 32]>,
+        undrop: bool,
+        reason: String,
+    ) -> Self {
+        let header = PostHeader::new(public_key, [0; 64], links, UNBLOCK_POST, timestamp);
+        let body = PostBody::Unblock {
+            recipients,
+            undrop: undrop as u64,
+            reason,
+            privacy: 0,
+        };
+
+        Post { header, body }
+    }
+
     /// Return the channel name associated with a post.
     pub fn get_channel(&self) -> Option<&Channel> {
         match &self.body {
@@ -240,6 +453,16 @@ impl Post {
             PostBody::Topic { channel, .. } => Some(channel),
             PostBody::Join { channel, .. } => Some(channel),
             PostBody::Leave { channel, .. } => Some(channel),
+            // An empty channel name denotes the whole cabal.
+            PostBody::Role { channel, .. } | PostBody::Moderation { channel, .. } => {
+                if channel.is_empty() {
+                    None
+                } else {
+                    Some(channel)
+                }
+            }
+            PostBody::Block { .. } => None,
+            PostBody::Unblock { .. } => None,
             PostBody::Unrecognized { .. } => None,
         }
     }
@@ -292,7 +515,11 @@ impl Post {
             PostBody::Topic { .. } => TOPIC_POST,
             PostBody::Join { .. } => JOIN_POST,
             PostBody::Leave { .. } => LEAVE_POST,
-            PostBody::Unrecognized { post_type } => *post_type,
+            PostBody::Role { .. } => ROLE_POST,
+            PostBody::Moderation { .. } => MODERATION_POST,
+            PostBody::Block { .. } => BLOCK_POST,
+            PostBody::Unblock { .. } => UNBLOCK_POST,
+            PostBody::Unrecognized { post_type, .. } => *post_type,
         }
     }
 
@@ -344,6 +571,10 @@ impl fmt::Display for Post {
             3 => write!(f, "post/topic {{ {}, {} }}", &self.header, &self.body),
             4 => write!(f, "post/join {{ {}, {} }}", &self.header, &self.body),
             5 => write!(f, "post/leave {{ {}, {} }}", &self.header, &self.body),
+            6 => write!(f, "post/role {{ {}, {} }}", &self.header, &self.body),
+            7 => write!(f, "post/moderation {{ {}, {} }}", &self.header, &self.body),
+            8 => write!(f, "post/block {{ {}, {} }}", &self.header, &self.body),
+            9 => write!(f, "post/unblock {{ {}, {} }}", &self.header, &self.body),
             _ => write!(f, "post/unknown {{ {}, {} }}", &self.header, &self.body),
         }
     }
@@ -457,11 +688,71 @@ impl ToBytes for Post {
                 buf[offset..offset + channel.len()].copy_from_slice(channel.as_bytes());
                 offset += channel.len();
             }
-            PostBody::Unrecognized { post_type } => {
-                return CableErrorKind::PostWriteUnrecognizedType {
-                    post_type: *post_type,
+            PostBody::Role {
+                channel,
+                recipient,
+                role,
+                reason,
+                privacy,
+            } => {
+                offset += varint::encode(channel.len() as u64, &mut buf[offset..])?;
+                buf[offset..offset + channel.len()].copy_from_slice(channel.as_bytes());
+                offset += channel.len();
+
+                buf[offset..offset + 32].copy_from_slice(recipient);
+                offset += recipient.len();
+
+                offset += varint::encode(*role, &mut buf[offset..])?;
+                offset += write_reason_and_privacy(reason, *privacy, &mut buf[offset..])?;
+            }
+            PostBody::Moderation {
+                channel,
+                recipients,
+                action,
+                reason,
+                privacy,
+            } => {
+                offset += varint::encode(channel.len() as u64, &mut buf[offset..])?;
+                buf[offset..offset + channel.len()].copy_from_slice(channel.as_bytes());
+                offset += channel.len();
+
+                offset += write_recipients(recipients, &mut buf[offset..])?;
+                offset += varint::encode(*action, &mut buf[offset..])?;
+                offset += write_reason_and_privacy(reason, *privacy, &mut buf[offset..])?;
+            }
+            PostBody::Block {
+                recipients,
+                drop,
+                notify,
+                reason,
+                privacy,
+            } => {
+                offset += write_recipients(recipients, &mut buf[offset..])?;
+                offset += varint::encode(*drop, &mut buf[offset..])?;
+                offset += varint::encode(*notify, &mut buf[offset..])?;
+                offset += write_reason_and_privacy(reason, *privacy, &mut buf[offset..])?;
+            }
+            PostBody::Unblock {
+                recipients,
+                undrop,
+                reason,
+                privacy,
+            } => {
+                offset += write_recipients(recipients, &mut buf[offset..])?;
+                offset += varint::encode(*undrop, &mut buf[offset..])?;
+                offset += write_reason_and_privacy(reason, *privacy, &mut buf[offset..])?;
+            }
+            PostBody::Unrecognized { body, .. } => {
+                // Write the retained body bytes unchanged.
+                if offset + body.len() > buf.len() {
+                    return CableErrorKind::DstTooSmall {
+                        required: offset + body.len(),
+                        provided: buf.len(),
+                    }
+                    .raise();
                 }
-                .raise();
+                buf[offset..offset + body.len()].copy_from_slice(body);
+                offset += body.len();
             }
         }
 
@@ -608,12 +899,10 @@ impl FromBytes for Post {
                     let val = String::from_utf8(buf[offset..offset + val_len as usize].to_vec())?;
                     offset += val_len as usize;
 
-                    let key_val = if key == "name" {
-                        // This method also performs validation.
-                        UserInfo::name(val)?
-                    } else {
-                        UserInfo::new(key, val)
-                    };
+                    // Validate the key and value, including any constraints
+                    // on the values of known keys (e.g. "name").
+                    let key_val = UserInfo::new(key, val);
+                    key_val.validate()?;
 
                     info.push(key_val);
                 }
@@ -676,8 +965,101 @@ impl FromBytes for Post {
 
                 PostBody::Leave { channel }
             }
-            // Unrecognized.
-            post_type => PostBody::Unrecognized { post_type },
+            ROLE_POST => {
+                let (s, channel) = read_context(&buf[offset..])?;
+                offset += s;
+
+                // Read the recipient bytes and increment the offset.
+                if offset + 32 > buf.len() {
+                    return CableErrorKind::MessageHashResponseEnd {}.raise();
+                }
+                let mut recipient = [0; 32];
+                recipient.copy_from_slice(&buf[offset..offset + 32]);
+                offset += 32;
+
+                // Read the role byte and increment the offset.
+                let (s, role) = varint::decode(&buf[offset..])?;
+                offset += s;
+
+                let (s, (reason, privacy)) = read_reason_and_privacy(&buf[offset..])?;
+                offset += s;
+
+                PostBody::Role {
+                    channel,
+                    recipient,
+                    role,
+                    reason,
+                    privacy,
+                }
+            }
+            MODERATION_POST => {
+                let (s, channel) = read_context(&buf[offset..])?;
+                offset += s;
+
+                let (s, recipients) = read_recipients(&buf[offset..])?;
+                offset += s;
+
+                // Read the action byte and increment the offset.
+                let (s, action) = varint::decode(&buf[offset..])?;
+                offset += s;
+
+                let (s, (reason, privacy)) = read_reason_and_privacy(&buf[offset..])?;
+                offset += s;
+
+                PostBody::Moderation {
+                    channel,
+                    recipients,
+                    action,
+                    reason,
+                    privacy,
+                }
+            }
+            BLOCK_POST => {
+                let (s, recipients) = read_recipients(&buf[offset..])?;
+                offset += s;
+
+                // Read the drop and notify bytes and increment the offset.
+                let (s, drop) = varint::decode(&buf[offset..])?;
+                offset += s;
+                let (s, notify) = varint::decode(&buf[offset..])?;
+                offset += s;
+
+                let (s, (reason, privacy)) = read_reason_and_privacy(&buf[offset..])?;
+                offset += s;
+
+                PostBody::Block {
+                    recipients,
+                    drop,
+                    notify,
+                    reason,
+                    privacy,
+                }
+            }
+            UNBLOCK_POST => {
+                let (s, recipients) = read_recipients(&buf[offset..])?;
+                offset += s;
+
+                // Read the undrop byte and increment the offset.
+                let (s, undrop) = varint::decode(&buf[offset..])?;
+                offset += s;
+
+                let (s, (reason, privacy)) = read_reason_and_privacy(&buf[offset..])?;
+                offset += s;
+
+                PostBody::Unblock {
+                    recipients,
+                    undrop,
+                    reason,
+                    privacy,
+                }
+            }
+            // Unrecognized; retain the remaining bytes as the body.
+            post_type => {
+                let body = buf[offset..].to_vec();
+                offset = buf.len();
+
+                PostBody::Unrecognized { post_type, body }
+            }
         };
 
         Ok((offset, Post { header, body }))
@@ -722,7 +1104,58 @@ impl CountBytes for Post {
             }
             PostBody::Join { channel } => varint::length(channel.len() as u64) + channel.len(),
             PostBody::Leave { channel } => varint::length(channel.len() as u64) + channel.len(),
-            PostBody::Unrecognized { .. } => 0,
+            PostBody::Role {
+                channel,
+                role,
+                reason,
+                privacy,
+                ..
+            } => {
+                varint::length(channel.len() as u64)
+                    + channel.len()
+                    + 32
+                    + varint::length(*role)
+                    + count_reason_and_privacy(reason, *privacy)
+            }
+            PostBody::Moderation {
+                channel,
+                recipients,
+                action,
+                reason,
+                privacy,
+            } => {
+                varint::length(channel.len() as u64)
+                    + channel.len()
+                    + varint::length(recipients.len() as u64)
+                    + recipients.len() * 32
+                    + varint::length(*action)
+                    + count_reason_and_privacy(reason, *privacy)
+            }
+            PostBody::Block {
+                recipients,
+                drop,
+                notify,
+                reason,
+                privacy,
+            } => {
+                varint::length(recipients.len() as u64)
+                    + recipients.len() * 32
+                    + varint::length(*drop)
+                    + varint::length(*notify)
+                    + count_reason_and_privacy(reason, *privacy)
+            }
+            PostBody::Unblock {
+                recipients,
+                undrop,
+                reason,
+                privacy,
+            } => {
+                varint::length(recipients.len() as u64)
+                    + recipients.len() * 32
+                    + varint::length(*undrop)
+                    + count_reason_and_privacy(reason, *privacy)
+            }
+            PostBody::Unrecognized { body, .. } => body.len(),
         };
 
         header_size + body_size
@@ -739,12 +1172,113 @@ impl CountBytes for Post {
     }
 }
 
+/* MODERATION POST FIELD HELPERS */
+
+/// Write the given recipients (public keys or post hashes) to the buffer,
+/// prefixed by the number of recipients, and return the number of bytes
+/// written.
+fn write_recipients(recipients: &[[u8; 32]], buf: &mut [u8]) -> Result<usize, Error> {
+    let mut offset = varint::encode(recipients.len() as u64, buf)?;
+    for recipient in recipients {
+        if offset + recipient.len() > buf.len() {
+            return CableErrorKind::DstTooSmall {
+                required: offset + recipient.len(),
+                provided: buf.len(),
+            }
+            .raise();
+        }
+        buf[offset..offset + recipient.len()].copy_from_slice(recipient);
+        offset += recipient.len();
+    }
+
+    Ok(offset)
+}
+
+/// Write the given reason and privacy fields to the buffer and return the
+/// number of bytes written.
+fn write_reason_and_privacy(reason: &str, privacy: u64, buf: &mut [u8]) -> Result<usize, Error> {
+    let mut offset = varint::encode(reason.len() as u64, buf)?;
+    buf[offset..offset + reason.len()].copy_from_slice(reason.as_bytes());
+    offset += reason.len();
+    offset += varint::encode(privacy, &mut buf[offset..])?;
+
+    Ok(offset)
+}
+
+/// Count the bytes of the given reason and privacy fields.
+fn count_reason_and_privacy(reason: &str, privacy: u64) -> usize {
+    varint::length(reason.len() as u64) + reason.len() + varint::length(privacy)
+}
+
+/// Read a channel name from the buffer, returning the number of bytes read.
+///
+/// An empty channel name denotes the whole cabal and is therefore permitted;
+/// any other channel name is validated.
+fn read_context(buf: &[u8]) -> Result<(usize, Channel), Error> {
+    // Read the channel length byte and increment the offset.
+    let (mut offset, channel_len) = varint::decode(buf)?;
+
+    // Read the channel bytes.
+    let channel = String::from_utf8(buf[offset..offset + channel_len as usize].to_vec())?;
+    if !channel.is_empty() {
+        // Validate the length of the channel name.
+        validation::validate_channel(&channel)?;
+    }
+    offset += channel_len as usize;
+
+    Ok((offset, channel))
+}
+
+/// Read the number of recipients and the recipients (public keys or post
+/// hashes) from the buffer, returning the number of bytes read.
+fn read_recipients(buf: &[u8]) -> Result<(usize, Vec<[u8; 32]>), Error> {
+    let (mut offset, num_recipients) = varint::decode(buf)?;
+
+    let mut recipients = Vec::with_capacity((num_recipients as usize).min(buf.len() / 32));
+
+    // Iterate over the recipients, reading the bytes from the buffer and
+    // incrementing the offset for each one.
+    for _ in 0..num_recipients {
+        if offset + 32 > buf.len() {
+            return CableErrorKind::MessageHashResponseEnd {}.raise();
+        }
+
+        let mut recipient = [0; 32];
+        recipient.copy_from_slice(&buf[offset..offset + 32]);
+        offset += 32;
+
+        recipients.push(recipient);
+    }
+
+    Ok((offset, recipients))
+}
+
+/// Read the reason and privacy fields from the buffer, returning the number
+/// of bytes read.
+fn read_reason_and_privacy(buf: &[u8]) -> Result<(usize, (String, u64)), Error> {
+    // Read the reason length byte and increment the offset.
+    let (mut offset, reason_len) = varint::decode(buf)?;
+
+    // Read the reason bytes.
+    let reason = String::from_utf8(buf[offset..offset + reason_len as usize].to_vec())?;
+    // Validate the length of the reason.
+    validation::validate_reason(&reason)?;
+    offset += reason_len as usize;
+
+    // Read the privacy byte and increment the offset.
+    let (s, privacy) = varint::decode(&buf[offset..])?;
+    offset += s;
+
+    Ok((offset, (reason, privacy)))
+}
+
 #[cfg(test)]
 mod test {
     use super::{
-        Error, FromBytes, Hash, Post, PostBody, PostHeader, ToBytes, UserInfo, DELETE_POST,
-        INFO_POST, JOIN_POST, LEAVE_POST, TEXT_POST, TOPIC_POST,
+        CountBytes, Error, FromBytes, Hash, Post, PostBody, PostHeader, ToBytes, UserInfo,
+        DELETE_POST, INFO_POST, JOIN_POST, LEAVE_POST, TEXT_POST, TOPIC_POST,
     };
+    use crate::constants::{DROP_POST, MODERATOR_ROLE};
 
     use hex::FromHex;
 
@@ -1400,4 +1934,200 @@ mod test {
 
         Ok(())
     }
+
+    /* MODERATION POST TESTS */
+
+    #[test]
+    fn role_post_roundtrip() -> Result<(), Error> {
+        let public_key = <[u8; 32]>::from_hex(PUBLIC_KEY)?;
+        let links = vec![<[u8; 32]>::from_hex(POST_HASH)?];
+        let recipient = <[u8; 32]>::from_hex(HASH_1)?;
+
+        // Construct a new role post for the whole cabal.
+        let post = Post::role(
+            public_key,
+            links,
+            80,
+            String::new(),
+            recipient,
+            MODERATOR_ROLE,
+            "keeps the peace".to_string(),
+        );
+
+        // Ensure a role post for the whole cabal has no channel.
+        assert!(post.get_channel().is_none());
+
+        let post_bytes = post.to_bytes()?;
+        assert_eq!(post_bytes.len(), post.count_bytes());
+
+        // Decode the bytes and ensure the post body fields are unchanged.
+        let (s, decoded_post) = Post::from_bytes(&post_bytes)?;
+        assert_eq!(s, post_bytes.len());
+        assert_eq!(decoded_post.to_bytes()?, post_bytes);
+
+        if let PostBody::Role {
+            channel,
+            recipient: decoded_recipient,
+            role,
+            reason,
+            privacy,
+        } = decoded_post.body
+        {
+            assert!(channel.is_empty());
+            assert_eq!(decoded_recipient, recipient);
+            assert_eq!(role, MODERATOR_ROLE);
+            assert_eq!(reason, "keeps the peace");
+            assert_eq!(privacy, 0);
+        } else {
+            panic!("Incorrect post type: expected role");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn moderation_post_roundtrip() -> Result<(), Error> {
+        let public_key = <[u8; 32]>::from_hex(PUBLIC_KEY)?;
+        let links = vec![<[u8; 32]>::from_hex(POST_HASH)?];
+        let hashes: Vec<Hash> = vec![<[u8; 32]>::from_hex(HASH_1)?, <[u8; 32]>::from_hex(HASH_2)?];
+
+        // Construct a new moderation post for the "default" channel.
+        let post = Post::moderation(
+            public_key,
+            links,
+            80,
+            "default".to_string(),
+            hashes.clone(),
+            DROP_POST,
+            String::new(),
+        );
+
+        assert_eq!(post.get_channel(), Some(&"default".to_string()));
+
+        let post_bytes = post.to_bytes()?;
+        assert_eq!(post_bytes.len(), post.count_bytes());
+
+        // Decode the bytes and ensure the post body fields are unchanged.
+        let (s, decoded_post) = Post::from_bytes(&post_bytes)?;
+        assert_eq!(s, post_bytes.len());
+        assert_eq!(decoded_post.to_bytes()?, post_bytes);
+
+        if let PostBody::Moderation {
+            channel,
+            recipients,
+            action,
+            reason,
+            privacy,
+        } = decoded_post.body
+        {
+            assert_eq!(channel, "default");
+            assert_eq!(recipients, hashes);
+            assert_eq!(action, DROP_POST);
+            assert!(reason.is_empty());
+            assert_eq!(privacy, 0);
+        } else {
+            panic!("Incorrect post type: expected moderation");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn block_and_unblock_post_roundtrip() -> Result<(), Error> {
+        let public_key = <[u8; 32]>::from_hex(PUBLIC_KEY)?;
+        let recipients = vec![<[u8; 32]>::from_hex(HASH_3)?];
+
+        // Construct and decode a new block post.
+        let post = Post::block(
+            public_key,
+            Vec::new(),
+            80,
+            recipients.clone(),
+            true,
+            false,
+            "spam".to_string(),
+        );
+        let post_bytes = post.to_bytes()?;
+        assert_eq!(post_bytes.len(), post.count_bytes());
+
+        let (s, decoded_post) = Post::from_bytes(&post_bytes)?;
+        assert_eq!(s, post_bytes.len());
+
+        if let PostBody::Block {
+            recipients: decoded_recipients,
+            drop,
+            notify,
+            reason,
+            privacy,
+        } = decoded_post.body
+        {
+            assert_eq!(decoded_recipients, recipients);
+            assert_eq!(drop, 1);
+            assert_eq!(notify, 0);
+            assert_eq!(reason, "spam");
+            assert_eq!(privacy, 0);
+        } else {
+            panic!("Incorrect post type: expected block");
+        }
+
+        // Construct and decode a new unblock post.
+        let post = Post::unblock(
+            public_key,
+            Vec::new(),
+            81,
+            recipients.clone(),
+            true,
+            String::new(),
+        );
+        let post_bytes = post.to_bytes()?;
+        assert_eq!(post_bytes.len(), post.count_bytes());
+
+        let (s, decoded_post) = Post::from_bytes(&post_bytes)?;
+        assert_eq!(s, post_bytes.len());
+
+        if let PostBody::Unblock {
+            recipients: decoded_recipients,
+            undrop,
+            ..
+        } = decoded_post.body
+        {
+            assert_eq!(decoded_recipients, recipients);
+            assert_eq!(undrop, 1);
+        } else {
+            panic!("Incorrect post type: expected unblock");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn unrecognized_post_roundtrip() -> Result<(), Error> {
+        // Replace the post type of the text post test vector with an
+        // unrecognized post type (42). The post type follows the public key,
+        // signature, number of links and link.
+        let mut post_bytes = <Vec<u8>>::from_hex(TEXT_POST_HEX_BINARY)?;
+        let post_type_offset = 32 + 64 + 1 + 32;
+        assert_eq!(post_bytes[post_type_offset], TEXT_POST as u8);
+        post_bytes[post_type_offset] = 42;
+
+        // Decode the byte slice to a `Post`.
+        let (s, post) = Post::from_bytes(&post_bytes)?;
+        assert_eq!(s, post_bytes.len());
+        assert_eq!(post.post_type(), 42);
+        assert!(post.get_channel().is_none());
+
+        // Ensure the body bytes have been retained.
+        if let PostBody::Unrecognized { post_type, body } = &post.body {
+            assert_eq!(*post_type, 42);
+            assert_eq!(body[..], post_bytes[post_type_offset + 2..]);
+        } else {
+            panic!("Incorrect post type: expected unrecognized");
+        }
+
+        // Ensure the post is re-encoded byte-for-byte.
+        assert_eq!(post.count_bytes(), post_bytes.len());
+        assert_eq!(post.to_bytes()?, post_bytes);
+
+        Ok(())
+    }
 }
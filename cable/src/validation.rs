@@ -1,6 +1,10 @@
 //! Validation functions.
 
-use crate::error::{CableErrorKind, Error};
+use crate::{
+    constants::{INFO_ACCEPT_ROLE_KEY, INFO_NAME_KEY},
+    error::{CableErrorKind, Error},
+    UserInfo,
+};
 
 /// Validate the length of a channel name (1 to 64 UTF-8 codepoints).
 pub fn validate_channel(channel: &String) -> Result<(), Error> {
@@ -50,9 +54,82 @@ pub fn validate_topic(topic: &String) -> Result<(), Error> {
     Ok(())
 }
 
+/// Validate the length of a moderation reason (0 to 128 UTF-8 codepoints).
+pub fn validate_reason(reason: &String) -> Result<(), Error> {
+    // Determine the length of the given reason in UTF-8 codepoints.
+    let reason_len = reason.chars().count();
+    // The reason must be between 0 and 128 codepoints.
+    if reason_len > 128 {
+        return CableErrorKind::ReasonLengthIncorrect {
+            reason: reason.to_owned(),
+            len: reason_len,
+        }
+        .raise();
+    }
+
+    Ok(())
+}
+
+/// Validate the length of a user's display name (1 to 32 UTF-8 codepoints).
+pub fn validate_name(name: &String) -> Result<(), Error> {
+    // Determine the length of the given username in UTF-8 codepoints.
+    let name_len = name.chars().count();
+    // The name must be between 1 and 32 codepoints.
+    if !(1..=32).contains(&name_len) {
+        return CableErrorKind::UsernameLengthIncorrect {
+            name: name.to_owned(),
+            len: name_len,
+        }
+        .raise();
+    }
+
+    Ok(())
+}
+
+/// Validate a `post/info` key-value pair.
+///
+/// The key must be between 1 and 128 UTF-8 codepoints and the value must not
+/// exceed 4096 bytes. The values of known keys are further constrained: a
+/// `name` must be between 1 and 32 codepoints and an `accept-role` value must
+/// be either `0` or `1`.
+pub fn validate_info(info: &UserInfo) -> Result<(), Error> {
+    let UserInfo { key, val } = info;
+
+    // Determine the length of the given key in UTF-8 codepoints.
+    let key_len = key.chars().count();
+    // The key must be between 1 and 128 codepoints.
+    if !(1..=128).contains(&key_len) {
+        return CableErrorKind::InfoKeyLengthIncorrect {
+            key: key.to_owned(),
+            len: key_len,
+        }
+        .raise();
+    }
+
+    // The value must not exceed 4096 bytes.
+    if val.len() > 4096 {
+        return CableErrorKind::InfoValueLengthIncorrect {
+            key: key.to_owned(),
+            len: val.len(),
+        }
+        .raise();
+    }
+
+    match key.as_str() {
+        INFO_NAME_KEY => validate_name(val),
+        INFO_ACCEPT_ROLE_KEY if val != "0" && val != "1" => {
+            CableErrorKind::AcceptRoleValueIncorrect {
+                val: val.to_owned(),
+            }
+            .raise()
+        }
+        _ => Ok(()),
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use super::{validate_channel, validate_topic};
+    use super::{validate_channel, validate_info, validate_reason, validate_topic};
     use crate::{Channel, Error, Topic, UserInfo};
 
     #[test]
@@ -141,4 +218,79 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn validate_reason_len() -> Result<(), Error> {
+        // Test valid reasons.
+        let valid_reason = String::from("");
+        validate_reason(&valid_reason)?;
+        let valid_reason_spam = String::from("spam");
+        validate_reason(&valid_reason_spam)?;
+
+        // Test invalid reasons.
+
+        let invalid_reason_long = "spam ".repeat(26);
+
+        // Reason too long.
+        match validate_reason(&invalid_reason_long) {
+            Err(e) => assert_eq!(
+                e.to_string(),
+                format!(
+                    "expected reason between 0 and 128 codepoints; reason `{}` is 130 codepoints",
+                    invalid_reason_long
+                )
+            ),
+            _ => panic!(),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn validate_info_pairs() -> Result<(), Error> {
+        // Test valid key-value pairs.
+        validate_info(&UserInfo::new("name", "glyph"))?;
+        validate_info(&UserInfo::accept_role(true))?;
+        validate_info(&UserInfo::new("bio", ""))?;
+
+        // Test invalid key-value pairs.
+
+        // Key too short.
+        match validate_info(&UserInfo::new("", "glyph")) {
+            Err(e) => assert_eq!(
+                e.to_string(),
+                "expected info key between 1 and 128 codepoints; key `` is 0 codepoints"
+            ),
+            _ => panic!(),
+        }
+
+        // Value too long.
+        match validate_info(&UserInfo::new("bio", "a".repeat(4097))) {
+            Err(e) => assert_eq!(
+                e.to_string(),
+                "expected info value of 4096 bytes or less; value of key `bio` is 4097 bytes"
+            ),
+            _ => panic!(),
+        }
+
+        // Name too short.
+        match validate_info(&UserInfo::new("name", "")) {
+            Err(e) => assert_eq!(
+                e.to_string(),
+                "expected username between 1 and 32 codepoints; name `` is 0 codepoints"
+            ),
+            _ => panic!(),
+        }
+
+        // Unknown accept-role value.
+        match validate_info(&UserInfo::new("accept-role", "yes")) {
+            Err(e) => assert_eq!(
+                e.to_string(),
+                "expected accept-role value of `0` or `1`; value is `yes`"
+            ),
+            _ => panic!(),
+        }
+
+        Ok(())
+    }
 }
@@ -9,11 +9,18 @@
 //! `cargo run --example chat -- 0.0.0.0:8008`
 //!
 //! Write text to either terminal and press <Enter> to post.
+//!
+//! Pass a hex-encoded 32 byte cabal key to both peers to perform a handshake
+//! and encrypt all messages:
+//!
+//! `cargo run --example chat -- -l 8008 -k <cabal key>`
+//!
+//! `cargo run --example chat -- 0.0.0.0:8008 -k <cabal key>`
 
 use async_std::{io, net, prelude::*, task};
 
 use cable::ChannelOptions;
-use cable_core::{CableManager, MemoryStore};
+use cable_core::{CableManager, MemoryStore, Role};
 
 type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
 
@@ -32,6 +39,16 @@ fn main() -> Result<(), Error> {
     let (args, argv) = argmap::parse(std::env::args());
 
     task::block_on(async move {
+        // Parse the optional cabal key.
+        let cabal_key: Option<[u8; 32]> = match argv.get("k").and_then(|x| x.first()) {
+            Some(key) => Some(
+                hex::decode(key)?
+                    .try_into()
+                    .map_err(|_| "expected a cabal key of 32 bytes")?,
+            ),
+            None => None,
+        };
+
         let store = MemoryStore::default();
         let cable = CableManager::new(store);
 
@@ -78,7 +95,14 @@ fn main() -> Result<(), Error> {
                 let stream = stream.unwrap();
                 let client = cable.clone();
                 task::spawn(async move {
-                    client.listen(stream).await.unwrap();
+                    if let Some(key) = cabal_key {
+                        client
+                            .listen_secure(stream, key, Role::Responder)
+                            .await
+                            .unwrap();
+                    } else {
+                        client.listen(stream).await.unwrap();
+                    }
                 });
             }
         // Connect to a TCP server and pass the stream to the cable manager.
@@ -86,7 +110,11 @@ fn main() -> Result<(), Error> {
             println!("Connecting to TCP server on {addr}");
 
             let stream = net::TcpStream::connect(addr).await?;
-            cable.listen(stream).await?;
+            if let Some(key) = cabal_key {
+                cable.listen_secure(stream, key, Role::Initiator).await?;
+            } else {
+                cable.listen(stream).await?;
+            }
         }
 
         Ok(())
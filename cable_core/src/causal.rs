@@ -0,0 +1,89 @@
+//! Causal ordering of posts using post links.
+//!
+//! Each post links to the hashes of the latest posts known to its author at
+//! the time of publication: its parents. A post was therefore published after
+//! all of its parents, regardless of the timestamps claimed by the posts. A
+//! causally consistent ordering places every post after all of its parents,
+//! falling back to timestamps only for posts which are not causally related.
+//!
+//! A post which claims a timestamp earlier than the timestamp of one of its
+//! parents has an implausible timestamp; either the clock of the author is
+//! skewed or the timestamp has been manipulated.
+
+use std::collections::{BTreeSet, HashMap};
+
+use cable::{post::Post, Error, Hash};
+
+/// Sort the given posts into a causally consistent order.
+///
+/// Every post is placed after all of its parents which are included in the
+/// given posts. Posts which are not causally related are ordered by timestamp
+/// and then by hash.
+pub fn causal_order(posts: Vec<Post>) -> Result<Vec<Post>, Error> {
+    let hashes = posts
+        .iter()
+        .map(|post| post.hash())
+        .collect::<Result<Vec<Hash>, Error>>()?;
+    let index: HashMap<Hash, usize> = hashes
+        .iter()
+        .enumerate()
+        .map(|(i, hash)| (*hash, i))
+        .collect();
+
+    // Count the parents of each post and record the children of each post,
+    // considering only the given posts.
+    let mut parent_counts = vec![0; posts.len()];
+    let mut children = vec![Vec::new(); posts.len()];
+    for (i, post) in posts.iter().enumerate() {
+        let mut links = post.header.links.clone();
+        links.sort_unstable();
+        links.dedup();
+        for link in links {
+            if let Some(&parent) = index.get(&link) {
+                if parent != i {
+                    parent_counts[i] += 1;
+                    children[parent].push(i);
+                }
+            }
+        }
+    }
+
+    let sort_key = |i: usize| (posts[i].get_timestamp(), hashes[i], i);
+
+    // Posts whose parents have all been placed, ordered by timestamp and
+    // hash.
+    let mut ready: BTreeSet<_> = (0..posts.len())
+        .filter(|i| parent_counts[*i] == 0)
+        .map(sort_key)
+        .collect();
+
+    let mut order = Vec::with_capacity(posts.len());
+    let mut placed = vec![false; posts.len()];
+    while let Some((_timestamp, _hash, i)) = ready.pop_first() {
+        order.push(i);
+        placed[i] = true;
+        for &child in &children[i] {
+            parent_counts[child] -= 1;
+            if parent_counts[child] == 0 {
+                ready.insert(sort_key(child));
+            }
+        }
+    }
+
+    // Links can only form a cycle if hashes collide; should that happen, the
+    // remaining posts are placed in order of timestamp.
+    let mut remaining: Vec<usize> = (0..posts.len()).filter(|i| !placed[*i]).collect();
+    remaining.sort_by_key(|i| sort_key(*i));
+    order.extend(remaining);
+
+    let mut posts: Vec<Option<Post>> = posts.into_iter().map(Some).collect();
+
+    Ok(order.into_iter().filter_map(|i| posts[i].take()).collect())
+}
+
+/// Query whether the timestamp of the given post is plausible relative to the
+/// timestamp of the given parent post; a post cannot be published before its
+/// parent.
+pub fn is_timestamp_plausible(post: &Post, parent: &Post) -> bool {
+    post.get_timestamp() >= parent.get_timestamp()
+}
@@ -0,0 +1,320 @@
+//! Configuration of the cable manager.
+//!
+//! The configuration defines the default time-to-live (TTL) of outbound
+//! requests, per request type. The TTL determines how many times a request
+//! will be forwarded by remote peers and is never permitted to exceed the
+//! maximum defined by the cable specification (`MAX_TTL`).
+//!
+//! The TTL may optionally be adjusted according to the number of connected
+//! peers; for example, to reach further into the network when only a few
+//! peers are connected.
+//!
+//! The configuration also defines how long the manager waits for responses
+//! to outbound requests, how often unanswered post requests are retried and
+//! how long request state is retained before being discarded.
+//!
+//! Finally, the configuration limits the resources which each remote peer may
+//! consume: the rate at which requests are handled, the number of live
+//! requests and the number of hashes returned per response. Peers who
+//! repeatedly exceed these limits are disconnected.
+//!
+//! The retention policy bounds the posts kept by the store: by age, by number
+//! per channel and by the total size of the stored payloads. By default, all
+//! posts are retained.
+//!
+//! Optional extensions of the cable handshake may be enabled as well; all are
+//! disabled by default, in which case the handshake conforms to the
+//! specification.
+
+use std::{
+    fmt::{Debug, Formatter, Result as FmtResult},
+    sync::Arc,
+    time::Duration,
+};
+
+use cable::constants::{
+    CANCEL_REQUEST, CHANNEL_LIST_REQUEST, CHANNEL_STATE_REQUEST, CHANNEL_TIME_RANGE_REQUEST,
+    MAX_TTL, POST_REQUEST,
+};
+use cable_handshake::Extensions;
+
+/// A function which receives the default TTL of a request and the number of
+/// connected peers, returning the TTL to be used for the request.
+pub type DynamicTtl = Arc<dyn Fn(u8, usize) -> u8 + Send + Sync>;
+
+/// The default TTL of outbound requests, per request type.
+#[derive(Clone)]
+pub struct TtlConfig {
+    /// The default TTL of post requests.
+    pub post: u8,
+    /// The default TTL of cancel requests.
+    pub cancel: u8,
+    /// The default TTL of channel time range requests.
+    pub channel_time_range: u8,
+    /// The default TTL of channel state requests.
+    pub channel_state: u8,
+    /// The default TTL of channel list requests.
+    pub channel_list: u8,
+    /// An optional adjustment of the default TTL based on the number of
+    /// connected peers.
+    pub dynamic: Option<DynamicTtl>,
+}
+
+impl Default for TtlConfig {
+    fn default() -> Self {
+        Self::uniform(1)
+    }
+}
+
+impl Debug for TtlConfig {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        f.debug_struct("TtlConfig")
+            .field("post", &self.post)
+            .field("cancel", &self.cancel)
+            .field("channel_time_range", &self.channel_time_range)
+            .field("channel_state", &self.channel_state)
+            .field("channel_list", &self.channel_list)
+            .field("dynamic", &self.dynamic.is_some())
+            .finish()
+    }
+}
+
+impl TtlConfig {
+    /// Create a TTL configuration with the same default TTL for all request
+    /// types.
+    pub fn uniform(ttl: u8) -> Self {
+        Self {
+            post: ttl,
+            cancel: ttl,
+            channel_time_range: ttl,
+            channel_state: ttl,
+            channel_list: ttl,
+            dynamic: None,
+        }
+    }
+
+    /// Adjust the default TTL of all request types using the given function,
+    /// which receives the default TTL and the number of connected peers.
+    pub fn with_dynamic<F>(mut self, dynamic: F) -> Self
+    where
+        F: Fn(u8, usize) -> u8 + Send + Sync + 'static,
+    {
+        self.dynamic = Some(Arc::new(dynamic));
+        self
+    }
+
+    /// Return the TTL for a request of the given message type, taking into
+    /// account the number of connected peers.
+    ///
+    /// The returned TTL never exceeds `MAX_TTL`.
+    pub fn ttl(&self, msg_type: u64, peer_count: usize) -> u8 {
+        let ttl = match msg_type {
+            POST_REQUEST => self.post,
+            CANCEL_REQUEST => self.cancel,
+            CHANNEL_TIME_RANGE_REQUEST => self.channel_time_range,
+            CHANNEL_STATE_REQUEST => self.channel_state,
+            CHANNEL_LIST_REQUEST => self.channel_list,
+            _ => 0,
+        };
+
+        let ttl = match &self.dynamic {
+            Some(dynamic) => dynamic(ttl, peer_count),
+            None => ttl,
+        };
+
+        ttl.min(MAX_TTL)
+    }
+}
+
+/// Deadlines and retry policy of outbound requests, along with the retention
+/// of request state.
+///
+/// Live requests never expire; they remain active until cancelled.
+#[derive(Clone, Debug)]
+pub struct RequestConfig {
+    /// The time to wait for all requested posts to be received before a post
+    /// request is retried with another connected peer.
+    pub post_timeout: Duration,
+    /// The maximum number of times an unanswered post request is retried.
+    pub max_retries: usize,
+    /// The time after which any other non-live outbound request is
+    /// discarded, if it has not been concluded by all peers.
+    pub request_timeout: Duration,
+    /// The time for which the ID of a handled request is retained in order to
+    /// detect duplicate messages and request loops.
+    pub handled_retention: Duration,
+    /// The maximum number of handled request IDs to retain; the oldest are
+    /// discarded first once the limit has been reached.
+    pub handled_capacity: usize,
+    /// The interval at which expired requests are retried or discarded while
+    /// peers are connected.
+    pub sweep_interval: Duration,
+}
+
+impl Default for RequestConfig {
+    fn default() -> Self {
+        Self {
+            post_timeout: Duration::from_secs(10),
+            max_retries: 3,
+            request_timeout: Duration::from_secs(60),
+            handled_retention: Duration::from_secs(600),
+            handled_capacity: 65536,
+            sweep_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+/// A limit on the rate at which requests of a single type are handled,
+/// enforced using a token bucket.
+#[derive(Clone, Copy, Debug)]
+pub struct RateLimit {
+    /// The maximum number of requests handled in a burst.
+    pub burst: u32,
+    /// The number of requests per second handled once a burst has been
+    /// exhausted.
+    pub per_second: f64,
+}
+
+impl RateLimit {
+    /// Create a rate limit with the given burst size and sustained rate.
+    pub fn new(burst: u32, per_second: f64) -> Self {
+        Self { burst, per_second }
+    }
+}
+
+/// Limits on the resources consumed by each remote peer.
+#[derive(Clone, Debug)]
+pub struct LimitConfig {
+    /// The rate limit of post requests.
+    pub post: RateLimit,
+    /// The rate limit of cancel requests.
+    pub cancel: RateLimit,
+    /// The rate limit of channel time range requests.
+    pub channel_time_range: RateLimit,
+    /// The rate limit of channel state requests.
+    pub channel_state: RateLimit,
+    /// The rate limit of channel list requests.
+    pub channel_list: RateLimit,
+    /// The maximum number of live requests kept alive per peer. Further live
+    /// requests are answered with the known hashes and then concluded.
+    pub max_live_requests: usize,
+    /// The maximum number of hashes or posts returned in a single response.
+    pub max_hashes: usize,
+    /// The maximum number of messages from a single peer being handled
+    /// concurrently. Reading from the peer stream is paused once the limit
+    /// has been reached.
+    pub max_concurrent_messages: usize,
+    /// The number of times a peer may exceed a limit before being
+    /// disconnected.
+    pub max_violations: usize,
+    /// The maximum length in bytes of a single encrypted message received
+    /// from a peer. The connection is closed if a peer announces a longer
+    /// message, before any memory is allocated for it. The limit must
+    /// accommodate the largest expected post response.
+    pub max_frame_size: u32,
+}
+
+impl Default for LimitConfig {
+    fn default() -> Self {
+        Self {
+            post: RateLimit::new(100, 50.0),
+            cancel: RateLimit::new(100, 50.0),
+            channel_time_range: RateLimit::new(20, 10.0),
+            channel_state: RateLimit::new(20, 10.0),
+            channel_list: RateLimit::new(10, 5.0),
+            max_live_requests: 32,
+            max_hashes: 4096,
+            max_concurrent_messages: 16,
+            max_violations: 50,
+            max_frame_size: 32 * 1024 * 1024,
+        }
+    }
+}
+
+impl LimitConfig {
+    /// Return the rate limit for requests of the given message type, if the
+    /// message type is a known request type.
+    pub fn rate_limit(&self, msg_type: u64) -> Option<RateLimit> {
+        match msg_type {
+            POST_REQUEST => Some(self.post),
+            CANCEL_REQUEST => Some(self.cancel),
+            CHANNEL_TIME_RANGE_REQUEST => Some(self.channel_time_range),
+            CHANNEL_STATE_REQUEST => Some(self.channel_state),
+            CHANNEL_LIST_REQUEST => Some(self.channel_list),
+            _ => None,
+        }
+    }
+}
+
+/// Retention policy of stored posts.
+///
+/// The posts making up the channel state of every channel (spec section
+/// 5.4.4), all moderation posts and all `post/delete` posts are always
+/// retained, regardless of the policy. Pruned posts are removed from the
+/// store, while their hashes are remembered for `pruned_hash_ttl` so that the
+/// posts are not requested from peers again.
+#[derive(Clone, Debug)]
+pub struct RetentionConfig {
+    /// The maximum age of channel posts; older posts are pruned.
+    pub max_age: Option<Duration>,
+    /// The maximum number of posts retained per channel; the oldest posts are
+    /// pruned first.
+    pub max_posts_per_channel: Option<usize>,
+    /// The maximum total size in bytes of all stored post payloads; the
+    /// oldest posts are pruned first once the quota has been exceeded.
+    pub max_payload_bytes: Option<usize>,
+    /// Prune `post/join`, `post/leave`, `post/info` and `post/topic` posts
+    /// which have been superseded by later posts and are no longer part of
+    /// the channel state.
+    pub prune_superseded: bool,
+    /// The interval at which the retention policy is applied while peers are
+    /// connected.
+    pub interval: Duration,
+    /// The duration for which the hashes of pruned posts are remembered.
+    /// Pruned posts are not requested from peers again during this time.
+    pub pruned_hash_ttl: Duration,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            max_age: None,
+            max_posts_per_channel: None,
+            max_payload_bytes: None,
+            prune_superseded: false,
+            interval: Duration::from_secs(60),
+            pruned_hash_ttl: Duration::from_secs(7 * 24 * 60 * 60),
+        }
+    }
+}
+
+impl RetentionConfig {
+    /// Query whether any posts may be pruned according to the policy.
+    pub fn is_enabled(&self) -> bool {
+        self.max_age.is_some()
+            || self.max_posts_per_channel.is_some()
+            || self.max_payload_bytes.is_some()
+            || self.prune_superseded
+    }
+}
+
+/// Configuration of the cable manager.
+#[derive(Clone, Debug, Default)]
+pub struct ManagerConfig {
+    /// The default TTL of outbound requests.
+    pub ttl: TtlConfig,
+    /// Deadlines and retry policy of outbound requests.
+    pub requests: RequestConfig,
+    /// Limits on the resources consumed by each remote peer.
+    pub limits: LimitConfig,
+    /// Retention policy of stored posts.
+    pub retention: RetentionConfig,
+    /// Extensions of the cable handshake enabled for secure connections.
+    ///
+    /// Only peers enabling the `identity_payload` extension learn the ed25519
+    /// public key of the remote peer during the handshake, allowing each
+    /// connection to be linked to a post author. The extension is not part of
+    /// the handshake specification, so a peer enabling it is unable to
+    /// complete a handshake with a peer which does not.
+    pub handshake_extensions: Extensions,
+}
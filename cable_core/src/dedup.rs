@@ -0,0 +1,144 @@
+//! A bounded cache of handled requests, used to detect duplicate messages
+//! and prevent request loops.
+//!
+//! Request IDs are only 4 bytes long and are generated by each peer from a
+//! wrapping counter, making collisions inevitable over time. Entries are
+//! therefore keyed by the peer from whom a message was received along with
+//! the request ID, and are discarded once the retention period has passed or
+//! once the capacity of the cache has been reached (oldest first).
+
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+use cable::ReqId;
+
+use crate::manager::PeerId;
+
+/// Metrics describing the detection of duplicate messages.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DedupMetrics {
+    /// The number of messages dropped because their request ID had already
+    /// been handled.
+    pub duplicates_dropped: u64,
+    /// The number of requests dropped because they had looped back to the
+    /// local peer.
+    pub loops_dropped: u64,
+    /// The number of entries evicted from the cache before the end of the
+    /// retention period, due to the capacity of the cache being reached.
+    pub evicted: u64,
+    /// The number of entries currently held in the cache.
+    pub cached: usize,
+}
+
+/// A cache of handled requests, keyed by peer ID and request ID.
+pub(crate) struct HandledRequests {
+    /// The maximum number of entries.
+    capacity: usize,
+    /// The time for which an entry is retained.
+    retention: Duration,
+    /// The time at which a message with the given key was last handled.
+    entries: HashMap<(PeerId, ReqId), Instant>,
+    /// Keys in the order in which they were handled, along with the time at
+    /// which they were handled.
+    ///
+    /// A key may appear more than once if it was handled repeatedly; only the
+    /// occurrence matching the time held in `entries` is current.
+    order: VecDeque<((PeerId, ReqId), Instant)>,
+    metrics: DedupMetrics,
+}
+
+impl HandledRequests {
+    /// Create an empty cache with the given capacity and retention period.
+    pub(crate) fn new(capacity: usize, retention: Duration) -> Self {
+        Self {
+            capacity,
+            retention,
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+            metrics: DedupMetrics::default(),
+        }
+    }
+
+    /// Query whether a message with the given request ID has been handled
+    /// from the given peer within the retention period.
+    pub(crate) fn contains(&self, peer_id: PeerId, req_id: &ReqId) -> bool {
+        self.last_handled(peer_id, req_id)
+            .is_some_and(|handled| handled.elapsed() < self.retention)
+    }
+
+    /// Return the time at which a message with the given request ID was last
+    /// handled from the given peer.
+    pub(crate) fn last_handled(&self, peer_id: PeerId, req_id: &ReqId) -> Option<Instant> {
+        self.entries.get(&(peer_id, *req_id)).copied()
+    }
+
+    /// Record a message with the given request ID as handled from the given
+    /// peer, evicting the oldest entries if the capacity has been exceeded.
+    pub(crate) fn insert(&mut self, peer_id: PeerId, req_id: ReqId) {
+        let key = (peer_id, req_id);
+        let now = Instant::now();
+
+        self.entries.insert(key, now);
+        self.order.push_back((key, now));
+
+        while self.entries.len() > self.capacity {
+            match self.order.pop_front() {
+                Some((key, handled)) => {
+                    if self.remove_current(&key, handled) {
+                        self.metrics.evicted += 1;
+                    }
+                }
+                None => break,
+            }
+        }
+
+        // Discard superseded occurrences of repeatedly handled keys.
+        if self.order.len() > self.capacity.saturating_mul(2) {
+            let entries = &self.entries;
+            self.order
+                .retain(|(key, handled)| entries.get(key) == Some(handled));
+        }
+    }
+
+    /// Discard all entries which have outlived the retention period.
+    pub(crate) fn prune(&mut self) {
+        while let Some((key, handled)) = self.order.front().copied() {
+            if handled.elapsed() < self.retention {
+                break;
+            }
+            self.order.pop_front();
+            self.remove_current(&key, handled);
+        }
+    }
+
+    /// Remove the entry with the given key if it was last handled at the
+    /// given time, returning whether an entry was removed.
+    fn remove_current(&mut self, key: &(PeerId, ReqId), handled: Instant) -> bool {
+        if self.entries.get(key) == Some(&handled) {
+            self.entries.remove(key);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Record a message dropped as a duplicate.
+    pub(crate) fn record_duplicate(&mut self) {
+        self.metrics.duplicates_dropped += 1;
+    }
+
+    /// Record a request dropped because it looped back to the local peer.
+    pub(crate) fn record_loop(&mut self) {
+        self.metrics.loops_dropped += 1;
+    }
+
+    /// Return the current metrics of the cache.
+    pub(crate) fn metrics(&self) -> DedupMetrics {
+        DedupMetrics {
+            cached: self.entries.len(),
+            ..self.metrics
+        }
+    }
+}
@@ -0,0 +1,296 @@
+//! Discovery of peers on the local network using UDP multicast or broadcast.
+//!
+//! Each announcement is a single datagram sent periodically to the configured
+//! group address:
+//!
+//! ```text
+//! magic (4 bytes) | version (1 byte) | instance id (16 bytes) | discovery key (32 bytes) | port (2 bytes)
+//! ```
+//!
+//! The port is the TCP port on which the announcing peer accepts connections
+//! and is encoded as a big-endian u16. The address of the peer is taken from
+//! the source address of the datagram.
+//!
+//! The instance ID is chosen at random on startup. It allows each instance to
+//! ignore its own announcements and ensures that only one of any two peers
+//! dials the other: a peer is only reported if its instance ID is greater
+//! than that of the local instance.
+
+use std::{
+    collections::HashMap,
+    net::{Ipv4Addr, SocketAddr, SocketAddrV4},
+    time::Duration,
+};
+
+use async_std::{
+    channel,
+    net::UdpSocket,
+    sync::{Arc, RwLock},
+    task,
+};
+use async_trait::async_trait;
+use cable::Error;
+use futures::future::{abortable, AbortHandle};
+use log::{debug, warn};
+use socket2::{Domain, Protocol, Socket, Type};
+use sodiumoxide::randombytes;
+
+use crate::discovery::{Discovery, DiscoveryKey};
+
+/// Identifies a datagram as a cable discovery announcement.
+const MAGIC: &[u8; 4] = b"cabl";
+/// The version of the announcement format.
+const VERSION: u8 = 1;
+/// The length of an instance ID.
+const INSTANCE_ID_LEN: usize = 16;
+/// The total length of an announcement.
+const ANNOUNCEMENT_LEN: usize = MAGIC.len() + 1 + INSTANCE_ID_LEN + 32 + 2;
+
+/// The default multicast group to which announcements are sent.
+pub const DEFAULT_GROUP: Ipv4Addr = Ipv4Addr::new(239, 255, 67, 66);
+/// The default UDP port to which announcements are sent.
+pub const DEFAULT_PORT: u16 = 13331;
+
+type InstanceId = [u8; INSTANCE_ID_LEN];
+
+/// Configuration of the LAN discovery backend.
+#[derive(Clone, Debug)]
+pub struct LanConfig {
+    /// The address to which announcements are sent. Multicast is used if the
+    /// address is a multicast address, otherwise broadcast is used (for
+    /// example, `255.255.255.255`).
+    pub group: Ipv4Addr,
+    /// The UDP port to which announcements are sent and on which
+    /// announcements are received.
+    pub port: u16,
+    /// The address of the interface on which to send and receive multicast
+    /// announcements. The unspecified address selects the default interface.
+    pub interface: Ipv4Addr,
+    /// The interval between announcements.
+    pub interval: Duration,
+}
+
+impl Default for LanConfig {
+    fn default() -> Self {
+        Self {
+            group: DEFAULT_GROUP,
+            port: DEFAULT_PORT,
+            interface: Ipv4Addr::UNSPECIFIED,
+            interval: Duration::from_secs(5),
+        }
+    }
+}
+
+/// A cabal announced by the local peer.
+struct Topic {
+    /// The TCP port on which the local peer accepts connections.
+    port: u16,
+    /// The sender for addresses of discovered peers.
+    discovered: channel::Sender<SocketAddr>,
+}
+
+type TopicMap = HashMap<DiscoveryKey, Topic>;
+
+/// Encode an announcement.
+fn encode_announcement(
+    instance_id: &InstanceId,
+    discovery_key: &DiscoveryKey,
+    port: u16,
+) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(ANNOUNCEMENT_LEN);
+    buf.extend_from_slice(MAGIC);
+    buf.push(VERSION);
+    buf.extend_from_slice(instance_id);
+    buf.extend_from_slice(discovery_key);
+    buf.extend_from_slice(&port.to_be_bytes());
+
+    buf
+}
+
+/// Decode an announcement, returning the instance ID, discovery key and port.
+fn decode_announcement(buf: &[u8]) -> Option<(InstanceId, DiscoveryKey, u16)> {
+    if buf.len() != ANNOUNCEMENT_LEN || &buf[..MAGIC.len()] != MAGIC {
+        return None;
+    }
+    if buf[MAGIC.len()] != VERSION {
+        return None;
+    }
+
+    let (instance_id, remainder) = buf[MAGIC.len() + 1..].split_at(INSTANCE_ID_LEN);
+    let (discovery_key, port) = remainder.split_at(32);
+
+    Some((
+        instance_id.try_into().ok()?,
+        discovery_key.try_into().ok()?,
+        u16::from_be_bytes(port.try_into().ok()?),
+    ))
+}
+
+/// Create a UDP socket for sending and receiving announcements.
+///
+/// The port is shared with other sockets, allowing several instances to run
+/// on the same host.
+fn bind_socket(config: &LanConfig) -> Result<UdpSocket, Error> {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    #[cfg(unix)]
+    socket.set_reuse_port(true)?;
+    socket.bind(&SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, config.port).into())?;
+
+    if config.group.is_multicast() {
+        socket.join_multicast_v4(&config.group, &config.interface)?;
+        socket.set_multicast_if_v4(&config.interface)?;
+        socket.set_multicast_loop_v4(true)?;
+    } else {
+        socket.set_broadcast(true)?;
+    }
+    socket.set_nonblocking(true)?;
+
+    Ok(UdpSocket::from(std::net::UdpSocket::from(socket)))
+}
+
+/// Discovery of peers on the local network using UDP multicast or broadcast.
+///
+/// Announcements are sent and received by background tasks, which run until
+/// the backend is dropped.
+pub struct LanDiscovery {
+    config: LanConfig,
+    instance_id: InstanceId,
+    socket: Arc<UdpSocket>,
+    topics: Arc<RwLock<TopicMap>>,
+    tasks: Vec<AbortHandle>,
+}
+
+impl LanDiscovery {
+    /// Create a LAN discovery backend with the given configuration, binding
+    /// the UDP socket and starting the announcement tasks.
+    pub fn new(config: LanConfig) -> Result<Self, Error> {
+        let socket = Arc::new(bind_socket(&config)?);
+        let topics = Arc::new(RwLock::new(HashMap::new()));
+
+        let mut instance_id = [0; INSTANCE_ID_LEN];
+        randombytes::randombytes_into(&mut instance_id);
+
+        let mut discovery = Self {
+            config,
+            instance_id,
+            socket,
+            topics,
+            tasks: Vec::new(),
+        };
+
+        let (announce, announce_handle) = abortable(discovery.announce_periodically());
+        task::spawn(announce);
+        let (receive, receive_handle) = abortable(discovery.receive_announcements());
+        task::spawn(receive);
+        discovery.tasks = vec![announce_handle, receive_handle];
+
+        Ok(discovery)
+    }
+
+    /// Return the address to which announcements are sent.
+    fn target(&self) -> SocketAddr {
+        SocketAddrV4::new(self.config.group, self.config.port).into()
+    }
+
+    /// Send an announcement for every joined cabal at the configured interval.
+    fn announce_periodically(&self) -> impl std::future::Future<Output = ()> {
+        let socket = self.socket.clone();
+        let topics = self.topics.clone();
+        let instance_id = self.instance_id;
+        let target = self.target();
+        let interval = self.config.interval;
+
+        async move {
+            loop {
+                for (discovery_key, topic) in topics.read().await.iter() {
+                    let announcement = encode_announcement(&instance_id, discovery_key, topic.port);
+                    if let Err(err) = socket.send_to(&announcement, target).await {
+                        warn!("Failed to send discovery announcement: {}", err);
+                    }
+                }
+
+                task::sleep(interval).await;
+            }
+        }
+    }
+
+    /// Receive announcements, reporting the addresses of peers announcing a
+    /// joined cabal.
+    fn receive_announcements(&self) -> impl std::future::Future<Output = ()> {
+        let socket = self.socket.clone();
+        let topics = self.topics.clone();
+        let instance_id = self.instance_id;
+
+        async move {
+            let mut buf = [0; ANNOUNCEMENT_LEN + 1];
+            loop {
+                let (len, src) = match socket.recv_from(&mut buf).await {
+                    Ok(res) => res,
+                    Err(err) => {
+                        warn!("Failed to receive discovery announcement: {}", err);
+                        continue;
+                    }
+                };
+
+                let (remote_instance_id, discovery_key, port) =
+                    match decode_announcement(&buf[..len]) {
+                        Some(announcement) => announcement,
+                        None => continue,
+                    };
+
+                // Ignore our own announcements and leave it to the remote
+                // instance to dial us if its instance ID is lower.
+                if remote_instance_id <= instance_id {
+                    continue;
+                }
+
+                if let Some(topic) = topics.read().await.get(&discovery_key) {
+                    let addr = SocketAddr::new(src.ip(), port);
+                    debug!("Discovered peer {}", addr);
+
+                    let _ = topic.discovered.try_send(addr);
+                }
+            }
+        }
+    }
+}
+
+impl Drop for LanDiscovery {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+#[async_trait]
+impl Discovery for LanDiscovery {
+    async fn join(
+        &self,
+        discovery_key: DiscoveryKey,
+        port: u16,
+    ) -> Result<channel::Receiver<SocketAddr>, Error> {
+        let (send, recv) = channel::unbounded();
+        self.topics.write().await.insert(
+            discovery_key,
+            Topic {
+                port,
+                discovered: send,
+            },
+        );
+
+        // Announce immediately rather than waiting for the next interval.
+        let announcement = encode_announcement(&self.instance_id, &discovery_key, port);
+        self.socket.send_to(&announcement, self.target()).await?;
+
+        Ok(recv)
+    }
+
+    async fn leave(&self, discovery_key: &DiscoveryKey) -> Result<(), Error> {
+        // Dropping the sender closes the receiver.
+        self.topics.write().await.remove(discovery_key);
+
+        Ok(())
+    }
+}
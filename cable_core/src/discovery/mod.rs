@@ -0,0 +1,151 @@
+//! Peer discovery.
+//!
+//! A discovery backend announces the local peer as a member of a cabal and
+//! reports the addresses of other peers announcing the same cabal. Cabals are
+//! identified by a discovery key (a hash of the cabal key), allowing peers to
+//! find each other without revealing the cabal key itself.
+//!
+//! `CableManager::discover()` combines a backend with a TCP listener, dialing
+//! discovered peers and performing a handshake with each one.
+
+mod lan;
+
+use std::{collections::HashSet, net::SocketAddr};
+
+use async_std::{
+    channel,
+    net::{TcpListener, TcpStream},
+    prelude::*,
+    sync::{Arc, RwLock},
+    task,
+};
+use async_trait::async_trait;
+use cable::{error::CableErrorKind, Error};
+use cable_handshake::Role;
+use log::debug;
+use sodiumoxide::crypto::generichash;
+
+pub use lan::{LanConfig, LanDiscovery, DEFAULT_GROUP, DEFAULT_PORT};
+
+use crate::{manager::CableManager, registry::CabalKey, store::Store};
+
+/// The key used to derive discovery keys from cabal keys.
+const DISCOVERY_KEY_CONTEXT: &[u8] = b"cable discovery key";
+
+/// A discovery key; identifies a cabal during discovery.
+pub type DiscoveryKey = [u8; 32];
+
+/// Derive the discovery key for the cabal with the given key.
+pub fn discovery_key(cabal_key: &CabalKey) -> Result<DiscoveryKey, Error> {
+    let digest =
+        if let Ok(hash) = generichash::hash(cabal_key, Some(32), Some(DISCOVERY_KEY_CONTEXT)) {
+            hash
+        } else {
+            return CableErrorKind::NoneError {
+                context: "failed to derive discovery key".to_string(),
+            }
+            .raise();
+        };
+
+    Ok(digest.as_ref().try_into()?)
+}
+
+/// A backend for discovering peers.
+#[async_trait]
+pub trait Discovery: Send + Sync {
+    /// Announce the local peer as a member of the cabal identified by the
+    /// given discovery key, reachable on the given TCP port.
+    ///
+    /// Returns a receiver of the addresses of discovered peers. An address
+    /// may be reported more than once.
+    async fn join(
+        &self,
+        discovery_key: DiscoveryKey,
+        port: u16,
+    ) -> Result<channel::Receiver<SocketAddr>, Error>;
+
+    /// Stop announcing the local peer and discovering peers for the cabal
+    /// identified by the given discovery key.
+    ///
+    /// The receiver returned by `join()` for the discovery key is closed.
+    async fn leave(&self, discovery_key: &DiscoveryKey) -> Result<(), Error>;
+}
+
+impl<S> CableManager<S>
+where
+    S: Store,
+{
+    /// Announce the local peer using the given discovery backend and connect
+    /// to discovered peers of the cabal with the given key.
+    ///
+    /// Inbound connections are accepted from the given listener. A handshake
+    /// is performed for every connection, inbound and outbound, before
+    /// listening for peer messages. Each discovered address is dialed at
+    /// most once at a time.
+    ///
+    /// Runs until the discovery backend stops reporting peers or the listener
+    /// fails.
+    pub async fn discover<D: Discovery>(
+        &self,
+        discovery: &D,
+        cabal_key: CabalKey,
+        listener: TcpListener,
+    ) -> Result<(), Error> {
+        let port = listener.local_addr()?.port();
+        let discovered = discovery.join(discovery_key(&cabal_key)?, port).await?;
+
+        let accept = async {
+            let mut incoming = listener.incoming();
+            while let Some(stream) = incoming.next().await {
+                let stream = stream?;
+                debug!("Accepted connection from {}", stream.peer_addr()?);
+
+                let cable = self.clone();
+                task::spawn(async move {
+                    if let Err(err) = cable
+                        .listen_secure(stream, cabal_key, Role::Responder)
+                        .await
+                    {
+                        debug!("Inbound connection closed with error: {}", err);
+                    }
+                });
+            }
+
+            Result::<(), Error>::Ok(())
+        };
+
+        let dial = async {
+            // Addresses with which a connection is currently underway.
+            let connected = Arc::new(RwLock::new(HashSet::<SocketAddr>::new()));
+
+            while let Ok(addr) = discovered.recv().await {
+                if !connected.write().await.insert(addr) {
+                    continue;
+                }
+                debug!("Dialing discovered peer {}", addr);
+
+                let cable = self.clone();
+                let connected = connected.clone();
+                task::spawn(async move {
+                    let res = match TcpStream::connect(addr).await {
+                        Ok(stream) => {
+                            cable
+                                .listen_secure(stream, cabal_key, Role::Initiator)
+                                .await
+                        }
+                        Err(err) => Err(err.into()),
+                    };
+                    if let Err(err) = res {
+                        debug!("Outbound connection to {} closed with error: {}", addr, err);
+                    }
+
+                    connected.write().await.remove(&addr);
+                });
+            }
+
+            Result::<(), Error>::Ok(())
+        };
+
+        accept.race(dial).await
+    }
+}
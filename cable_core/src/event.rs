@@ -0,0 +1,114 @@
+//! Events describing the activity of the cable manager.
+//!
+//! Applications subscribe to events using `CableManager::events()`, allowing a
+//! user interface to react to peer activity and store updates without
+//! polling.
+
+use async_std::{
+    channel,
+    sync::{Arc, RwLock},
+};
+use cable::{Channel, Hash, Post, ReqId, Topic};
+
+use crate::{manager::PeerId, store::PublicKey};
+
+/// An event emitted by the cable manager.
+#[derive(Clone, Debug)]
+pub enum CableEvent {
+    /// A connection with a peer was established. The public key is known if
+    /// a handshake was performed.
+    PeerConnected {
+        peer_id: PeerId,
+        public_key: Option<PublicKey>,
+    },
+    /// The connection with a peer was closed.
+    PeerDisconnected { peer_id: PeerId },
+    /// A request of the given message type was received from a peer.
+    RequestReceived {
+        peer_id: PeerId,
+        req_id: ReqId,
+        msg_type: u64,
+    },
+    /// A hash response was received from a peer.
+    HashesReceived {
+        peer_id: PeerId,
+        req_id: ReqId,
+        hashes: Vec<Hash>,
+    },
+    /// A post was stored; either published locally or received from a peer.
+    PostStored { hash: Hash, post: Post },
+    /// A post was deleted by a stored delete post from the author of the post.
+    PostDeleted { hash: Hash },
+    /// A post claims a timestamp earlier than the timestamp of a post it
+    /// links to (its parent).
+    ImplausibleTimestamp { hash: Hash, parent: Hash },
+    /// A channel became known for the first time.
+    ChannelDiscovered { channel: Channel },
+    /// The topic of a channel was changed.
+    TopicChanged {
+        channel: Channel,
+        topic: Topic,
+        public_key: PublicKey,
+    },
+    /// A peer joined a channel.
+    MemberJoined {
+        channel: Channel,
+        public_key: PublicKey,
+    },
+    /// A peer left a channel.
+    MemberLeft {
+        channel: Channel,
+        public_key: PublicKey,
+    },
+    /// An error occurred while handling a message received from a peer.
+    HandlerError { peer_id: PeerId, error: String },
+}
+
+/// The subscribers to a stream of events.
+pub(crate) struct Subscribers<T> {
+    senders: Arc<RwLock<Vec<channel::Sender<T>>>>,
+}
+
+impl<T> Clone for Subscribers<T> {
+    fn clone(&self) -> Self {
+        Self {
+            senders: self.senders.clone(),
+        }
+    }
+}
+
+impl<T> Default for Subscribers<T> {
+    fn default() -> Self {
+        Self {
+            senders: Arc::new(RwLock::new(Vec::new())),
+        }
+    }
+}
+
+impl<T: Clone> Subscribers<T> {
+    /// Add a subscriber, returning the receiver for all events emitted from
+    /// now on.
+    pub(crate) async fn subscribe(&self) -> channel::Receiver<T> {
+        let (send, recv) = channel::unbounded();
+        self.senders.write().await.push(send);
+
+        recv
+    }
+
+    /// Query whether there are any subscribers.
+    ///
+    /// Subscribers whose receiver has been dropped are only removed on the
+    /// next call to `emit()`.
+    pub(crate) async fn is_empty(&self) -> bool {
+        self.senders.read().await.is_empty()
+    }
+
+    /// Send an event to all subscribers, removing any subscribers whose
+    /// receiver has been dropped.
+    pub(crate) async fn emit(&self, event: T) {
+        self.senders
+            .write()
+            .await
+            .retain(|sender| sender.try_send(event.clone()).is_ok());
+    }
+}
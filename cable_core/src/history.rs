@@ -0,0 +1,38 @@
+//! Cursor-based paging through the history of a channel.
+//!
+//! The posts of a channel are ordered by timestamp and then by hash, allowing
+//! any post to serve as a stable position in the history of the channel. A
+//! page of history contains the posts which precede a given cursor, newest
+//! first, along with a cursor marking the end of the page. Passing the cursor
+//! of one page to `CableManager::channel_history()` returns the next, older
+//! page.
+
+use cable::{post::Post, Hash, Timestamp};
+
+/// A position in the history of a channel, defined by the timestamp and hash
+/// of a post.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct HistoryCursor {
+    pub timestamp: Timestamp,
+    pub hash: Hash,
+}
+
+impl HistoryCursor {
+    /// Create a new instance of `HistoryCursor`.
+    pub fn new(timestamp: Timestamp, hash: Hash) -> Self {
+        HistoryCursor { timestamp, hash }
+    }
+}
+
+/// A page of the history of a channel.
+#[derive(Clone, Debug)]
+pub struct HistoryPage {
+    /// The posts of the page, newest first.
+    pub posts: Vec<Post>,
+    /// The position of the oldest post considered for the page; pass it to
+    /// `CableManager::channel_history()` to retrieve the next page.
+    ///
+    /// The cursor is only `None` if no cursor was given and no posts of the
+    /// channel are known.
+    pub cursor: Option<HistoryCursor>,
+}
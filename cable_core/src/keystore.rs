@@ -0,0 +1,237 @@
+//! Encrypted persistence and import / export of the local keypair.
+//!
+//! The keypair is saved to disk as an encrypted 32 byte ed25519 seed from
+//! which the full keypair can be regenerated. The encryption key is derived
+//! from a user-supplied passphrase using Argon2id; the seed is then encrypted
+//! and authenticated using XSalsa20-Poly1305.
+//!
+//! Keystore file layout:
+//!
+//! ```text
+//! magic (8 bytes) | version (1 byte) | salt (16 bytes) | nonce (24 bytes) | ciphertext (48 bytes)
+//! ```
+
+use std::{
+    convert::TryInto,
+    error::Error as StdError,
+    fmt::{Display, Formatter, Result as FmtResult},
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+
+use cable::Error;
+use sodiumoxide::crypto::{
+    pwhash::argon2id13,
+    secretbox,
+    sign::{self, ed25519::Seed},
+};
+
+use crate::store::Keypair;
+
+/// Identifies a file as a cable keystore.
+const MAGIC: &[u8; 8] = b"cablekey";
+/// The version of the keystore file format.
+const VERSION: u8 = 1;
+/// The length of an ed25519 seed.
+const SEED_LEN: usize = 32;
+/// The length of the keystore file header (magic and version).
+const HEADER_LEN: usize = MAGIC.len() + 1;
+/// The total length of a keystore file.
+const KEYSTORE_LEN: usize =
+    HEADER_LEN + argon2id13::SALTBYTES + secretbox::NONCEBYTES + SEED_LEN + secretbox::MACBYTES;
+
+#[derive(Debug, PartialEq)]
+/// Error that can occur when saving, loading, importing or exporting a
+/// keypair.
+pub enum KeystoreError {
+    /// The keystore file is malformed or of an unsupported version.
+    InvalidKeystore,
+    /// The encryption key could not be derived from the passphrase.
+    KeyDerivationFailed,
+    /// The keystore could not be decrypted; most likely due to an incorrect
+    /// passphrase.
+    DecryptionFailed,
+    /// The given seed is not a valid hex-encoded 32 byte ed25519 seed.
+    InvalidSeed,
+}
+
+impl StdError for KeystoreError {}
+
+impl Display for KeystoreError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            KeystoreError::InvalidKeystore => {
+                write!(
+                    f,
+                    "Keystore file is malformed or has an unsupported version"
+                )
+            }
+            KeystoreError::KeyDerivationFailed => {
+                write!(f, "Failed to derive encryption key from passphrase")
+            }
+            KeystoreError::DecryptionFailed => {
+                write!(f, "Failed to decrypt keystore; incorrect passphrase?")
+            }
+            KeystoreError::InvalidSeed => {
+                write!(f, "Expected a hex-encoded seed of {} bytes", SEED_LEN)
+            }
+        }
+    }
+}
+
+/// Derive the keypair from the given ed25519 seed.
+fn keypair_from_seed(seed: &[u8; SEED_LEN]) -> Keypair {
+    let (pk, sk) = sign::keypair_from_seed(&Seed(*seed));
+
+    (
+        pk.as_ref().try_into().unwrap(),
+        sk.as_ref().try_into().unwrap(),
+    )
+}
+
+/// Derive a symmetric encryption key from the given passphrase and salt.
+fn derive_key(passphrase: &str, salt: &argon2id13::Salt) -> Result<secretbox::Key, Error> {
+    let mut key = secretbox::Key([0; secretbox::KEYBYTES]);
+    argon2id13::derive_key(
+        &mut key.0,
+        passphrase.as_bytes(),
+        salt,
+        argon2id13::OPSLIMIT_INTERACTIVE,
+        argon2id13::MEMLIMIT_INTERACTIVE,
+    )
+    .map_err(|_| KeystoreError::KeyDerivationFailed)?;
+
+    Ok(key)
+}
+
+/// Export the given keypair as a hex-encoded ed25519 seed.
+///
+/// The seed is sufficient to regenerate the full keypair and should be kept
+/// secret.
+pub fn export_seed(keypair: &Keypair) -> String {
+    let (_pk, sk) = keypair;
+
+    // An ed25519 secret key is the concatenation of the seed and public key.
+    hex::encode(&sk[..SEED_LEN])
+}
+
+/// Import a keypair from the given hex-encoded ed25519 seed.
+pub fn import_seed(seed: &str) -> Result<Keypair, Error> {
+    let seed: [u8; SEED_LEN] = hex::decode(seed.trim())
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or(KeystoreError::InvalidSeed)?;
+
+    Ok(keypair_from_seed(&seed))
+}
+
+/// A passphrase-encrypted keypair stored in a file on disk.
+pub struct Keystore {
+    path: PathBuf,
+}
+
+impl Keystore {
+    /// Create a keystore referencing the file at the given path.
+    ///
+    /// The file is not read or created until a keypair is loaded or saved.
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Query whether a keystore file exists at the path of this keystore.
+    pub fn exists(&self) -> bool {
+        self.path.is_file()
+    }
+
+    /// Encrypt the given keypair using the given passphrase and write it to
+    /// disk, overwriting any existing keystore file.
+    ///
+    /// The file is only readable and writable by the owner (on Unix). It is
+    /// written to a temporary file which then replaces the keystore file,
+    /// such that an interrupted write never leaves a truncated keystore.
+    pub fn save(&self, keypair: &Keypair, passphrase: &str) -> Result<(), Error> {
+        let (_pk, sk) = keypair;
+
+        let salt = argon2id13::gen_salt();
+        let nonce = secretbox::gen_nonce();
+        let key = derive_key(passphrase, &salt)?;
+        let ciphertext = secretbox::seal(&sk[..SEED_LEN], &nonce, &key);
+
+        let mut bytes = Vec::with_capacity(KEYSTORE_LEN);
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&salt.0);
+        bytes.extend_from_slice(&nonce.0);
+        bytes.extend_from_slice(&ciphertext);
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut tmp_path = self.path.clone().into_os_string();
+        tmp_path.push(".tmp");
+
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+
+        let mut file = options.open(&tmp_path)?;
+        file.write_all(&bytes)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, &self.path)?;
+
+        Ok(())
+    }
+
+    /// Read the keystore file from disk and decrypt the keypair using the
+    /// given passphrase.
+    pub fn load(&self, passphrase: &str) -> Result<Keypair, Error> {
+        let bytes = fs::read(&self.path)?;
+
+        if bytes.len() != KEYSTORE_LEN || &bytes[..MAGIC.len()] != MAGIC {
+            return Err(KeystoreError::InvalidKeystore.into());
+        }
+        if bytes[MAGIC.len()] != VERSION {
+            return Err(KeystoreError::InvalidKeystore.into());
+        }
+
+        let (salt, remainder) = bytes[HEADER_LEN..].split_at(argon2id13::SALTBYTES);
+        let (nonce, ciphertext) = remainder.split_at(secretbox::NONCEBYTES);
+
+        // The lengths were checked above; these conversions cannot fail.
+        let salt = argon2id13::Salt::from_slice(salt).ok_or(KeystoreError::InvalidKeystore)?;
+        let nonce = secretbox::Nonce::from_slice(nonce).ok_or(KeystoreError::InvalidKeystore)?;
+
+        let key = derive_key(passphrase, &salt)?;
+        let seed: [u8; SEED_LEN] = secretbox::open(ciphertext, &nonce, &key)
+            .ok()
+            .and_then(|seed| seed.try_into().ok())
+            .ok_or(KeystoreError::DecryptionFailed)?;
+
+        Ok(keypair_from_seed(&seed))
+    }
+
+    /// Load the keypair from disk if the keystore file exists, otherwise
+    /// generate a new keypair and save it to disk.
+    pub fn load_or_create(&self, passphrase: &str) -> Result<Keypair, Error> {
+        if self.exists() {
+            self.load(passphrase)
+        } else {
+            let (pk, sk) = sign::gen_keypair();
+            let keypair = (
+                pk.as_ref().try_into().unwrap(),
+                sk.as_ref().try_into().unwrap(),
+            );
+            self.save(&keypair, passphrase)?;
+
+            Ok(keypair)
+        }
+    }
+}
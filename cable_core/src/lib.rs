@@ -1,9 +1,43 @@
 #![cfg_attr(feature = "nightly-features", feature(async_closure, drain_filter))]
 #![doc=include_str!("../README.md")]
 
+mod causal;
+mod config;
+mod dedup;
+mod discovery;
+mod event;
+mod history;
+mod keystore;
+mod limits;
 mod manager;
+mod moderation;
+mod registry;
+#[cfg(feature = "search")]
+mod search;
+mod sled_store;
 mod store;
 mod stream;
+mod supervisor;
 
-pub use manager::CableManager;
-pub use store::{MemoryStore, Store};
+pub use cable_handshake::{Extensions, Role};
+pub use causal::{causal_order, is_timestamp_plausible};
+pub use config::{
+    DynamicTtl, LimitConfig, ManagerConfig, RateLimit, RequestConfig, RetentionConfig, TtlConfig,
+};
+pub use dedup::DedupMetrics;
+pub use discovery::{
+    discovery_key, Discovery, DiscoveryKey, LanConfig, LanDiscovery, DEFAULT_GROUP, DEFAULT_PORT,
+};
+pub use event::CableEvent;
+pub use history::{HistoryCursor, HistoryPage};
+pub use keystore::{export_seed, import_seed, Keystore, KeystoreError};
+pub use manager::{CableManager, RequestOutcome};
+pub use moderation::{Moderation, ModerationState};
+pub use registry::{CabalKey, CabalRegistry};
+#[cfg(feature = "search")]
+pub use search::{tokenize, SearchIndex, SearchQuery, SearchResult};
+pub use sled_store::SledStore;
+pub use store::{Keypair, MemoryStore, Store};
+pub use supervisor::{
+    ConnectionError, ConnectionEvent, ConnectionStatus, ConnectionSupervisor, SupervisorConfig,
+};
@@ -0,0 +1,131 @@
+//! Enforcement of the limits on the resources consumed by each remote peer.
+//!
+//! Requests of each type are admitted according to a token bucket per peer.
+//! Each rejected request, or any other attempt to exceed a limit, counts as a
+//! violation; the manager disconnects a peer once the maximum number of
+//! violations has been reached.
+
+use std::{collections::HashMap, time::Instant};
+
+use async_std::channel;
+
+use crate::config::{LimitConfig, RateLimit};
+
+/// A token bucket, replenished continuously according to a rate limit.
+struct TokenBucket {
+    /// The number of requests which may currently be admitted.
+    tokens: f64,
+    /// The time at which the bucket was last replenished.
+    replenished: Instant,
+}
+
+impl TokenBucket {
+    fn new(limit: &RateLimit) -> Self {
+        Self {
+            tokens: limit.burst as f64,
+            replenished: Instant::now(),
+        }
+    }
+
+    /// Replenish the bucket and take a token, returning whether a token was
+    /// available.
+    fn take(&mut self, limit: &RateLimit) -> bool {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.replenished).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * limit.per_second).min(limit.burst as f64);
+        self.replenished = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// The resource usage of a single peer.
+#[derive(Default)]
+pub(crate) struct PeerLimiter {
+    /// Token buckets, indexed by request message type.
+    buckets: HashMap<u64, TokenBucket>,
+    /// The number of times the peer has exceeded a limit.
+    violations: usize,
+}
+
+impl PeerLimiter {
+    /// Query whether a request of the given message type may be admitted
+    /// according to the configured rate limits. Requests of unknown types
+    /// are always admitted.
+    pub(crate) fn admit(&mut self, config: &LimitConfig, msg_type: u64) -> bool {
+        match config.rate_limit(msg_type) {
+            Some(limit) => self
+                .buckets
+                .entry(msg_type)
+                .or_insert_with(|| TokenBucket::new(&limit))
+                .take(&limit),
+            None => true,
+        }
+    }
+
+    /// Record a violation, returning whether the peer has reached the
+    /// maximum number of violations.
+    pub(crate) fn violate(&mut self, config: &LimitConfig) -> bool {
+        self.violations += 1;
+        self.violations >= config.max_violations
+    }
+}
+
+/// A limit on the number of messages from a single peer being handled
+/// concurrently.
+#[derive(Clone)]
+pub(crate) struct HandlerSlots {
+    /// The number of slots.
+    slots: usize,
+    /// Blocks once all slots have been taken.
+    take: channel::Sender<()>,
+    /// Frees a taken slot.
+    free: channel::Receiver<()>,
+}
+
+impl HandlerSlots {
+    /// Create the given number of slots (at least one).
+    pub(crate) fn new(slots: usize) -> Self {
+        let slots = slots.max(1);
+        let (take, free) = channel::bounded(slots);
+
+        Self { slots, take, free }
+    }
+
+    /// Take a slot, waiting for one to be freed if all have been taken.
+    ///
+    /// The slot is freed once the returned guard is dropped, including when
+    /// the task holding it panics.
+    pub(crate) async fn take(&self) -> HandlerSlot {
+        let _ = self.take.send(()).await;
+
+        HandlerSlot {
+            free: self.free.clone(),
+        }
+    }
+
+    /// Wait until all taken slots have been freed, taking every slot such
+    /// that no further slots can be taken.
+    pub(crate) async fn drain(&self) {
+        for _ in 0..self.slots {
+            let _ = self.take.send(()).await;
+        }
+    }
+}
+
+/// A taken handler slot, freed once dropped.
+pub(crate) struct HandlerSlot {
+    /// Frees the slot.
+    free: channel::Receiver<()>,
+}
+
+impl Drop for HandlerSlot {
+    fn drop(&mut self) {
+        let _ = self.free.try_recv();
+    }
+}
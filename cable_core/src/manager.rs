@@ -5,33 +5,57 @@
 use std::{
     collections::{HashMap, HashSet},
     convert::TryInto,
+    io::{self, ErrorKind},
+    time::Instant,
 };
 
 use async_std::{
-    channel,
+    channel, future,
     prelude::*,
-    sync::{Arc, RwLock},
+    sync::{Arc, Mutex, RwLock},
     task,
 };
 use cable::{
-    constants::NO_CIRCUIT,
+    constants::{
+        CANCEL_REQUEST, CHANNEL_STATE_REQUEST, CHANNEL_TIME_RANGE_REQUEST, MAX_TTL, NO_CIRCUIT,
+        POST_REQUEST,
+    },
     message::{Message, MessageBody, MessageHeader, RequestBody, ResponseBody},
-    validation, Channel, ChannelOptions, Error, Hash, Post, ReqId, Timestamp, UserInfo,
+    post::PostBody,
+    validation, Channel, ChannelOptions, CircuitId, Error, Hash, Payload, Post, ReqId, Timestamp,
+    UserInfo,
+};
+use cable_handshake::{
+    async_std::handshake,
+    post_handshake::{
+        read_encrypted_message_from_async_stream, write_encrypted_message_to_async_stream,
+    },
+    Handshake, HandshakeComplete, Role, Version,
 };
 use desert::{FromBytes, ToBytes};
 use futures::io::{AsyncRead, AsyncWrite};
 use length_prefixed_stream::{decode_with_options, DecodeOptions};
-use log::debug;
-
-use crate::{store::Store, stream::PostStream};
+use log::{debug, warn};
+
+#[cfg(feature = "search")]
+use crate::search::SearchQuery;
+use crate::{
+    causal,
+    config::ManagerConfig,
+    dedup::{DedupMetrics, HandledRequests},
+    event::{CableEvent, Subscribers},
+    history::{HistoryCursor, HistoryPage},
+    limits::{HandlerSlots, PeerLimiter},
+    moderation::{Moderation, ModerationState},
+    store::{Keypair, PublicKey, Store},
+    stream::PostStream,
+    supervisor::ConnectionError,
+};
 
-// Define the TTL (how many times a request will be
-// forwarded.
-//
-// NOTE: We may want to set this dynamically in the
-// future, either based on user choice or connectivity
-// status.
-const TTL: u8 = 1;
+/// The major version of the cable handshake protocol.
+pub(crate) const HANDSHAKE_MAJOR_VERSION: u8 = 1;
+/// The minor version of the cable handshake protocol.
+pub(crate) const HANDSHAKE_MINOR_VERSION: u8 = 0;
 
 /// A locally-defined peer ID used to track requests.
 pub type PeerId = usize;
@@ -63,23 +87,128 @@ impl LiveRequest {
 }
 
 /// The origin of a request.
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug)]
 enum RequestOrigin {
     /// Local request.
     Local,
-    /// Remote request (from a peer).
-    Remote,
+    /// Remote request (from the peer with the given ID).
+    Remote(PeerId),
 }
 
 impl RequestOrigin {
     fn is_local(&self) -> bool {
         match self {
             RequestOrigin::Local => true,
-            RequestOrigin::Remote => false,
+            RequestOrigin::Remote(_) => false,
+        }
+    }
+}
+
+/// An outbound request of local origin which has not yet been concluded.
+struct PendingRequest {
+    /// Peers to whom the request has been sent and who have not yet
+    /// concluded it.
+    peers: HashSet<PeerId>,
+    /// Peers to whom the request has been sent, including previous attempts
+    /// of a retried post request.
+    tried_peers: HashSet<PeerId>,
+    /// The number of times the request has been retried.
+    retries: usize,
+    /// The time at which the request was created.
+    created: Instant,
+    /// Closed once the request has been concluded, notifying those awaiting
+    /// completion of the request.
+    concluded: channel::Receiver<()>,
+    /// Dropped once the request has been concluded.
+    _conclude: channel::Sender<()>,
+    /// Closed once no peers remain from whom a response is awaited,
+    /// notifying those awaiting completion of the request.
+    abandoned: channel::Receiver<()>,
+    /// Dropped once no peers remain from whom a response is awaited; replaced
+    /// once the request is next sent to a peer.
+    abandon: Option<channel::Sender<()>>,
+}
+
+impl PendingRequest {
+    fn new() -> Self {
+        let (_conclude, concluded) = channel::bounded(1);
+        let (abandon, abandoned) = channel::bounded(1);
+
+        Self {
+            peers: HashSet::new(),
+            tried_peers: HashSet::new(),
+            retries: 0,
+            created: Instant::now(),
+            concluded,
+            _conclude,
+            abandoned,
+            abandon: Some(abandon),
+        }
+    }
+
+    /// Record that the request has been sent to the given peer.
+    fn add_peer(&mut self, peer_id: PeerId) {
+        self.peers.insert(peer_id);
+        self.tried_peers.insert(peer_id);
+
+        if self.abandon.is_none() {
+            let (abandon, abandoned) = channel::bounded(1);
+            self.abandoned = abandoned;
+            self.abandon = Some(abandon);
+        }
+    }
+
+    /// Record that a response is no longer awaited from the given peer,
+    /// notifying those awaiting completion of the request if no peers remain.
+    fn remove_peer(&mut self, peer_id: &PeerId) {
+        self.peers.remove(peer_id);
+
+        if self.peers.is_empty() {
+            self.abandon = None;
         }
     }
 }
 
+/// The outcome of awaiting an outbound request of local origin.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RequestOutcome {
+    /// The request was concluded by all peers to whom it was sent, or it was
+    /// cancelled.
+    Concluded,
+    /// The request was not concluded since no peers remain from whom a
+    /// response is awaited. The request remains active and is sent to peers
+    /// who connect later.
+    NoPeers,
+    /// The request was not concluded before the request deadline.
+    TimedOut,
+}
+
+/// Query whether the given message is a request to which hash responses are
+/// returned.
+fn expects_hash_response(msg: &Message) -> bool {
+    matches!(
+        msg.body,
+        MessageBody::Request {
+            body: RequestBody::ChannelTimeRange { .. } | RequestBody::ChannelState { .. },
+            ..
+        }
+    )
+}
+
+/// Query whether the given message is a live request; a channel time range
+/// request with an end time of 0 or a channel state request with a future
+/// value of 1.
+fn is_live(msg: &Message) -> bool {
+    matches!(
+        msg.body,
+        MessageBody::Request {
+            body: RequestBody::ChannelTimeRange { time_end: 0, .. }
+                | RequestBody::ChannelState { future: 1, .. },
+            ..
+        }
+    )
+}
+
 /// Generate a timestamp for the current time.
 fn now() -> Result<u64, Error> {
     let timestamp = std::time::SystemTime::now()
@@ -94,13 +223,27 @@ fn now() -> Result<u64, Error> {
 /// The manager for a single cable instance.
 #[derive(Clone)]
 pub struct CableManager<S: Store> {
-    /// Hashes of posts which remote peers have marked for deletion, or which
-    /// have been authored and deleted by the local peer.
-    deleted_posts: Arc<RwLock<HashSet<Hash>>>,
-    /// Requests of remote origin which have been forwarded to other peers.
+    /// Closed once the manager is being closed, stopping the tasks spawned by
+    /// the manager.
+    closing: channel::Receiver<()>,
+    /// Keeps the `closing` channel open until it is closed explicitly.
+    _closing: channel::Sender<()>,
+    /// The configuration of the manager.
+    config: Arc<ManagerConfig>,
+    /// Requests of remote origin which have been forwarded to other peers,
+    /// indexed by request ID.
+    ///
+    /// Responses received from these peers are relayed to the peer from whom
+    /// the request originated.
     forwarded_requests: Arc<RwLock<HashMap<ReqId, HashSet<PeerId>>>>,
-    /// Request IDs of requests which have been handled.
-    handled_requests: Arc<RwLock<HashSet<ReqId>>>,
+    /// Requests which have been handled, indexed by the peer from whom they
+    /// were received and the request ID.
+    handled_requests: Arc<RwLock<HandledRequests>>,
+    /// The keypair of the local peer, if defined with `with_keypair()`.
+    ///
+    /// The keypair is held in memory only; the keypair of the store is used
+    /// otherwise.
+    keypair: Option<Keypair>,
     /// The most recently assigned peer ID.
     last_peer_id: Arc<RwLock<PeerId>>,
     /// The most recently assigned request ID.
@@ -112,13 +255,35 @@ pub struct CableManager<S: Store> {
     /// of 0, indicating that the peer wishes to receive new post hashes as they
     /// become known.
     live_requests: Arc<RwLock<PeerRequestMap>>,
+    /// The moderation state derived from all known moderation posts, or
+    /// `None` if the state must be derived anew.
+    moderation_state: Arc<Mutex<Option<Arc<ModerationState>>>>,
     /// Active outbound requests (includes requests of local and remote origin).
     outbound_requests: Arc<RwLock<HashMap<ReqId, (RequestOrigin, Message)>>>,
+    /// The resource usage of each peer, indexed by peer ID.
+    peer_limits: Arc<RwLock<HashMap<PeerId, PeerLimiter>>>,
+    /// The ed25519 public keys of peers with whom a handshake has been
+    /// completed, indexed by peer ID.
+    peer_public_keys: Arc<RwLock<HashMap<PeerId, PublicKey>>>,
     /// Peers with whom communication is underway.
     peers: Arc<RwLock<HashMap<PeerId, channel::Sender<Message>>>>,
+    /// Outbound requests of local origin which have not yet been concluded by
+    /// all peers to whom they were sent, indexed by request ID.
+    pending_requests: Arc<RwLock<HashMap<ReqId, PendingRequest>>>,
+    /// The time at which the retention policy was last applied.
+    pruned_at: Arc<Mutex<Instant>>,
+    /// Closed once the manager and all of its clones, including those held
+    /// by spawned tasks, have been dropped.
+    released: channel::Receiver<()>,
+    /// Held by the manager and each of its clones.
+    _release: channel::Sender<()>,
     /// Hashes of posts which have been requested from remote peers by the
     /// local peer.
     requested_posts: Arc<RwLock<HashSet<Hash>>>,
+    /// Subscribers to the events emitted by the manager.
+    subscribers: Subscribers<CableEvent>,
+    /// Whether a task is sweeping expired requests.
+    sweeping: Arc<Mutex<bool>>,
     /// A cable store.
     pub store: S,
 }
@@ -128,31 +293,127 @@ where
     S: Store,
 {
     pub fn new(store: S) -> Self {
+        Self::with_config(store, ManagerConfig::default())
+    }
+
+    /// Create a cable manager using the given store and configuration.
+    pub fn with_config(store: S, config: ManagerConfig) -> Self {
+        let handled_requests = HandledRequests::new(
+            config.requests.handled_capacity,
+            config.requests.handled_retention,
+        );
+
+        let (_closing, closing) = channel::bounded(1);
+        let (_release, released) = channel::bounded(1);
+
         Self {
-            deleted_posts: Arc::new(RwLock::new(HashSet::new())),
+            closing,
+            _closing,
+            config: Arc::new(config),
             forwarded_requests: Arc::new(RwLock::new(HashMap::new())),
-            handled_requests: Arc::new(RwLock::new(HashSet::new())),
+            handled_requests: Arc::new(RwLock::new(handled_requests)),
+            keypair: None,
             last_peer_id: Arc::new(RwLock::new(0)),
             // Generate a random u32 on startup to reduce chance of collisions.
             last_req_id: Arc::new(RwLock::new(fastrand::u32(..))),
             live_requests: Arc::new(RwLock::new(HashMap::new())),
+            moderation_state: Arc::new(Mutex::new(None)),
             outbound_requests: Arc::new(RwLock::new(HashMap::new())),
+            peer_limits: Arc::new(RwLock::new(HashMap::new())),
+            peer_public_keys: Arc::new(RwLock::new(HashMap::new())),
             peers: Arc::new(RwLock::new(HashMap::new())),
+            pending_requests: Arc::new(RwLock::new(HashMap::new())),
+            pruned_at: Arc::new(Mutex::new(Instant::now())),
+            released,
+            _release,
             requested_posts: Arc::new(RwLock::new(HashSet::new())),
+            subscribers: Subscribers::default(),
+            sweeping: Arc::new(Mutex::new(false)),
             store,
         }
     }
 
+    /// Create a cable manager using the given store and an existing keypair.
+    ///
+    /// The keypair takes precedence over any keypair defined for the store
+    /// and is held in memory only; it is never written to the store. Use this
+    /// constructor to run a peer with a persistent identity (for example, a
+    /// keypair loaded from a `Keystore` or imported from a seed).
+    pub async fn with_keypair(store: S, keypair: Keypair) -> Self {
+        let mut cable = Self::new(store);
+        cable.keypair = Some(keypair);
+
+        cable
+    }
+
+    /// Retrieve the configuration of the manager.
+    pub fn config(&self) -> &ManagerConfig {
+        &self.config
+    }
+
+    /// Retrieve metrics describing the messages dropped as duplicates.
+    pub async fn dedup_metrics(&self) -> DedupMetrics {
+        self.handled_requests.read().await.metrics()
+    }
+
+    /// Return the maximum number of hashes to send in response to a request
+    /// with the given limit.
+    ///
+    /// A limit of 0 means there is no limit on the number of hashes
+    /// requested; no more than the configured maximum number of hashes are
+    /// sent either way.
+    fn hash_limit(&self, limit: u64) -> u64 {
+        let max_hashes = self.config.limits.max_hashes as u64;
+        if limit == 0 {
+            max_hashes
+        } else {
+            limit.min(max_hashes)
+        }
+    }
+
+    /// Return the TTL for a new outbound request of the given message type,
+    /// taking into account the number of connected peers.
+    async fn ttl(&self, msg_type: u64) -> u8 {
+        let peer_count = self.peers.read().await.len();
+
+        self.config.ttl.ttl(msg_type, peer_count)
+    }
+
+    /// Subscribe to the events emitted by the manager.
+    ///
+    /// Only events emitted after subscribing are received. The returned
+    /// receiver implements `Stream`.
+    pub async fn events(&self) -> channel::Receiver<CableEvent> {
+        self.subscribers.subscribe().await
+    }
+
+    /// Send an event to all subscribers.
+    async fn emit(&self, event: CableEvent) {
+        self.subscribers.emit(event).await;
+    }
+
+    /// Retrieve the keypair of the local peer, as defined with
+    /// `with_keypair()` or otherwise by the store.
+    async fn get_keypair(&self) -> Keypair {
+        match self.keypair {
+            Some(keypair) => keypair,
+            None => {
+                let mut store = self.store.clone();
+                store.get_or_create_keypair().await
+            }
+        }
+    }
+
     /// Retrieve the public key of the local peer.
     pub async fn get_public_key(&mut self) -> Result<[u8; 32], Error> {
-        let (pk, _sk) = self.store.get_or_create_keypair().await;
+        let (pk, _sk) = self.get_keypair().await;
 
         Ok(pk)
     }
 
     /// Retrieve the secret key of the local peer.
     pub async fn get_secret_key(&mut self) -> Result<[u8; 64], Error> {
-        let (_pk, sk) = self.store.get_or_create_keypair().await;
+        let (_pk, sk) = self.get_keypair().await;
 
         Ok(sk)
     }
@@ -187,6 +448,179 @@ where
         Ok(peer_id)
     }
 
+    /// Retrieve all stored posts matching the given channel options in a
+    /// causally consistent order.
+    ///
+    /// Every post is placed after all of the posts it links to, regardless of
+    /// the timestamps claimed by the posts. Posts which are not causally
+    /// related are ordered by timestamp.
+    pub async fn get_posts_causal(&self, opts: &ChannelOptions) -> Result<Vec<Post>, Error> {
+        let mut posts = Vec::new();
+        let mut stream = self.store.get_posts(opts).await;
+        while let Some(post) = stream.next().await {
+            posts.push(post?);
+        }
+
+        causal::causal_order(posts)
+    }
+
+    /// Retrieve a page of up to `page_size` posts from the local history of
+    /// the given channel, newest first, beginning with the post which precedes
+    /// the given cursor (or with the latest post if no cursor is given).
+    ///
+    /// The posts of hidden and blocked authors, along with any posts which are
+    /// not shown according to the moderation state, are omitted.
+    ///
+    /// If the local history is exhausted before the page is filled, a channel
+    /// time range request for older posts is broadcast to all peers. Any posts
+    /// received in response are stored and returned by later calls with the
+    /// cursor of the returned page.
+    pub async fn channel_history(
+        &mut self,
+        channel: &Channel,
+        cursor: Option<HistoryCursor>,
+        page_size: usize,
+    ) -> Result<HistoryPage, Error> {
+        let mut posts = Vec::new();
+        let mut cursor = cursor;
+        if page_size == 0 {
+            return Ok(HistoryPage { posts, cursor });
+        }
+
+        let moderation_state = self.moderation_state().await;
+
+        // Retrieve batches of posts until the page is filled or the local
+        // history is exhausted.
+        let exhausted = 'fill: loop {
+            let batch = self
+                .store
+                .get_posts_before(channel, cursor.as_ref(), page_size)
+                .await;
+            let exhausted = batch.len() < page_size;
+
+            for (post, hash) in batch {
+                cursor = Some(HistoryCursor::new(post.get_timestamp(), hash));
+
+                let shown = moderation_state.shows(&post, &hash)
+                    && self
+                        .store
+                        .get_moderation(&post.get_public_key())
+                        .await
+                        .is_none();
+                if shown {
+                    posts.push(post);
+                    if posts.len() == page_size {
+                        break 'fill false;
+                    }
+                }
+            }
+
+            if exhausted {
+                break true;
+            }
+        };
+
+        if exhausted {
+            // Request posts published up to the oldest known position from
+            // peers to backfill the history.
+            let time_end = match cursor {
+                Some(cursor) => cursor.timestamp,
+                None => now()?,
+            };
+            self.request_history(channel, time_end, page_size).await?;
+        }
+
+        Ok(HistoryPage { posts, cursor })
+    }
+
+    /// Search the stored text posts, returning the posts matching the given
+    /// query ranked by relevance.
+    ///
+    /// The posts of hidden and blocked authors, along with any posts which are
+    /// not shown according to the moderation state, are omitted.
+    #[cfg(feature = "search")]
+    pub async fn search(&self, query: &SearchQuery) -> Vec<Post> {
+        let moderation_state = self.moderation_state().await;
+
+        // The limit is applied once omitted posts have been removed.
+        let unlimited_query = SearchQuery {
+            limit: 0,
+            ..query.clone()
+        };
+
+        let mut posts = Vec::new();
+        for result in self.store.search(&unlimited_query).await {
+            if let Some(post) = self.get_stored_post(&result.hash).await {
+                let shown = moderation_state.shows(&post, &result.hash)
+                    && self
+                        .store
+                        .get_moderation(&post.get_public_key())
+                        .await
+                        .is_none();
+                if shown {
+                    posts.push(post);
+                    if posts.len() == query.limit {
+                        break;
+                    }
+                }
+            }
+        }
+
+        posts
+    }
+
+    /// Broadcast a non-live channel time range request for up to `limit` posts
+    /// of the given channel published up to the given end time, unless an
+    /// identical request is still outstanding.
+    async fn request_history(
+        &self,
+        channel: &Channel,
+        time_end: Timestamp,
+        limit: usize,
+    ) -> Result<(), Error> {
+        // An end time of 0 would define a live request; no earlier posts can
+        // exist.
+        if time_end == 0 {
+            return Ok(());
+        }
+
+        let opts = ChannelOptions::new(channel, 0, time_end, limit as u64);
+        let is_outstanding =
+            self.outbound_requests
+                .read()
+                .await
+                .values()
+                .any(|(origin, request)| {
+                    matches!(
+                        (origin, &request.body),
+                        (
+                            RequestOrigin::Local,
+                            MessageBody::Request {
+                                body: RequestBody::ChannelTimeRange {
+                                    channel: req_channel,
+                                    time_start: 0,
+                                    time_end: req_time_end,
+                                    ..
+                                },
+                                ..
+                            },
+                        ) if req_channel == channel && *req_time_end == time_end
+                    )
+                });
+        if is_outstanding {
+            return Ok(());
+        }
+
+        debug!("Requesting history: {}", opts);
+
+        let (_req_id, req_id_bytes) = self.new_req_id().await?;
+        let ttl = self.ttl(CHANNEL_TIME_RANGE_REQUEST).await;
+        let request = Message::channel_time_range_request(NO_CIRCUIT, req_id_bytes, ttl, opts);
+        self.send_request(request).await?;
+
+        Ok(())
+    }
+
     /// Create a channel time range request and a channel state request matching
     /// the given channel parameters and broadcast them to all peers, listening
     /// for responses.
@@ -201,29 +635,49 @@ where
 
         // Create and broadcast a channel time range request.
         let (_req_id, req_id_bytes) = self.new_req_id().await?;
+        let ttl = self.ttl(CHANNEL_TIME_RANGE_REQUEST).await;
         let request = Message::channel_time_range_request(
             NO_CIRCUIT,
             req_id_bytes,
-            TTL,
+            ttl,
             channel_opts.to_owned(),
         );
-        self.outbound_requests
-            .write()
-            .await
-            .insert(req_id_bytes, (RequestOrigin::Local, request.clone()));
-        self.broadcast(&request).await?;
+        self.send_request(request).await?;
 
         // Create and broadcast a channel state request.
         let (_req_id, req_id_bytes) = self.new_req_id().await?;
+        let ttl = self.ttl(CHANNEL_STATE_REQUEST).await;
         let request =
-            Message::channel_state_request(NO_CIRCUIT, req_id_bytes, TTL, channel, future);
-        self.outbound_requests
-            .write()
-            .await
-            .insert(req_id_bytes, (RequestOrigin::Local, request.clone()));
-        self.broadcast(&request).await?;
+            Message::channel_state_request(NO_CIRCUIT, req_id_bytes, ttl, channel, future);
+        self.send_request(request).await?;
+
+        // Omit the posts of hidden and blocked authors, along with any posts
+        // which are not shown according to the moderation state.
+        let manager = self.clone();
+        let post_stream = self.store.get_posts_live(channel_opts).await;
+        let post_stream = futures::StreamExt::filter(post_stream, move |post| {
+            let manager = manager.clone();
+            let post = post.as_ref().ok().cloned();
+            async move {
+                match post {
+                    Some(post) => {
+                        let shown = match post.hash() {
+                            Ok(hash) => manager.moderation_state().await.shows(&post, &hash),
+                            Err(_) => true,
+                        };
+                        shown
+                            && manager
+                                .store
+                                .get_moderation(&post.get_public_key())
+                                .await
+                                .is_none()
+                    }
+                    None => true,
+                }
+            }
+        });
 
-        Ok(self.store.get_posts_live(channel_opts).await)
+        Ok(Box::new(Box::pin(post_stream)))
     }
 
     /// Create a cancel request for all active outbound channel time range
@@ -233,7 +687,7 @@ where
         debug!("Closing channel {}", channel);
         let close_channel = channel;
 
-        let mut outbound_requests = self.outbound_requests.write().await;
+        let outbound_requests = self.outbound_requests.read().await;
 
         // Vector to hold the request IDs of all outbound channel time range
         // requests with channel names matching the given channel.
@@ -252,212 +706,1507 @@ where
                 }
             }
         }
+        drop(outbound_requests);
 
         for channel_req_id in channel_req_ids {
-            let (_req_id, req_id_bytes) = self.new_req_id().await?;
-            let request = Message::cancel_request(NO_CIRCUIT, req_id_bytes, TTL, channel_req_id);
-            self.broadcast(&request).await?;
-            outbound_requests.remove(&channel_req_id);
+            self.cancel_request(&channel_req_id).await?;
         }
 
         Ok(())
     }
 
-    /// Listen for incoming peer messages and respond with locally-generated
-    /// messages.
+    /// Hide the posts of the author with the given public key.
     ///
-    /// Decode each received message and pass it off to the handler.
-    pub async fn listen<T>(&self, stream: T) -> Result<(), Error>
-    where
-        T: AsyncRead + AsyncWrite + Clone + Unpin + Send + Sync + 'static,
-    {
-        debug!("Listening for incoming peer messages...");
+    /// Posts by the author are no longer emitted on the post streams returned
+    /// by `open_channel()`, but are still stored and served to remote peers.
+    pub async fn hide(&mut self, public_key: &PublicKey) {
+        debug!("Hiding author {}", hex::encode(public_key));
+        self.store
+            .set_moderation(public_key, Moderation::Hide)
+            .await;
+    }
 
-        // Generate a new peer ID.
-        let peer_id = self.new_peer_id().await?;
+    /// Block the author with the given public key.
+    ///
+    /// All posts by the author are purged from the store, along with the
+    /// author's channel membership. Posts by the author are never stored or
+    /// emitted on the post streams returned by `open_channel()`, and posts
+    /// known to be by the author are never requested.
+    pub async fn block(&mut self, public_key: &PublicKey) {
+        debug!("Blocking author {}", hex::encode(public_key));
+        self.store
+            .set_moderation(public_key, Moderation::Block)
+            .await;
+
+        // Purge the posts of the author, recording their hashes to ensure
+        // they are not requested again.
+        for hash in self.store.get_post_hashes_by_author(public_key).await {
+            self.store.delete_post(&hash).await;
+            self.store.insert_blocked_hash(public_key, &hash).await;
+        }
 
-        // Create a bounded message channel.
-        let (send, recv) = channel::bounded(100);
+        if let Some(channels) = self.store.get_channels().await {
+            for channel in channels {
+                self.store.remove_channel_member(&channel, public_key).await;
+                self.store
+                    .remove_ex_channel_member(&channel, public_key)
+                    .await;
+            }
+        }
 
-        // Insert the peer ID and channel sender into the list of peers.
-        self.peers.write().await.insert(peer_id, send);
+        // Moderation posts by the author may have been purged.
+        self.invalidate_moderation_state().await;
+    }
 
-        // Process and send outbound requests to the connected peer.
-        self.process_and_send_outbound_requests(stream.clone(), peer_id)
-            .await?;
+    /// Remove any block or hide applied to the author with the given public
+    /// key.
+    ///
+    /// Posts by the author which were purged when the author was blocked are
+    /// requested again once their hashes are next received.
+    pub async fn unblock(&mut self, public_key: &PublicKey) {
+        debug!("Unblocking author {}", hex::encode(public_key));
+        self.store.remove_moderation(public_key).await;
+    }
 
-        let write_to_stream_res = {
-            let mut stream_c = stream.clone();
+    /// Retrieve the moderation action applied to the author with the given
+    /// public key, if any.
+    pub async fn get_moderation(&self, public_key: &PublicKey) -> Option<Moderation> {
+        self.store.get_moderation(public_key).await
+    }
 
-            task::spawn(async move {
-                // Listen for incoming locally-generated messages.
-                while let Ok(msg) = recv.recv().await {
-                    let msg_bytes = &msg.to_bytes()?;
+    /// Retrieve the profile of the author with the given public key: the
+    /// latest value of every key assigned by their `post/info` posts, sorted
+    /// by key.
+    pub async fn get_peer_info(&self, public_key: &PublicKey) -> Vec<UserInfo> {
+        self.store
+            .get_peer_info(public_key)
+            .await
+            .unwrap_or_default()
+    }
 
-                    // Write the message to the stream.
-                    stream_c.write_all(msg_bytes).await?;
+    /// Retrieve the moderation state derived from all known moderation
+    /// posts.
+    ///
+    /// The state is derived when first requested and cached until another
+    /// moderation post is stored.
+    pub async fn moderation_state(&self) -> Arc<ModerationState> {
+        // Hold the lock while deriving the state, ensuring that the state is
+        // not invalidated part-way through.
+        let mut cached_state = self.moderation_state.lock().await;
+        if let Some(state) = cached_state.as_ref() {
+            return state.clone();
+        }
 
-                    debug!("Wrote a message to the TCP stream: {}", msg,);
-                }
+        // Retrieve the moderation posts for the whole cabal and for each
+        // known channel.
+        let mut contexts = vec![Channel::new()];
+        contexts.extend(self.store.get_channels().await.unwrap_or_default());
 
-                // Type inference fails without binding concretely to `Result`.
-                Result::<(), Error>::Ok(())
-            })
-        };
+        let mut hashes = Vec::new();
+        for context in &contexts {
+            if let Some(context_hashes) = self.store.get_moderation_hashes(context).await {
+                hashes.extend(context_hashes);
+            }
+        }
 
-        // Define the stream decoder parameters.
-        let options = DecodeOptions {
-            include_len: true,
-            ..Default::default()
+        let posts = self
+            .store
+            .get_post_payloads(&hashes)
+            .await
+            .iter()
+            .filter_map(|payload| Post::from_bytes(payload).ok())
+            .map(|(_s, post)| post)
+            .collect();
+
+        let local_key = match self.keypair {
+            Some((pk, _sk)) => Some(pk),
+            None => self.store.get_keypair().await.map(|(pk, _sk)| pk),
         };
+        let state = Arc::new(ModerationState::new(local_key, posts));
+        *cached_state = Some(state.clone());
+
+        state
+    }
+
+    /// Discard the cached moderation state, ensuring it is derived anew when
+    /// next requested.
+    async fn invalidate_moderation_state(&self) {
+        *self.moderation_state.lock().await = None;
+    }
+
+    /// Invalidate the cached moderation state if the given newly-stored post
+    /// may change it (a moderation post or a delete post).
+    async fn update_moderation_state(&self, post: &Post) {
+        if matches!(
+            post.body,
+            PostBody::Role { .. }
+                | PostBody::Moderation { .. }
+                | PostBody::Block { .. }
+                | PostBody::Unblock { .. }
+                | PostBody::Delete { .. }
+        ) {
+            self.invalidate_moderation_state().await;
+        }
+    }
 
-        let mut length_prefixed_stream = decode_with_options(stream, options);
+    /// Send a post request for the given hashes to the given peer, tracking
+    /// the request until all requested posts have been received.
+    async fn request_posts(
+        &mut self,
+        peer_id: PeerId,
+        circuit_id: CircuitId,
+        hashes: Vec<Hash>,
+    ) -> Result<(), Error> {
+        if hashes.is_empty() {
+            return Ok(());
+        }
 
-        // Iterate over the stream.
-        while let Some(read_buf) = length_prefixed_stream.next().await {
-            let buf = read_buf?;
+        let (_, new_req_id) = self.new_req_id().await?;
+        let ttl = self.ttl(POST_REQUEST).await;
 
-            // Deserialize the received message.
-            let (_, msg) = Message::from_bytes(&buf)?;
+        let request = Message::post_request(circuit_id, new_req_id, ttl, hashes.to_owned());
 
-            debug!("Received a message from the TCP stream: {}", msg,);
+        // Update the list of requested posts.
+        let mut requested_posts = self.requested_posts.write().await;
+        for hash in &hashes {
+            requested_posts.insert(*hash);
+        }
+        drop(requested_posts);
 
-            let mut this = self.clone();
-            task::spawn(async move {
-                // Handle the received message.
-                if let Err(err) = this.handle(peer_id, &msg).await {
-                    // TODO: Consider a better way to report.
-                    eprintln!("{err}");
+        // Track the request until all requested posts have been received,
+        // allowing post responses relayed by other peers to be accepted.
+        self.outbound_requests
+            .write()
+            .await
+            .insert(new_req_id, (RequestOrigin::Local, request.clone()));
+        self.pending_requests
+            .write()
+            .await
+            .insert(new_req_id, PendingRequest::new());
+
+        self.send_local_request(peer_id, new_req_id, &request).await
+    }
+
+    /// Emit an event for each known post whose timestamp is implausible
+    /// relative to the given newly-stored post: either the post itself, if
+    /// it claims a timestamp earlier than one of its parents, or any of its
+    /// children which claim a timestamp earlier than the post.
+    async fn check_timestamps(&self, post: &Post, hash: &Hash) {
+        let mut implausible = Vec::new();
+
+        for parent_hash in &post.header.links {
+            if let Some(parent) = self.get_stored_post(parent_hash).await {
+                if !causal::is_timestamp_plausible(post, &parent) {
+                    implausible.push((*hash, *parent_hash));
                 }
-            });
+            }
         }
 
-        // Continue reading and writing to the peer stream until the stream is
-        // closed (either intentionally or because of an error).
-        write_to_stream_res.await?;
+        for child_hash in self.store.get_child_hashes(hash).await.unwrap_or_default() {
+            if let Some(child) = self.get_stored_post(&child_hash).await {
+                if !causal::is_timestamp_plausible(&child, post) {
+                    implausible.push((child_hash, *hash));
+                }
+            }
+        }
 
-        // Remove the peer from the list of active peers.
-        self.peers.write().await.remove(&peer_id);
+        for (hash, parent) in implausible {
+            warn!(
+                "Post {} claims a timestamp earlier than its parent {}",
+                hex::encode(hash),
+                hex::encode(parent)
+            );
+            self.emit(CableEvent::ImplausibleTimestamp { hash, parent })
+                .await;
+        }
+    }
+
+    /// Retrieve and decode the stored post with the given hash.
+    async fn get_stored_post(&self, hash: &Hash) -> Option<Post> {
+        let payload = self.store.get_post_payload(hash).await?;
+
+        Post::from_bytes(&payload).ok().map(|(_s, post)| post)
+    }
+
+    /// Update the channel state index of every channel whose state may be
+    /// changed by the given newly-stored post and send the hashes added to
+    /// the channel state to all peers holding live channel state requests.
+    ///
+    /// Moderation posts are not part of the channel state index; their hashes
+    /// are sent to peers holding live channel state requests for the channel
+    /// to which they apply as soon as they are stored.
+    async fn update_channel_state(&mut self, post: &Post, hash: &Hash) -> Result<(), Error> {
+        let channels = match &post.body {
+            PostBody::Join { channel }
+            | PostBody::Leave { channel }
+            | PostBody::Topic { channel, .. } => vec![channel.to_owned()],
+            // Info and delete posts do not have a channel and may change the
+            // state of any channel.
+            PostBody::Info { .. } | PostBody::Delete { .. } => {
+                self.store.get_channels().await.unwrap_or_default()
+            }
+            PostBody::Role { channel, .. } | PostBody::Moderation { channel, .. } => {
+                return self.send_channel_state_hashes(channel, vec![*hash]).await;
+            }
+            PostBody::Block { .. } | PostBody::Unblock { .. } => {
+                return self
+                    .send_channel_state_hashes(&Channel::new(), vec![*hash])
+                    .await;
+            }
+            _ => return Ok(()),
+        };
+
+        for channel in channels {
+            if let Some(mut hashes) = self.store.update_channel_state(&channel).await {
+                // Send the hash of a delete post which changed the channel
+                // state, allowing the peer to remove the deleted post.
+                if let PostBody::Delete { .. } = post.body {
+                    hashes.push(*hash);
+                }
+                self.send_channel_state_hashes(&channel, hashes).await?;
+            }
+        }
 
         Ok(())
     }
-    pub async fn get_peer_ids(&self) -> Vec<usize> {
-        self.peers
-            .read()
+
+    /// Send the given hashes to all peers holding live channel state requests
+    /// for the given channel. An empty channel name denotes the whole cabal
+    /// and matches the requests for every channel.
+    async fn send_channel_state_hashes(
+        &self,
+        channel: &Channel,
+        hashes: Vec<Hash>,
+    ) -> Result<(), Error> {
+        if hashes.is_empty() {
+            return Ok(());
+        }
+
+        for (peer_id, live_requests) in self.live_requests.read().await.iter() {
+            for live_request in live_requests {
+                if let LiveRequest::ChannelState(req_id, req_channel) = live_request {
+                    if channel.is_empty() || req_channel == channel {
+                        debug!(
+                            "Matched channel state live request: {}",
+                            hex::encode(req_id)
+                        );
+
+                        let response = Message::hash_response(NO_CIRCUIT, *req_id, hashes.clone());
+                        self.send(*peer_id, &response).await?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Omit the payloads of any posts which are not served to remote peers
+    /// according to the moderation state.
+    async fn served_payloads(&self, payloads: Vec<Payload>) -> Vec<Payload> {
+        let state = self.moderation_state().await;
+
+        payloads
+            .into_iter()
+            .filter(|payload| match Post::from_bytes(payload) {
+                Ok((_s, post)) => post
+                    .hash()
+                    .map(|hash| state.serves(&post, &hash))
+                    .unwrap_or(true),
+                Err(_) => true,
+            })
+            .collect()
+    }
+
+    /// Broadcast the given request of local origin to all peers, returning
+    /// the request ID.
+    ///
+    /// The request is also sent to peers who connect while the request is
+    /// active. It remains active until it has been concluded by all peers to
+    /// whom it was sent, or until it is cancelled.
+    pub async fn send_request(&self, request: Message) -> Result<ReqId, Error> {
+        let req_id = request.header.req_id;
+
+        self.outbound_requests
+            .write()
             .await
-            .keys()
-            .copied()
-            .collect::<Vec<usize>>()
+            .insert(req_id, (RequestOrigin::Local, request.clone()));
+        self.pending_requests
+            .write()
+            .await
+            .insert(req_id, PendingRequest::new());
+
+        for peer_id in self.get_peer_ids().await {
+            self.send_local_request(peer_id, req_id, &request).await?;
+        }
+
+        Ok(req_id)
+    }
+
+    /// Send a request of local origin to the given peer, recording the peer
+    /// as a recipient from whom a response is awaited.
+    async fn send_local_request(
+        &self,
+        peer_id: PeerId,
+        req_id: ReqId,
+        msg: &Message,
+    ) -> Result<(), Error> {
+        if let Some(pending_request) = self.pending_requests.write().await.get_mut(&req_id) {
+            pending_request.add_peer(peer_id);
+        }
+
+        self.send(peer_id, msg).await
+    }
+
+    /// Broadcast a cancel request for the outbound request of local origin
+    /// with the given ID and retire the request.
+    pub async fn cancel_request(&self, cancel_id: &ReqId) -> Result<(), Error> {
+        let (_req_id, req_id_bytes) = self.new_req_id().await?;
+        let ttl = self.ttl(CANCEL_REQUEST).await;
+        let request = Message::cancel_request(NO_CIRCUIT, req_id_bytes, ttl, *cancel_id);
+        self.broadcast(&request).await?;
+
+        self.retire_request(cancel_id).await;
+
+        Ok(())
     }
 
-    pub async fn get_links(&mut self, channel: &Channel) -> Option<Vec<Hash>> {
-        self.store.get_latest_hashes(channel).await
-    }
+    /// Wait until the outbound request of local origin with the given ID has
+    /// been concluded by all peers to whom it was sent.
+    ///
+    /// Returns immediately if the request is not active. Resolves without
+    /// the request having been concluded once no peers remain from whom a
+    /// response is awaited, or once the request deadline configured with
+    /// `RequestConfig` has passed. A live request is only concluded once it
+    /// has been cancelled.
+    pub async fn await_request(&self, req_id: &ReqId) -> RequestOutcome {
+        loop {
+            let outbound_requests = self.outbound_requests.read().await;
+            let pending_requests = self.pending_requests.read().await;
+            let (concluded, abandoned, deadline) = match pending_requests.get(req_id) {
+                Some(pending_request) if pending_request.peers.is_empty() => {
+                    return RequestOutcome::NoPeers
+                }
+                Some(pending_request) => {
+                    let timeout = match outbound_requests
+                        .get(req_id)
+                        .map(|(_, request)| &request.body)
+                    {
+                        Some(MessageBody::Request {
+                            body: RequestBody::Post { .. },
+                            ..
+                        }) => self.config.requests.post_timeout,
+                        _ => self.config.requests.request_timeout,
+                    };
+                    (
+                        pending_request.concluded.clone(),
+                        pending_request.abandoned.clone(),
+                        pending_request.created + timeout,
+                    )
+                }
+                None => return RequestOutcome::Concluded,
+            };
+            drop(pending_requests);
+            drop(outbound_requests);
+
+            // Each channel is closed once the respective condition is met. A
+            // request which has expired is retired without being concluded.
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let _ = future::timeout(remaining, concluded.recv().race(abandoned.recv())).await;
+            if Instant::now() >= deadline {
+                return RequestOutcome::TimedOut;
+            }
+        }
+    }
+
+    /// Record that the given peer has concluded the outbound request of local
+    /// origin with the given ID, retiring the request once all peers to whom
+    /// it was sent have concluded it.
+    async fn conclude_request(&self, peer_id: PeerId, req_id: &ReqId) {
+        let mut pending_requests = self.pending_requests.write().await;
+        let concluded = match pending_requests.get_mut(req_id) {
+            Some(pending_request) => {
+                pending_request.peers.remove(&peer_id);
+                pending_request.peers.is_empty()
+            }
+            None => false,
+        };
+        drop(pending_requests);
+
+        if concluded {
+            debug!("Request {} has been concluded", hex::encode(req_id));
+            self.retire_request(req_id).await;
+        }
+    }
+
+    /// Remove the outbound request with the given ID, notifying those
+    /// awaiting completion of the request.
+    async fn retire_request(&self, req_id: &ReqId) {
+        self.outbound_requests.write().await.remove(req_id);
+        self.pending_requests.write().await.remove(req_id);
+    }
+
+    /// Register a new peer, returning the assigned peer ID and the receiver
+    /// for all messages to be sent to the peer.
+    async fn add_peer(&self) -> Result<(PeerId, channel::Receiver<Message>), Error> {
+        // Generate a new peer ID.
+        let peer_id = self.new_peer_id().await?;
+
+        // Create a bounded message channel.
+        let (send, recv) = channel::bounded(100);
+
+        // Insert the peer ID and channel sender into the list of peers.
+        self.peers.write().await.insert(peer_id, send);
+
+        // Start sweeping expired requests, if not already underway.
+        let mut sweeping = self.sweeping.lock().await;
+        if !*sweeping {
+            *sweeping = true;
+
+            let this = self.clone();
+            task::spawn(async move { this.sweep().await });
+        }
+
+        Ok((peer_id, recv))
+    }
+
+    /// Periodically retry or discard expired requests until no peers remain
+    /// connected.
+    async fn sweep(&self) {
+        loop {
+            // Nothing is ever sent on the channel; it is only closed once the
+            // manager is being closed, at which point sweeping stops.
+            let interval = self.config.requests.sweep_interval;
+            let closing = future::timeout(interval, self.closing.recv()).await.is_ok();
+
+            let mut sweeping = self.sweeping.lock().await;
+            if closing || self.peers.read().await.is_empty() {
+                *sweeping = false;
+                break;
+            }
+            drop(sweeping);
+
+            if let Err(err) = self.expire_requests().await {
+                warn!("Failed to expire requests: {}", err);
+            }
+
+            // Apply the retention policy once the interval has elapsed.
+            let retention = &self.config.retention;
+            let mut pruned_at = self.pruned_at.lock().await;
+            if retention.is_enabled() && pruned_at.elapsed() >= retention.interval {
+                *pruned_at = Instant::now();
+                drop(pruned_at);
+
+                if let Err(err) = self.clone().prune().await {
+                    warn!("Failed to prune posts: {}", err);
+                }
+            }
+        }
+    }
+
+    /// Prune the stored posts according to the configured retention policy
+    /// and return the hashes of all pruned posts.
+    ///
+    /// The policy is applied periodically while peers are connected; this
+    /// method can be used to apply it at a specific point in time.
+    pub async fn prune(&mut self) -> Result<Vec<Hash>, Error> {
+        let pruned = self.store.prune(&self.config.retention, now()?).await;
+        if !pruned.is_empty() {
+            debug!("Pruned {} posts", pruned.len());
+        }
+
+        Ok(pruned)
+    }
+
+    /// Retry or discard expired requests and discard stale request state.
+    ///
+    /// A post request which has not been answered with all requested posts
+    /// before the deadline is retried with another connected peer, up to the
+    /// configured number of retries; the remaining posts are no longer
+    /// requested once all retries have been exhausted. Any other non-live
+    /// request is discarded once the deadline has passed, both for requests
+    /// of local and remote origin.
+    pub async fn expire_requests(&self) -> Result<(), Error> {
+        let config = &self.config.requests;
+        let now = Instant::now();
+
+        // Collect expired requests of local origin.
+        let outbound_requests = self.outbound_requests.read().await;
+        let expired_requests: Vec<(ReqId, Message, HashSet<PeerId>, usize)> = self
+            .pending_requests
+            .read()
+            .await
+            .iter()
+            .filter_map(|(req_id, pending_request)| {
+                let (_request_origin, request) = outbound_requests.get(req_id)?;
+                let timeout = match request.body {
+                    MessageBody::Request {
+                        body: RequestBody::Post { .. },
+                        ..
+                    } => config.post_timeout,
+                    _ => config.request_timeout,
+                };
+                if is_live(request) || now.duration_since(pending_request.created) < timeout {
+                    return None;
+                }
+
+                Some((
+                    *req_id,
+                    request.clone(),
+                    pending_request.tried_peers.clone(),
+                    pending_request.retries,
+                ))
+            })
+            .collect();
+        drop(outbound_requests);
+
+        for (req_id, request, tried_peers, retries) in expired_requests {
+            debug!("Request {} has expired", hex::encode(req_id));
+            self.retire_request(&req_id).await;
+
+            if let MessageBody::Request {
+                body: RequestBody::Post { hashes },
+                ..
+            } = request.body
+            {
+                self.retry_post_request(hashes, tried_peers, retries)
+                    .await?;
+            }
+        }
+
+        // Discard expired requests of remote origin, along with their
+        // routing state. The deadline is measured from the last time a
+        // message with the request ID was handled.
+        let handled_requests = self.handled_requests.read().await;
+        let expired_req_ids: Vec<ReqId> = self
+            .outbound_requests
+            .read()
+            .await
+            .iter()
+            .filter(|(req_id, (request_origin, request))| match request_origin {
+                RequestOrigin::Remote(origin) if !is_live(request) => handled_requests
+                    .last_handled(*origin, req_id)
+                    .is_none_or(|handled| now.duration_since(handled) >= config.request_timeout),
+                _ => false,
+            })
+            .map(|(req_id, _)| *req_id)
+            .collect();
+        drop(handled_requests);
+
+        let mut outbound_requests = self.outbound_requests.write().await;
+        for req_id in expired_req_ids {
+            debug!("Forwarded request {} has expired", hex::encode(req_id));
+            outbound_requests.remove(&req_id);
+        }
+
+        // Discard routing state of requests which are no longer active.
+        self.forwarded_requests
+            .write()
+            .await
+            .retain(|req_id, _peers| outbound_requests.contains_key(req_id));
+        drop(outbound_requests);
+
+        // Discard the IDs of requests handled long ago.
+        self.handled_requests.write().await.prune();
+
+        Ok(())
+    }
+
+    /// Request the given posts from a connected peer to whom the request has
+    /// not yet been sent, unless the maximum number of retries has been
+    /// reached.
+    ///
+    /// Posts which have since been received are not requested again. Posts
+    /// which cannot be requested are removed from the list of requested
+    /// posts, allowing them to be requested again once their hashes are next
+    /// received.
+    async fn retry_post_request(
+        &self,
+        hashes: Vec<Hash>,
+        tried_peers: HashSet<PeerId>,
+        retries: usize,
+    ) -> Result<(), Error> {
+        let requested_posts = self.requested_posts.read().await;
+        let hashes: Vec<Hash> = hashes
+            .into_iter()
+            .filter(|hash| requested_posts.contains(hash))
+            .collect();
+        drop(requested_posts);
+
+        if hashes.is_empty() {
+            return Ok(());
+        }
+
+        let peer_id = self
+            .get_peer_ids()
+            .await
+            .into_iter()
+            .filter(|peer_id| !tried_peers.contains(peer_id))
+            .min();
+
+        match peer_id {
+            Some(peer_id) if retries < self.config.requests.max_retries => {
+                debug!("Retrying post request with peer {}", peer_id);
+
+                let (_req_id, req_id) = self.new_req_id().await?;
+                let ttl = self.ttl(POST_REQUEST).await;
+                let request = Message::post_request(NO_CIRCUIT, req_id, ttl, hashes);
+
+                let mut pending_request = PendingRequest::new();
+                pending_request.tried_peers = tried_peers;
+                pending_request.retries = retries + 1;
+
+                self.outbound_requests
+                    .write()
+                    .await
+                    .insert(req_id, (RequestOrigin::Local, request.clone()));
+                self.pending_requests
+                    .write()
+                    .await
+                    .insert(req_id, pending_request);
+
+                self.send_local_request(peer_id, req_id, &request).await
+            }
+            _ => {
+                debug!("Abandoning post request for {} posts", hashes.len());
+
+                let mut requested_posts = self.requested_posts.write().await;
+                for hash in &hashes {
+                    requested_posts.remove(hash);
+                }
+
+                Ok(())
+            }
+        }
+    }
+
+    /// Remove the peer from the list of active peers.
+    ///
+    /// This drops the channel sender for the peer, thereby terminating the
+    /// task responsible for writing messages to the peer stream.
+    ///
+    /// Live requests of the peer are dropped. Outbound requests of local
+    /// origin no longer await a response from the peer, but remain active
+    /// and are sent to peers who connect later.
+    async fn remove_peer(&self, peer_id: PeerId) {
+        self.peers.write().await.remove(&peer_id);
+        self.peer_public_keys.write().await.remove(&peer_id);
+        self.peer_limits.write().await.remove(&peer_id);
+        self.live_requests.write().await.remove(&peer_id);
+
+        for pending_request in self.pending_requests.write().await.values_mut() {
+            pending_request.remove_peer(&peer_id);
+        }
+
+        if let Err(err) = self.remove_peer_routes(peer_id).await {
+            debug!("Failed to remove routes for peer {}: {}", peer_id, err);
+        }
+    }
+
+    /// Remove all request routing state associated with the given peer.
+    ///
+    /// The peer is removed from the set of peers to whom each request was
+    /// forwarded; a request is concluded on behalf of the originating peer
+    /// once no peers remain. Requests which originated from the peer are
+    /// cancelled and a cancel request is sent to all peers to whom they were
+    /// forwarded, since there is no longer a path over which to relay
+    /// responses.
+    async fn remove_peer_routes(&self, peer_id: PeerId) -> Result<(), Error> {
+        let mut concluded_req_ids = Vec::new();
+        let mut forwarded_requests = self.forwarded_requests.write().await;
+        for (req_id, peers) in forwarded_requests.iter_mut() {
+            if peers.remove(&peer_id) && peers.is_empty() {
+                concluded_req_ids.push(*req_id);
+            }
+        }
+        for req_id in &concluded_req_ids {
+            forwarded_requests.remove(req_id);
+        }
+        drop(forwarded_requests);
+
+        for req_id in concluded_req_ids {
+            let request = self.outbound_requests.read().await.get(&req_id).cloned();
+            if let Some((RequestOrigin::Remote(origin), request)) = request {
+                if origin != peer_id && expects_hash_response(&request) {
+                    self.end_response(origin, request.header.circuit_id, req_id)
+                        .await?;
+                }
+            }
+        }
+
+        let orphaned_req_ids: Vec<ReqId> = self
+            .outbound_requests
+            .read()
+            .await
+            .iter()
+            .filter_map(|(req_id, (request_origin, _msg))| match request_origin {
+                RequestOrigin::Remote(origin) if *origin == peer_id => Some(*req_id),
+                _ => None,
+            })
+            .collect();
+
+        for req_id in orphaned_req_ids {
+            let (_req_id, req_id_bytes) = self.new_req_id().await?;
+            let ttl = self.ttl(CANCEL_REQUEST).await;
+            let request = Message::cancel_request(NO_CIRCUIT, req_id_bytes, ttl, req_id);
+            self.cancel_forwarded_request(&req_id, &request).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Retrieve the secret key of the local peer without requiring a mutable
+    /// reference to the manager.
+    pub(crate) async fn get_local_secret_key(&self) -> [u8; 64] {
+        let (_pk, sk) = self.get_keypair().await;
+
+        sk
+    }
+
+    /// Spawn a task to handle a message received from the given peer.
+    ///
+    /// Waits for a handler slot to become available if the maximum number of
+    /// messages from the peer are already being handled.
+    async fn spawn_handler(&self, peer_id: PeerId, msg: Message, slots: &HandlerSlots) {
+        let slot = slots.take().await;
+
+        let mut this = self.clone();
+        task::spawn(async move {
+            // The slot is freed once the task ends, even if it panics.
+            let _slot = slot;
+
+            // Handle the received message.
+            if let Err(err) = this.handle(peer_id, &msg).await {
+                warn!("Failed to handle message from peer {}: {}", peer_id, err);
+                this.emit(CableEvent::HandlerError {
+                    peer_id,
+                    error: err.to_string(),
+                })
+                .await;
+            }
+        });
+    }
+
+    /// Listen for incoming peer messages and respond with locally-generated
+    /// messages.
+    ///
+    /// Decode each received message and pass it off to the handler.
+    ///
+    /// Messages are read from and written to the stream without encryption.
+    /// See `listen_secure()` for an authenticated and encrypted alternative.
+    pub async fn listen<T>(&self, stream: T) -> Result<(), Error>
+    where
+        T: AsyncRead + AsyncWrite + Clone + Unpin + Send + Sync + 'static,
+    {
+        debug!("Listening for incoming peer messages...");
+
+        let (peer_id, recv) = self.add_peer().await?;
+        self.emit(CableEvent::PeerConnected {
+            peer_id,
+            public_key: None,
+        })
+        .await;
+
+        // Closed when the writer task terminates.
+        let (writer_done_send, writer_done_recv) = channel::bounded::<()>(1);
+
+        let write_to_stream_res = {
+            let mut stream_c = stream.clone();
+
+            task::spawn(async move {
+                let _writer_done_send = writer_done_send;
+
+                // Listen for incoming locally-generated messages.
+                while let Ok(msg) = recv.recv().await {
+                    let msg_bytes = &msg.to_bytes()?;
+
+                    // Write the message to the stream.
+                    stream_c.write_all(msg_bytes).await?;
+
+                    debug!("Wrote a message to the TCP stream: {}", msg,);
+                }
+
+                // Close the stream for writing. The stream may already have
+                // been closed by the peer.
+                let _ = futures::AsyncWriteExt::close(&mut stream_c).await;
+
+                // Type inference fails without binding concretely to `Result`.
+                Result::<(), Error>::Ok(())
+            })
+        };
+
+        let slots = HandlerSlots::new(self.config.limits.max_concurrent_messages);
+
+        let read_from_stream_res = async {
+            // Process and send outbound requests to the connected peer.
+            self.process_and_send_outbound_requests(peer_id).await?;
+
+            // Define the stream decoder parameters.
+            let options = DecodeOptions {
+                include_len: true,
+                ..Default::default()
+            };
+
+            let mut length_prefixed_stream = decode_with_options(stream, options);
+
+            // Iterate over the stream.
+            while let Some(read_buf) = length_prefixed_stream.next().await {
+                let buf = read_buf?;
+
+                // Deserialize the received message.
+                let (_, msg) = Message::from_bytes(&buf)?;
+
+                debug!("Received a message from the TCP stream: {}", msg,);
+
+                self.spawn_handler(peer_id, msg, &slots).await;
+            }
+
+            Result::<(), Error>::Ok(())
+        }
+        // Stop reading once the writer task has terminated, for example
+        // because the peer was disconnected locally. Unlike an encrypted
+        // stream, an unencrypted stream has no end-of-stream marker with
+        // which to notify the peer; dropping the stream closes the
+        // connection instead.
+        .race(async {
+            let _ = writer_done_recv.recv().await;
+            Ok(())
+        })
+        .await;
+
+        // Continue reading and writing to the peer stream until the stream is
+        // closed (either intentionally or because of an error). Messages
+        // which have already been received are handled before the peer is
+        // removed, such that requests concluded by the peer are not
+        // abandoned. Removing the peer terminates the writer task.
+        slots.drain().await;
+        self.remove_peer(peer_id).await;
+        self.emit(CableEvent::PeerDisconnected { peer_id }).await;
+        write_to_stream_res.await?;
+
+        read_from_stream_res
+    }
+
+    /// Perform a cable handshake over the given stream and then listen for
+    /// incoming peer messages, responding with locally-generated messages.
+    ///
+    /// The handshake authenticates both peers using the given cabal key (the
+    /// pre-shared key) and the local keypair. All subsequent messages are
+    /// encrypted and framed according to the cable handshake specification.
+    /// The handshake extensions enabled in the manager configuration must be
+    /// enabled by the remote peer as well.
+    ///
+    /// The peer initiating the connection should take the `Initiator` role,
+    /// while the peer accepting the connection should take the `Responder`
+    /// role.
+    pub async fn listen_secure<T>(
+        &self,
+        mut stream: T,
+        cabal_key: [u8; 32],
+        role: Role,
+    ) -> Result<(), Error>
+    where
+        T: AsyncRead + AsyncWrite + Clone + Unpin + Send + Sync + 'static,
+    {
+        debug!("Performing handshake as {:?}...", role);
+
+        let private_key = self.get_local_secret_key().await;
+        let version = Version::init(HANDSHAKE_MAJOR_VERSION, HANDSHAKE_MINOR_VERSION);
+
+        let handshake = match role {
+            Role::Initiator => {
+                handshake::client_with_extensions(
+                    &mut stream,
+                    version,
+                    cabal_key,
+                    private_key,
+                    self.config.handshake_extensions,
+                )
+                .await?
+            }
+            Role::Responder => {
+                handshake::server_with_extensions(
+                    &mut stream,
+                    version,
+                    cabal_key,
+                    private_key,
+                    self.config.handshake_extensions,
+                )
+                .await?
+            }
+        };
+
+        self.listen_encrypted(stream, handshake).await
+    }
+
+    /// Listen for incoming peer messages and respond with locally-generated
+    /// messages, encrypting and decrypting all messages using the given
+    /// completed handshake.
+    pub(crate) async fn listen_encrypted<T>(
+        &self,
+        mut stream: T,
+        handshake: Handshake<HandshakeComplete>,
+    ) -> Result<(), Error>
+    where
+        T: AsyncRead + AsyncWrite + Clone + Unpin + Send + Sync + 'static,
+    {
+        debug!("Handshake complete; listening for incoming peer messages...");
+
+        let remote_public_key = handshake.get_remote_public_key();
+
+        // The encryption state is shared by the reader and the writer.
+        let handshake = Arc::new(Mutex::new(handshake));
+
+        let (peer_id, recv) = self.add_peer().await?;
+
+        // Record the public key of the peer to allow the connection to be
+        // linked to the author of received posts.
+        //
+        // Only a single connection is permitted per remote public key; the
+        // connection is closed if one has already been established.
+        if let Some(public_key) = remote_public_key {
+            let mut peer_public_keys = self.peer_public_keys.write().await;
+            if peer_public_keys.values().any(|pk| pk == &public_key) {
+                drop(peer_public_keys);
+                debug!(
+                    "Closing duplicate connection to {}",
+                    hex::encode(public_key)
+                );
+                self.remove_peer(peer_id).await;
+
+                return Err(ConnectionError::DuplicatePublicKey.into());
+            }
+            peer_public_keys.insert(peer_id, public_key);
+        }
+        self.emit(CableEvent::PeerConnected {
+            peer_id,
+            public_key: remote_public_key,
+        })
+        .await;
+
+        // Closed when the writer task terminates.
+        let (writer_done_send, writer_done_recv) = channel::bounded::<()>(1);
+
+        let write_to_stream_res = {
+            let mut stream_c = stream.clone();
+            let handshake_c = handshake.clone();
+
+            task::spawn(async move {
+                let _writer_done_send = writer_done_send;
+
+                // Listen for incoming locally-generated messages.
+                while let Ok(msg) = recv.recv().await {
+                    let msg_bytes = &msg.to_bytes()?;
+
+                    // Encrypt the message without holding the lock while
+                    // writing to the stream.
+                    let (_len, encrypted_msg) =
+                        handshake_c.lock().await.write_message(msg_bytes)?;
+                    write_encrypted_message_to_async_stream(&mut stream_c, &encrypted_msg).await?;
+
+                    debug!("Wrote an encrypted message to the stream: {}", msg,);
+                }
+
+                // Notify the peer that no further messages will be sent.
+                // The stream may already have been closed by the peer.
+                let _ = handshake_c
+                    .lock()
+                    .await
+                    .write_eos_marker_to_async_stream(&mut stream_c)
+                    .await;
+                let _ = futures::AsyncWriteExt::close(&mut stream_c).await;
+
+                // Type inference fails without binding concretely to `Result`.
+                Result::<(), Error>::Ok(())
+            })
+        };
+
+        let slots = HandlerSlots::new(self.config.limits.max_concurrent_messages);
+
+        let read_from_stream_res = async {
+            // Process and send outbound requests to the connected peer.
+            self.process_and_send_outbound_requests(peer_id).await?;
+
+            loop {
+                // Read the encrypted message without holding the lock, such
+                // that messages can be written while awaiting the next
+                // message. Messages exceeding the maximum frame size are
+                // refused before any memory is allocated for them.
+                let recv_buf = match read_encrypted_message_from_async_stream(
+                    &mut stream,
+                    self.config.limits.max_frame_size,
+                )
+                .await
+                {
+                    Ok(recv_buf) => recv_buf,
+                    // The stream was closed without an end-of-stream marker.
+                    Err(err)
+                        if err
+                            .downcast_ref::<io::Error>()
+                            .is_some_and(|err| err.kind() == ErrorKind::UnexpectedEof) =>
+                    {
+                        break
+                    }
+                    Err(err) => return Err(err),
+                };
+
+                // A zero-length message is an end-of-stream marker.
+                if recv_buf.is_empty() {
+                    debug!("Received end-of-stream marker from peer {}", peer_id);
+                    break;
+                }
+
+                // Decrypt and deserialize the received message.
+                let buf = handshake
+                    .lock()
+                    .await
+                    .read_message(&recv_buf, recv_buf.len() as u32)?;
+                let (_, msg) = Message::from_bytes(&buf)?;
+
+                debug!("Received an encrypted message from the stream: {}", msg,);
+
+                self.spawn_handler(peer_id, msg, &slots).await;
+            }
+
+            Result::<(), Error>::Ok(())
+        }
+        // Stop reading once the writer task has terminated, for example
+        // because the peer was disconnected locally. The writer task has
+        // already notified the peer with an end-of-stream marker.
+        .race(async {
+            let _ = writer_done_recv.recv().await;
+            Ok(())
+        })
+        .await;
+
+        // Messages which have already been received are handled before the
+        // peer is removed. Removing the peer terminates the writer task.
+        slots.drain().await;
+        self.remove_peer(peer_id).await;
+        self.emit(CableEvent::PeerDisconnected { peer_id }).await;
+        write_to_stream_res.await?;
+
+        read_from_stream_res
+    }
+
+    /// Retrieve the ed25519 public key of the peer identified by the given
+    /// peer ID.
+    ///
+    /// The public key is only known for peers connected via
+    /// `listen_secure()`, since it is exchanged during the handshake.
+    pub async fn get_peer_public_key(&self, peer_id: PeerId) -> Option<PublicKey> {
+        self.peer_public_keys.read().await.get(&peer_id).copied()
+    }
+
+    /// Retrieve the ID of the peer with the given ed25519 public key.
+    pub async fn get_peer_id(&self, public_key: &PublicKey) -> Option<PeerId> {
+        self.peer_public_keys
+            .read()
+            .await
+            .iter()
+            .find(|(_peer_id, pk)| *pk == public_key)
+            .map(|(peer_id, _pk)| *peer_id)
+    }
+
+    /// Disconnect the peer identified by the given peer ID.
+    ///
+    /// No further messages are sent to the peer and the stream is closed for
+    /// writing. The listener for the peer stops reading from the stream and
+    /// returns.
+    pub async fn disconnect(&self, peer_id: PeerId) {
+        debug!("Disconnecting peer {}", peer_id);
+        self.remove_peer(peer_id).await;
+    }
+
+    /// Query whether a request of the given message type from the given peer
+    /// is within the configured rate limits, recording a violation if not.
+    ///
+    /// Requests from peers who are no longer connected are not admitted.
+    async fn admit_request(&self, peer_id: PeerId, msg_type: u64) -> bool {
+        // Query the peers while holding the lock on the limits, ensuring the
+        // limits of a peer are not recreated after the peer is removed.
+        let mut peer_limits = self.peer_limits.write().await;
+        if !self.peers.read().await.contains_key(&peer_id) {
+            return false;
+        }
+        let admitted = peer_limits
+            .entry(peer_id)
+            .or_default()
+            .admit(&self.config.limits, msg_type);
+        drop(peer_limits);
+
+        if !admitted {
+            self.record_violation(peer_id).await;
+        }
+
+        admitted
+    }
+
+    /// Record that the given peer has exceeded a limit, disconnecting the
+    /// peer once the maximum number of violations has been reached.
+    async fn record_violation(&self, peer_id: PeerId) {
+        let mut peer_limits = self.peer_limits.write().await;
+        if !self.peers.read().await.contains_key(&peer_id) {
+            return;
+        }
+        let exceeded = peer_limits
+            .entry(peer_id)
+            .or_default()
+            .violate(&self.config.limits);
+        drop(peer_limits);
+
+        if exceeded {
+            warn!("Peer {} repeatedly exceeded limits", peer_id);
+            self.disconnect(peer_id).await;
+        }
+    }
+
+    /// Conclude all requests and disconnect all peers.
+    ///
+    /// A hash response with no hashes is sent for each live request to which
+    /// the local peer is responding, and a cancel request is broadcast for
+    /// each active outbound request of local origin. Messages queued before
+    /// the peers are disconnected are still written to the peer streams.
+    pub async fn shutdown(&self) -> Result<(), Error> {
+        debug!("Shutting down");
+
+        let live_requests: Vec<(PeerId, ReqId)> = self
+            .live_requests
+            .write()
+            .await
+            .drain()
+            .flat_map(|(peer_id, peer_requests)| {
+                peer_requests
+                    .into_iter()
+                    .map(move |live_request| (peer_id, *live_request.req_id()))
+            })
+            .collect();
+        for (peer_id, req_id) in live_requests {
+            let response = Message::hash_response(NO_CIRCUIT, req_id, Vec::new());
+            self.send(peer_id, &response).await?;
+        }
+
+        let local_req_ids: Vec<ReqId> = self
+            .outbound_requests
+            .read()
+            .await
+            .iter()
+            .filter(|(_req_id, (request_origin, _msg))| request_origin.is_local())
+            .map(|(req_id, _)| *req_id)
+            .collect();
+        for req_id in local_req_ids {
+            self.cancel_request(&req_id).await?;
+        }
+
+        for peer_id in self.get_peer_ids().await {
+            self.disconnect(peer_id).await;
+        }
+
+        Ok(())
+    }
+
+    /// Shut down the manager and wait until it is no longer in use,
+    /// returning the store.
+    ///
+    /// All requests are concluded and all peers are disconnected as with
+    /// `shutdown()`. Resolves once every clone of the manager has been
+    /// dropped, including those held by tasks spawned by the manager and by
+    /// listeners, which return once their peer has been disconnected. Any
+    /// other clones (for example, those held by a `Supervisor` or a
+    /// `CabalRegistry`) must be dropped for the returned future to resolve.
+    ///
+    /// The store is no longer accessed by the manager once it has been
+    /// returned, allowing a persistent store to be closed and reopened.
+    pub async fn close(self) -> Result<S, Error> {
+        self.shutdown().await?;
+        self.closing.close();
+
+        let released = self.released.clone();
+        let store = self.store.clone();
+        drop(self);
+
+        // The channel is closed once all senders have been dropped.
+        let _ = released.recv().await;
+
+        Ok(store)
+    }
+
+    pub async fn get_peer_ids(&self) -> Vec<usize> {
+        self.peers
+            .read()
+            .await
+            .keys()
+            .copied()
+            .collect::<Vec<usize>>()
+    }
+
+    pub async fn get_links(&mut self, channel: &Channel) -> Option<Vec<Hash>> {
+        self.store.get_latest_hashes(channel).await
+    }
+
+    /// Query if the request defined by the given peer ID and request ID is an
+    /// active live request.
+    async fn is_live_request(&self, peer_id: &PeerId, req_id: &ReqId) -> bool {
+        let live_requests = self.live_requests.read().await;
+        // Check if there are active live requests for the given peer ID.
+        if let Some(peer_requests) = live_requests.get(peer_id) {
+            // Iterate over the peer requests and report on the presence of the
+            // given request ID.
+            peer_requests
+                .iter()
+                .any(|live_request| live_request.req_id() == req_id)
+        } else {
+            false
+        }
+    }
+
+    /// Add a live request of the given peer, unless the peer has reached the
+    /// maximum number of live requests.
+    ///
+    /// A live request which is not added is concluded once the known hashes
+    /// have been sent, as if it were not a live request.
+    async fn add_live_request(&self, peer_id: PeerId, live_request: LiveRequest) {
+        let mut live_requests = self.live_requests.write().await;
+        // Live requests are not kept for peers who are no longer connected.
+        if !self.peers.read().await.contains_key(&peer_id) {
+            return;
+        }
+        let peer_requests = live_requests.entry(peer_id).or_default();
+
+        if peer_requests.len() >= self.config.limits.max_live_requests {
+            drop(live_requests);
+            debug!("Peer {} has reached the live request limit", peer_id);
+            self.record_violation(peer_id).await;
+
+            return;
+        }
+
+        // TODO: Only push if `peer_requests` does not already contain this
+        // request.
+        peer_requests.push(live_request);
+    }
+
+    /// Remove the live request defined by the given peer ID and request ID.
+    async fn remove_live_request(&mut self, peer_id: &PeerId, req_id: &ReqId) -> Result<(), Error> {
+        // Remove the request from the map of live requests.
+        let mut live_requests = self.live_requests.write().await;
+        if let Some(peer_requests) = live_requests.get_mut(peer_id) {
+            // Iterate over the peer requests and retain only the
+            // requests for which the ID does not match the given
+            // request ID.
+            peer_requests.retain(|live_request| live_request.req_id() != req_id);
+        }
+
+        Ok(())
+    }
+
+    /// Process all outbound requests, sending each one to the connected
+    /// peer if it meets certain requirements.
+    ///
+    /// Requests of remote origin are never sent back to the peer from whom
+    /// they originated. The connected peer is recorded as a recipient of any
+    /// request of remote origin, allowing responses to be relayed to the
+    /// originating peer and cancel requests to be forwarded.
+    pub async fn process_and_send_outbound_requests(&self, peer_id: PeerId) -> Result<(), Error> {
+        // Take a snapshot of the outbound requests to avoid holding the lock
+        // while sending.
+        let outbound_requests: Vec<(ReqId, RequestOrigin, Message)> = self
+            .outbound_requests
+            .read()
+            .await
+            .iter()
+            .map(|(req_id, (request_origin, msg))| (*req_id, *request_origin, msg.clone()))
+            .collect();
+
+        for (req_id, request_origin, msg) in outbound_requests {
+            match request_origin {
+                RequestOrigin::Local => self.send_local_request(peer_id, req_id, &msg).await?,
+                RequestOrigin::Remote(origin) => {
+                    if origin != peer_id {
+                        self.send_forwarded_request(peer_id, req_id, &msg).await?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Send a request of remote origin to the given peer, recording the peer
+    /// as a recipient of the request.
+    async fn send_forwarded_request(
+        &self,
+        peer_id: PeerId,
+        req_id: ReqId,
+        msg: &Message,
+    ) -> Result<(), Error> {
+        self.forwarded_requests
+            .write()
+            .await
+            .entry(req_id)
+            .or_default()
+            .insert(peer_id);
+
+        self.send(peer_id, msg).await
+    }
+
+    /// Decrement the TTL of a request received from the given peer, write it
+    /// to the outbound requests store and forward it to all other connected
+    /// peers.
+    ///
+    /// A TTL exceeding `MAX_TTL` is clamped to `MAX_TTL` before being
+    /// decremented.
+    ///
+    /// The request is also sent to peers who connect while the request is
+    /// active.
+    async fn forward_request(
+        &self,
+        origin: PeerId,
+        req_id: ReqId,
+        msg: &Message,
+    ) -> Result<(), Error> {
+        let mut request = msg.clone();
+        if let MessageBody::Request { ref mut ttl, .. } = request.body {
+            *ttl = (*ttl).min(MAX_TTL);
+        }
+        request.decrement_ttl();
+
+        self.outbound_requests
+            .write()
+            .await
+            .insert(req_id, (RequestOrigin::Remote(origin), request.clone()));
+
+        for peer_id in self.get_peer_ids().await {
+            if peer_id != origin {
+                self.send_forwarded_request(peer_id, req_id, &request)
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Forward the given cancel request to all peers to whom the referenced
+    /// request was forwarded, then remove all routing state for the
+    /// referenced request.
+    async fn cancel_forwarded_request(
+        &self,
+        cancel_id: &ReqId,
+        cancel_request: &Message,
+    ) -> Result<(), Error> {
+        self.outbound_requests.write().await.remove(cancel_id);
+
+        let peers = self.forwarded_requests.write().await.remove(cancel_id);
+        if let Some(peers) = peers {
+            for peer_id in peers {
+                self.send(peer_id, cancel_request).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Relay a response received from the given peer to the peer from whom
+    /// the associated request originated, if the request was forwarded to
+    /// the given peer by the local peer.
+    ///
+    /// A hash response with no hashes indicates that the responding peer has
+    /// concluded the request; the routing state for the peer is removed and
+    /// the empty response is relayed once all peers to whom the request was
+    /// forwarded have concluded, unless the local peer is still responding to
+    /// the request as a live request. Post and channel list responses
+    /// conclude the request for the responding peer once relayed.
+    async fn relay_response(
+        &self,
+        peer_id: PeerId,
+        req_id: &ReqId,
+        msg: &Message,
+    ) -> Result<(), Error> {
+        let origin = match self.outbound_requests.read().await.get(req_id) {
+            Some((RequestOrigin::Remote(origin), _request)) => *origin,
+            _ => return Ok(()),
+        };
+
+        let concluded = match &msg.body {
+            MessageBody::Response {
+                body: ResponseBody::Hash { hashes },
+            } => hashes.is_empty(),
+            MessageBody::Response { .. } => true,
+            _ => return Ok(()),
+        };
 
-    /// Query if the request defined by the given peer ID and request ID is an
-    /// active live request.
-    async fn is_live_request(&mut self, peer_id: &PeerId, req_id: &ReqId) -> bool {
-        let live_requests = self.live_requests.read().await;
-        // Check if there are active live requests for the given peer ID.
-        if let Some(peer_requests) = live_requests.get(peer_id) {
-            // Iterate over the peer requests and report on the presence of the
-            // given request ID.
-            peer_requests
-                .iter()
-                .any(|live_request| live_request.req_id() == req_id)
-        } else {
-            false
+        let mut forwarded_requests = self.forwarded_requests.write().await;
+        let peers = match forwarded_requests.get_mut(req_id) {
+            Some(peers) if peers.contains(&peer_id) => peers,
+            // The request was not forwarded to this peer.
+            _ => return Ok(()),
+        };
+
+        if !concluded {
+            drop(forwarded_requests);
+            debug!("Relaying response from peer {} to peer {}", peer_id, origin);
+
+            return self.send(origin, msg).await;
         }
-    }
 
-    /// Remove the live request defined by the given peer ID and request ID.
-    async fn remove_live_request(&mut self, peer_id: &PeerId, req_id: &ReqId) -> Result<(), Error> {
-        // Remove the request from the map of live requests.
-        let mut live_requests = self.live_requests.write().await;
-        if let Some(peer_requests) = live_requests.get_mut(peer_id) {
-            // Iterate over the peer requests and retain only the
-            // requests for which the ID does not match the given
-            // request ID.
-            peer_requests.retain(|live_request| live_request.req_id() != req_id);
+        peers.remove(&peer_id);
+        let forwarding_concluded = peers.is_empty();
+        if forwarding_concluded {
+            forwarded_requests.remove(req_id);
+        }
+        drop(forwarded_requests);
+
+        let relay = match &msg.body {
+            MessageBody::Response {
+                body: ResponseBody::Hash { .. },
+            } => forwarding_concluded && !self.is_live_request(&origin, req_id).await,
+            _ => true,
+        };
+
+        if relay {
+            debug!("Relaying response from peer {} to peer {}", peer_id, origin);
+            self.send(origin, msg).await?;
         }
 
         Ok(())
     }
 
-    /// Process all outbound requests, sending each one to the connected
-    /// peer if it meets certain requirements.
+    /// Send a hash response with no hashes to the given peer, indicating that
+    /// the local peer has concluded the request with the given ID.
     ///
-    /// This method takes into account the TTL of the request. It also ensures
-    /// that cancel requests are forwarded to peers to whom the referenced
-    /// request was previously sent.
-    pub async fn process_and_send_outbound_requests<T>(
+    /// If the request was forwarded to other peers who have not yet concluded
+    /// it, the response is instead relayed once they have done so. A live
+    /// request is only concluded once it has been cancelled.
+    async fn end_response(
         &self,
-        mut stream: T,
-        peer_id: usize,
-    ) -> Result<(), Error>
-    where
-        T: AsyncRead + AsyncWrite + Clone + Unpin + Send + Sync + 'static,
-    {
-        'requests: for (req_id, (request_origin, msg)) in self.outbound_requests.read().await.iter()
+        peer_id: PeerId,
+        circuit_id: CircuitId,
+        req_id: ReqId,
+    ) -> Result<(), Error> {
+        if self.forwarded_requests.read().await.contains_key(&req_id)
+            || self.is_live_request(&peer_id, &req_id).await
         {
-            if let MessageBody::Request { ttl, body } = &msg.body {
-                // If the outbound request is a cancel request originating
-                // remotely, check if we previously sent the referenced
-                // request to the connected peer. If so, forward the cancel
-                // request. If not, move on to the next request without sending
-                // this one.
-                if let RequestBody::Cancel { cancel_id } = body {
-                    debug!("Processing cancel request...");
-                    if let RequestOrigin::Remote = request_origin {
-                        let mut forwarded_requests = self.forwarded_requests.write().await;
-                        if let Some(peers) = forwarded_requests.get_mut(cancel_id) {
-                            if peers.contains(&peer_id) {
-                                stream.write_all(&msg.to_bytes()?).await?;
-
-                                // Remove the connected peer from the set of
-                                // forwarded requests for the given cancel ID.
-                                peers.remove(&peer_id);
-
-                                // If the peer set for given cancel ID is
-                                // empty, remove the ID from the map of
-                                // forwarded requests.
-                                if peers.is_empty() {
-                                    forwarded_requests.remove(cancel_id);
-                                }
-                            } else {
-                                // Terminate the current iteration of the loop
-                                // and process the next request.
-                                continue 'requests;
-                            }
-                        }
-                    }
-                }
-                if *ttl == 0 {
-                    // The TTL for this request has been exhausted.
-                    self.outbound_requests.write().await.remove(req_id);
-                } else {
-                    // Send the message to the connected peer.
-                    stream.write_all(&msg.to_bytes()?).await?;
-
-                    // If the request originated remotely, add it to the list
-                    // of forwarded requests. This facilitates forwarding
-                    // cancel requests to these peers in the future, if
-                    // required.
-                    if let RequestOrigin::Remote = request_origin {
-                        let mut forwarded_requests = self.forwarded_requests.write().await;
-                        if let Some(peers) = forwarded_requests.get_mut(req_id) {
-                            peers.insert(peer_id);
-                        } else {
-                            let mut peer_set = HashSet::new();
-                            peer_set.insert(peer_id);
-                            forwarded_requests.insert(*req_id, peer_set);
-                        }
-                    }
-                }
-            }
+            return Ok(());
         }
 
-        Ok(())
+        let response = Message::hash_response(circuit_id, req_id, Vec::new());
+
+        self.send(peer_id, &response).await
+    }
+
+    /// Retrieve the hashes of all moderation posts applying to the given
+    /// channel or to the whole cabal.
+    async fn get_moderation_hashes(&self, channel: &Channel) -> Vec<Hash> {
+        let mut hashes = self
+            .store
+            .get_moderation_hashes(&Channel::new())
+            .await
+            .unwrap_or_default();
+        if let Some(channel_hashes) = self.store.get_moderation_hashes(channel).await {
+            hashes.extend(channel_hashes);
+        }
+
+        hashes
     }
 
     /// Post header value generator.
@@ -504,16 +2253,9 @@ where
         let links = Vec::new();
         let timestamp = now()?;
 
-        // Add the hashes to the store of deleted posts.
-        //
-        // This helps to ensure these posts won't be re-added to the
-        // local store if they are ever returned by a remote peer.
-        let mut deleted_posts = self.deleted_posts.write().await;
-        deleted_posts.extend(&hashes);
-
-        // Drop the mutable borrow of `self` to allow the later
-        // call to `self.post()` (immutable borrow).
-        drop(deleted_posts);
+        // Tombstones are recorded for the given hashes when the post is
+        // stored, ensuring these posts won't be re-added to the local store
+        // if they are ever returned by a remote peer.
 
         // Construct a new delete post.
         let post = Post::delete(public_key, links, timestamp, hashes);
@@ -523,15 +2265,29 @@ where
 
     /// Publish a new info post with the given name and return the hash.
     pub async fn post_info_name(&mut self, username: &str) -> Result<Hash, Error> {
+        // Validation is performed as part of this method.
+        let name_info = UserInfo::name(username)?;
+
+        self.post_info(vec![name_info]).await
+    }
+
+    /// Publish a new info post with the given key-value pairs and return the
+    /// hash.
+    ///
+    /// Keys which are not included retain the value assigned by any previous
+    /// info post.
+    pub async fn post_info(&mut self, info: Vec<UserInfo>) -> Result<Hash, Error> {
         let public_key = self.get_public_key().await?;
         let links = Vec::new();
         let timestamp = now()?;
 
-        // Validation is performed as part of this method.
-        let name_info = UserInfo::name(username)?;
+        // Ensure each key and value is valid.
+        for user_info in &info {
+            user_info.validate()?;
+        }
 
         // Construct a new info post.
-        let post = Post::info(public_key, links, timestamp, vec![name_info]);
+        let post = Post::info(public_key, links, timestamp, info);
 
         self.post(post).await
     }
@@ -583,6 +2339,121 @@ where
         self.post(post).await
     }
 
+    /// Publish a new role post assigning the given role to the given public
+    /// key and return the hash.
+    ///
+    /// An empty channel name assigns the role for the whole cabal.
+    pub async fn post_role<T: Into<String>, U: Into<String>>(
+        &mut self,
+        channel: T,
+        recipient: &PublicKey,
+        role: u64,
+        reason: U,
+    ) -> Result<Hash, Error> {
+        let channel = channel.into();
+        let (public_key, links, timestamp) = self.moderation_header_values(&channel).await?;
+        let reason = reason.into();
+
+        // Ensure the reason is between 0 and 128 UTF-8 codepoints.
+        validation::validate_reason(&reason)?;
+
+        // Construct a new role post.
+        let post = Post::role(
+            public_key, links, timestamp, channel, *recipient, role, reason,
+        );
+
+        self.post(post).await
+    }
+
+    /// Publish a new moderation post applying the given action to the given
+    /// recipients (public keys or post hashes) and return the hash.
+    ///
+    /// An empty channel name applies the action to the whole cabal.
+    pub async fn post_moderation<T: Into<String>, U: Into<String>>(
+        &mut self,
+        channel: T,
+        recipients: Vec<Hash>,
+        action: u64,
+        reason: U,
+    ) -> Result<Hash, Error> {
+        let channel = channel.into();
+        let (public_key, links, timestamp) = self.moderation_header_values(&channel).await?;
+        let reason = reason.into();
+
+        // Ensure the reason is between 0 and 128 UTF-8 codepoints.
+        validation::validate_reason(&reason)?;
+
+        // Construct a new moderation post.
+        let post = Post::moderation(
+            public_key, links, timestamp, channel, recipients, action, reason,
+        );
+
+        self.post(post).await
+    }
+
+    /// Publish a new block post for the given public keys and return the
+    /// hash.
+    pub async fn post_block<T: Into<String>>(
+        &mut self,
+        recipients: Vec<PublicKey>,
+        drop: bool,
+        notify: bool,
+        reason: T,
+    ) -> Result<Hash, Error> {
+        let public_key = self.get_public_key().await?;
+        let links = Vec::new();
+        let timestamp = now()?;
+        let reason = reason.into();
+
+        // Ensure the reason is between 0 and 128 UTF-8 codepoints.
+        validation::validate_reason(&reason)?;
+
+        // Construct a new block post.
+        let post = Post::block(
+            public_key, links, timestamp, recipients, drop, notify, reason,
+        );
+
+        self.post(post).await
+    }
+
+    /// Publish a new unblock post for the given public keys and return the
+    /// hash.
+    pub async fn post_unblock<T: Into<String>>(
+        &mut self,
+        recipients: Vec<PublicKey>,
+        undrop: bool,
+        reason: T,
+    ) -> Result<Hash, Error> {
+        let public_key = self.get_public_key().await?;
+        let links = Vec::new();
+        let timestamp = now()?;
+        let reason = reason.into();
+
+        // Ensure the reason is between 0 and 128 UTF-8 codepoints.
+        validation::validate_reason(&reason)?;
+
+        // Construct a new unblock post.
+        let post = Post::unblock(public_key, links, timestamp, recipients, undrop, reason);
+
+        self.post(post).await
+    }
+
+    /// Post header value generator for moderation posts, which may apply to
+    /// a single channel or to the whole cabal (an empty channel name).
+    async fn moderation_header_values(
+        &mut self,
+        channel: &Channel,
+    ) -> Result<([u8; 32], Vec<Hash>, Timestamp), Error> {
+        if channel.is_empty() {
+            Ok((self.get_public_key().await?, Vec::new(), now()?))
+        } else {
+            // Ensure the channel name is between 1 and 64 UTF-8 codepoints.
+            validation::validate_channel(channel)?;
+
+            self.post_header_values(channel).await
+        }
+    }
+
     /// Publish a post and return the hash.
     pub async fn post(&mut self, mut post: Post) -> Result<Hash, Error> {
         // Sign the post if required.
@@ -591,39 +2462,116 @@ where
         }
 
         // Insert the post into the local store.
-        let hash = self.store.insert_post(&post).await?;
+        let hash = self.store_post(&post).await?;
 
-        // Send post hashes to all peers for whom we hold inbound requests.
+        // Send post hashes to all peers for whom we hold inbound channel
+        // time range requests. Peers holding live channel state requests
+        // have already been sent any changes to the channel state.
         if let Some(channel) = post.get_channel() {
             self.send_post_hashes(channel).await?;
-        } else {
-            // Info and delete post types do not have a channel.
-            //
-            // Retrieve all channels of which the author of the post is a
-            // member and send post hashes for those channels. This ensures
-            // that any "live" channel state requests for these channels
-            // receive the latest `post/info` and `post/delete` hashes.
-            let public_key = post.get_public_key();
-            if let Some(channels) = self.store.get_channels().await {
-                for channel in channels {
-                    if self.store.is_channel_member(&channel, &public_key).await {
-                        self.send_post_hashes(&channel).await?;
+        }
+
+        Ok(hash)
+    }
+
+    /// Insert a post into the local store, returning the hash, and emit
+    /// events describing the resulting changes to the store.
+    async fn store_post(&mut self, post: &Post) -> Result<Hash, Error> {
+        let has_subscribers = !self.subscribers.is_empty().await;
+
+        let is_new_channel = match post.get_channel() {
+            Some(channel) if has_subscribers => !self
+                .store
+                .get_channels()
+                .await
+                .is_some_and(|channels| channels.contains(channel)),
+            _ => false,
+        };
+
+        let (hash, deleted_hashes) = self.store.insert_post(post).await?;
+        self.update_moderation_state(post).await;
+        self.update_channel_state(post, &hash).await?;
+
+        if !has_subscribers {
+            return Ok(hash);
+        }
+
+        // A post which was deleted by its author before it arrived has been
+        // refused by the store.
+        if self.store.is_deleted(post, &hash).await {
+            return Ok(hash);
+        }
+
+        self.emit(CableEvent::PostStored {
+            hash,
+            post: post.clone(),
+        })
+        .await;
+
+        if is_new_channel {
+            if let Some(channel) = post.get_channel() {
+                self.emit(CableEvent::ChannelDiscovered {
+                    channel: channel.to_owned(),
+                })
+                .await;
+            }
+        }
+
+        let public_key = post.get_public_key();
+        match &post.body {
+            // Only report deletions which have been applied; the author of
+            // the `post/delete` post may not be the author of the referenced
+            // posts, or the posts may not have been received yet.
+            PostBody::Delete { .. } => {
+                for hash in deleted_hashes {
+                    self.emit(CableEvent::PostDeleted { hash }).await;
+                }
+            }
+            // Only report a topic change if the post defines the latest
+            // topic of the channel; an older topic post may have been
+            // received.
+            PostBody::Topic { channel, topic } => {
+                let latest_topic = self.store.get_channel_topic_and_hash(channel).await;
+                if latest_topic.is_some_and(|(_topic, topic_hash)| topic_hash == hash) {
+                    self.emit(CableEvent::TopicChanged {
+                        channel: channel.to_owned(),
+                        topic: topic.to_owned(),
+                        public_key,
+                    })
+                    .await;
+                }
+            }
+            // Likewise, only report membership changes which are reflected
+            // in the channel membership index.
+            PostBody::Join { channel } | PostBody::Leave { channel } => {
+                let is_member = self.store.is_channel_member(channel, &public_key).await;
+                let channel = channel.to_owned();
+                match (&post.body, is_member) {
+                    (PostBody::Join { .. }, true) => {
+                        self.emit(CableEvent::MemberJoined {
+                            channel,
+                            public_key,
+                        })
+                        .await
                     }
+                    (PostBody::Leave { .. }, false) => {
+                        self.emit(CableEvent::MemberLeft {
+                            channel,
+                            public_key,
+                        })
+                        .await
+                    }
+                    _ => {}
                 }
             }
+            _ => {}
         }
 
         Ok(hash)
     }
 
     /// Send post hashes matching peer request parameters for all live
-    /// requests.
-    ///
-    /// Live requests may have originated from a ChannelTimeRange (`time_end`
-    /// of 0) or a ChannelState request (`future` of 1).
-    ///
-    /// Hashes of `post/delete` and `post/info` posts are sent as part of
-    /// ChannelState responses.
+    /// ChannelTimeRange requests (`time_end` of 0).
     async fn send_post_hashes(&mut self, channel: &Channel) -> Result<(), Error> {
         // Iterate over all live peer requests.
         for (peer_id, live_requests) in self.live_requests.read().await.iter() {
@@ -634,75 +2582,9 @@ where
                 let mut hashes = Vec::new();
 
                 match live_request {
-                    LiveRequest::ChannelState(req_id, req_channel) => {
-                        debug!(
-                            "Matched channel state live request: {}",
-                            hex::encode(req_id)
-                        );
-
-                        // Only send hashes if the channel of the post which invoked
-                        // the call to `send_post_hashes()` matches the channel of
-                        // the peer request.
-                        if req_channel == channel {
-                            // Return all channel state post hashes for this channel.
-                            if let Some(mut channel_membership_hashes) =
-                                self.store.get_channel_membership_hashes(channel).await
-                            {
-                                hashes.append(&mut channel_membership_hashes)
-                            }
-
-                            // Return the channel topic post hash for this channel.
-                            let channel_topic_hash =
-                                self.store.get_channel_topic_and_hash(channel).await;
-                            if let Some((_topic, hash)) = channel_topic_hash {
-                                hashes.push(hash)
-                            }
-
-                            // Retrieve public keys of all channel members.
-                            if let Some(channel_members) =
-                                self.store.get_channel_members(channel).await
-                            {
-                                for public_key in channel_members {
-                                    // Return all delete post hashes for members of
-                                    // this channel.
-                                    if let Some(peer_delete_hashes) =
-                                        self.store.get_delete_hashes(&public_key).await
-                                    {
-                                        hashes.extend(peer_delete_hashes)
-                                    }
-
-                                    // Send the most-recent name-setting info
-                                    // post hash for each peer.
-                                    if let Some((_peer_name, peer_name_hash)) =
-                                        self.store.get_peer_name_and_hash(&public_key).await
-                                    {
-                                        hashes.push(peer_name_hash)
-                                    }
-                                }
-                            }
-
-                            // Retrieve public keys of all ex-channel members.
-                            if let Some(ex_channel_members) =
-                                self.store.get_ex_channel_members(channel).await
-                            {
-                                for public_key in ex_channel_members {
-                                    // Send the most-recent name-setting info
-                                    // post hash for each peer.
-                                    if let Some((_peer_name, peer_name_hash)) =
-                                        self.store.get_peer_name_and_hash(&public_key).await
-                                    {
-                                        hashes.push(peer_name_hash)
-                                    }
-                                }
-                            }
-
-                            // Construct a new hash response message.
-                            let response = Message::hash_response(NO_CIRCUIT, *req_id, hashes);
-
-                            // Send the response to the peer.
-                            self.send(*peer_id, &response).await?;
-                        }
-                    }
+                    // Changes to the channel state are sent by
+                    // `update_channel_state()`.
+                    LiveRequest::ChannelState(..) => {}
                     LiveRequest::ChannelTimeRange(req_id, channel_opts) => {
                         debug!(
                             "Matched channel time range live request: {}",
@@ -713,14 +2595,27 @@ where
                         // the call to `send_post_hashes()` matches the channel of
                         // the peer request.
                         if &channel_opts.channel == channel {
-                            let limit = channel_opts.limit.min(4096);
-
-                            // Get all post hashes matching the request parameters.
-                            let mut stream = self.store.get_post_hashes(channel_opts).await;
+                            let limit = self.hash_limit(channel_opts.limit);
+
+                            let moderation_state = self.moderation_state().await;
+
+                            // Get all post hashes matching the request parameters,
+                            // newest first, omitting the hashes of dropped posts.
+                            // The limit is applied once dropped posts have been
+                            // omitted.
+                            let unlimited_opts = ChannelOptions {
+                                limit: 0,
+                                ..channel_opts.clone()
+                            };
+                            let mut stream = self.store.get_post_hashes(&unlimited_opts).await;
                             while let Some(result) = stream.next().await {
-                                hashes.push(result?);
+                                let hash = result?;
+                                if moderation_state.is_post_dropped(&hash) {
+                                    continue;
+                                }
+                                hashes.push(hash);
                                 // Break once the request limit has been reached.
-                                if limit != 0 && hashes.len() as u64 >= limit {
+                                if hashes.len() as u64 >= limit {
                                     break;
                                 }
                             }
@@ -763,18 +2658,6 @@ where
         Ok(())
     }
 
-    /// Decrement the TTL of a request message and write it to the outbound
-    /// requests store.
-    async fn decrement_ttl_and_write_to_outbound(&self, req_id: ReqId, msg: &Message) {
-        let mut request = msg.clone();
-        request.decrement_ttl();
-
-        self.outbound_requests
-            .write()
-            .await
-            .insert(req_id, (RequestOrigin::Remote, request));
-    }
-
     /// Handle a request or response message.
     pub async fn handle(&mut self, peer_id: usize, msg: &Message) -> Result<(), Error> {
         let MessageHeader {
@@ -785,10 +2668,15 @@ where
 
         // Ignore this message if the request ID has previously been handled
         // and it is not an active live request or outbound request.
-        if self.handled_requests.read().await.contains(&req_id)
+        if self
+            .handled_requests
+            .read()
+            .await
+            .contains(peer_id, &req_id)
             && !self.is_live_request(&peer_id, &req_id).await
             && !self.outbound_requests.read().await.contains_key(&req_id)
         {
+            self.handled_requests.write().await.record_duplicate();
             debug!(
                 "Dropping message from handler; request ID has been seen before: {}",
                 msg.header
@@ -797,22 +2685,56 @@ where
             return Ok(());
         }
 
-        // TODO: Forward requests.
+        // Ignore requests which originated locally or which have previously
+        // been forwarded; the request has looped back to the local peer.
+        if let MessageBody::Request { .. } = msg.body {
+            if self.outbound_requests.read().await.contains_key(&req_id) {
+                self.handled_requests.write().await.record_loop();
+                debug!(
+                    "Dropping request from handler; request has looped back: {}",
+                    msg.header
+                );
+
+                return Ok(());
+            }
+        }
+
+        if let MessageBody::Request { .. } = msg.body {
+            if !self.admit_request(peer_id, msg.message_type()).await {
+                debug!(
+                    "Dropping request from handler; rate limit exceeded: {}",
+                    msg.header
+                );
+
+                return Ok(());
+            }
+
+            self.emit(CableEvent::RequestReceived {
+                peer_id,
+                req_id,
+                msg_type: msg.message_type(),
+            })
+            .await;
+        }
+
         match &msg.body {
             MessageBody::Request { ttl, body } => match body {
                 RequestBody::Post { hashes } => {
                     debug!("Handling post request...");
 
-                    // If the request TTL is > 0, decrement it and add the
-                    // message to `outbound_requests` so that it will be
-                    // forwarded to other connected peers.
-                    //
-                    // TODO: Set the TTL to 16 if it is > 16.
+                    // If the request TTL is > 0, decrement it and forward the
+                    // message to other connected peers. Responses from those
+                    // peers are relayed back to the requesting peer.
                     if *ttl > 0 {
-                        self.decrement_ttl_and_write_to_outbound(req_id, msg).await;
+                        self.forward_request(peer_id, req_id, msg).await?;
                     }
 
-                    let posts = self.store.get_post_payloads(hashes).await;
+                    // Return no more than the maximum number of posts.
+                    let max_hashes = hashes.len().min(self.config.limits.max_hashes);
+                    let posts = self.store.get_post_payloads(&hashes[..max_hashes]).await;
+                    // Omit posts which are not served according to the
+                    // moderation state.
+                    let posts = self.served_payloads(posts).await;
                     let response = Message::post_response(circuit_id, req_id, posts);
 
                     self.send(peer_id, &response).await?
@@ -820,16 +2742,26 @@ where
                 RequestBody::Cancel { cancel_id } => {
                     debug!("Handling cancel request...");
 
-                    // TTL is ignored for cancel requests so we decrement and
-                    // write the message without first checking the value.
-                    self.decrement_ttl_and_write_to_outbound(req_id, msg).await;
-
                     // Remove the request from the map of live requests.
+                    let is_live = self.is_live_request(&peer_id, cancel_id).await;
                     self.remove_live_request(&peer_id, cancel_id).await?;
 
-                    // Remove the request from the list of outbound requests.
-                    // The associated message will no longer be sent to peers.
-                    self.outbound_requests.write().await.remove(cancel_id);
+                    // If the referenced request was forwarded on behalf of
+                    // this peer, forward the cancel request to every peer
+                    // who received it and remove the request from the list of
+                    // outbound requests. TTL is ignored for cancel requests.
+                    let is_forwarded = matches!(
+                        self.outbound_requests.read().await.get(cancel_id),
+                        Some((RequestOrigin::Remote(origin), _request)) if *origin == peer_id
+                    );
+                    if is_forwarded {
+                        self.cancel_forwarded_request(cancel_id, msg).await?;
+                    }
+
+                    // Conclude the cancelled live request.
+                    if is_live {
+                        self.end_response(peer_id, circuit_id, *cancel_id).await?;
+                    }
                 }
                 RequestBody::ChannelTimeRange {
                     channel,
@@ -839,26 +2771,33 @@ where
                 } => {
                     debug!("Handling channel time range request...");
 
-                    if *ttl > 0 {
-                        self.decrement_ttl_and_write_to_outbound(req_id, msg).await;
-                    }
-
                     let channel_opts = ChannelOptions::new(channel, *time_start, *time_end, *limit);
 
-                    let n_limit = (*limit).min(4096);
+                    let n_limit = self.hash_limit(*limit);
+
+                    let moderation_state = self.moderation_state().await;
 
                     let mut hashes = Vec::new();
-                    // Create a stream of post hashes matching the given criteria.
-                    let mut stream = self.store.get_post_hashes(&channel_opts).await;
+                    // Create a stream of post hashes matching the given
+                    // criteria, newest first, such that the latest posts are
+                    // returned once the limit is applied. The limit is applied
+                    // once the hashes of dropped posts have been omitted.
+                    let unlimited_opts = ChannelOptions {
+                        limit: 0,
+                        ..channel_opts.clone()
+                    };
+                    let mut stream = self.store.get_post_hashes(&unlimited_opts).await;
                     // Iterate over the hashes in the stream.
                     while let Some(result) = stream.next().await {
-                        hashes.push(result?);
+                        let hash = result?;
+                        // Omit the hashes of dropped posts.
+                        if moderation_state.is_post_dropped(&hash) {
+                            continue;
+                        }
+                        hashes.push(hash);
                         // Break out of the loop once the requested limit is
                         // met.
-                        //
-                        // A limit of 0 means there is no limit on the number
-                        // of hashes that may be returned.
-                        if n_limit != 0 && hashes.len() as u64 >= n_limit {
+                        if hashes.len() as u64 >= n_limit {
                             break;
                         }
                     }
@@ -873,51 +2812,56 @@ where
                     // alive and send new messages as they become available).
                     if *time_end == 0 {
                         let live_request = LiveRequest::ChannelTimeRange(req_id, channel_opts);
-
-                        let mut live_requests = self.live_requests.write().await;
-                        if let Some(peer_requests) = live_requests.get_mut(&peer_id) {
-                            // TODO: Only push if `peer_requests` does not
-                            // already contain this request.
-                            peer_requests.push(live_request);
-                        } else {
-                            live_requests.insert(peer_id, vec![live_request]);
-                        }
+                        self.add_live_request(peer_id, live_request).await;
 
                         // Only send a response if there are post hashes matching
                         // the given request parameters.
                         if !hashes.is_empty() {
                             self.send(peer_id, &response).await?
                         }
-                    } else {
-                        // Send a hash response, even if there are no known
-                        // hashes matching the request parameters.
+                    } else if !hashes.is_empty() {
+                        // Send the known hashes.
                         self.send(peer_id, &response).await?
                     }
+
+                    // Forward the request once the known hashes have been
+                    // sent, ensuring they precede any relayed response which
+                    // concludes the request.
+                    if *ttl > 0 {
+                        self.forward_request(peer_id, req_id, msg).await?;
+                    }
+
+                    // Send an empty hash response to conclude the request,
+                    // unless it is a live request or was forwarded.
+                    self.end_response(peer_id, circuit_id, req_id).await?;
                 }
                 RequestBody::ChannelState { channel, future } => {
                     debug!("Handling channel state request...");
 
-                    if *ttl > 0 {
-                        self.decrement_ttl_and_write_to_outbound(req_id, msg).await;
+                    // Ensure the channel state index is up to date, sending
+                    // any changes to peers holding live requests for the
+                    // channel state.
+                    if let Some(changed_hashes) = self.store.update_channel_state(channel).await {
+                        self.send_channel_state_hashes(channel, changed_hashes)
+                            .await?;
                     }
 
-                    let mut hashes = Vec::new();
+                    // Get the hashes of the latest join or leave post of all
+                    // channel members and ex-members, the latest topic post
+                    // and the latest info posts of all members and
+                    // ex-members (spec section 5.4.4).
+                    let mut hashes = self
+                        .store
+                        .get_channel_state_hashes(channel)
+                        .await
+                        .unwrap_or_default();
 
-                    // Get the hash of the latest join or leave post for all
-                    // channel members and ex-members.
-                    if let Some(mut channel_membership_hashes) =
-                        self.store.get_channel_membership_hashes(channel).await
-                    {
-                        hashes.append(&mut channel_membership_hashes)
-                    }
+                    // Return the hashes of all moderation posts applying to
+                    // the channel or to the whole cabal.
+                    hashes.append(&mut self.get_moderation_hashes(channel).await);
 
-                    // If a topic has been set for the channel, return the hash
-                    // of the post.
-                    if let Some((_topic, topic_hash)) =
-                        self.store.get_channel_topic_and_hash(channel).await
-                    {
-                        hashes.push(topic_hash)
-                    }
+                    // Return no more than the maximum number of hashes.
+                    hashes.truncate(self.config.limits.max_hashes);
 
                     let response = Message::hash_response(circuit_id, req_id, hashes.clone());
 
@@ -927,29 +2871,13 @@ where
                         if !hashes.is_empty() {
                             // Send the known hashes.
                             self.send(peer_id, &response).await?;
-
-                            // Compost and send an empty hash response to
-                            // terminate the request.
-                            let closing_response =
-                                Message::hash_response(circuit_id, req_id, Vec::new());
-                            self.send(peer_id, &closing_response).await?;
-                        } else {
-                            // Send the empty hash response to terminate the
-                            // request.
-                            self.send(peer_id, &response).await?;
                         }
                     } else if *future == 1 {
                         // Add the peer and request ID to the request tracker if
                         // the future field has been set to 1 (i.e. keep this request
                         // alive and send new messages as they become available).
                         let live_request = LiveRequest::ChannelState(req_id, channel.to_string());
-
-                        let mut live_requests = self.live_requests.write().await;
-                        if let Some(peer_requests) = live_requests.get_mut(&peer_id) {
-                            peer_requests.push(live_request);
-                        } else {
-                            live_requests.insert(peer_id, vec![live_request]);
-                        }
+                        self.add_live_request(peer_id, live_request).await;
 
                         // Only send a response if there are post hashes matching
                         // the given request parameters.
@@ -958,22 +2886,21 @@ where
                         }
                     }
 
-                    /*
-                    TODO: We will require channel state indexes before this
-                    handler can be completed.
-
-                    Channel state includes (spec section 5.4.4):
+                    // Forward the request once the known hashes have been
+                    // sent.
+                    if *ttl > 0 {
+                        self.forward_request(peer_id, req_id, msg).await?;
+                    }
 
-                    The latest post/info post of all members and ex-members.
-                    The latest of all users' post/join or post/leave posts to the channel.
-                    The latest post/topic post made to the channel.
-                    */
+                    // Send an empty hash response to conclude the request,
+                    // unless it is a live request or was forwarded.
+                    self.end_response(peer_id, circuit_id, req_id).await?;
                 }
                 RequestBody::ChannelList { skip, limit } => {
                     debug!("Handling channel list request...");
 
                     if *ttl > 0 {
-                        self.decrement_ttl_and_write_to_outbound(req_id, msg).await;
+                        self.forward_request(peer_id, req_id, msg).await?;
                     }
 
                     let skip = *skip as usize;
@@ -985,12 +2912,15 @@ where
                         let channels_len = all_channels.len();
 
                         // Define the channel query limit based on the request
-                        // limit and the number of known channels.
+                        // limit and the number of known channels. The range
+                        // is clamped to the known channels, since both values
+                        // are chosen by the remote peer.
                         let limit = if limit == 0 || limit > channels_len {
                             channels_len
                         } else {
                             limit
                         };
+                        let skip = skip.min(limit);
 
                         // Drain the channels matching the given range.
                         all_channels.drain(skip..limit).collect()
@@ -1004,95 +2934,158 @@ where
                     self.send(peer_id, &response).await?
                 }
             },
-            MessageBody::Response { body } => match body {
-                // TODO: A responder MUST send a Hash Response message with
-                // hash_count = 0 to indicate that they do not intend to return
-                // any further hashes for the given req_id and they have
-                // concluded the request on their side.
-                ResponseBody::Hash { hashes } => {
-                    debug!("Handling hash response...");
-
-                    let wanted_hashes = self.store.want(hashes).await;
-                    if !wanted_hashes.is_empty() {
-                        let (_, new_req_id) = self.new_req_id().await?;
-
-                        // If a hash appears in our list of wanted hashed,
+            MessageBody::Response { body } => {
+                // Relay the response to the peer from whom the associated
+                // request originated, if the request was forwarded.
+                self.relay_response(peer_id, &req_id, msg).await?;
+
+                match body {
+                    ResponseBody::Hash { hashes } => {
+                        debug!("Handling hash response...");
+
+                        self.emit(CableEvent::HashesReceived {
+                            peer_id,
+                            req_id,
+                            hashes: hashes.to_owned(),
+                        })
+                        .await;
+
+                        // If a hash appears in our list of wanted hashes,
                         // send a request for the associated post.
-                        let request = Message::post_request(
-                            circuit_id,
-                            new_req_id,
-                            TTL,
-                            wanted_hashes.to_owned(),
-                        );
-
-                        self.send(peer_id, &request).await?;
-
-                        // Update the list of requested posts.
-                        let mut requested_posts = self.requested_posts.write().await;
-                        for hash in &wanted_hashes {
-                            requested_posts.insert(*hash);
+                        let wanted_hashes = self.store.want(hashes).await;
+                        self.request_posts(peer_id, circuit_id, wanted_hashes)
+                            .await?;
+
+                        // A hash response with no hashes indicates that the
+                        // peer has concluded the request.
+                        if hashes.is_empty() {
+                            self.conclude_request(peer_id, &req_id).await;
                         }
                     }
+                    ResponseBody::Post { posts } => {
+                        debug!("Handling post response...");
+
+                        let mut missing_links = Vec::new();
+
+                        // Iterate over the encoded posts.
+                        for post_bytes in posts {
+                            // Verify the post signature.
+                            if !Post::verify(post_bytes) {
+                                // Skip to the next post, bypassing the rest of the
+                                // code in this `for` loop.
+                                continue;
+                            }
 
-                    // TODO: If hash_count == 0, remove the request.
-                    // This may be more relevant when responding to a channel
-                    // time range request (ie. sending a hash response).
-                }
-                ResponseBody::Post { posts } => {
-                    debug!("Handling post response...");
-
-                    // Iterate over the encoded posts.
-                    for post_bytes in posts {
-                        // Verify the post signature.
-                        if !Post::verify(post_bytes) {
-                            // Skip to the next post, bypassing the rest of the
-                            // code in this `for` loop.
-                            continue;
-                        }
+                            // Deserialize the post.
+                            let (s, post) = Post::from_bytes(post_bytes)?;
 
-                        // Deserialize the post.
-                        let (s, post) = Post::from_bytes(post_bytes)?;
+                            // Ensure the number of processed bytes matches the
+                            // received amount.
+                            if s != post_bytes.len() {
+                                continue;
+                            }
 
-                        // Ensure the number of processed bytes matches the
-                        // received amount.
-                        if s != post_bytes.len() {
-                            continue;
-                        }
+                            let post_hash = post.hash()?;
 
-                        let post_hash = post.hash()?;
+                            // Check if a delete post by the same author has
+                            // previously been encountered which references
+                            // this post hash.
+                            if self.store.is_deleted(&post, &post_hash).await {
+                                // Skip processing this post so that we do not add
+                                // it to the local store.
+                                continue;
+                            }
 
-                        let deleted_posts = self.deleted_posts.read().await;
-                        // Check if a delete post has previously been
-                        // encountered which references this post hash.
-                        if deleted_posts.contains(&post_hash) {
-                            // Skip processing this post so that we do not add
-                            // it to the local store.
-                            continue;
-                        }
+                            let mut requested_posts = self.requested_posts.write().await;
+                            // Check if this post was previously requested.
+                            if !requested_posts.contains(&post_hash) {
+                                // Skip this post if it was not requested.
+                                continue;
+                            }
+                            // Remove the post hash from the list of requested
+                            // posts.
+                            requested_posts.remove(&post_hash);
+
+                            // Drop the locks to allow the later call to
+                            // `self.store_post()` (mutable borrow).
+                            drop(requested_posts);
+
+                            // Skip posts by blocked authors, ensuring they
+                            // are not requested again.
+                            let public_key = post.get_public_key();
+                            if self.store.get_moderation(&public_key).await
+                                == Some(Moderation::Block)
+                            {
+                                self.store
+                                    .insert_blocked_hash(&public_key, &post_hash)
+                                    .await;
+                                continue;
+                            }
 
-                        let mut requested_posts = self.requested_posts.write().await;
-                        // Check if this post was previously requested.
-                        if !requested_posts.contains(&post_hash) {
-                            // Skip this post if it was not requested.
-                            continue;
+                            self.store_post(&post).await?;
+                            self.check_timestamps(&post, &post_hash).await;
+
+                            // Note any linked ancestors which are not yet
+                            // known.
+                            for link in self.store.want(&post.header.links).await {
+                                if !missing_links.contains(&link) {
+                                    missing_links.push(link);
+                                }
+                            }
                         }
-                        // Remove the post hash from the list of requested
-                        // posts.
-                        requested_posts.remove(&post_hash);
 
-                        self.store.insert_post(&post).await?;
+                        // Request any missing ancestors of the received
+                        // posts from the same peer, unless they have already
+                        // been requested. Deleted posts are never wanted.
+                        let requested_posts = self.requested_posts.read().await;
+                        missing_links.retain(|link| !requested_posts.contains(link));
+                        drop(requested_posts);
+                        self.request_posts(peer_id, circuit_id, missing_links)
+                            .await?;
+
+                        // Retire the post request once all requested posts
+                        // have been received.
+                        let outbound_requests = self.outbound_requests.read().await;
+                        if let Some((
+                            RequestOrigin::Local,
+                            Message {
+                                body:
+                                    MessageBody::Request {
+                                        body: RequestBody::Post { hashes },
+                                        ..
+                                    },
+                                ..
+                            },
+                        )) = outbound_requests.get(&req_id)
+                        {
+                            let requested_posts = self.requested_posts.read().await;
+                            if !hashes.iter().any(|hash| requested_posts.contains(hash)) {
+                                drop(requested_posts);
+                                drop(outbound_requests);
+                                self.retire_request(&req_id).await;
+                            }
+                        }
                     }
-                }
-                ResponseBody::ChannelList { channels } => {
-                    debug!("Handling channel list response...");
+                    ResponseBody::ChannelList { channels } => {
+                        debug!("Handling channel list response...");
+
+                        // A channel list response concludes the request.
+                        self.conclude_request(peer_id, &req_id).await;
+
+                        let known_channels = self.store.get_channels().await.unwrap_or_default();
+                        for channel in channels {
+                            self.store.insert_channel(channel).await;
 
-                    // TODO: Do we need to take action to conclude the request
-                    // which resulted in this response?
-                    for channel in channels {
-                        self.store.insert_channel(channel).await;
+                            if !known_channels.contains(channel) {
+                                self.emit(CableEvent::ChannelDiscovered {
+                                    channel: channel.to_owned(),
+                                })
+                                .await;
+                            }
+                        }
                     }
                 }
-            },
+            }
             // Ignore unrecognized message type.
             MessageBody::Unrecognized { .. } => {
                 debug!("Received unrecognized message; skipping message handling...");
@@ -1100,7 +3093,7 @@ where
         }
 
         // Mark this request as "handled" (to prevent request loops).
-        self.handled_requests.write().await.insert(req_id);
+        self.handled_requests.write().await.insert(peer_id, req_id);
 
         Ok(())
     }
@@ -0,0 +1,283 @@
+//! Local moderation of authors and the moderation state defined by
+//! moderation posts.
+//!
+//! Local moderation actions are applied by the local peer to the public keys
+//! of other authors and are never shared with remote peers. The posts of a
+//! hidden author are stored and served to remote peers as usual, but are not
+//! emitted on the post streams returned by `CableManager::open_channel()`. The
+//! posts of a blocked author are additionally purged from the store and are
+//! never requested or stored again.
+//!
+//! Local moderation actions are persisted through the `Store` trait.
+//!
+//! Moderation posts (`post/role`, `post/moderation`, `post/block` and
+//! `post/unblock`) are shared with remote peers. The `ModerationState`
+//! derived from these posts only honours the posts of authors with the
+//! authority to make them; the local peer is always an admin of the cabal.
+
+use std::collections::{HashMap, HashSet};
+
+use cable::{
+    constants::{
+        ADMIN_ROLE, DROP_CHANNEL, DROP_POST, HIDE_POST, HIDE_USER, MODERATOR_ROLE, UNDROP_CHANNEL,
+        UNDROP_POST, UNHIDE_POST, UNHIDE_USER, USER_ROLE,
+    },
+    post::{Post, PostBody},
+    Channel, Hash,
+};
+
+use crate::store::PublicKey;
+
+/// A moderation action applied to an author by the local peer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Moderation {
+    /// Posts by the author are not displayed.
+    Hide,
+    /// Posts by the author are not displayed, requested or stored.
+    Block,
+}
+
+impl Moderation {
+    /// Encode the moderation action as a single byte.
+    pub(crate) fn to_byte(self) -> u8 {
+        match self {
+            Moderation::Hide => 0,
+            Moderation::Block => 1,
+        }
+    }
+
+    /// Decode a moderation action from a single byte.
+    pub(crate) fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(Moderation::Hide),
+            1 => Some(Moderation::Block),
+            _ => None,
+        }
+    }
+}
+
+/// The moderation state of the cabal, derived from all known moderation
+/// posts.
+///
+/// Context is given by a channel name; an empty channel name denotes the
+/// whole cabal. Roles and hidden users in the context of the whole cabal
+/// apply to every channel.
+#[derive(Clone, Debug, Default)]
+pub struct ModerationState {
+    /// The public key of the local peer, which is always an admin.
+    local_key: Option<PublicKey>,
+    /// Assigned admin and moderator roles, indexed by context and public key.
+    roles: HashMap<(Channel, PublicKey), u64>,
+    /// Hidden users, indexed by context.
+    hidden_users: HashSet<(Channel, PublicKey)>,
+    /// Hashes of hidden posts.
+    hidden_posts: HashSet<Hash>,
+    /// Hashes of dropped posts.
+    dropped_posts: HashSet<Hash>,
+    /// Dropped channels.
+    dropped_channels: HashSet<Channel>,
+    /// Blocked users, along with whether their posts are dropped.
+    blocked_users: HashMap<PublicKey, bool>,
+}
+
+impl ModerationState {
+    /// Derive the moderation state from the given moderation posts.
+    ///
+    /// Posts are applied in order of timestamp; each post is only honoured if
+    /// the author holds the required role at the time it is applied.
+    pub fn new(local_key: Option<PublicKey>, mut posts: Vec<Post>) -> Self {
+        let mut state = ModerationState {
+            local_key,
+            ..ModerationState::default()
+        };
+
+        posts.sort_by_key(|post| post.get_timestamp());
+        for post in &posts {
+            state.apply(post);
+        }
+
+        state
+    }
+
+    /// Apply a single moderation post to the state.
+    fn apply(&mut self, post: &Post) {
+        let author = post.get_public_key();
+
+        match &post.body {
+            PostBody::Role {
+                channel,
+                recipient,
+                role,
+                ..
+            } => {
+                // Only admins may assign roles.
+                if !self.is_admin(channel, &author) {
+                    return;
+                }
+                let key = (channel.to_owned(), *recipient);
+                match *role {
+                    ADMIN_ROLE | MODERATOR_ROLE => {
+                        self.roles.insert(key, *role);
+                    }
+                    USER_ROLE => {
+                        self.roles.remove(&key);
+                    }
+                    _ => {}
+                }
+            }
+            PostBody::Moderation {
+                channel,
+                recipients,
+                action,
+                ..
+            } => {
+                if !self.is_moderator(channel, &author) {
+                    return;
+                }
+                for recipient in recipients {
+                    match *action {
+                        HIDE_USER => {
+                            self.hidden_users.insert((channel.to_owned(), *recipient));
+                        }
+                        UNHIDE_USER => {
+                            self.hidden_users.remove(&(channel.to_owned(), *recipient));
+                        }
+                        HIDE_POST => {
+                            self.hidden_posts.insert(*recipient);
+                        }
+                        UNHIDE_POST => {
+                            self.hidden_posts.remove(recipient);
+                        }
+                        DROP_POST => {
+                            self.dropped_posts.insert(*recipient);
+                        }
+                        UNDROP_POST => {
+                            self.dropped_posts.remove(recipient);
+                        }
+                        _ => {}
+                    }
+                }
+                // Channel actions have no recipients.
+                match *action {
+                    DROP_CHANNEL if !channel.is_empty() => {
+                        self.dropped_channels.insert(channel.to_owned());
+                    }
+                    UNDROP_CHANNEL => {
+                        self.dropped_channels.remove(channel);
+                    }
+                    _ => {}
+                }
+            }
+            PostBody::Block {
+                recipients, drop, ..
+            } => {
+                // Blocks apply to the whole cabal.
+                if !self.is_moderator(&Channel::new(), &author) {
+                    return;
+                }
+                for recipient in recipients {
+                    // The local peer cannot be blocked.
+                    if Some(*recipient) != self.local_key {
+                        self.blocked_users.insert(*recipient, *drop != 0);
+                    }
+                }
+            }
+            PostBody::Unblock { recipients, .. } => {
+                if !self.is_moderator(&Channel::new(), &author) {
+                    return;
+                }
+                for recipient in recipients {
+                    self.blocked_users.remove(recipient);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Return the role of the given public key in the given channel, taking
+    /// into account any role assigned for the whole cabal.
+    pub fn get_role(&self, channel: &Channel, public_key: &PublicKey) -> u64 {
+        if Some(*public_key) == self.local_key {
+            return ADMIN_ROLE;
+        }
+
+        let cabal_role = self.roles.get(&(Channel::new(), *public_key));
+        let channel_role = self.roles.get(&(channel.to_owned(), *public_key));
+
+        match (cabal_role, channel_role) {
+            (Some(&ADMIN_ROLE), _) | (_, Some(&ADMIN_ROLE)) => ADMIN_ROLE,
+            (Some(&MODERATOR_ROLE), _) | (_, Some(&MODERATOR_ROLE)) => MODERATOR_ROLE,
+            _ => USER_ROLE,
+        }
+    }
+
+    /// Query whether the given public key is an admin in the given channel.
+    pub fn is_admin(&self, channel: &Channel, public_key: &PublicKey) -> bool {
+        self.get_role(channel, public_key) == ADMIN_ROLE
+    }
+
+    /// Query whether the given public key is an admin or moderator in the
+    /// given channel.
+    pub fn is_moderator(&self, channel: &Channel, public_key: &PublicKey) -> bool {
+        self.get_role(channel, public_key) != USER_ROLE
+    }
+
+    /// Query whether the given public key is hidden in the given channel.
+    pub fn is_user_hidden(&self, channel: &Channel, public_key: &PublicKey) -> bool {
+        self.hidden_users.contains(&(Channel::new(), *public_key))
+            || self
+                .hidden_users
+                .contains(&(channel.to_owned(), *public_key))
+    }
+
+    /// Query whether the given public key is blocked.
+    pub fn is_user_blocked(&self, public_key: &PublicKey) -> bool {
+        self.blocked_users.contains_key(public_key)
+    }
+
+    /// Query whether the post with the given hash is hidden.
+    pub fn is_post_hidden(&self, hash: &Hash) -> bool {
+        self.hidden_posts.contains(hash)
+    }
+
+    /// Query whether the post with the given hash is dropped.
+    pub fn is_post_dropped(&self, hash: &Hash) -> bool {
+        self.dropped_posts.contains(hash)
+    }
+
+    /// Query whether the given channel is dropped.
+    pub fn is_channel_dropped(&self, channel: &Channel) -> bool {
+        self.dropped_channels.contains(channel)
+    }
+
+    /// Query whether the given post should be displayed.
+    pub fn shows(&self, post: &Post, hash: &Hash) -> bool {
+        let public_key = post.get_public_key();
+
+        if self.is_user_blocked(&public_key)
+            || self.is_post_hidden(hash)
+            || self.is_post_dropped(hash)
+        {
+            return false;
+        }
+
+        match post.get_channel() {
+            Some(channel) => {
+                !self.is_channel_dropped(channel) && !self.is_user_hidden(channel, &public_key)
+            }
+            None => !self.is_user_hidden(&Channel::new(), &public_key),
+        }
+    }
+
+    /// Query whether the given post should be served to remote peers.
+    ///
+    /// Dropped posts, posts in dropped channels and the posts of users who
+    /// were blocked with their posts dropped are not served.
+    pub fn serves(&self, post: &Post, hash: &Hash) -> bool {
+        !self.is_post_dropped(hash)
+            && self.blocked_users.get(&post.get_public_key()) != Some(&true)
+            && !post
+                .get_channel()
+                .is_some_and(|channel| self.is_channel_dropped(channel))
+    }
+}
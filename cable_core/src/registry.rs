@@ -0,0 +1,152 @@
+//! The cabal registry allows a single process to participate in several
+//! cabals, each identified by a cabal key.
+//!
+//! Every cabal is served by a dedicated `CableManager`, with its own store,
+//! peer set and request state. Outbound connections are made to a specific
+//! cabal, while inbound connections are routed to the cabal whose key was
+//! used by the connecting peer during the handshake.
+
+use std::collections::HashMap;
+
+use async_std::sync::{Arc, RwLock};
+use cable::{error::CableErrorKind, Error};
+use cable_handshake::{async_std::handshake, Extensions, Role, Version};
+use futures::io::{AsyncRead, AsyncWrite};
+use log::debug;
+
+use crate::{
+    manager::{CableManager, HANDSHAKE_MAJOR_VERSION, HANDSHAKE_MINOR_VERSION},
+    store::Store,
+};
+
+/// A cabal key; the pre-shared key used during the handshake.
+pub type CabalKey = [u8; 32];
+
+/// A registry of cabals, indexed by cabal key.
+#[derive(Clone)]
+pub struct CabalRegistry<S: Store> {
+    cabals: Arc<RwLock<HashMap<CabalKey, CableManager<S>>>>,
+}
+
+impl<S: Store> Default for CabalRegistry<S> {
+    fn default() -> Self {
+        Self {
+            cabals: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+}
+
+impl<S> CabalRegistry<S>
+where
+    S: Store,
+{
+    /// Create an empty cabal registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a cabal with the given key and store to the registry, returning
+    /// the manager for the cabal.
+    ///
+    /// Any cabal previously registered with the same key is replaced.
+    pub async fn add_cabal(&self, cabal_key: CabalKey, store: S) -> CableManager<S> {
+        let manager = CableManager::new(store);
+        self.insert(cabal_key, manager.clone()).await;
+
+        manager
+    }
+
+    /// Insert the manager for the cabal with the given key into the registry,
+    /// returning the manager previously registered with the same key (if any).
+    pub async fn insert(
+        &self,
+        cabal_key: CabalKey,
+        manager: CableManager<S>,
+    ) -> Option<CableManager<S>> {
+        self.cabals.write().await.insert(cabal_key, manager)
+    }
+
+    /// Remove the cabal with the given key from the registry, returning the
+    /// manager for the cabal (if any).
+    ///
+    /// Existing connections to peers of the removed cabal are not closed.
+    pub async fn remove(&self, cabal_key: &CabalKey) -> Option<CableManager<S>> {
+        self.cabals.write().await.remove(cabal_key)
+    }
+
+    /// Retrieve the manager for the cabal with the given key.
+    pub async fn get(&self, cabal_key: &CabalKey) -> Option<CableManager<S>> {
+        self.cabals.read().await.get(cabal_key).cloned()
+    }
+
+    /// Retrieve the keys of all registered cabals.
+    pub async fn cabal_keys(&self) -> Vec<CabalKey> {
+        self.cabals.read().await.keys().copied().collect()
+    }
+
+    /// Connect to a peer of the cabal with the given key by performing a
+    /// handshake as initiator, then listen for incoming peer messages.
+    pub async fn connect<T>(&self, stream: T, cabal_key: &CabalKey) -> Result<(), Error>
+    where
+        T: AsyncRead + AsyncWrite + Clone + Unpin + Send + Sync + 'static,
+    {
+        match self.get(cabal_key).await {
+            Some(manager) => {
+                manager
+                    .listen_secure(stream, *cabal_key, Role::Initiator)
+                    .await
+            }
+            None => CableErrorKind::NoneError {
+                context: "no cabal registered for the given key".to_string(),
+            }
+            .raise(),
+        }
+    }
+
+    /// Accept a connection from a peer by performing a handshake as responder,
+    /// then listen for incoming peer messages.
+    ///
+    /// The connection is routed to the cabal whose key was used by the peer
+    /// during the handshake. The handshake fails if the peer used a key which
+    /// does not belong to any registered cabal.
+    ///
+    /// Since the cabal is only known once the handshake is underway, a
+    /// handshake extension is enabled only if it is enabled in the
+    /// configuration of every registered cabal.
+    pub async fn accept<T>(&self, mut stream: T) -> Result<(), Error>
+    where
+        T: AsyncRead + AsyncWrite + Clone + Unpin + Send + Sync + 'static,
+    {
+        // Take a snapshot of the registered cabals; cabals added while the
+        // handshake is underway are not considered.
+        let managers: Vec<(CabalKey, CableManager<S>)> = self
+            .cabals
+            .read()
+            .await
+            .iter()
+            .map(|(cabal_key, manager)| (*cabal_key, manager.clone()))
+            .collect();
+
+        // Pair each cabal key with the secret key of the cabal keypair.
+        let mut keys = Vec::with_capacity(managers.len());
+        for (cabal_key, manager) in &managers {
+            keys.push((*cabal_key, manager.get_local_secret_key().await));
+        }
+
+        let extensions = Extensions {
+            identity_payload: !managers.is_empty()
+                && managers.iter().all(|(_cabal_key, manager)| {
+                    manager.config().handshake_extensions.identity_payload
+                }),
+        };
+
+        let version = Version::init(HANDSHAKE_MAJOR_VERSION, HANDSHAKE_MINOR_VERSION);
+        let (index, handshake) =
+            handshake::server_with_keys(&mut stream, version, &keys, extensions).await?;
+
+        debug!("Routing inbound connection to cabal {}", index);
+
+        let (_cabal_key, manager) = &managers[index];
+        manager.listen_encrypted(stream, handshake).await
+    }
+}
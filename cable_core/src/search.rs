@@ -0,0 +1,262 @@
+//! Full-text search over stored text posts.
+//!
+//! The text of each `post/text` post is split into words according to the
+//! Unicode word boundary rules and lowercased. The resulting terms are held
+//! in an inverted index, along with the position of each term in the post,
+//! allowing phrase queries to be answered without decoding the posts.
+//!
+//! A query consists of terms and quoted phrases (`"hyphal fusion"`); a post
+//! matches if it contains every term and every phrase. Matching posts are
+//! ranked using the BM25 scoring function, with newer posts ranked first
+//! among posts of equal score.
+
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+};
+
+use cable::{
+    post::{Post, PostBody},
+    Channel, Hash, Timestamp,
+};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::store::PublicKey;
+
+// BM25 parameters: term frequency saturation and document length
+// normalisation.
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+
+/// Split the given text into lowercase terms.
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.unicode_words()
+        .map(|word| word.to_lowercase())
+        .collect()
+}
+
+/// Query parameters defining the text to search for, as well as optional
+/// channel and author filters and the maximum number of results.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchQuery {
+    pub text: String,
+    pub channel: Option<Channel>,
+    pub author: Option<PublicKey>,
+    /// A limit of 0 means there is no limit on the number of results.
+    pub limit: usize,
+}
+
+impl SearchQuery {
+    /// Create a new instance of `SearchQuery` matching the given text in all
+    /// channels, by all authors, without a limit.
+    pub fn new<T: Into<String>>(text: T) -> Self {
+        SearchQuery {
+            text: text.into(),
+            channel: None,
+            author: None,
+            limit: 0,
+        }
+    }
+
+    /// Split the query text into phrases of one or more terms. Quoted text
+    /// forms a single phrase, while each unquoted term forms a phrase of its
+    /// own.
+    fn phrases(&self) -> Vec<Vec<String>> {
+        self.text
+            .split('"')
+            .enumerate()
+            .flat_map(|(i, segment)| {
+                let terms = tokenize(segment);
+                if i % 2 == 1 {
+                    vec![terms]
+                } else {
+                    terms.into_iter().map(|term| vec![term]).collect()
+                }
+            })
+            .filter(|phrase| !phrase.is_empty())
+            .collect()
+    }
+}
+
+/// A post matching a search query, along with its relevance score.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchResult {
+    pub hash: Hash,
+    pub score: f64,
+}
+
+/// An indexed text post.
+#[derive(Clone, Debug)]
+struct Document {
+    channel: Channel,
+    public_key: PublicKey,
+    timestamp: Timestamp,
+    terms: Vec<String>,
+}
+
+/// An inverted index of the terms of text posts.
+#[derive(Clone, Debug, Default)]
+pub struct SearchIndex {
+    /// The positions of each term in each post, indexed by term and then by
+    /// post hash.
+    postings: HashMap<String, HashMap<Hash, Vec<usize>>>,
+    /// The indexed posts, indexed by hash.
+    documents: HashMap<Hash, Document>,
+    /// The sum of the number of terms of all indexed posts.
+    total_terms: usize,
+}
+
+impl SearchIndex {
+    /// Add the given post to the index. Posts other than `post/text` posts
+    /// are ignored.
+    pub fn insert(&mut self, hash: &Hash, post: &Post) {
+        let (channel, text) = match &post.body {
+            PostBody::Text { channel, text } => (channel, text),
+            _ => return,
+        };
+        if self.documents.contains_key(hash) {
+            return;
+        }
+
+        let terms = tokenize(text);
+        for (position, term) in terms.iter().enumerate() {
+            self.postings
+                .entry(term.to_owned())
+                .or_default()
+                .entry(*hash)
+                .or_default()
+                .push(position);
+        }
+
+        self.total_terms += terms.len();
+        self.documents.insert(
+            *hash,
+            Document {
+                channel: channel.to_owned(),
+                public_key: post.get_public_key(),
+                timestamp: post.get_timestamp(),
+                terms,
+            },
+        );
+    }
+
+    /// Remove the post with the given hash from the index.
+    pub fn remove(&mut self, hash: &Hash) {
+        let document = match self.documents.remove(hash) {
+            Some(document) => document,
+            None => return,
+        };
+
+        self.total_terms -= document.terms.len();
+        for term in document.terms.iter().collect::<HashSet<_>>() {
+            if let Some(posts) = self.postings.get_mut(term) {
+                posts.remove(hash);
+                if posts.is_empty() {
+                    self.postings.remove(term);
+                }
+            }
+        }
+    }
+
+    /// Return the posts matching the given query, ranked by relevance.
+    pub fn search(&self, query: &SearchQuery) -> Vec<SearchResult> {
+        let phrases = query.phrases();
+        let mut terms: Vec<&String> = phrases.iter().flatten().collect();
+        terms.sort_unstable();
+        terms.dedup();
+
+        // Retrieve the postings of every term; a post must contain them all.
+        let mut postings = Vec::new();
+        for term in &terms {
+            match self.postings.get(*term) {
+                Some(posts) => postings.push((*term, posts)),
+                None => return Vec::new(),
+            }
+        }
+        // Begin with the rarest term to minimise the number of candidates.
+        postings.sort_by_key(|(_term, posts)| posts.len());
+        let candidates = match postings.first() {
+            Some((_term, candidates)) => *candidates,
+            None => return Vec::new(),
+        };
+
+        let n_documents = self.documents.len() as f64;
+        let avg_len = self.total_terms as f64 / n_documents;
+
+        let mut results: Vec<(SearchResult, Timestamp)> = candidates
+            .keys()
+            .filter(|hash| {
+                postings
+                    .iter()
+                    .all(|(_term, posts)| posts.contains_key(*hash))
+            })
+            .filter_map(|hash| Some((hash, self.documents.get(hash)?)))
+            .filter(|(_hash, document)| {
+                query
+                    .channel
+                    .as_ref()
+                    .is_none_or(|channel| &document.channel == channel)
+                    && query
+                        .author
+                        .is_none_or(|public_key| document.public_key == public_key)
+            })
+            .filter(|(hash, _document)| {
+                phrases
+                    .iter()
+                    .all(|phrase| self.contains_phrase(hash, phrase))
+            })
+            .map(|(hash, document)| {
+                let len = document.terms.len() as f64;
+                let score = postings
+                    .iter()
+                    .map(|(_term, posts)| {
+                        let df = posts.len() as f64;
+                        let tf = posts.get(hash).map_or(0, |positions| positions.len()) as f64;
+                        let idf = (1.0 + (n_documents - df + 0.5) / (df + 0.5)).ln();
+                        idf * tf * (K1 + 1.0) / (tf + K1 * (1.0 - B + B * len / avg_len))
+                    })
+                    .sum();
+
+                (SearchResult { hash: *hash, score }, document.timestamp)
+            })
+            .collect();
+
+        // Rank by score, then by timestamp (newest first) and finally by hash
+        // to ensure a stable order.
+        results.sort_by(|(a, a_timestamp), (b, b_timestamp)| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(Ordering::Equal)
+                .then(b_timestamp.cmp(a_timestamp))
+                .then(a.hash.cmp(&b.hash))
+        });
+        if query.limit > 0 {
+            results.truncate(query.limit);
+        }
+
+        results
+            .into_iter()
+            .map(|(result, _timestamp)| result)
+            .collect()
+    }
+
+    /// Query whether the post with the given hash contains the given terms
+    /// consecutively.
+    fn contains_phrase(&self, hash: &Hash, phrase: &[String]) -> bool {
+        let positions: Vec<&Vec<usize>> = phrase
+            .iter()
+            .filter_map(|term| self.postings.get(term)?.get(hash))
+            .collect();
+        if positions.len() < phrase.len() {
+            return false;
+        }
+
+        positions[0].iter().any(|start| {
+            positions
+                .iter()
+                .enumerate()
+                .skip(1)
+                .all(|(offset, term_positions)| term_positions.contains(&(start + offset)))
+        })
+    }
+}
@@ -0,0 +1,1129 @@
+//! A persistent implementation of the `Store` trait, backed by the `sled`
+//! embedded key-value database.
+//!
+//! Each index maintained by the `MemoryStore` is represented by a dedicated
+//! `sled` tree. Keys are constructed so that lexicographic ordering of the key
+//! bytes matches the ordering of the equivalent in-memory index; this allows
+//! time range queries to be performed as range scans over a single tree.
+//!
+//! Live streams are held in memory, as they are for the `MemoryStore`. So is
+//! the keypair, such that the secret key is never written to disk.
+
+use std::{
+    collections::BTreeMap,
+    convert::TryInto,
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+use async_std::{
+    prelude::*,
+    stream,
+    sync::{Arc, Mutex, RwLock},
+    task,
+};
+use cable::{
+    post::Post, Channel, ChannelOptions, Error, Hash, Nickname, Payload, Timestamp, Topic, UserInfo,
+};
+use desert::{varint, FromBytes, ToBytes};
+use fs2::FileExt;
+use log::error;
+use sled::{Db, IVec, Tree};
+
+#[cfg(feature = "search")]
+use crate::search::{SearchIndex, SearchQuery, SearchResult};
+use crate::{
+    history::HistoryCursor,
+    moderation::Moderation,
+    store::{register_live_stream, send_to_live_streams, Keypair, LiveStreamMap, PublicKey, Store},
+    stream::{HashStream, PostStream},
+};
+
+/// Log the error (if any) returned by a database operation, returning the
+/// successful value as an `Option`.
+fn log_db_error<T>(result: sled::Result<T>) -> Option<T> {
+    result
+        .map_err(|err| error!("Store database operation failed: {err}"))
+        .ok()
+}
+
+/// Encode the given bytes as a length-prefixed key segment.
+///
+/// The length prefix ensures that no segment is a prefix of another segment
+/// (e.g. "myco" and "mycology").
+fn length_prefixed_key(bytes: &[u8]) -> Vec<u8> {
+    let mut key = vec![0; varint::length(bytes.len() as u64)];
+    // Encoding cannot fail since the buffer has been sized to fit the varint.
+    let _ = varint::encode(bytes.len() as u64, &mut key);
+    key.extend_from_slice(bytes);
+
+    key
+}
+
+/// Encode a channel name as a length-prefixed key segment.
+fn channel_key(channel: &Channel) -> Vec<u8> {
+    length_prefixed_key(channel.as_bytes())
+}
+
+/// Encode an optional channel name as a key segment for the `posts` tree.
+///
+/// Posts without a channel are stored under a prefix of `0`, while posts with
+/// a channel are stored under a prefix of `1` followed by the channel key.
+fn posts_prefix(channel: &Option<Channel>) -> Vec<u8> {
+    match channel {
+        Some(channel) => {
+            let mut key = vec![1];
+            key.extend(channel_key(channel));
+            key
+        }
+        None => vec![0],
+    }
+}
+
+/// Concatenate the given key segments.
+fn concat_key(segments: &[&[u8]]) -> Vec<u8> {
+    segments.concat()
+}
+
+/// Read a big-endian timestamp from the given offset of a key.
+fn read_timestamp(key: &[u8], offset: usize) -> Option<Timestamp> {
+    key.get(offset..offset + 8)
+        .and_then(|bytes| bytes.try_into().ok())
+        .map(Timestamp::from_be_bytes)
+}
+
+/// Read a hash from the given offset of a key or value.
+fn read_hash(bytes: &[u8], offset: usize) -> Option<Hash> {
+    bytes
+        .get(offset..offset + 32)
+        .and_then(|bytes| bytes.try_into().ok())
+}
+
+/// Read a public key from the end of a key.
+fn read_public_key_suffix(key: &[u8]) -> Option<PublicKey> {
+    key.len()
+        .checked_sub(32)
+        .and_then(|offset| key[offset..].try_into().ok())
+}
+
+/// Split a value into a hash (the first 32 bytes) and a UTF-8 string (the
+/// remaining bytes).
+fn read_hash_and_string(value: &[u8]) -> Option<(Hash, String)> {
+    let hash = read_hash(value, 0)?;
+    let string = String::from_utf8(value[32..].to_vec()).ok()?;
+
+    Some((hash, string))
+}
+
+/// Read the info key from a key of the `peer_info` tree (public key +
+/// length-prefixed info key + timestamp).
+fn read_info_key(key: &[u8]) -> Option<String> {
+    let (s, len) = varint::decode(key.get(32..)?).ok()?;
+    let start = 32 + s;
+    let bytes = key.get(start..start + len as usize)?;
+
+    String::from_utf8(bytes.to_vec()).ok()
+}
+
+/// Split a value of concatenated hashes into a vector of hashes.
+fn read_hashes(value: &[u8]) -> Vec<Hash> {
+    value
+        .chunks_exact(32)
+        .filter_map(|chunk| chunk.try_into().ok())
+        .collect()
+}
+
+#[derive(Clone)]
+/// A persistent store containing post data.
+///
+/// The keypair of the store is held in memory only and is never written to
+/// disk; a new keypair is generated each time the store is opened unless one
+/// is defined. Use a `Keystore` to persist the keypair encrypted under a
+/// passphrase.
+pub struct SledStore {
+    /// The database handle.
+    db: Db,
+    /// The keypair associated with the store.
+    keypair: Arc<RwLock<Option<Keypair>>>,
+    /// The path of the database directory.
+    path: PathBuf,
+    /// All channels in the store.
+    ///
+    /// Key: channel. Value: empty.
+    channels: Tree,
+    /// The public keys of all members, indexed by channel.
+    ///
+    /// Key: channel key + public key. Value: empty.
+    channel_members: Tree,
+    /// The public keys of all ex-members, indexed by channel.
+    ///
+    /// Key: channel key + public key. Value: empty.
+    ex_channel_members: Tree,
+    /// The hash of the latest `post/join` or `post/leave` post for each known
+    /// peer, indexed by channel and public key.
+    ///
+    /// Key: channel key + public key. Value: hash.
+    channel_membership: Tree,
+    /// The topic and hash of each `post/topic` post, indexed by channel and
+    /// timestamp.
+    ///
+    /// Key: channel key + timestamp. Value: hash + topic.
+    channel_topics: Tree,
+    /// The hashes of all known `post/delete` posts, indexed by public key.
+    ///
+    /// Key: public key. Value: concatenated hashes.
+    delete_hashes: Tree,
+    /// The public keys of the authors of all deleted posts, indexed by the
+    /// hash of the deleted post.
+    ///
+    /// Key: hash. Value: public key.
+    tombstones: Tree,
+    /// The public keys of the authors of all `post/delete` posts referencing
+    /// posts which have not been seen yet, along with the hashes of the
+    /// `post/delete` posts, indexed by the hash of the referenced post.
+    ///
+    /// Key: hash. Value: concatenated public key + delete hash pairs.
+    pending_tombstones: Tree,
+    /// The hashes of all known `post/info` posts, indexed by public key.
+    ///
+    /// Key: public key. Value: concatenated hashes.
+    info_hashes: Tree,
+    /// The hashes of all known moderation posts, indexed by channel (an
+    /// empty channel name denotes the whole cabal).
+    ///
+    /// Key: channel key. Value: concatenated hashes.
+    moderation_hashes: Tree,
+    /// The nickname and hash of each name-setting `post/info` post, indexed
+    /// by public key and timestamp.
+    ///
+    /// Key: public key + timestamp. Value: hash + nickname.
+    peer_names: Tree,
+    /// The value and hash of every `post/info` key-value pair, indexed by
+    /// public key, info key and timestamp.
+    ///
+    /// Key: public key + info key + timestamp. Value: hash + value.
+    peer_info: Tree,
+    /// The hashes of all posts which make up the state of each channel,
+    /// indexed by channel.
+    ///
+    /// Key: channel key. Value: concatenated hashes.
+    channel_state: Tree,
+    /// The hashes linked to by each post, indexed by the post hash.
+    ///
+    /// Key: hash. Value: concatenated hashes.
+    parent_links: Tree,
+    /// The hashes of the posts linking to each hash, indexed by the linked
+    /// hash.
+    ///
+    /// Key: hash. Value: concatenated hashes.
+    child_links: Tree,
+    /// All posts in the store, indexed by channel, timestamp and hash.
+    ///
+    /// Key: posts prefix + timestamp + hash. Value: encoded post.
+    posts: Tree,
+    /// Binary payloads for all posts in the store, indexed by the post hash.
+    ///
+    /// Key: hash. Value: payload.
+    post_payloads: Tree,
+    /// The keys of the entries referencing each post in the `posts`,
+    /// `channel_membership`, `channel_topics`, `info_hashes`,
+    /// `moderation_hashes`, `peer_names` and `peer_info` trees, indexed by
+    /// the post hash and the tree name. This allows the entries of a post to
+    /// be removed without scanning the trees.
+    ///
+    /// Key: hash + tree name key + entry key. Value: empty.
+    entry_keys: Tree,
+    /// Moderation actions applied by the local peer, indexed by public key.
+    ///
+    /// Key: public key. Value: moderation action byte.
+    moderation: Tree,
+    /// The author of each known post by a blocked author, indexed by the
+    /// post hash.
+    ///
+    /// Key: hash. Value: public key.
+    blocked_hashes: Tree,
+    /// The time at which each recently pruned post was pruned, indexed by
+    /// the post hash.
+    ///
+    /// Key: hash. Value: timestamp.
+    pruned_hashes: Tree,
+    /// All active live streams, indexed by channel.
+    live_streams: Arc<RwLock<LiveStreamMap>>,
+    /// The unique identifier of a live stream.
+    live_stream_id: Arc<Mutex<usize>>,
+    /// The full-text search index of all text posts in the store.
+    ///
+    /// The index is held in memory and rebuilt from the `posts` tree when the
+    /// store is opened.
+    #[cfg(feature = "search")]
+    search_index: Arc<RwLock<SearchIndex>>,
+}
+
+impl SledStore {
+    /// Open (or create) a persistent store at the given path.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref().to_path_buf();
+        let db = sled::open(&path)?;
+        let posts = db.open_tree("posts")?;
+
+        // Index the text of all stored posts.
+        #[cfg(feature = "search")]
+        let search_index = {
+            let mut search_index = SearchIndex::default();
+            for (key, value) in posts.iter().filter_map(log_db_error) {
+                // The key ends with the hash of the post.
+                let hash = key
+                    .len()
+                    .checked_sub(32)
+                    .and_then(|offset| read_hash(&key, offset));
+                if let (Some(hash), Ok((_s, post))) = (hash, Post::from_bytes(&value)) {
+                    search_index.insert(&hash, &post);
+                }
+            }
+            Arc::new(RwLock::new(search_index))
+        };
+
+        Ok(Self {
+            channels: db.open_tree("channels")?,
+            channel_members: db.open_tree("channel_members")?,
+            ex_channel_members: db.open_tree("ex_channel_members")?,
+            channel_membership: db.open_tree("channel_membership")?,
+            channel_topics: db.open_tree("channel_topics")?,
+            delete_hashes: db.open_tree("delete_hashes")?,
+            tombstones: db.open_tree("tombstones")?,
+            pending_tombstones: db.open_tree("pending_tombstones")?,
+            info_hashes: db.open_tree("info_hashes")?,
+            moderation_hashes: db.open_tree("moderation_hashes")?,
+            peer_names: db.open_tree("peer_names")?,
+            peer_info: db.open_tree("peer_info")?,
+            channel_state: db.open_tree("channel_state")?,
+            parent_links: db.open_tree("parent_links")?,
+            child_links: db.open_tree("child_links")?,
+            posts,
+            post_payloads: db.open_tree("post_payloads")?,
+            entry_keys: db.open_tree("entry_keys")?,
+            moderation: db.open_tree("moderation")?,
+            blocked_hashes: db.open_tree("blocked_hashes")?,
+            pruned_hashes: db.open_tree("pruned_hashes")?,
+            live_streams: Arc::new(RwLock::new(Default::default())),
+            live_stream_id: Arc::new(Mutex::new(0)),
+            #[cfg(feature = "search")]
+            search_index,
+            db,
+            keypair: Arc::new(RwLock::new(None)),
+            path,
+        })
+    }
+
+    /// Flush all pending writes to disk, returning the number of bytes
+    /// flushed.
+    ///
+    /// `sled` flushes periodically in the background; this method can be used
+    /// to ensure durability at a specific point in time (e.g. before exit).
+    pub async fn flush(&self) -> Result<usize, Error> {
+        Ok(self.db.flush_async().await?)
+    }
+
+    /// Flush all pending writes to disk and close the store, waiting until
+    /// the database has been released such that it can be opened again.
+    ///
+    /// `sled` releases the lock on the database from background threads
+    /// some time after the last handle has been dropped. All clones of the
+    /// store must have been dropped for the returned future to resolve.
+    pub async fn close(self) -> Result<(), Error> {
+        self.flush().await?;
+
+        // The database file is locked by `sled` for as long as it is open.
+        let db_path = self.path.join("db");
+        drop(self);
+
+        task::spawn_blocking(move || {
+            let file = File::open(db_path)?;
+            file.lock_exclusive()?;
+            file.unlock()?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    /// Append the given hash to the concatenated hashes stored under the
+    /// given key.
+    fn append_hash(tree: &Tree, key: &[u8], hash: &Hash) {
+        log_db_error(tree.fetch_and_update(key, |value| {
+            let mut hashes = value.map(|value| value.to_vec()).unwrap_or_default();
+            hashes.extend_from_slice(hash);
+            Some(hashes)
+        }));
+    }
+
+    /// Return the prefix of the `entry_keys` tree under which the keys of
+    /// all entries in the given tree referencing the given hash are stored.
+    fn entry_keys_prefix(tree: &Tree, hash: &Hash) -> Vec<u8> {
+        concat_key(&[hash, &length_prefixed_key(&tree.name())])
+    }
+
+    /// Record that the entry with the given key in the given tree references
+    /// the given hash.
+    fn insert_entry_key(&self, tree: &Tree, hash: &Hash, key: &[u8]) {
+        let entry_key = concat_key(&[&Self::entry_keys_prefix(tree, hash), key]);
+        log_db_error(self.entry_keys.insert(entry_key, &[]));
+    }
+
+    /// Remove and return the keys of all entries in the given tree which
+    /// reference the given hash.
+    fn take_entry_keys(&self, tree: &Tree, hash: &Hash) -> Vec<Vec<u8>> {
+        let prefix = Self::entry_keys_prefix(tree, hash);
+
+        let mut keys = Vec::new();
+        for (entry_key, _value) in self
+            .entry_keys
+            .scan_prefix(&prefix)
+            .filter_map(log_db_error)
+        {
+            log_db_error(self.entry_keys.remove(&entry_key));
+            keys.push(entry_key[prefix.len()..].to_vec());
+        }
+
+        keys
+    }
+
+    /// Insert an entry whose value begins with the given hash into the given
+    /// tree, replacing the recorded key of any entry it overwrites.
+    fn insert_hash_entry(&self, tree: &Tree, key: &[u8], value: &[u8], hash: &Hash) {
+        let replaced = log_db_error(tree.insert(key, value))
+            .flatten()
+            .and_then(|value| read_hash(&value, 0));
+        if let Some(replaced) = replaced.filter(|replaced| replaced != hash) {
+            let entry_key = concat_key(&[&Self::entry_keys_prefix(tree, &replaced), key]);
+            log_db_error(self.entry_keys.remove(entry_key));
+        }
+        self.insert_entry_key(tree, hash, key);
+    }
+
+    /// Remove all entries whose value begins with the given hash from the
+    /// given tree.
+    fn remove_hash_entries(&self, tree: &Tree, hash: &Hash) {
+        for key in self.take_entry_keys(tree, hash) {
+            // The entry may have been overwritten by a later post.
+            log_db_error(tree.fetch_and_update(key, |value| {
+                let value = value?;
+                if read_hash(value, 0).as_ref() == Some(hash) {
+                    None
+                } else {
+                    Some(value.to_vec())
+                }
+            }));
+        }
+    }
+
+    /// Append the given hash to the concatenated hashes stored under the
+    /// given key, recording the key of the entry.
+    fn append_indexed_hash(&self, tree: &Tree, key: &[u8], hash: &Hash) {
+        Self::append_hash(tree, key, hash);
+        self.insert_entry_key(tree, hash, key);
+    }
+
+    /// Remove the given hash from all concatenated hashes in the given tree.
+    fn remove_indexed_hash(&self, tree: &Tree, hash: &Hash) {
+        for key in self.take_entry_keys(tree, hash) {
+            log_db_error(tree.fetch_and_update(key, |value| {
+                let retained: Vec<u8> = read_hashes(value?)
+                    .iter()
+                    .filter(|stored_hash| *stored_hash != hash)
+                    .flatten()
+                    .copied()
+                    .collect();
+                Some(retained)
+            }));
+        }
+    }
+
+    /// Retrieve all public keys stored under the given channel in the given
+    /// tree.
+    fn get_public_keys(tree: &Tree, channel: &Channel) -> Option<Vec<PublicKey>> {
+        let public_keys: Vec<PublicKey> = tree
+            .scan_prefix(channel_key(channel))
+            .filter_map(log_db_error)
+            .filter_map(|(key, _value)| read_public_key_suffix(&key))
+            .collect();
+
+        if public_keys.is_empty() {
+            None
+        } else {
+            Some(public_keys)
+        }
+    }
+
+    /// Retrieve the key-value pairs of the `posts` tree matching the given
+    /// channel options, newest first.
+    ///
+    /// The time range semantics match those of the `MemoryStore`: both the
+    /// start and end time are inclusive and an end time of 0 indicates that
+    /// the range is unbounded. A limit of 0 indicates that there is no limit.
+    fn posts_in_range(&self, opts: &ChannelOptions) -> Vec<(IVec, IVec)> {
+        let prefix = posts_prefix(&Some(opts.channel.to_owned()));
+        let end = if opts.time_end == 0 {
+            Timestamp::MAX
+        } else {
+            opts.time_end
+        };
+        if opts.time_start > end {
+            return Vec::new();
+        }
+        let start = concat_key(&[&prefix, &opts.time_start.to_be_bytes()]);
+        // Include every hash stored with the end time.
+        let end = concat_key(&[&prefix, &end.to_be_bytes(), &[u8::MAX; 32]]);
+        let limit = if opts.limit == 0 {
+            usize::MAX
+        } else {
+            opts.limit as usize
+        };
+
+        self.posts
+            .range(start..=end)
+            .rev()
+            .filter_map(log_db_error)
+            .take(limit)
+            .collect()
+    }
+}
+
+/// Decode each value in the given key-value pairs as a post.
+fn decode_posts(entries: impl Iterator<Item = (IVec, IVec)>) -> Vec<Result<Post, Error>> {
+    entries
+        .map(|(_key, value)| Post::from_bytes(&value).map(|(_s, post)| post))
+        .collect()
+}
+
+#[async_trait::async_trait]
+impl Store for SledStore {
+    async fn get_keypair(&self) -> Option<Keypair> {
+        *self.keypair.read().await
+    }
+
+    async fn set_keypair(&mut self, keypair: Keypair) {
+        *self.keypair.write().await = Some(keypair);
+    }
+
+    async fn get_channels(&self) -> Option<Vec<Channel>> {
+        let channels: Vec<Channel> = self
+            .channels
+            .iter()
+            .filter_map(log_db_error)
+            .filter_map(|(key, _value)| String::from_utf8(key.to_vec()).ok())
+            .collect();
+
+        if channels.is_empty() {
+            None
+        } else {
+            Some(channels)
+        }
+    }
+
+    async fn insert_channel(&mut self, channel: &Channel) {
+        log_db_error(self.channels.insert(channel.as_bytes(), &[]));
+    }
+
+    async fn get_channel_members(&self, channel: &Channel) -> Option<Vec<PublicKey>> {
+        Self::get_public_keys(&self.channel_members, channel)
+    }
+
+    async fn insert_channel_member(&mut self, channel: &Channel, public_key: &PublicKey) {
+        let key = concat_key(&[&channel_key(channel), public_key]);
+        log_db_error(self.channel_members.insert(key, &[]));
+    }
+
+    async fn is_channel_member(&self, channel: &Channel, public_key: &PublicKey) -> bool {
+        let key = concat_key(&[&channel_key(channel), public_key]);
+        log_db_error(self.channel_members.contains_key(key)).unwrap_or(false)
+    }
+
+    async fn remove_channel_member(&mut self, channel: &Channel, public_key: &PublicKey) {
+        let key = concat_key(&[&channel_key(channel), public_key]);
+        log_db_error(self.channel_members.remove(key));
+    }
+
+    async fn get_channel_membership_hashes(&self, channel: &Channel) -> Option<Vec<Hash>> {
+        let hashes: Vec<Hash> = self
+            .channel_membership
+            .scan_prefix(channel_key(channel))
+            .filter_map(log_db_error)
+            .filter_map(|(_key, value)| read_hash(&value, 0))
+            .collect();
+
+        if hashes.is_empty() {
+            None
+        } else {
+            Some(hashes)
+        }
+    }
+
+    async fn remove_channel_membership_hash(&mut self, hash: &Hash) {
+        self.remove_hash_entries(&self.channel_membership, hash);
+    }
+
+    async fn update_channel_membership_hashes(
+        &mut self,
+        channel: &Channel,
+        public_key: &PublicKey,
+        hash: &Hash,
+    ) {
+        let key = concat_key(&[&channel_key(channel), public_key]);
+        self.insert_hash_entry(&self.channel_membership, &key, hash, hash);
+    }
+
+    async fn get_ex_channel_members(&self, channel: &Channel) -> Option<Vec<PublicKey>> {
+        Self::get_public_keys(&self.ex_channel_members, channel)
+    }
+
+    async fn insert_ex_channel_member(&mut self, channel: &Channel, public_key: &PublicKey) {
+        let key = concat_key(&[&channel_key(channel), public_key]);
+        log_db_error(self.ex_channel_members.insert(key, &[]));
+    }
+
+    async fn remove_ex_channel_member(&mut self, channel: &Channel, public_key: &PublicKey) {
+        let key = concat_key(&[&channel_key(channel), public_key]);
+        log_db_error(self.ex_channel_members.remove(key));
+    }
+
+    async fn get_channel_topic_and_hash(&self, channel: &Channel) -> Option<(Topic, Hash)> {
+        // Get the entry with the largest timestamp.
+        self.channel_topics
+            .scan_prefix(channel_key(channel))
+            .next_back()
+            .and_then(log_db_error)
+            .and_then(|(_key, value)| read_hash_and_string(&value))
+            .map(|(hash, topic)| (topic, hash))
+    }
+
+    async fn insert_channel_topic(
+        &mut self,
+        channel: &Channel,
+        topic: &Topic,
+        timestamp: &Timestamp,
+        hash: &Hash,
+    ) {
+        let key = concat_key(&[&channel_key(channel), &timestamp.to_be_bytes()]);
+        let value = concat_key(&[hash, topic.as_bytes()]);
+        self.insert_hash_entry(&self.channel_topics, &key, &value, hash);
+    }
+
+    async fn remove_channel_topic(&mut self, hash: &Hash) {
+        self.remove_hash_entries(&self.channel_topics, hash);
+    }
+
+    async fn get_delete_hashes(&self, public_key: &PublicKey) -> Option<Vec<Hash>> {
+        log_db_error(self.delete_hashes.get(public_key))
+            .flatten()
+            .map(|value| read_hashes(&value))
+    }
+
+    async fn insert_delete_hash(&mut self, public_key: &PublicKey, hash: &Hash) {
+        Self::append_hash(&self.delete_hashes, public_key, hash);
+    }
+
+    async fn get_tombstone(&self, hash: &Hash) -> Option<PublicKey> {
+        log_db_error(self.tombstones.get(hash))
+            .flatten()
+            .and_then(|value| read_hash(&value, 0))
+    }
+
+    async fn insert_tombstone(&mut self, hash: &Hash, public_key: &PublicKey) {
+        log_db_error(self.tombstones.insert(hash, public_key));
+    }
+
+    async fn get_pending_tombstones(&self, hash: &Hash) -> Option<Vec<(PublicKey, Hash)>> {
+        log_db_error(self.pending_tombstones.get(hash))
+            .flatten()
+            .map(|value| {
+                read_hashes(&value)
+                    .chunks_exact(2)
+                    .map(|pair| (pair[0], pair[1]))
+                    .collect()
+            })
+    }
+
+    async fn insert_pending_tombstone(
+        &mut self,
+        hash: &Hash,
+        public_key: &PublicKey,
+        delete_hash: &Hash,
+    ) {
+        let tombstones = self.get_pending_tombstones(hash).await.unwrap_or_default();
+        if !tombstones.contains(&(*public_key, *delete_hash)) {
+            let value = concat_key(&[public_key, delete_hash]);
+            log_db_error(self.pending_tombstones.fetch_and_update(hash, |stored| {
+                let mut tombstones = stored.map(|stored| stored.to_vec()).unwrap_or_default();
+                tombstones.extend_from_slice(&value);
+                Some(tombstones)
+            }));
+        }
+    }
+
+    async fn remove_pending_tombstones(&mut self, hash: &Hash) {
+        log_db_error(self.pending_tombstones.remove(hash));
+    }
+
+    async fn get_info_hashes(&self, public_key: &PublicKey) -> Option<Vec<Hash>> {
+        log_db_error(self.info_hashes.get(public_key))
+            .flatten()
+            .map(|value| read_hashes(&value))
+    }
+
+    async fn insert_info_hash(&mut self, public_key: &PublicKey, hash: &Hash) {
+        self.append_indexed_hash(&self.info_hashes, public_key, hash);
+    }
+
+    async fn remove_info_hash(&mut self, hash: &Hash) {
+        self.remove_indexed_hash(&self.info_hashes, hash);
+    }
+
+    async fn get_moderation_hashes(&self, channel: &Channel) -> Option<Vec<Hash>> {
+        log_db_error(self.moderation_hashes.get(channel_key(channel)))
+            .flatten()
+            .map(|value| read_hashes(&value))
+    }
+
+    async fn insert_moderation_hash(&mut self, channel: &Channel, hash: &Hash) {
+        // A post may be received more than once.
+        if !self
+            .get_moderation_hashes(channel)
+            .await
+            .is_some_and(|hashes| hashes.contains(hash))
+        {
+            self.append_indexed_hash(&self.moderation_hashes, &channel_key(channel), hash);
+        }
+    }
+
+    async fn remove_moderation_hash(&mut self, hash: &Hash) {
+        self.remove_indexed_hash(&self.moderation_hashes, hash);
+    }
+
+    async fn get_latest_hashes(&self, channel: &Channel) -> Option<Vec<Hash>> {
+        let prefix = posts_prefix(&Some(channel.to_owned()));
+
+        let mut latest_timestamp = None;
+        let mut hashes = Vec::new();
+
+        // Iterate over the posts in reverse order (latest first) and collect
+        // the hashes of all posts sharing the latest timestamp.
+        for (key, _value) in self
+            .posts
+            .scan_prefix(&prefix)
+            .rev()
+            .filter_map(log_db_error)
+        {
+            let timestamp = read_timestamp(&key, prefix.len());
+            if latest_timestamp.is_some() && latest_timestamp != timestamp {
+                break;
+            }
+            latest_timestamp = timestamp;
+
+            if let Some(hash) = read_hash(&key, prefix.len() + 8) {
+                hashes.push(hash)
+            }
+        }
+
+        if hashes.is_empty() {
+            None
+        } else {
+            Some(hashes)
+        }
+    }
+
+    async fn get_peer_name_and_hash(&self, public_key: &PublicKey) -> Option<(Nickname, Hash)> {
+        // Get the entry with the largest timestamp.
+        self.peer_names
+            .scan_prefix(public_key)
+            .next_back()
+            .and_then(log_db_error)
+            .and_then(|(_key, value)| read_hash_and_string(&value))
+            .map(|(hash, name)| (name, hash))
+    }
+
+    async fn insert_peer_name(
+        &mut self,
+        public_key: &PublicKey,
+        name: &Nickname,
+        timestamp: &Timestamp,
+        hash: &Hash,
+    ) {
+        let key = concat_key(&[public_key, &timestamp.to_be_bytes()]);
+        let value = concat_key(&[hash, name.as_bytes()]);
+        self.insert_hash_entry(&self.peer_names, &key, &value, hash);
+    }
+
+    async fn remove_peer_name(&mut self, hash: &Hash) {
+        self.remove_hash_entries(&self.peer_names, hash);
+    }
+
+    async fn get_peer_info(&self, public_key: &PublicKey) -> Option<Vec<UserInfo>> {
+        let mut info = BTreeMap::new();
+
+        // Entries are ordered by info key and then by timestamp, so the last
+        // value inserted for each info key is the latest.
+        for (key, value) in self
+            .peer_info
+            .scan_prefix(public_key)
+            .filter_map(log_db_error)
+        {
+            if let (Some(info_key), Some((_hash, val))) =
+                (read_info_key(&key), read_hash_and_string(&value))
+            {
+                info.insert(info_key, val);
+            }
+        }
+
+        if info.is_empty() {
+            None
+        } else {
+            Some(
+                info.into_iter()
+                    .map(|(key, val)| UserInfo::new(key, val))
+                    .collect(),
+            )
+        }
+    }
+
+    async fn insert_peer_info(
+        &mut self,
+        public_key: &PublicKey,
+        info: &UserInfo,
+        timestamp: &Timestamp,
+        hash: &Hash,
+    ) {
+        let key = concat_key(&[
+            public_key,
+            &channel_key(&info.key),
+            &timestamp.to_be_bytes(),
+        ]);
+        let value = concat_key(&[hash, info.val.as_bytes()]);
+        self.insert_hash_entry(&self.peer_info, &key, &value, hash);
+    }
+
+    async fn remove_peer_info(&mut self, hash: &Hash) {
+        self.remove_hash_entries(&self.peer_info, hash);
+    }
+
+    async fn get_peer_info_hashes(&self, public_key: &PublicKey) -> Option<Vec<Hash>> {
+        let mut latest = BTreeMap::new();
+
+        // Entries are ordered by info key and then by timestamp, so the last
+        // hash inserted for each info key is the latest.
+        for (key, value) in self
+            .peer_info
+            .scan_prefix(public_key)
+            .filter_map(log_db_error)
+        {
+            if let (Some(info_key), Some(hash)) = (read_info_key(&key), read_hash(&value, 0)) {
+                latest.insert(info_key, hash);
+            }
+        }
+
+        let mut hashes = Vec::new();
+        for hash in latest.into_values() {
+            if !hashes.contains(&hash) {
+                hashes.push(hash);
+            }
+        }
+
+        if hashes.is_empty() {
+            None
+        } else {
+            Some(hashes)
+        }
+    }
+
+    async fn get_channel_state_hashes(&self, channel: &Channel) -> Option<Vec<Hash>> {
+        log_db_error(self.channel_state.get(channel_key(channel)))
+            .flatten()
+            .map(|value| read_hashes(&value))
+    }
+
+    async fn set_channel_state_hashes(&mut self, channel: &Channel, hashes: &[Hash]) {
+        log_db_error(
+            self.channel_state
+                .insert(channel_key(channel), hashes.concat()),
+        );
+    }
+
+    async fn get_parent_hashes(&self, hash: &Hash) -> Option<Vec<Hash>> {
+        log_db_error(self.parent_links.get(hash))
+            .flatten()
+            .map(|value| read_hashes(&value))
+    }
+
+    async fn get_child_hashes(&self, hash: &Hash) -> Option<Vec<Hash>> {
+        log_db_error(self.child_links.get(hash))
+            .flatten()
+            .map(|value| read_hashes(&value))
+    }
+
+    async fn insert_links(&mut self, hash: &Hash, links: &[Hash]) {
+        log_db_error(self.parent_links.insert(hash, links.concat()));
+
+        for link in links {
+            let is_known = self
+                .get_child_hashes(link)
+                .await
+                .is_some_and(|children| children.contains(hash));
+            if !is_known {
+                Self::append_hash(&self.child_links, link, hash);
+            }
+        }
+    }
+
+    async fn remove_links(&mut self, hash: &Hash) {
+        let links = match log_db_error(self.parent_links.remove(hash)).flatten() {
+            Some(value) => read_hashes(&value),
+            None => return,
+        };
+
+        for link in links {
+            log_db_error(self.child_links.fetch_and_update(link, |value| {
+                let retained: Vec<u8> = read_hashes(value?)
+                    .iter()
+                    .filter(|child| *child != hash)
+                    .flatten()
+                    .copied()
+                    .collect();
+                // Remove the entry once no children remain.
+                if retained.is_empty() {
+                    None
+                } else {
+                    Some(retained)
+                }
+            }));
+        }
+    }
+
+    async fn get_posts(&self, opts: &ChannelOptions) -> PostStream {
+        // Retrieve the latest posts matching the given channel options, in
+        // chronological order.
+        let mut posts = decode_posts(self.posts_in_range(opts).into_iter().rev());
+
+        // Retrieve all posts which do not have a channel field.
+        // For example, `post/info` posts.
+        let non_channel_posts = decode_posts(
+            self.posts
+                .scan_prefix(posts_prefix(&None))
+                .filter_map(log_db_error),
+        );
+
+        // Add the non-channel posts to the channel posts.
+        posts.extend(non_channel_posts);
+
+        // Return a post stream.
+        Box::new(stream::from_iter(posts))
+    }
+
+    async fn get_posts_live(&mut self, opts: &ChannelOptions) -> PostStream {
+        let live_stream =
+            register_live_stream(&self.live_streams, &self.live_stream_id, opts).await;
+
+        // Retrieve all stored posts matching the channel options,
+        // as well as all non-channel posts.
+        let post_stream = self.get_posts(opts).await;
+
+        // Merge the existing post stream with the live post stream.
+        Box::new(post_stream.merge(live_stream))
+    }
+
+    async fn get_posts_before(
+        &self,
+        channel: &Channel,
+        cursor: Option<&HistoryCursor>,
+        limit: usize,
+    ) -> Vec<(Post, Hash)> {
+        let prefix = posts_prefix(&Some(channel.to_owned()));
+
+        // Keys are ordered by timestamp and then by hash, matching the order
+        // of cursors.
+        let entries = match cursor {
+            Some(cursor) => self.posts.range(
+                prefix.clone()
+                    ..concat_key(&[&prefix, &cursor.timestamp.to_be_bytes(), &cursor.hash]),
+            ),
+            None => self.posts.scan_prefix(&prefix),
+        };
+
+        entries
+            .rev()
+            .filter_map(log_db_error)
+            .take(limit)
+            .filter_map(|(key, value)| {
+                let hash = read_hash(&key, prefix.len() + 8)?;
+                let (_s, post) = Post::from_bytes(&value).ok()?;
+                Some((post, hash))
+            })
+            .collect()
+    }
+
+    async fn get_post_hashes(&self, opts: &ChannelOptions) -> HashStream {
+        let prefix_len = posts_prefix(&Some(opts.channel.to_owned())).len();
+
+        let hashes: Vec<Result<Hash, Error>> = self
+            .posts_in_range(opts)
+            .into_iter()
+            .filter_map(|(key, _value)| read_hash(&key, prefix_len + 8))
+            .map(Ok)
+            .collect();
+
+        // Return a hash stream.
+        Box::new(stream::from_iter(hashes))
+    }
+
+    async fn remove_post(&mut self, hash: &Hash) {
+        for key in self.take_entry_keys(&self.posts, hash) {
+            log_db_error(self.posts.remove(key));
+        }
+    }
+
+    #[cfg(feature = "search")]
+    async fn index_post(&mut self, hash: &Hash, post: &Post) {
+        self.search_index.write().await.insert(hash, post);
+    }
+
+    #[cfg(feature = "search")]
+    async fn unindex_post(&mut self, hash: &Hash) {
+        self.search_index.write().await.remove(hash);
+    }
+
+    #[cfg(feature = "search")]
+    async fn search(&self, query: &SearchQuery) -> Vec<SearchResult> {
+        self.search_index.read().await.search(query)
+    }
+
+    async fn update_posts(
+        &mut self,
+        post: &Post,
+        channel: Option<Channel>,
+        timestamp: &Timestamp,
+        hash: Hash,
+    ) {
+        let key = concat_key(&[&posts_prefix(&channel), &timestamp.to_be_bytes(), &hash]);
+        match post.to_bytes() {
+            Ok(value) => {
+                log_db_error(self.posts.insert(&key, value));
+                self.insert_entry_key(&self.posts, &hash, &key);
+            }
+            Err(err) => error!("Failed to encode post for storage: {err}"),
+        }
+    }
+
+    async fn get_post_payload(&self, hash: &Hash) -> Option<Payload> {
+        log_db_error(self.post_payloads.get(hash))
+            .flatten()
+            .map(|value| value.to_vec())
+    }
+
+    async fn get_post_payloads(&self, hashes: &[Hash]) -> Vec<Payload> {
+        hashes
+            .iter()
+            .filter_map(|hash| log_db_error(self.post_payloads.get(hash)).flatten())
+            .map(|value| value.to_vec())
+            .collect()
+    }
+
+    async fn get_payload_sizes(&self) -> Vec<(Hash, usize)> {
+        self.post_payloads
+            .iter()
+            .filter_map(log_db_error)
+            .filter_map(|(key, value)| Some((read_hash(&key, 0)?, value.len())))
+            .collect()
+    }
+
+    async fn insert_post_payload(&mut self, hash: &Hash, payload: Payload) {
+        log_db_error(self.post_payloads.insert(hash, payload));
+    }
+
+    async fn remove_post_payload(&mut self, hash: &Hash) {
+        log_db_error(self.post_payloads.remove(hash));
+    }
+
+    async fn send_post_to_live_streams(&self, post: &Post, channel: &Channel) {
+        send_to_live_streams(&self.live_streams, post, channel).await
+    }
+
+    async fn want(&self, hashes: &[Hash]) -> Vec<Hash> {
+        let contains =
+            |tree: &Tree, hash: &Hash| log_db_error(tree.contains_key(hash)).unwrap_or(false);
+
+        // Return the "wanted" hashes.
+        hashes
+            .iter()
+            .filter(|hash| {
+                !contains(&self.post_payloads, hash)
+                    && !contains(&self.blocked_hashes, hash)
+                    && !contains(&self.tombstones, hash)
+                    && !contains(&self.pruned_hashes, hash)
+            })
+            .cloned()
+            .collect()
+    }
+
+    async fn insert_pruned_hashes(&mut self, hashes: &[Hash], timestamp: Timestamp) {
+        for hash in hashes {
+            log_db_error(self.pruned_hashes.insert(hash, &timestamp.to_be_bytes()));
+        }
+    }
+
+    async fn remove_pruned_hashes_before(&mut self, timestamp: Timestamp) {
+        for (key, value) in self.pruned_hashes.iter().filter_map(log_db_error) {
+            if read_timestamp(&value, 0).is_some_and(|pruned_at| pruned_at < timestamp) {
+                log_db_error(self.pruned_hashes.remove(key));
+            }
+        }
+    }
+
+    async fn get_post_hashes_by_author(&self, public_key: &PublicKey) -> Vec<Hash> {
+        self.post_payloads
+            .iter()
+            .filter_map(log_db_error)
+            .filter(|(_key, value)| {
+                Post::from_bytes(value)
+                    .map(|(_s, post)| &post.get_public_key() == public_key)
+                    .unwrap_or(false)
+            })
+            .filter_map(|(key, _value)| read_hash(&key, 0))
+            .collect()
+    }
+
+    async fn get_moderation(&self, public_key: &PublicKey) -> Option<Moderation> {
+        log_db_error(self.moderation.get(public_key))
+            .flatten()
+            .and_then(|value| value.first().copied())
+            .and_then(Moderation::from_byte)
+    }
+
+    async fn get_moderated_keys(&self) -> Vec<(PublicKey, Moderation)> {
+        self.moderation
+            .iter()
+            .filter_map(log_db_error)
+            .filter_map(|(key, value)| {
+                let public_key = key.as_ref().try_into().ok()?;
+                let moderation = Moderation::from_byte(*value.first()?)?;
+                Some((public_key, moderation))
+            })
+            .collect()
+    }
+
+    async fn set_moderation(&mut self, public_key: &PublicKey, moderation: Moderation) {
+        log_db_error(self.moderation.insert(public_key, &[moderation.to_byte()]));
+    }
+
+    async fn remove_moderation(&mut self, public_key: &PublicKey) {
+        log_db_error(self.moderation.remove(public_key));
+
+        for (key, value) in self.blocked_hashes.iter().filter_map(log_db_error) {
+            if value.as_ref() == public_key {
+                log_db_error(self.blocked_hashes.remove(key));
+            }
+        }
+    }
+
+    async fn insert_blocked_hash(&mut self, public_key: &PublicKey, hash: &Hash) {
+        log_db_error(self.blocked_hashes.insert(hash, public_key));
+    }
+}
@@ -2,7 +2,7 @@
 //! an in-memory implementation of the `Store` trait.
 
 use std::{
-    collections::{BTreeMap, BTreeSet, HashMap},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     convert::TryInto,
 };
 
@@ -13,13 +13,21 @@ use async_std::{
     task,
 };
 use cable::{
+    constants::INFO_NAME_KEY,
     post::{Post, PostBody},
     Channel, ChannelOptions, Error, Hash, Nickname, Payload, Timestamp, Topic, UserInfo,
 };
 use desert::{FromBytes, ToBytes};
 use sodiumoxide::crypto;
 
-use crate::stream::{HashStream, LiveStream, PostStream};
+#[cfg(feature = "search")]
+use crate::search::{SearchIndex, SearchQuery, SearchResult};
+use crate::{
+    config::RetentionConfig,
+    history::HistoryCursor,
+    moderation::Moderation,
+    stream::{HashStream, LiveStream, PostStream},
+};
 
 /// A public key.
 pub type PublicKey = [u8; 32];
@@ -31,6 +39,13 @@ pub type Keypair = ([u8; 32], [u8; 64]);
 /// of a `Vec` of streams (wrapped in an `Arc` and `RwLock`).
 pub type LiveStreamMap = HashMap<Channel, Arc<RwLock<Vec<LiveStream>>>>;
 
+/// A `HashMap` of peer info with a key of public key and a value of a
+/// `BTreeMap`. The `BTreeMap` has a key of info key and a value of a second
+/// `BTreeMap`, which has a key of timestamp and a value of a tuple of info
+/// value and hash. The hash is of the `post/info` post which defined the
+/// stored value.
+pub type InfoHashMap = HashMap<PublicKey, BTreeMap<String, BTreeMap<Timestamp, (String, Hash)>>>;
+
 /// A `HashMap` of peer names with a key of public key and a value of a
 /// `BTreeMap`. The `BTreeMap` has a key of timestamp and a value of a tuple
 /// of name and hash. The hash is of the `post/info` post which defined the
@@ -46,6 +61,12 @@ pub type NameHashMap = HashMap<PublicKey, BTreeMap<Timestamp, (Nickname, Hash)>>
 /// key of `None`.
 pub type PostMap = HashMap<Option<Channel>, BTreeMap<Timestamp, Vec<(Post, Hash)>>>;
 
+/// A `HashMap` of pending tombstones with a key of the hash of a post which
+/// has not been seen yet and a value of a `Vec` of tuple with the public key
+/// of the author of a `post/delete` post referencing the post and the hash of
+/// the `post/delete` post.
+pub type PendingTombstoneMap = HashMap<Hash, Vec<(PublicKey, Hash)>>;
+
 /// A `HashMap` of channel topics with a key of channel name and a value of a
 /// `BTreeMap`. The `BTreeMap` has a key of timestamp and a value of a tuple
 /// of topic and hash. The hash is of the `post/topic` post which defined the
@@ -151,6 +172,42 @@ pub trait Store: Clone + Send + Sync + Unpin + 'static {
     /// by the given public key.
     async fn insert_delete_hash(&mut self, public_key: &PublicKey, hash: &Hash);
 
+    /// Retrieve the public key of the author of the post with the given hash
+    /// if the post has been deleted by its author (the tombstone of the hash).
+    async fn get_tombstone(&self, hash: &Hash) -> Option<PublicKey>;
+
+    /// Record a tombstone for the given hash, deleted by the given public key.
+    ///
+    /// Tombstones are retained after the referenced post has been deleted,
+    /// ensuring the post is neither requested nor stored again.
+    async fn insert_tombstone(&mut self, hash: &Hash, public_key: &PublicKey);
+
+    /// Retrieve the public keys of the authors of all `post/delete` posts
+    /// referencing the given hash before the post has been seen, along with
+    /// the hashes of the `post/delete` posts (the pending tombstones of the
+    /// hash).
+    async fn get_pending_tombstones(&self, hash: &Hash) -> Option<Vec<(PublicKey, Hash)>>;
+
+    /// Record a pending tombstone for the given hash, deleted by the given
+    /// public key in the `post/delete` post with the given hash.
+    ///
+    /// The deletion is only authorised if the post turns out to be authored
+    /// by the given public key; the post is therefore still wanted.
+    async fn insert_pending_tombstone(
+        &mut self,
+        hash: &Hash,
+        public_key: &PublicKey,
+        delete_hash: &Hash,
+    );
+
+    /// Remove all pending tombstones for the given hash.
+    async fn remove_pending_tombstones(&mut self, hash: &Hash);
+
+    /// Query whether the given post has been deleted by its author.
+    async fn is_deleted(&self, post: &Post, hash: &Hash) -> bool {
+        self.get_tombstone(hash).await == Some(post.get_public_key())
+    }
+
     /// Retrieve the hashes of all known info posts authored by the given
     /// public key.
     async fn get_info_hashes(&self, public_key: &PublicKey) -> Option<Vec<Hash>>;
@@ -162,6 +219,18 @@ pub trait Store: Clone + Send + Sync + Unpin + 'static {
     /// Remove the info post data for the given post hash.
     async fn remove_info_hash(&mut self, hash: &Hash);
 
+    /// Retrieve the hashes of all known moderation posts (`post/role`,
+    /// `post/moderation`, `post/block` and `post/unblock`) applying to the
+    /// given channel. An empty channel name denotes the whole cabal.
+    async fn get_moderation_hashes(&self, channel: &Channel) -> Option<Vec<Hash>>;
+
+    /// Insert the given moderation post hash into the store using the key
+    /// defined by the given channel.
+    async fn insert_moderation_hash(&mut self, channel: &Channel, hash: &Hash);
+
+    /// Remove the moderation post data for the given post hash.
+    async fn remove_moderation_hash(&mut self, hash: &Hash);
+
     /// Retrieve the hash(es) of the most recently published post(s) in the
     /// given channel.
     ///
@@ -186,8 +255,119 @@ pub trait Store: Clone + Send + Sync + Unpin + 'static {
     /// Remove the peer name data for the given post hash.
     async fn remove_peer_name(&mut self, hash: &Hash);
 
+    /// Retrieve the latest `post/info` value of every key for the given
+    /// public key, sorted by key.
+    async fn get_peer_info(&self, public_key: &PublicKey) -> Option<Vec<UserInfo>>;
+
+    /// Insert the given `post/info` key-value pair, timestamp and hash into
+    /// the store.
+    async fn insert_peer_info(
+        &mut self,
+        public_key: &PublicKey,
+        info: &UserInfo,
+        timestamp: &Timestamp,
+        hash: &Hash,
+    );
+
+    /// Remove the peer info data for the given post hash.
+    async fn remove_peer_info(&mut self, hash: &Hash);
+
+    /// Retrieve the hashes of the `post/info` posts which define the latest
+    /// value of every key for the given public key.
+    ///
+    /// This is usually the hash of the latest `post/info` post, along with
+    /// the hashes of any earlier posts assigning keys which have not since
+    /// been updated.
+    async fn get_peer_info_hashes(&self, public_key: &PublicKey) -> Option<Vec<Hash>>;
+
+    /// Retrieve the hashes of all posts which make up the state of the given
+    /// channel, as recorded by the last call to `update_channel_state()`.
+    async fn get_channel_state_hashes(&self, channel: &Channel) -> Option<Vec<Hash>>;
+
+    /// Replace the channel state hashes of the given channel.
+    async fn set_channel_state_hashes(&mut self, channel: &Channel, hashes: &[Hash]);
+
+    /// Retrieve the hashes making up the current channel state of the given
+    /// channel, without consulting or updating the channel state index.
+    ///
+    /// The channel state (spec section 5.4.4) is made up of the latest
+    /// `post/join` or `post/leave` post of every member and ex-member, the
+    /// latest `post/topic` post and the `post/info` posts defining the latest
+    /// info of every member and ex-member.
+    async fn get_channel_state(&self, channel: &Channel) -> Vec<Hash> {
+        let mut hashes = self
+            .get_channel_membership_hashes(channel)
+            .await
+            .unwrap_or_default();
+
+        if let Some((_topic, topic_hash)) = self.get_channel_topic_and_hash(channel).await {
+            hashes.push(topic_hash);
+        }
+
+        let mut public_keys = self.get_channel_members(channel).await.unwrap_or_default();
+        if let Some(ex_members) = self.get_ex_channel_members(channel).await {
+            public_keys.extend(ex_members);
+        }
+        for public_key in public_keys {
+            if let Some(info_hashes) = self.get_peer_info_hashes(&public_key).await {
+                hashes.extend(info_hashes);
+            }
+        }
+
+        hashes
+    }
+
+    /// Update the channel state index for the given channel and return the
+    /// hashes which have been added to the channel state, or `None` if the
+    /// channel state is unchanged.
+    async fn update_channel_state(&mut self, channel: &Channel) -> Option<Vec<Hash>> {
+        let hashes = self.get_channel_state(channel).await;
+
+        let previous_hashes = self
+            .get_channel_state_hashes(channel)
+            .await
+            .unwrap_or_default();
+
+        // The order of the hashes is not significant.
+        if hashes.len() == previous_hashes.len()
+            && hashes.iter().all(|hash| previous_hashes.contains(hash))
+        {
+            return None;
+        }
+
+        self.set_channel_state_hashes(channel, &hashes).await;
+
+        Some(
+            hashes
+                .into_iter()
+                .filter(|hash| !previous_hashes.contains(hash))
+                .collect(),
+        )
+    }
+
+    /// Retrieve the hashes linked to by the post with the given hash (its
+    /// parents).
+    async fn get_parent_hashes(&self, hash: &Hash) -> Option<Vec<Hash>>;
+
+    /// Retrieve the hashes of all known posts which link to the given hash
+    /// (its children).
+    async fn get_child_hashes(&self, hash: &Hash) -> Option<Vec<Hash>>;
+
+    /// Insert the links of the post with the given hash into the link graph.
+    async fn insert_links(&mut self, hash: &Hash, links: &[Hash]);
+
+    /// Remove the links of the post with the given hash from the link graph.
+    ///
+    /// The links of any children of the post are retained.
+    async fn remove_links(&mut self, hash: &Hash);
+
     /// Retrieve all posts matching the parameters defined by the given
     /// `ChannelOptions`.
+    ///
+    /// The time range includes both `time_start` and `time_end`; an end time
+    /// of 0 denotes no upper bound. A non-zero limit selects only the latest
+    /// posts in the range. The channel posts are returned in chronological
+    /// order, followed by all posts which do not have a channel.
     async fn get_posts(&self, opts: &ChannelOptions) -> PostStream;
 
     /// Retrieve all posts matching the parameters defined by the given
@@ -195,12 +375,189 @@ pub trait Store: Clone + Send + Sync + Unpin + 'static {
     /// available (stream remains active).
     async fn get_posts_live<'a>(&'a mut self, opts: &ChannelOptions) -> PostStream;
 
+    /// Retrieve up to `limit` posts of the given channel which precede the
+    /// given cursor, newest first, along with their hashes. Posts are ordered
+    /// by timestamp and then by hash; if no cursor is given, the latest posts
+    /// of the channel are returned.
+    async fn get_posts_before(
+        &self,
+        channel: &Channel,
+        cursor: Option<&HistoryCursor>,
+        limit: usize,
+    ) -> Vec<(Post, Hash)>;
+
     /// Retrieve the hashes of all posts matching the parameters defined by the
-    /// given `ChannelOptions`.
+    /// given `ChannelOptions`, newest first.
+    ///
+    /// The time range and limit are applied as for `get_posts()`, such that a
+    /// limit of `n` returns the hashes of the latest `n` posts in the range.
     async fn get_post_hashes(&self, opts: &ChannelOptions) -> HashStream;
 
-    /// Insert the given post into the store and return the hash.
-    async fn insert_post(&mut self, post: &Post) -> Result<Hash, Error>;
+    /// Insert the given post into the store and return the hash, along with
+    /// the hashes of all posts deleted by the inserted post.
+    ///
+    /// Only the posts of the author of a `post/delete` post are deleted; the
+    /// returned hashes exclude referenced posts which were not deleted.
+    async fn insert_post(&mut self, post: &Post) -> Result<(Hash, Vec<Hash>), Error> {
+        let timestamp = &post.get_timestamp();
+
+        let hash = post.hash()?;
+
+        // Refuse a post which has already been deleted by its author.
+        if self.is_deleted(post, &hash).await {
+            return Ok((hash, Vec::new()));
+        }
+
+        // Verify any deletions of the post received before the post itself.
+        // A deletion by the author of the post is authorised and the post is
+        // refused; deletions by any other public key are discarded.
+        if let Some(pending_tombstones) = self.get_pending_tombstones(&hash).await {
+            self.remove_pending_tombstones(&hash).await;
+
+            let public_key = post.get_public_key();
+            if let Some((_public_key, delete_hash)) = pending_tombstones
+                .iter()
+                .find(|(deleted_by, _delete_hash)| deleted_by == &public_key)
+            {
+                self.insert_tombstone(&hash, &public_key).await;
+
+                // The `post/delete` post may already have been recorded as
+                // an authorised deletion of another post.
+                let delete_hashes = self.get_delete_hashes(&public_key).await;
+                if !delete_hashes.is_some_and(|hashes| hashes.contains(delete_hash)) {
+                    self.insert_delete_hash(&public_key, delete_hash).await;
+                }
+
+                return Ok((hash, Vec::new()));
+            }
+        }
+
+        let mut deleted_hashes = Vec::new();
+
+        match &post.body {
+            PostBody::Text { channel, text: _ } => {
+                // Insert the post into the `posts` store.
+                self.update_posts(post, Some(channel.to_owned()), timestamp, hash)
+                    .await;
+                self.insert_post_payload(&hash, post.to_bytes()?).await;
+                #[cfg(feature = "search")]
+                self.index_post(&hash, post).await;
+                self.send_post_to_live_streams(post, channel).await;
+            }
+            PostBody::Join { channel } => {
+                let public_key = &post.get_public_key();
+
+                self.update_channel_membership_hashes(channel, public_key, &hash)
+                    .await;
+                self.insert_channel_member(channel, public_key).await;
+                self.remove_ex_channel_member(channel, public_key).await;
+                self.insert_post_payload(&hash, post.to_bytes()?).await;
+            }
+            PostBody::Leave { channel } => {
+                let public_key = &post.get_public_key();
+
+                self.update_channel_membership_hashes(channel, public_key, &hash)
+                    .await;
+                self.remove_channel_member(channel, public_key).await;
+                self.insert_ex_channel_member(channel, public_key).await;
+                self.insert_post_payload(&hash, post.to_bytes()?).await;
+            }
+            PostBody::Topic { channel, topic } => {
+                // Insert the post into the `posts` store.
+                self.update_posts(post, Some(channel.to_owned()), timestamp, hash)
+                    .await;
+                self.insert_channel_topic(channel, topic, timestamp, &hash)
+                    .await;
+                self.insert_post_payload(&hash, post.to_bytes()?).await;
+                self.send_post_to_live_streams(post, channel).await;
+            }
+            PostBody::Delete { hashes } => {
+                let public_key = &post.get_public_key();
+
+                for post_hash in hashes {
+                    if let Some(payload) = self.get_post_payload(post_hash).await {
+                        // TODO: Consider whether it is more efficient to
+                        // decode the payload or retrieve the post from the
+                        // `posts` store.
+                        let (_s, stored_post) = Post::from_bytes(&payload)?;
+                        // Only delete the post if the author matches the
+                        // author of the `post/delete` post.
+                        if post.get_public_key() == stored_post.get_public_key() {
+                            // Delete the post from all stores.
+                            self.delete_post(post_hash).await;
+                            self.insert_tombstone(post_hash, public_key).await;
+                            deleted_hashes.push(*post_hash);
+                        }
+                    } else if self.get_tombstone(post_hash).await.is_none() {
+                        // The referenced post has not been seen yet; the
+                        // deletion is verified if and when it arrives.
+                        self.insert_pending_tombstone(post_hash, public_key, &hash)
+                            .await;
+                    }
+                }
+
+                if !deleted_hashes.is_empty() {
+                    // The hash of the `post/delete` post is inserted, not the
+                    // hash of the post referenced by the `post/delete` post.
+                    self.insert_delete_hash(public_key, &hash).await;
+                }
+
+                // The `post/delete` post is retained and served to other
+                // peers, allowing them to converge on the deletion.
+                self.insert_post_payload(&hash, post.to_bytes()?).await;
+            }
+            PostBody::Info { info } => {
+                // Insert the post into the `posts` store.
+                self.update_posts(post, None, timestamp, hash).await;
+
+                let public_key = &post.get_public_key();
+
+                // Insert every key-value pair into the peer info store, as
+                // well as the assigned name if the key of the info element
+                // is "name".
+                for user_info in info {
+                    self.insert_peer_info(public_key, user_info, timestamp, &hash)
+                        .await;
+                    let UserInfo { key, val } = user_info;
+                    if key == INFO_NAME_KEY {
+                        self.insert_peer_name(public_key, val, timestamp, &hash)
+                            .await;
+                    }
+                }
+
+                self.insert_info_hash(public_key, &hash).await;
+                self.insert_post_payload(&hash, post.to_bytes()?).await;
+            }
+            PostBody::Role { channel, .. } | PostBody::Moderation { channel, .. } => {
+                self.insert_moderation_hash(channel, &hash).await;
+                self.insert_post_payload(&hash, post.to_bytes()?).await;
+            }
+            PostBody::Block { .. } | PostBody::Unblock { .. } => {
+                // Blocks always apply to the whole cabal.
+                self.insert_moderation_hash(&Channel::new(), &hash).await;
+                self.insert_post_payload(&hash, post.to_bytes()?).await;
+            }
+            PostBody::Unrecognized { .. } => {
+                // Posts of unrecognized types are not indexed, but are stored
+                // unchanged so that they can be served to other peers.
+                self.insert_post_payload(&hash, post.to_bytes()?).await;
+            }
+        }
+
+        // Record the parent-child relationships defined by the post links.
+        if !post.header.links.is_empty() {
+            self.insert_links(&hash, &post.header.links).await;
+        }
+
+        let channel = post.get_channel();
+
+        // Update the store of known channels.
+        if let Some(channel) = channel {
+            self.insert_channel(channel).await;
+        }
+
+        Ok((hash, deleted_hashes))
+    }
 
     /// Remove the given post from the posts and post hashes stores.
     async fn remove_post(&mut self, hash: &Hash);
@@ -209,7 +566,151 @@ pub trait Store: Clone + Send + Sync + Unpin + 'static {
     ///
     /// This method combines several removal methods to achieve complete
     /// removal of the post.
-    async fn delete_post(&mut self, hash: &Hash);
+    async fn delete_post(&mut self, hash: &Hash) {
+        // Remove post from all stores.
+        self.remove_channel_topic(hash).await;
+        self.remove_channel_membership_hash(hash).await;
+        self.remove_peer_name(hash).await;
+        self.remove_peer_info(hash).await;
+        self.remove_info_hash(hash).await;
+        self.remove_moderation_hash(hash).await;
+        self.remove_links(hash).await;
+        #[cfg(feature = "search")]
+        self.unindex_post(hash).await;
+        self.remove_post(hash).await;
+        self.remove_post_payload(hash).await;
+    }
+
+    /// Add the given post to the full-text search index.
+    #[cfg(feature = "search")]
+    async fn index_post(&mut self, hash: &Hash, post: &Post);
+
+    /// Remove the post with the given hash from the full-text search index.
+    #[cfg(feature = "search")]
+    async fn unindex_post(&mut self, hash: &Hash);
+
+    /// Search the stored text posts, returning the hashes of all posts
+    /// matching the given query, ranked by relevance.
+    #[cfg(feature = "search")]
+    async fn search(&self, query: &SearchQuery) -> Vec<SearchResult>;
+
+    /// Prune the stored posts according to the given retention policy and
+    /// return the hashes of all pruned posts. The age of posts is determined
+    /// relative to the given time.
+    ///
+    /// The posts making up the channel state of every channel, all
+    /// moderation posts and all `post/delete` posts are never pruned.
+    async fn prune(&mut self, retention: &RetentionConfig, now: Timestamp) -> Vec<Hash> {
+        let channels = self.get_channels().await.unwrap_or_default();
+
+        // Collect the hashes of all posts which must be retained.
+        let mut retained: HashSet<Hash> = self
+            .get_moderation_hashes(&Channel::new())
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .collect();
+        for channel in &channels {
+            retained.extend(self.get_channel_state(channel).await);
+            retained.extend(
+                self.get_moderation_hashes(channel)
+                    .await
+                    .unwrap_or_default(),
+            );
+        }
+
+        let mut pruned = HashSet::new();
+
+        // Prune channel posts exceeding the maximum age or the maximum number
+        // of posts per channel, newest first.
+        let min_timestamp = retention
+            .max_age
+            .map(|max_age| now.saturating_sub(max_age.as_millis() as Timestamp));
+        if min_timestamp.is_some() || retention.max_posts_per_channel.is_some() {
+            for channel in &channels {
+                let posts = self.get_posts_before(channel, None, usize::MAX).await;
+                for (i, (post, hash)) in posts.iter().enumerate() {
+                    let is_expired = min_timestamp.is_some_and(|min| post.get_timestamp() < min);
+                    let is_excess = retention.max_posts_per_channel.is_some_and(|max| i >= max);
+                    if (is_expired || is_excess) && !retained.contains(hash) {
+                        pruned.insert(*hash);
+                    }
+                }
+            }
+        }
+
+        // Decode the remaining posts which may be pruned, along with the size
+        // of each payload. Delete posts are retained so that deletions
+        // continue to propagate to other peers.
+        let payload_sizes = self.get_payload_sizes().await;
+        let mut candidates = Vec::new();
+        for (hash, size) in &payload_sizes {
+            if retained.contains(hash) || pruned.contains(hash) {
+                continue;
+            }
+            if let Some(payload) = self.get_post_payload(hash).await {
+                if let Ok((_s, post)) = Post::from_bytes(&payload) {
+                    if !matches!(post.body, PostBody::Delete { .. }) {
+                        candidates.push((post, *hash, *size));
+                    }
+                }
+            }
+        }
+
+        // Prune superseded posts. Any membership or topic post which is not
+        // part of the channel state of its channel has been superseded, while
+        // an info post has been superseded once none of the keys it assigns
+        // define the latest info of the author.
+        if retention.prune_superseded {
+            for (post, hash, _size) in &candidates {
+                let is_superseded = match &post.body {
+                    PostBody::Join { .. } | PostBody::Leave { .. } | PostBody::Topic { .. } => true,
+                    PostBody::Info { .. } => !self
+                        .get_peer_info_hashes(&post.get_public_key())
+                        .await
+                        .unwrap_or_default()
+                        .contains(hash),
+                    _ => false,
+                };
+                if is_superseded {
+                    pruned.insert(*hash);
+                }
+            }
+        }
+
+        // Prune the oldest posts until the total size of the remaining
+        // payloads no longer exceeds the quota.
+        if let Some(max_payload_bytes) = retention.max_payload_bytes {
+            let mut total_bytes: usize = payload_sizes
+                .iter()
+                .filter(|(hash, _size)| !pruned.contains(hash))
+                .map(|(_hash, size)| size)
+                .sum();
+
+            candidates.retain(|(_post, hash, _size)| !pruned.contains(hash));
+            candidates.sort_by_key(|(post, hash, _size)| (post.get_timestamp(), *hash));
+            for (_post, hash, size) in candidates {
+                if total_bytes <= max_payload_bytes {
+                    break;
+                }
+                total_bytes -= size;
+                pruned.insert(hash);
+            }
+        }
+
+        for hash in &pruned {
+            self.delete_post(hash).await;
+        }
+
+        // Record the pruned hashes so that the posts are not requested again,
+        // forgetting those which have been recorded for longer than the TTL.
+        let pruned: Vec<Hash> = pruned.into_iter().collect();
+        let expiry = now.saturating_sub(retention.pruned_hash_ttl.as_millis() as Timestamp);
+        self.remove_pruned_hashes_before(expiry).await;
+        self.insert_pruned_hashes(&pruned, now).await;
+
+        pruned
+    }
 
     /// Update the posts store by inserting the given post.
     ///
@@ -227,6 +728,9 @@ pub trait Store: Clone + Send + Sync + Unpin + 'static {
     /// Retrieve the post payload for the post represented by the given hash.
     async fn get_post_payload(&self, hash: &Hash) -> Option<Payload>;
 
+    /// Retrieve the hash and size in bytes of every stored post payload.
+    async fn get_payload_sizes(&self) -> Vec<(Hash, usize)>;
+
     /// Retrieve the post payloads for all posts represented by the given hashes.
     async fn get_post_payloads(&self, hashes: &[Hash]) -> Vec<Payload>;
 
@@ -243,7 +747,144 @@ pub trait Store: Clone + Send + Sync + Unpin + 'static {
     /// Retrieve the hashes of all posts representing the subset of the given
     /// hashes for which post data is not available locally (ie. the hashes of
     /// all posts which are not already in the store).
+    ///
+    /// Hashes of posts by blocked authors and of recently pruned posts are
+    /// never wanted.
     async fn want(&self, hashes: &[Hash]) -> Vec<Hash>;
+
+    /// Record the given hashes as those of posts pruned at the given time.
+    async fn insert_pruned_hashes(&mut self, hashes: &[Hash], timestamp: Timestamp);
+
+    /// Forget the hashes of all posts pruned before the given time.
+    async fn remove_pruned_hashes_before(&mut self, timestamp: Timestamp);
+
+    /// Retrieve the hashes of all stored posts authored by the given public
+    /// key.
+    async fn get_post_hashes_by_author(&self, public_key: &PublicKey) -> Vec<Hash>;
+
+    /// Retrieve the moderation action applied to the given public key, if
+    /// any.
+    async fn get_moderation(&self, public_key: &PublicKey) -> Option<Moderation>;
+
+    /// Retrieve all moderated public keys, along with the applied moderation
+    /// actions.
+    async fn get_moderated_keys(&self) -> Vec<(PublicKey, Moderation)>;
+
+    /// Apply the given moderation action to the given public key, replacing
+    /// any previously applied action.
+    async fn set_moderation(&mut self, public_key: &PublicKey, moderation: Moderation);
+
+    /// Remove the moderation action applied to the given public key, along
+    /// with the hashes of all blocked posts by the author.
+    async fn remove_moderation(&mut self, public_key: &PublicKey);
+
+    /// Record the hash of a post by the given blocked author, ensuring the
+    /// post is not wanted again.
+    async fn insert_blocked_hash(&mut self, public_key: &PublicKey, hash: &Hash);
+}
+
+/// Select the channel posts matching the given channel options from the given
+/// post map, newest first.
+///
+/// The time range includes both the start and end time; an end time of 0
+/// denotes no upper bound. A limit of 0 denotes no limit.
+fn latest_posts(posts: &PostMap, opts: &ChannelOptions) -> Vec<(Post, Hash)> {
+    let end = if opts.time_end == 0 {
+        Timestamp::MAX
+    } else {
+        opts.time_end
+    };
+    if opts.time_start > end {
+        return Vec::new();
+    }
+    let limit = if opts.limit == 0 {
+        usize::MAX
+    } else {
+        opts.limit as usize
+    };
+
+    posts
+        .get(&Some(opts.channel.to_owned()))
+        .map(|post_map| {
+            post_map
+                .range(opts.time_start..=end)
+                .rev()
+                .flat_map(|(_time, posts)| posts.iter().rev())
+                .take(limit)
+                .cloned()
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Create a new live stream matching the given channel options and add it to
+/// the given map of live streams, returning the newly-created stream.
+///
+/// This function is shared by all `Store` implementations, since live streams
+/// are held in memory regardless of how posts are stored.
+pub(crate) async fn register_live_stream(
+    live_streams_map: &RwLock<LiveStreamMap>,
+    live_stream_id_counter: &Mutex<usize>,
+    opts: &ChannelOptions,
+) -> LiveStream {
+    let mut live_streams = live_streams_map.write().await;
+
+    // Select existing streams which match the given channel.
+    if let Some(streams) = live_streams.get_mut(&opts.channel) {
+        let live_stream = {
+            let mut id = live_stream_id_counter.lock().await;
+            // Increment the live stream counter.
+            *id += 1;
+            // Return a new live stream.
+            LiveStream::new(*id, opts.clone(), streams.clone())
+        };
+        let live = live_stream.clone();
+        task::block_on(async move {
+            // Add the newly-created stream to the streams store for
+            // the given channel.
+            streams.write().await.push(live);
+        });
+
+        live_stream
+    } else {
+        // No streams were found which match the given channel.
+
+        let streams = Arc::new(RwLock::new(vec![]));
+        // Generate a new live stream ID.
+        let live_stream_id = {
+            let mut id = live_stream_id_counter.lock().await;
+            *id += 1;
+            id
+        };
+        let streams_c = streams.clone();
+        // Create a new stream and add it to the streams `Vec`.
+        let live_stream = task::block_on(async move {
+            let live_stream = LiveStream::new(*live_stream_id, opts.clone(), streams_c.clone());
+            streams_c.write().await.push(live_stream.clone());
+            live_stream
+        });
+        // Add the newly-created stream to the streams store
+        // for the given channel.
+        live_streams.insert(opts.channel.clone(), streams);
+
+        live_stream
+    }
+}
+
+/// Send the given post to each live stream in the given map for which the
+/// channel option criteria are satisfied.
+pub(crate) async fn send_to_live_streams(
+    live_streams: &RwLock<LiveStreamMap>,
+    post: &Post,
+    channel: &Channel,
+) {
+    if let Some(senders) = live_streams.read().await.get(channel) {
+        for stream in senders.write().await.iter_mut() {
+            if stream.matches(post) {
+                stream.send(post.clone()).await;
+            }
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -271,11 +912,32 @@ pub struct MemoryStore {
     channel_topics: Arc<RwLock<TopicHashMap>>,
     /// The hashes of all known `post/delete` posts.
     delete_hashes: Arc<RwLock<HashMap<PublicKey, Vec<Hash>>>>,
+    /// The public keys of the authors of all deleted posts, indexed by the
+    /// hash of the deleted post.
+    tombstones: Arc<RwLock<HashMap<Hash, PublicKey>>>,
+    /// The public keys of the authors of all `post/delete` posts referencing
+    /// posts which have not been seen yet, along with the hashes of the
+    /// `post/delete` posts, indexed by the hash of the referenced post.
+    pending_tombstones: Arc<RwLock<PendingTombstoneMap>>,
     /// The hashes of all known `post/info` posts.
     info_hashes: Arc<RwLock<HashMap<PublicKey, Vec<Hash>>>>,
+    /// The hashes of all known moderation posts, indexed by channel (an
+    /// empty channel name denotes the whole cabal).
+    moderation_hashes: Arc<RwLock<HashMap<Channel, Vec<Hash>>>>,
     /// The nickname, timestamp and hash of the latest `post/info` post for
     /// each known peer, indexed by public key.
     peer_names: Arc<RwLock<NameHashMap>>,
+    /// The value, timestamp and hash of every `post/info` key for each known
+    /// peer, indexed by public key and info key.
+    peer_info: Arc<RwLock<InfoHashMap>>,
+    /// The hashes of all posts which make up the state of each channel,
+    /// indexed by channel.
+    channel_state: Arc<RwLock<HashMap<Channel, Vec<Hash>>>>,
+    /// The hashes linked to by each post, indexed by the post hash.
+    parent_links: Arc<RwLock<HashMap<Hash, Vec<Hash>>>>,
+    /// The hashes of the posts linking to each hash, indexed by the linked
+    /// hash.
+    child_links: Arc<RwLock<HashMap<Hash, Vec<Hash>>>>,
     /// All posts and hashes in the store divided according to channel (the
     /// outer key) and indexed by timestamp (the inner key).
     posts: Arc<RwLock<PostMap>>,
@@ -283,10 +945,21 @@ pub struct MemoryStore {
     post_payloads: Arc<RwLock<HashMap<Hash, Payload>>>,
     /// An empty `BTreeMap` of posts and hashes, indexed by timestamp.
     empty_post_bt: BTreeMap<u64, Vec<(Post, Hash)>>,
+    /// The full-text search index of all text posts in the store.
+    #[cfg(feature = "search")]
+    search_index: Arc<RwLock<SearchIndex>>,
     /// All active live streams, indexed by channel.
     live_streams: Arc<RwLock<LiveStreamMap>>,
     /// The unique identifier of a live stream.
     live_stream_id: Arc<Mutex<usize>>,
+    /// Moderation actions applied by the local peer, indexed by public key.
+    moderation: Arc<RwLock<HashMap<PublicKey, Moderation>>>,
+    /// The author of each known post by a blocked author, indexed by the
+    /// post hash.
+    blocked_hashes: Arc<RwLock<HashMap<Hash, PublicKey>>>,
+    /// The time at which each recently pruned post was pruned, indexed by
+    /// the post hash.
+    pruned_hashes: Arc<RwLock<HashMap<Hash, Timestamp>>>,
 }
 
 impl Default for MemoryStore {
@@ -305,13 +978,25 @@ impl Default for MemoryStore {
             channel_membership: Arc::new(RwLock::new(HashMap::new())),
             channel_topics: Arc::new(RwLock::new(HashMap::new())),
             delete_hashes: Arc::new(RwLock::new(HashMap::new())),
+            tombstones: Arc::new(RwLock::new(HashMap::new())),
+            pending_tombstones: Arc::new(RwLock::new(HashMap::new())),
             info_hashes: Arc::new(RwLock::new(HashMap::new())),
+            moderation_hashes: Arc::new(RwLock::new(HashMap::new())),
             peer_names: Arc::new(RwLock::new(HashMap::new())),
+            peer_info: Arc::new(RwLock::new(HashMap::new())),
+            channel_state: Arc::new(RwLock::new(HashMap::new())),
+            parent_links: Arc::new(RwLock::new(HashMap::new())),
+            child_links: Arc::new(RwLock::new(HashMap::new())),
             posts: Arc::new(RwLock::new(HashMap::new())),
             post_payloads: Arc::new(RwLock::new(HashMap::new())),
             empty_post_bt: BTreeMap::new(),
+            #[cfg(feature = "search")]
+            search_index: Arc::new(RwLock::new(SearchIndex::default())),
             live_streams: Arc::new(RwLock::new(HashMap::new())),
             live_stream_id: Arc::new(Mutex::new(0)),
+            moderation: Arc::new(RwLock::new(HashMap::new())),
+            blocked_hashes: Arc::new(RwLock::new(HashMap::new())),
+            pruned_hashes: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 }
@@ -553,6 +1238,39 @@ impl Store for MemoryStore {
         }
     }
 
+    async fn get_tombstone(&self, hash: &Hash) -> Option<PublicKey> {
+        self.tombstones.read().await.get(hash).copied()
+    }
+
+    async fn insert_tombstone(&mut self, hash: &Hash, public_key: &PublicKey) {
+        self.tombstones.write().await.insert(*hash, *public_key);
+    }
+
+    async fn get_pending_tombstones(&self, hash: &Hash) -> Option<Vec<(PublicKey, Hash)>> {
+        self.pending_tombstones
+            .read()
+            .await
+            .get(hash)
+            .map(|tombstones| tombstones.to_owned())
+    }
+
+    async fn insert_pending_tombstone(
+        &mut self,
+        hash: &Hash,
+        public_key: &PublicKey,
+        delete_hash: &Hash,
+    ) {
+        let mut pending_tombstones = self.pending_tombstones.write().await;
+        let tombstones = pending_tombstones.entry(*hash).or_default();
+        if !tombstones.contains(&(*public_key, *delete_hash)) {
+            tombstones.push((*public_key, *delete_hash));
+        }
+    }
+
+    async fn remove_pending_tombstones(&mut self, hash: &Hash) {
+        self.pending_tombstones.write().await.remove(hash);
+    }
+
     async fn get_info_hashes(&self, public_key: &PublicKey) -> Option<Vec<Hash>> {
         self.info_hashes
             .read()
@@ -584,6 +1302,31 @@ impl Store for MemoryStore {
             .for_each(|(_public_key, hashes)| hashes.retain(|stored_hash| stored_hash != hash));
     }
 
+    async fn get_moderation_hashes(&self, channel: &Channel) -> Option<Vec<Hash>> {
+        self.moderation_hashes
+            .read()
+            .await
+            .get(channel)
+            .map(|hashes| hashes.to_owned())
+    }
+
+    async fn insert_moderation_hash(&mut self, channel: &Channel, hash: &Hash) {
+        let mut moderation_hashes = self.moderation_hashes.write().await;
+        let hashes = moderation_hashes.entry(channel.to_owned()).or_default();
+        // A post may be received more than once.
+        if !hashes.contains(hash) {
+            hashes.push(*hash)
+        }
+    }
+
+    async fn remove_moderation_hash(&mut self, hash: &Hash) {
+        let mut moderation_hashes = self.moderation_hashes.write().await;
+
+        moderation_hashes
+            .iter_mut()
+            .for_each(|(_channel, hashes)| hashes.retain(|stored_hash| stored_hash != hash));
+    }
+
     async fn get_latest_hashes(&self, channel: &Channel) -> Option<Vec<Hash>> {
         // Open the posts store for reading.
         let posts_map = self.posts.read().await;
@@ -659,30 +1402,134 @@ impl Store for MemoryStore {
         });
     }
 
-    async fn get_posts(&self, opts: &ChannelOptions) -> PostStream {
-        let start = opts.time_start;
-        let end = opts.time_end;
-        let empty = self.empty_post_bt.range(..);
+    async fn get_peer_info(&self, public_key: &PublicKey) -> Option<Vec<UserInfo>> {
+        self.peer_info
+            .read()
+            .await
+            .get(public_key)
+            .map(|info_map| {
+                info_map
+                    .iter()
+                    // Get the value with the largest timestamp for each key.
+                    .filter_map(|(key, values)| {
+                        values
+                            .last_key_value()
+                            .map(|(_, (val, _hash))| UserInfo::new(key, val))
+                    })
+                    .collect::<Vec<UserInfo>>()
+            })
+            .filter(|info| !info.is_empty())
+    }
 
+    async fn insert_peer_info(
+        &mut self,
+        public_key: &PublicKey,
+        info: &UserInfo,
+        timestamp: &Timestamp,
+        hash: &Hash,
+    ) {
+        let mut peer_info = self.peer_info.write().await;
+        // Insert the given value and hash into the map for the given public
+        // key and info key, using the timestamp as the key.
+        peer_info
+            .entry(public_key.to_owned())
+            .or_default()
+            .entry(info.key.to_owned())
+            .or_default()
+            .insert(*timestamp, (info.val.to_owned(), *hash));
+    }
+
+    async fn remove_peer_info(&mut self, hash: &Hash) {
+        let mut peer_info = self.peer_info.write().await;
+
+        for info_map in peer_info.values_mut() {
+            // Remove any value for which the stored hash of the info post
+            // matches the given hash.
+            for values in info_map.values_mut() {
+                values.retain(|_timestamp, (_val, stored_hash)| stored_hash != hash)
+            }
+            // Remove any info key which no longer has a value.
+            info_map.retain(|_key, values| !values.is_empty());
+        }
+    }
+
+    async fn get_peer_info_hashes(&self, public_key: &PublicKey) -> Option<Vec<Hash>> {
+        let peer_info = self.peer_info.read().await;
+
+        let mut hashes = Vec::new();
+        for values in peer_info.get(public_key)?.values() {
+            // Get the hash of the value with the largest timestamp.
+            if let Some((_, (_val, hash))) = values.last_key_value() {
+                if !hashes.contains(hash) {
+                    hashes.push(*hash);
+                }
+            }
+        }
+
+        if hashes.is_empty() {
+            None
+        } else {
+            Some(hashes)
+        }
+    }
+
+    async fn get_channel_state_hashes(&self, channel: &Channel) -> Option<Vec<Hash>> {
+        self.channel_state.read().await.get(channel).cloned()
+    }
+
+    async fn set_channel_state_hashes(&mut self, channel: &Channel, hashes: &[Hash]) {
+        self.channel_state
+            .write()
+            .await
+            .insert(channel.to_owned(), hashes.to_vec());
+    }
+
+    async fn get_parent_hashes(&self, hash: &Hash) -> Option<Vec<Hash>> {
+        self.parent_links.read().await.get(hash).cloned()
+    }
+
+    async fn get_child_hashes(&self, hash: &Hash) -> Option<Vec<Hash>> {
+        self.child_links.read().await.get(hash).cloned()
+    }
+
+    async fn insert_links(&mut self, hash: &Hash, links: &[Hash]) {
+        self.parent_links
+            .write()
+            .await
+            .insert(*hash, links.to_vec());
+
+        let mut child_links = self.child_links.write().await;
+        for link in links {
+            let children = child_links.entry(*link).or_default();
+            if !children.contains(hash) {
+                children.push(*hash);
+            }
+        }
+    }
+
+    async fn remove_links(&mut self, hash: &Hash) {
+        if let Some(links) = self.parent_links.write().await.remove(hash) {
+            let mut child_links = self.child_links.write().await;
+            for link in links {
+                if let Some(children) = child_links.get_mut(&link) {
+                    children.retain(|child| child != hash);
+                    if children.is_empty() {
+                        child_links.remove(&link);
+                    }
+                }
+            }
+        }
+    }
+
+    async fn get_posts(&self, opts: &ChannelOptions) -> PostStream {
         let all_posts = self.posts.read().await;
 
-        // Retrieve all posts matching the given channel options.
-        let mut posts = all_posts
-            .get(&Some(opts.channel.to_owned()))
-            // Return only the posts for which the key (timestamp: `x`)
-            // matches the given range (provided via `opts`).
-            .map(|x| match (start, end) {
-                (0, 0) => x.range(..),
-                (0, end) => x.range(..end),
-                (start, 0) => x.range(start..),
-                _ => x.range(start..end),
-            })
-            // Return an empty map if no posts are found matching the given
-            // channel.
-            .unwrap_or(empty)
-            // Iterate over the post data and extract the post for each one,
-            // wrapping it in a `Result`.
-            .flat_map(|(_time, posts)| posts.iter().map(|(post, _hash)| Ok(post.clone())))
+        // Retrieve the latest posts matching the given channel options and
+        // return them in chronological order, wrapping each in a `Result`.
+        let mut posts = latest_posts(&all_posts, opts)
+            .into_iter()
+            .rev()
+            .map(|(post, _hash)| Ok(post))
             .collect::<Vec<Result<Post, Error>>>();
 
         // TODO: Would it be better to split this into another method?
@@ -707,51 +1554,8 @@ impl Store for MemoryStore {
     }
 
     async fn get_posts_live(&mut self, opts: &ChannelOptions) -> PostStream {
-        let live_stream = {
-            let mut live_streams = self.live_streams.write().await;
-
-            // Select existing streams which match the given channel.
-            if let Some(streams) = live_streams.get_mut(&opts.channel) {
-                let live_stream = {
-                    let mut id = self.live_stream_id.lock().await;
-                    // Increment the live stream counter.
-                    *id += 1;
-                    // Return a new live stream.
-                    LiveStream::new(*id, opts.clone(), streams.clone())
-                };
-                let live = live_stream.clone();
-                task::block_on(async move {
-                    // Add the newly-created stream to the streams store for
-                    // the given channel.
-                    streams.write().await.push(live);
-                });
-
-                live_stream
-            } else {
-                // No streams were found which match the given channel.
-
-                let streams = Arc::new(RwLock::new(vec![]));
-                // Generate a new live stream ID.
-                let live_stream_id = {
-                    let mut id = self.live_stream_id.lock().await;
-                    *id += 1;
-                    id
-                };
-                let streams_c = streams.clone();
-                // Create a new stream and add it to the streams `Vec`.
-                let live_stream = task::block_on(async move {
-                    let live_stream =
-                        LiveStream::new(*live_stream_id, opts.clone(), streams_c.clone());
-                    streams_c.write().await.push(live_stream.clone());
-                    live_stream
-                });
-                // Add the newly-created stream to the streams store
-                // for the given channel.
-                live_streams.insert(opts.channel.clone(), streams);
-
-                live_stream
-            }
-        };
+        let live_stream =
+            register_live_stream(&self.live_streams, &self.live_stream_id, opts).await;
 
         // Retrieve all stored posts matching the channel options,
         // as well as all non-channel posts.
@@ -761,129 +1565,57 @@ impl Store for MemoryStore {
         Box::new(post_stream.merge(live_stream))
     }
 
-    async fn get_post_hashes(&self, opts: &ChannelOptions) -> HashStream {
-        let start = opts.time_start;
-        let end = opts.time_end;
-        let empty = self.empty_post_bt.range(..);
-
-        let hashes = self
-            .posts
+    async fn get_posts_before(
+        &self,
+        channel: &Channel,
+        cursor: Option<&HistoryCursor>,
+        limit: usize,
+    ) -> Vec<(Post, Hash)> {
+        let end = cursor
+            .map(|cursor| cursor.timestamp)
+            .unwrap_or(Timestamp::MAX);
+
+        self.posts
             .read()
             .await
-            .get(&Some(opts.channel.to_owned()))
-            // Return only the hashes for which the key (timestamp: `x`)
-            // matches the given range (provided via `opts`).
-            .map(|x| match (start, end) {
-                (0, 0) => x.range(..),
-                (0, end) => x.range(..end),
-                (start, 0) => x.range(start..),
-                _ => x.range(start..end),
+            .get(&Some(channel.to_owned()))
+            .map(|post_map| {
+                post_map
+                    .range(..=end)
+                    .rev()
+                    .flat_map(|(timestamp, posts)| {
+                        // Order the posts sharing a timestamp by hash, newest
+                        // first, omitting any which do not precede the cursor.
+                        let mut posts: Vec<(Post, Hash)> = posts
+                            .iter()
+                            .filter(|(_post, hash)| {
+                                cursor.is_none_or(|cursor| {
+                                    HistoryCursor::new(*timestamp, *hash) < *cursor
+                                })
+                            })
+                            .cloned()
+                            .collect();
+                        posts.sort_by(|(_a, a_hash), (_b, b_hash)| b_hash.cmp(a_hash));
+                        posts
+                    })
+                    .take(limit)
+                    .collect()
             })
-            .unwrap_or(empty)
-            // Iterate over the post data and extract the hash for each one,
-            // wrapping it in a `Result`.
-            .flat_map(|(_time, posts)| posts.iter().map(|(_post, hash)| Ok(*hash)))
+            .unwrap_or_default()
+    }
+
+    async fn get_post_hashes(&self, opts: &ChannelOptions) -> HashStream {
+        // Retrieve the hashes of the latest posts matching the given channel
+        // options, wrapping each in a `Result`.
+        let hashes = latest_posts(&*self.posts.read().await, opts)
+            .into_iter()
+            .map(|(_post, hash)| Ok(hash))
             .collect::<Vec<Result<Hash, Error>>>();
 
         // Return a hash stream.
         Box::new(stream::from_iter(hashes))
     }
 
-    async fn insert_post(&mut self, post: &Post) -> Result<Hash, Error> {
-        let timestamp = &post.get_timestamp();
-
-        let hash = post.hash()?;
-
-        match &post.body {
-            PostBody::Text { channel, text: _ } => {
-                // Insert the post into the `posts` store.
-                self.update_posts(post, Some(channel.to_owned()), timestamp, hash)
-                    .await;
-                self.insert_post_payload(&hash, post.to_bytes()?).await;
-                self.send_post_to_live_streams(post, channel).await;
-            }
-            PostBody::Join { channel } => {
-                let public_key = &post.get_public_key();
-
-                self.update_channel_membership_hashes(channel, public_key, &hash)
-                    .await;
-                self.insert_channel_member(channel, public_key).await;
-                self.remove_ex_channel_member(channel, public_key).await;
-                self.insert_post_payload(&hash, post.to_bytes()?).await;
-            }
-            PostBody::Leave { channel } => {
-                let public_key = &post.get_public_key();
-
-                self.update_channel_membership_hashes(channel, public_key, &hash)
-                    .await;
-                self.remove_channel_member(channel, public_key).await;
-                self.insert_ex_channel_member(channel, public_key).await;
-                self.insert_post_payload(&hash, post.to_bytes()?).await;
-            }
-            PostBody::Topic { channel, topic } => {
-                // Insert the post into the `posts` store.
-                self.update_posts(post, Some(channel.to_owned()), timestamp, hash)
-                    .await;
-                self.insert_channel_topic(channel, topic, timestamp, &hash)
-                    .await;
-                self.insert_post_payload(&hash, post.to_bytes()?).await;
-                self.send_post_to_live_streams(post, channel).await;
-            }
-            PostBody::Delete { hashes } => {
-                let public_key = &post.get_public_key();
-
-                for post_hash in hashes {
-                    if let Some(payload) = self.get_post_payload(post_hash).await {
-                        // TODO: Consider whether it is more efficient to
-                        // decode the payload or retrieve the post from the
-                        // `posts` store.
-                        let (_s, stored_post) = Post::from_bytes(&payload)?;
-                        // Only delete the post if the author matches the
-                        // author of the `post/delete` post.
-                        if post.get_public_key() == stored_post.get_public_key() {
-                            // Delete the post from all stores.
-                            self.delete_post(post_hash).await;
-                            // The hash of the `post/delete` post is inserted,
-                            // not the hash of the post referenced by the
-                            // `post/delete` post.
-                            self.insert_delete_hash(public_key, &hash).await;
-                        }
-                    }
-                }
-
-                self.insert_post_payload(&hash, post.to_bytes()?).await;
-            }
-            PostBody::Info { info } => {
-                // Insert the post into the `posts` store.
-                self.update_posts(post, None, timestamp, hash).await;
-
-                let public_key = &post.get_public_key();
-
-                // Insert the public key of the post author and the assigned
-                // name if the key of the info element is "name".
-                for UserInfo { key, val } in info {
-                    if key == "name" {
-                        self.insert_peer_name(public_key, val, timestamp, &hash)
-                            .await;
-                    }
-                }
-
-                self.insert_info_hash(public_key, &hash).await;
-                self.insert_post_payload(&hash, post.to_bytes()?).await;
-            }
-            _ => {}
-        }
-
-        let channel = post.get_channel();
-
-        // Update the store of known channels.
-        if let Some(channel) = channel {
-            self.insert_channel(channel).await;
-        }
-
-        Ok(hash)
-    }
-
     async fn remove_post(&mut self, hash: &Hash) {
         // Open the post store for writing.
         let mut posts = self.posts.write().await;
@@ -901,14 +1633,19 @@ impl Store for MemoryStore {
         });
     }
 
-    async fn delete_post(&mut self, hash: &Hash) {
-        // Remove post from all stores.
-        self.remove_channel_topic(hash).await;
-        self.remove_channel_membership_hash(hash).await;
-        self.remove_peer_name(hash).await;
-        self.remove_info_hash(hash).await;
-        self.remove_post(hash).await;
-        self.remove_post_payload(hash).await;
+    #[cfg(feature = "search")]
+    async fn index_post(&mut self, hash: &Hash, post: &Post) {
+        self.search_index.write().await.insert(hash, post);
+    }
+
+    #[cfg(feature = "search")]
+    async fn unindex_post(&mut self, hash: &Hash) {
+        self.search_index.write().await.remove(hash);
+    }
+
+    #[cfg(feature = "search")]
+    async fn search(&self, query: &SearchQuery) -> Vec<SearchResult> {
+        self.search_index.read().await.search(query)
     }
 
     async fn update_posts(
@@ -964,6 +1701,15 @@ impl Store for MemoryStore {
             .collect()
     }
 
+    async fn get_payload_sizes(&self) -> Vec<(Hash, usize)> {
+        self.post_payloads
+            .read()
+            .await
+            .iter()
+            .map(|(hash, payload)| (*hash, payload.len()))
+            .collect()
+    }
+
     async fn insert_post_payload(&mut self, hash: &Hash, payload: Payload) {
         self.post_payloads.write().await.insert(*hash, payload);
     }
@@ -978,23 +1724,85 @@ impl Store for MemoryStore {
     }
 
     async fn send_post_to_live_streams(&self, post: &Post, channel: &Channel) {
-        if let Some(senders) = self.live_streams.read().await.get(channel) {
-            for stream in senders.write().await.iter_mut() {
-                if stream.matches(post) {
-                    stream.send(post.clone()).await;
-                }
-            }
-        }
+        send_to_live_streams(&self.live_streams, post, channel).await
     }
 
     async fn want(&self, hashes: &[Hash]) -> Vec<Hash> {
         let post_payloads = self.post_payloads.read().await;
+        let blocked_hashes = self.blocked_hashes.read().await;
+        let tombstones = self.tombstones.read().await;
+        let pruned_hashes = self.pruned_hashes.read().await;
 
         // Return the "wanted" hashes.
         hashes
             .iter()
-            .filter(|hash| !post_payloads.contains_key(&(*hash).clone()))
+            .filter(|hash| {
+                !post_payloads.contains_key(*hash)
+                    && !blocked_hashes.contains_key(*hash)
+                    && !tombstones.contains_key(*hash)
+                    && !pruned_hashes.contains_key(*hash)
+            })
             .cloned()
             .collect()
     }
+
+    async fn insert_pruned_hashes(&mut self, hashes: &[Hash], timestamp: Timestamp) {
+        let mut pruned_hashes = self.pruned_hashes.write().await;
+        for hash in hashes {
+            pruned_hashes.insert(*hash, timestamp);
+        }
+    }
+
+    async fn remove_pruned_hashes_before(&mut self, timestamp: Timestamp) {
+        self.pruned_hashes
+            .write()
+            .await
+            .retain(|_hash, pruned_at| *pruned_at >= timestamp);
+    }
+
+    async fn get_post_hashes_by_author(&self, public_key: &PublicKey) -> Vec<Hash> {
+        self.post_payloads
+            .read()
+            .await
+            .iter()
+            .filter(|(_hash, payload)| {
+                Post::from_bytes(payload)
+                    .map(|(_s, post)| &post.get_public_key() == public_key)
+                    .unwrap_or(false)
+            })
+            .map(|(hash, _payload)| *hash)
+            .collect()
+    }
+
+    async fn get_moderation(&self, public_key: &PublicKey) -> Option<Moderation> {
+        self.moderation.read().await.get(public_key).copied()
+    }
+
+    async fn get_moderated_keys(&self) -> Vec<(PublicKey, Moderation)> {
+        self.moderation
+            .read()
+            .await
+            .iter()
+            .map(|(public_key, moderation)| (*public_key, *moderation))
+            .collect()
+    }
+
+    async fn set_moderation(&mut self, public_key: &PublicKey, moderation: Moderation) {
+        self.moderation
+            .write()
+            .await
+            .insert(*public_key, moderation);
+    }
+
+    async fn remove_moderation(&mut self, public_key: &PublicKey) {
+        self.moderation.write().await.remove(public_key);
+        self.blocked_hashes
+            .write()
+            .await
+            .retain(|_hash, author| author != public_key);
+    }
+
+    async fn insert_blocked_hash(&mut self, public_key: &PublicKey, hash: &Hash) {
+        self.blocked_hashes.write().await.insert(*hash, *public_key);
+    }
 }
@@ -0,0 +1,334 @@
+//! The connection supervisor maintains connections to a list of known peer
+//! addresses.
+//!
+//! Each known address is dialed and a handshake is performed before listening
+//! for peer messages. When a connection fails or is closed, the address is
+//! redialed after a delay which grows exponentially with each consecutive
+//! failure. The number of simultaneous connections is capped and only a
+//! single connection is maintained per remote public key.
+//!
+//! Connections are identified by the public key of the remote peer, which is
+//! only exchanged during the handshake if the `identity_payload` extension is
+//! enabled in the configuration of the manager. Dialing fails otherwise.
+//!
+//! Changes in connection status are reported as events, allowing a user
+//! interface to display the state of each connection.
+
+use std::{
+    collections::HashMap,
+    error::Error as StdError,
+    fmt::{Display, Formatter, Result as FmtResult},
+    net::SocketAddr,
+    time::Duration,
+};
+
+use async_std::{
+    channel,
+    net::TcpStream,
+    sync::{Arc, RwLock},
+    task,
+};
+use cable::Error;
+use cable_handshake::{async_std::handshake, Role, Version};
+use futures::io::{AsyncRead, AsyncWrite};
+use log::debug;
+
+use crate::{
+    event::Subscribers,
+    manager::{CableManager, HANDSHAKE_MAJOR_VERSION, HANDSHAKE_MINOR_VERSION},
+    registry::CabalKey,
+    store::{PublicKey, Store},
+};
+
+#[derive(Debug, PartialEq)]
+/// Error that can occur when establishing a connection to a peer.
+pub enum ConnectionError {
+    /// A connection to the remote public key has already been established.
+    DuplicatePublicKey,
+    /// The maximum number of simultaneous connections has been reached.
+    ConnectionLimitReached,
+}
+
+impl StdError for ConnectionError {}
+
+impl Display for ConnectionError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            ConnectionError::DuplicatePublicKey => {
+                write!(f, "A connection to the peer has already been established")
+            }
+            ConnectionError::ConnectionLimitReached => {
+                write!(f, "The maximum number of connections has been reached")
+            }
+        }
+    }
+}
+
+/// Configuration of the connection supervisor.
+#[derive(Clone, Debug)]
+pub struct SupervisorConfig {
+    /// The maximum number of simultaneous connections, inbound and outbound.
+    pub max_connections: usize,
+    /// The delay before redialing an address after the first failure.
+    pub initial_backoff: Duration,
+    /// The maximum delay before redialing an address.
+    pub max_backoff: Duration,
+}
+
+impl Default for SupervisorConfig {
+    fn default() -> Self {
+        Self {
+            max_connections: 32,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+        }
+    }
+}
+
+impl SupervisorConfig {
+    /// Return the delay before redialing an address after the given number of
+    /// consecutive failures, including a random jitter of up to a quarter of
+    /// the delay.
+    fn backoff(&self, failures: u32) -> Duration {
+        let delay = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(failures.saturating_sub(1)))
+            .min(self.max_backoff);
+
+        delay + delay.mul_f64(fastrand::f64() / 4.0)
+    }
+}
+
+/// The status of a connection to a known peer address.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConnectionStatus {
+    /// The address is being dialed.
+    Connecting,
+    /// The handshake is complete and messages are being exchanged with the
+    /// peer with the given public key.
+    Connected(PublicKey),
+    /// The connection was closed, with an error description if the
+    /// connection failed.
+    Disconnected(Option<String>),
+    /// A connection to the peer with the given public key has already been
+    /// established; this connection was closed.
+    Duplicate(PublicKey),
+    /// The address will be redialed after the given delay.
+    Reconnecting(Duration),
+    /// The address was removed from the list of known peers and will not be
+    /// redialed.
+    Removed,
+}
+
+/// A change in the status of a connection to a known peer address.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConnectionEvent {
+    /// The address of the peer.
+    pub addr: SocketAddr,
+    /// The status of the connection.
+    pub status: ConnectionStatus,
+}
+
+/// The state of a known peer address.
+#[derive(Default)]
+struct KnownPeer {
+    /// The public key of the peer, if a connection is currently established.
+    public_key: Option<PublicKey>,
+}
+
+/// A supervisor of connections to known peer addresses.
+#[derive(Clone)]
+pub struct ConnectionSupervisor<S: Store> {
+    cable: CableManager<S>,
+    cabal_key: CabalKey,
+    config: SupervisorConfig,
+    known_peers: Arc<RwLock<HashMap<SocketAddr, KnownPeer>>>,
+    subscribers: Subscribers<ConnectionEvent>,
+}
+
+impl<S> ConnectionSupervisor<S>
+where
+    S: Store,
+{
+    /// Create a connection supervisor for the given manager, connecting to
+    /// peers of the cabal with the given key.
+    pub fn new(cable: CableManager<S>, cabal_key: CabalKey, config: SupervisorConfig) -> Self {
+        Self {
+            cable,
+            cabal_key,
+            config,
+            known_peers: Arc::new(RwLock::new(HashMap::new())),
+            subscribers: Subscribers::default(),
+        }
+    }
+
+    /// Subscribe to connection status events.
+    ///
+    /// Only events emitted after subscribing are received.
+    pub async fn events(&self) -> channel::Receiver<ConnectionEvent> {
+        self.subscribers.subscribe().await
+    }
+
+    /// Send a connection status event to all subscribers.
+    async fn emit(&self, addr: SocketAddr, status: ConnectionStatus) {
+        debug!("Connection status of {}: {:?}", addr, status);
+
+        self.subscribers
+            .emit(ConnectionEvent { addr, status })
+            .await;
+    }
+
+    /// Retrieve the addresses of all known peers.
+    pub async fn known_peers(&self) -> Vec<SocketAddr> {
+        self.known_peers.read().await.keys().copied().collect()
+    }
+
+    /// Add the given address to the list of known peers and maintain a
+    /// connection to it until it is removed.
+    ///
+    /// Adding an address which is already known has no effect.
+    pub async fn add_peer(&self, addr: SocketAddr) {
+        let mut known_peers = self.known_peers.write().await;
+        if known_peers.contains_key(&addr) {
+            return;
+        }
+        known_peers.insert(addr, KnownPeer::default());
+        drop(known_peers);
+
+        let supervisor = self.clone();
+        task::spawn(async move { supervisor.supervise(addr).await });
+    }
+
+    /// Remove the given address from the list of known peers, closing the
+    /// connection to it (if any).
+    pub async fn remove_peer(&self, addr: &SocketAddr) {
+        let known_peer = self.known_peers.write().await.remove(addr);
+
+        if let Some(public_key) = known_peer.and_then(|known_peer| known_peer.public_key) {
+            if let Some(peer_id) = self.cable.get_peer_id(&public_key).await {
+                self.cable.disconnect(peer_id).await;
+            }
+        }
+    }
+
+    /// Query whether the given address is a known peer.
+    async fn is_known(&self, addr: &SocketAddr) -> bool {
+        self.known_peers.read().await.contains_key(addr)
+    }
+
+    /// Query whether the maximum number of connections has been reached.
+    async fn is_at_capacity(&self) -> bool {
+        self.cable.get_peer_ids().await.len() >= self.config.max_connections
+    }
+
+    /// Accept an inbound connection, performing a handshake as responder and
+    /// then listening for peer messages.
+    ///
+    /// The connection is rejected if the maximum number of connections has
+    /// been reached.
+    pub async fn accept<T>(&self, stream: T) -> Result<(), Error>
+    where
+        T: AsyncRead + AsyncWrite + Clone + Unpin + Send + Sync + 'static,
+    {
+        if self.is_at_capacity().await {
+            return Err(ConnectionError::ConnectionLimitReached.into());
+        }
+
+        self.cable
+            .listen_secure(stream, self.cabal_key, Role::Responder)
+            .await
+    }
+
+    /// Dial the given address, redialing with exponential backoff, until the
+    /// address is removed from the list of known peers.
+    async fn supervise(&self, addr: SocketAddr) {
+        let mut failures = 0;
+
+        while self.is_known(&addr).await {
+            // Wait for a connection slot to become available.
+            if self.is_at_capacity().await {
+                task::sleep(self.config.initial_backoff).await;
+                continue;
+            }
+
+            self.emit(addr, ConnectionStatus::Connecting).await;
+            let (established, status) = self.connect(addr).await;
+            self.emit(addr, status).await;
+
+            // Reset the backoff once a connection has been established.
+            failures = if established { 1 } else { failures + 1 };
+
+            if !self.is_known(&addr).await {
+                break;
+            }
+
+            let delay = self.config.backoff(failures);
+            self.emit(addr, ConnectionStatus::Reconnecting(delay)).await;
+            task::sleep(delay).await;
+        }
+
+        self.emit(addr, ConnectionStatus::Removed).await;
+    }
+
+    /// Record the public key of the peer at the given address.
+    async fn set_public_key(&self, addr: &SocketAddr, public_key: Option<PublicKey>) {
+        if let Some(known_peer) = self.known_peers.write().await.get_mut(addr) {
+            known_peer.public_key = public_key;
+        }
+    }
+
+    /// Dial the given address, perform a handshake as initiator and then
+    /// listen for peer messages until the connection is closed.
+    ///
+    /// Returns whether the connection was established, along with the status
+    /// of the connection once it has been closed.
+    async fn connect(&self, addr: SocketAddr) -> (bool, ConnectionStatus) {
+        let failed = |err: Error| (false, ConnectionStatus::Disconnected(Some(err.to_string())));
+
+        let mut stream = match TcpStream::connect(addr).await {
+            Ok(stream) => stream,
+            Err(err) => return failed(err.into()),
+        };
+
+        let private_key = self.cable.get_local_secret_key().await;
+        let version = Version::init(HANDSHAKE_MAJOR_VERSION, HANDSHAKE_MINOR_VERSION);
+        let handshake = match handshake::client_with_extensions(
+            &mut stream,
+            version,
+            self.cabal_key,
+            private_key,
+            self.cable.config().handshake_extensions,
+        )
+        .await
+        {
+            Ok(handshake) => handshake,
+            Err(err) => return failed(err),
+        };
+
+        let public_key = match handshake.get_remote_public_key() {
+            Some(public_key) => public_key,
+            None => {
+                return failed("the identity payload handshake extension is not enabled".into())
+            }
+        };
+        if self.cable.get_peer_id(&public_key).await.is_some() {
+            return (false, ConnectionStatus::Duplicate(public_key));
+        }
+
+        self.set_public_key(&addr, Some(public_key)).await;
+        self.emit(addr, ConnectionStatus::Connected(public_key))
+            .await;
+
+        let res = self.cable.listen_encrypted(stream, handshake).await;
+        self.set_public_key(&addr, None).await;
+
+        match res {
+            Ok(()) => (true, ConnectionStatus::Disconnected(None)),
+            // Another connection to the peer was established concurrently.
+            Err(err) if err.downcast_ref() == Some(&ConnectionError::DuplicatePublicKey) => {
+                (false, ConnectionStatus::Duplicate(public_key))
+            }
+            Err(err) => (true, ConnectionStatus::Disconnected(Some(err.to_string()))),
+        }
+    }
+}
@@ -0,0 +1,116 @@
+//! Test routing of inbound connections to the correct cabal.
+//!
+//! Create a cabal registry with two cabals, each with its own store, and
+//! connect to the registry from a remote cable manager using the key of one of
+//! the cabals.
+//!
+//! Run the test with debug logging enabled in a terminal:
+//!
+//! `RUST_LOG=debug cargo test cabal_registry`
+//!
+//! An outline of the actions taken in this test:
+//!
+//! 1) Register two cabals and publish a different text post to the "myco"
+//! channel of each.
+//!
+//! 2) Connect to the registry using the key of the second cabal and open the
+//! "myco" channel.
+//!
+//! 3) Ensure that the post of the second cabal is received and that only the
+//! second cabal has a connected peer.
+//!
+//! 4) Attempt to connect using an unknown cabal key and ensure that the
+//! handshake fails.
+
+use std::time::Duration;
+
+use async_std::{
+    future,
+    net::{TcpListener, TcpStream},
+    stream::StreamExt,
+    task,
+};
+use cable::{ChannelOptions, Error};
+use log::info;
+
+use cable_core::{CabalRegistry, CableManager, MemoryStore, Role};
+
+const CABAL_KEY_1: [u8; 32] = [1; 32];
+const CABAL_KEY_2: [u8; 32] = [2; 32];
+
+// Initialise the logger in test mode.
+//
+// Set `is_test()` to `false` if you wish to see logging output during the
+// test run.
+fn init() {
+    let _ = env_logger::builder().is_test(true).try_init();
+}
+
+#[async_std::test]
+async fn cabal_registry() -> Result<(), Error> {
+    init();
+
+    // Register two cabals and publish a post to each.
+    let registry = CabalRegistry::new();
+    let mut cabal_1 = registry
+        .add_cabal(CABAL_KEY_1, MemoryStore::default())
+        .await;
+    let mut cabal_2 = registry
+        .add_cabal(CABAL_KEY_2, MemoryStore::default())
+        .await;
+    assert_eq!(registry.cabal_keys().await.len(), 2);
+
+    let _post_hash_1 = cabal_1.post_text("myco", "hyphal fusion").await?;
+    let post_hash_2 = cabal_2.post_text("myco", "mycorrhizal network").await?;
+
+    // Deploy a TCP listener and route all inbound connections via the
+    // registry.
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    info!("Deployed TCP server on {}", addr);
+
+    let registry_clone = registry.clone();
+    task::spawn(async move {
+        let mut incoming = listener.incoming();
+        while let Some(Ok(stream)) = incoming.next().await {
+            let registry = registry_clone.clone();
+            task::spawn(async move {
+                let _ = registry.accept(stream).await;
+            });
+        }
+    });
+
+    // Connect to the second cabal.
+    let peer = CableManager::new(MemoryStore::default());
+    let opts = ChannelOptions::new("myco", 0, 0, 10);
+    let mut peer_clone = peer.clone();
+    let mut post_stream = peer_clone.open_channel(&opts).await?;
+
+    let stream = TcpStream::connect(addr).await?;
+    let peer_c = peer.clone();
+    task::spawn(async move {
+        let _ = peer_c
+            .listen_secure(stream, CABAL_KEY_2, Role::Initiator)
+            .await;
+    });
+
+    // Ensure the post of the second cabal is received.
+    let post = future::timeout(Duration::from_secs(5), post_stream.next())
+        .await?
+        .expect("post stream ended")?;
+    assert_eq!(post.hash()?, post_hash_2);
+
+    assert!(cabal_1.get_peer_ids().await.is_empty());
+    assert_eq!(cabal_2.get_peer_ids().await.len(), 1);
+
+    // A peer using an unknown cabal key is unable to complete the handshake.
+    let stream = TcpStream::connect(addr).await?;
+    let res = future::timeout(
+        Duration::from_secs(5),
+        peer.listen_secure(stream, [3; 32], Role::Initiator),
+    )
+    .await?;
+    assert!(res.is_err());
+
+    Ok(())
+}
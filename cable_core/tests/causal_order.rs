@@ -0,0 +1,218 @@
+//! Test the causal ordering of posts using post links.
+//!
+//! Run the test with debug logging enabled in a terminal:
+//!
+//! `RUST_LOG=debug cargo test causal_order`
+//!
+//! An outline of the actions taken in this test:
+//!
+//! 1) Create three text posts in the "myco" channel: a parent, a child which
+//! links to the parent but claims an earlier timestamp and an unrelated post.
+//!
+//! 2) Insert the posts into the store of a cable manager. Ensure that the
+//! link graph records the parent-child relationship and that the causal view
+//! of the channel places the child after the parent.
+//!
+//! 3) Connect to a second cable manager as a raw TCP peer and announce the
+//! hash of the child post. Respond to the post request with the child post.
+//!
+//! 4) Ensure that the manager requests the missing parent post. Respond with
+//! the parent post and ensure that it is stored.
+//!
+//! 5) Ensure that the implausible timestamp of the child post is reported.
+
+use std::time::Duration;
+
+use async_std::{
+    channel::Receiver,
+    future,
+    net::{TcpListener, TcpStream},
+    stream::StreamExt,
+    task,
+};
+use cable::{
+    constants::{NO_CIRCUIT, POST_REQUEST},
+    message::{MessageBody, RequestBody},
+    ChannelOptions, Error, Hash, Message, Post,
+};
+use desert::{FromBytes, ToBytes};
+use futures::{AsyncWriteExt, Stream};
+use length_prefixed_stream::{decode_with_options, DecodeError, DecodeOptions};
+use log::info;
+
+use cable_core::{CableEvent, CableManager, MemoryStore, Store};
+
+// Initialise the logger in test mode.
+//
+// Set `is_test()` to `false` if you wish to see logging output during the
+// test run.
+fn init() {
+    let _ = env_logger::builder().is_test(true).try_init();
+}
+
+// Define the stream decoder parameters.
+fn decode_options() -> DecodeOptions {
+    DecodeOptions {
+        include_len: true,
+        ..Default::default()
+    }
+}
+
+// Read messages from the given stream until a post request is received and
+// return the request ID and requested hashes.
+async fn read_post_request<T>(messages: &mut T) -> Result<([u8; 4], Vec<Hash>), Error>
+where
+    T: Stream<Item = Result<Vec<u8>, DecodeError>> + Unpin,
+{
+    loop {
+        let buf = future::timeout(Duration::from_secs(5), messages.next())
+            .await?
+            .expect("stream closed")?;
+        let (_bytes_len, msg) = Message::from_bytes(&buf)?;
+
+        if msg.message_type() == POST_REQUEST {
+            if let MessageBody::Request {
+                body: RequestBody::Post { hashes },
+                ..
+            } = msg.body
+            {
+                return Ok((msg.header.req_id, hashes));
+            }
+        }
+    }
+}
+
+// Wait for an event matching the given predicate, skipping all other events.
+async fn expect_event<F>(events: &Receiver<CableEvent>, predicate: F) -> Result<CableEvent, Error>
+where
+    F: Fn(&CableEvent) -> bool,
+{
+    let event = future::timeout(Duration::from_secs(5), async {
+        loop {
+            let event = events.recv().await.expect("event channel closed");
+            if predicate(&event) {
+                return event;
+            }
+        }
+    })
+    .await?;
+
+    Ok(event)
+}
+
+#[async_std::test]
+async fn causal_order() -> Result<(), Error> {
+    init();
+
+    let channel = "myco".to_string();
+    let (public_key, secret_key) = MemoryStore::default().get_keypair().await.expect("keypair");
+
+    // The child links to the parent but claims an earlier timestamp.
+    let mut parent = Post::text(
+        public_key,
+        Vec::new(),
+        2000,
+        channel.clone(),
+        "spore".into(),
+    );
+    parent.sign(&secret_key)?;
+    let parent_hash = parent.hash()?;
+    let mut child = Post::text(
+        public_key,
+        vec![parent_hash],
+        1000,
+        channel.clone(),
+        "hypha".into(),
+    );
+    child.sign(&secret_key)?;
+    let child_hash = child.hash()?;
+    let mut unrelated = Post::text(public_key, Vec::new(), 1500, channel.clone(), "moss".into());
+    unrelated.sign(&secret_key)?;
+    let unrelated_hash = unrelated.hash()?;
+
+    /* LINK GRAPH */
+
+    let mut cable = CableManager::new(MemoryStore::default());
+    for post in [&child, &parent, &unrelated] {
+        cable.store.insert_post(post).await?;
+    }
+
+    assert_eq!(
+        cable.store.get_parent_hashes(&child_hash).await,
+        Some(vec![parent_hash])
+    );
+    assert_eq!(
+        cable.store.get_child_hashes(&parent_hash).await,
+        Some(vec![child_hash])
+    );
+
+    /* CAUSAL VIEW */
+
+    let opts = ChannelOptions::new(channel.clone(), 0, 0, 0);
+    let posts = cable.get_posts_causal(&opts).await?;
+    let hashes = posts
+        .iter()
+        .map(|post| post.hash())
+        .collect::<Result<Vec<Hash>, Error>>()?;
+    assert_eq!(hashes, vec![unrelated_hash, parent_hash, child_hash]);
+
+    /* ANCESTOR FETCHING */
+
+    let cable = CableManager::new(MemoryStore::default());
+    let cable_clone = cable.clone();
+    let events = cable.events().await;
+
+    // Deploy a TCP listener and pass inbound streams to the cable manager.
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    info!("Deployed TCP server on {}", addr);
+
+    task::spawn(async move {
+        let mut incoming = listener.incoming();
+        while let Some(Ok(stream)) = incoming.next().await {
+            let cable = cable_clone.clone();
+            task::spawn(async move {
+                let _ = cable.listen(stream).await;
+            });
+        }
+    });
+
+    let mut stream = TcpStream::connect(addr).await?;
+    let mut messages = decode_with_options(stream.clone(), decode_options());
+
+    // Announce only the hash of the child post.
+    let (_req_id, req_id) = cable.new_req_id().await?;
+    let response = Message::hash_response(NO_CIRCUIT, req_id, vec![child_hash]);
+    stream.write_all(&response.to_bytes()?).await?;
+
+    let (req_id, hashes) = read_post_request(&mut messages).await?;
+    assert_eq!(hashes, vec![child_hash]);
+    let response = Message::post_response(NO_CIRCUIT, req_id, vec![child.to_bytes()?]);
+    stream.write_all(&response.to_bytes()?).await?;
+
+    // The missing parent is requested.
+    let (req_id, hashes) = read_post_request(&mut messages).await?;
+    assert_eq!(hashes, vec![parent_hash]);
+    let response = Message::post_response(NO_CIRCUIT, req_id, vec![parent.to_bytes()?]);
+    stream.write_all(&response.to_bytes()?).await?;
+
+    future::timeout(Duration::from_secs(5), async {
+        while cable.store.get_post_payload(&parent_hash).await.is_none() {
+            task::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await?;
+
+    /* IMPLAUSIBLE TIMESTAMPS */
+
+    let event = expect_event(&events, |event| {
+        matches!(event, CableEvent::ImplausibleTimestamp { .. })
+    })
+    .await?;
+    if let CableEvent::ImplausibleTimestamp { hash, parent } = event {
+        assert_eq!(hash, child_hash);
+        assert_eq!(parent, parent_hash);
+    }
+
+    Ok(())
+}
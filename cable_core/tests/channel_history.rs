@@ -0,0 +1,244 @@
+//! Test paging backwards through the history of a channel.
+//!
+//! Run the test with debug logging enabled in a terminal:
+//!
+//! `RUST_LOG=debug cargo test channel_history`
+//!
+//! An outline of the actions taken in this test:
+//!
+//! 1) Create five text posts in the "myco" channel, two of which share a
+//! timestamp, and a sixth post which is older than the others. Insert the
+//! five posts into a `MemoryStore` and a `SledStore` and ensure that both
+//! return the same pages of posts.
+//!
+//! 2) Insert the five posts into the store of a cable manager and connect to
+//! the manager as a raw TCP peer.
+//!
+//! 3) Page through the history of the channel with a page size of two.
+//! Ensure that the posts are returned newest first, without gaps or
+//! duplicates.
+//!
+//! 4) Ensure that a channel time range request for older posts is received
+//! once the local history is exhausted. Respond with the hash of the sixth
+//! post and then with the post itself.
+//!
+//! 5) Ensure that the sixth post is returned when the history is paged with
+//! the cursor of the last page.
+
+use std::time::Duration;
+
+use async_std::{
+    future,
+    net::{TcpListener, TcpStream},
+    stream::StreamExt,
+    task,
+};
+use cable::{
+    constants::{CHANNEL_TIME_RANGE_REQUEST, NO_CIRCUIT, POST_REQUEST},
+    message::{MessageBody, RequestBody},
+    Error, Hash, Message, Post,
+};
+use desert::{FromBytes, ToBytes};
+use futures::{AsyncWriteExt, Stream};
+use length_prefixed_stream::{decode_with_options, DecodeError, DecodeOptions};
+use log::info;
+
+use cable_core::{CableManager, HistoryCursor, MemoryStore, SledStore, Store};
+
+// Initialise the logger in test mode.
+//
+// Set `is_test()` to `false` if you wish to see logging output during the
+// test run.
+fn init() {
+    let _ = env_logger::builder().is_test(true).try_init();
+}
+
+// Define the stream decoder parameters.
+fn decode_options() -> DecodeOptions {
+    DecodeOptions {
+        include_len: true,
+        ..Default::default()
+    }
+}
+
+// Read messages from the given stream until a message of the given type is
+// received.
+async fn read_message_of_type<T>(messages: &mut T, msg_type: u64) -> Result<Message, Error>
+where
+    T: Stream<Item = Result<Vec<u8>, DecodeError>> + Unpin,
+{
+    loop {
+        let buf = future::timeout(Duration::from_secs(5), messages.next())
+            .await?
+            .expect("stream closed")?;
+        let (_bytes_len, msg) = Message::from_bytes(&buf)?;
+
+        if msg.message_type() == msg_type {
+            return Ok(msg);
+        }
+    }
+}
+
+// Return the hashes of the given posts.
+fn hashes(posts: &[Post]) -> Result<Vec<Hash>, Error> {
+    posts.iter().map(|post| post.hash()).collect()
+}
+
+// Page through the posts of the "myco" channel in the given store, returning
+// the hashes of each page.
+async fn store_pages<S: Store>(store: &S, page_size: usize) -> Vec<Vec<Hash>> {
+    let channel = "myco".to_string();
+    let mut pages = Vec::new();
+    let mut cursor = None;
+    loop {
+        let page = store
+            .get_posts_before(&channel, cursor.as_ref(), page_size)
+            .await;
+        if let Some((post, hash)) = page.last() {
+            cursor = Some(HistoryCursor::new(post.get_timestamp(), *hash));
+        } else {
+            return pages;
+        }
+        pages.push(page.into_iter().map(|(_post, hash)| hash).collect());
+    }
+}
+
+#[async_std::test]
+async fn channel_history() -> Result<(), Error> {
+    init();
+
+    let channel = "myco".to_string();
+    let (public_key, secret_key) = MemoryStore::default().get_keypair().await.expect("keypair");
+
+    // Create five posts, two of which share a timestamp, and an older post.
+    let mut posts = Vec::new();
+    for (timestamp, text) in [
+        (1000, "spore"),
+        (2000, "hypha"),
+        (3000, "mycelium"),
+        (3000, "rhizomorph"),
+        (4000, "sporocarp"),
+        (500, "lichen"),
+    ] {
+        let mut post = Post::text(
+            public_key,
+            Vec::new(),
+            timestamp,
+            channel.clone(),
+            text.into(),
+        );
+        post.sign(&secret_key)?;
+        posts.push(post);
+    }
+    let old_post = posts.pop().expect("old post");
+    let old_hash = old_post.hash()?;
+
+    // Posts sharing a timestamp are ordered by hash.
+    let mut expected = hashes(&posts)?;
+    if expected[2] > expected[3] {
+        expected.swap(2, 3);
+    }
+    expected.reverse();
+
+    /* STORES */
+
+    let mut memory_store = MemoryStore::default();
+    let dir = tempfile::tempdir()?;
+    let mut sled_store = SledStore::open(dir.path())?;
+    for post in &posts {
+        memory_store.insert_post(post).await?;
+        sled_store.insert_post(post).await?;
+    }
+    let pages = store_pages(&memory_store, 2).await;
+    assert_eq!(pages.concat(), expected);
+    assert_eq!(pages, store_pages(&sled_store, 2).await);
+
+    /* PAGING */
+
+    let mut cable = CableManager::new(MemoryStore::default());
+    for post in &posts {
+        cable.store.insert_post(post).await?;
+    }
+    let cable_clone = cable.clone();
+
+    // Deploy a TCP listener and pass inbound streams to the cable manager.
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    info!("Deployed TCP server on {}", addr);
+
+    task::spawn(async move {
+        let mut incoming = listener.incoming();
+        while let Some(Ok(stream)) = incoming.next().await {
+            let cable = cable_clone.clone();
+            task::spawn(async move {
+                let _ = cable.listen(stream).await;
+            });
+        }
+    });
+
+    let mut stream = TcpStream::connect(addr).await?;
+    let mut messages = decode_with_options(stream.clone(), decode_options());
+
+    // Wait for the manager to register the peer.
+    future::timeout(Duration::from_secs(5), async {
+        while cable.get_peer_ids().await.is_empty() {
+            task::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await?;
+
+    let first_page = cable.channel_history(&channel, None, 2).await?;
+    assert_eq!(hashes(&first_page.posts)?, expected[0..2]);
+    let second_page = cable
+        .channel_history(&channel, first_page.cursor, 2)
+        .await?;
+    assert_eq!(hashes(&second_page.posts)?, expected[2..4]);
+    let third_page = cable
+        .channel_history(&channel, second_page.cursor, 2)
+        .await?;
+    assert_eq!(hashes(&third_page.posts)?, expected[4..5]);
+
+    /* BACKFILL */
+
+    // The local history is exhausted; older posts are requested.
+    let msg = read_message_of_type(&mut messages, CHANNEL_TIME_RANGE_REQUEST).await?;
+    if let MessageBody::Request {
+        body:
+            RequestBody::ChannelTimeRange {
+                channel: req_channel,
+                time_start,
+                time_end,
+                limit,
+            },
+        ..
+    } = &msg.body
+    {
+        assert_eq!(req_channel, &channel);
+        assert_eq!((*time_start, *time_end, *limit), (0, 1000, 2));
+    }
+
+    let response = Message::hash_response(NO_CIRCUIT, msg.header.req_id, vec![old_hash]);
+    stream.write_all(&response.to_bytes()?).await?;
+    let response = Message::hash_response(NO_CIRCUIT, msg.header.req_id, Vec::new());
+    stream.write_all(&response.to_bytes()?).await?;
+
+    let msg = read_message_of_type(&mut messages, POST_REQUEST).await?;
+    let response =
+        Message::post_response(NO_CIRCUIT, msg.header.req_id, vec![old_post.to_bytes()?]);
+    stream.write_all(&response.to_bytes()?).await?;
+
+    future::timeout(Duration::from_secs(5), async {
+        while cable.store.get_post_payload(&old_hash).await.is_none() {
+            task::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await?;
+
+    // The backfilled post follows the last page.
+    let fourth_page = cable
+        .channel_history(&channel, third_page.cursor, 2)
+        .await?;
+    assert_eq!(hashes(&fourth_page.posts)?, vec![old_hash]);
+
+    Ok(())
+}
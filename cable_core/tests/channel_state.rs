@@ -18,41 +18,51 @@
 //!
 //! 3) Publish a topic post to the "entomology" channel.
 //!
-//! 4) Ensure that two hashes are returned: one matching the hash of the join
-//! post and one matching the hash of the topic post.
+//! 4) Ensure that only the changed state is returned: a single hash matching
+//! the hash of the topic post.
 //!
 //! 5) Publish a second topic post to the "entomology" channel.
 //!
-//! 6) Ensure that two hashes are returned: one matching the hash of the join
-//! post and one matching the hash of the second topic post.
+//! 6) Ensure that a single hash is returned, matching the hash of the second
+//! topic post.
 //!
 //! 7) Publish a delete post with the hash of the second topic post.
 //!
-//! 8) Ensure that three hashes are returned: one matching the hash of the join
-//! post, one matching the hash of the first topic post and one matching the
-//! hash of the delete topic post.
+//! 8) Ensure that two hashes are returned: one matching the hash of the first
+//! topic post and one matching the hash of the delete topic post.
 //!
 //! 9) Publish an info post with the nickname "glyph".
 //!
-//! 10) Ensure that four hashes are returned: one matching the hash of the join
-//! post, one matching the hash of the first topic post, one matching the hash
-//! of the delete topic post and one matching the hash of the first name post.
+//! 10) Ensure that a single hash is returned, matching the hash of the first
+//! name post.
 //!
 //! 11) Publish a second info post with the nickname "mycognosist".
 //!
-//! 12) Ensure that four hashes are returned: one matching the hash of the join
-//! post, one matching the hash of the first topic post, one matching the hash
-//! of the delete topic post and one matching the hash of the second name post.
+//! 12) Ensure that a single hash is returned, matching the hash of the second
+//! name post.
 //!
 //! 13) Publish a leave post to the "entomology" channel.
 //!
-//! 10) Ensure that three hashes are returned: one matching the hash of the
-//! leave post, one matching the hash of the first topic post and one matching
-//! the hash of the second name post.
+//! 14) Ensure that a single hash is returned, matching the hash of the leave
+//! post.
+//!
+//! A second test, `channel_state_index`, covers each clause of the channel
+//! state definition (spec section 5.4.4):
+//!
+//! 1) Publish join, info and topic posts to the "entomology" channel using
+//! the cable manager. Insert posts by a second author who joins and then
+//! leaves the channel, by a third author who never joins and by a fourth
+//! author who only publishes text posts.
+//!
+//! 2) Send a channel state request with `future` set to 0. Ensure that the
+//! response contains exactly the latest join or leave post of each member and
+//! ex-member, the latest topic post and the latest info posts of each member
+//! and ex-member, and that an empty hash response concludes the request.
 
 use std::{thread, time::Duration};
 
 use async_std::{
+    future,
     net::{TcpListener, TcpStream},
     stream::StreamExt,
     task,
@@ -60,13 +70,14 @@ use async_std::{
 use cable::{
     constants::{HASH_RESPONSE, NO_CIRCUIT},
     message::{MessageBody, ResponseBody},
-    Error, Message,
+    Error, Hash, Message, Post, ReqId, UserInfo,
 };
 use desert::{FromBytes, ToBytes};
-use futures::{AsyncReadExt, AsyncWriteExt};
+use futures::{AsyncReadExt, AsyncWriteExt, Stream};
+use length_prefixed_stream::{decode_with_options, DecodeError, DecodeOptions};
 use log::{debug, info};
 
-use cable_core::{CableManager, MemoryStore};
+use cable_core::{CableManager, MemoryStore, Store};
 
 // The circuit_id field is not currently in use; set to all zeros.
 const CIRCUIT_ID: [u8; 4] = NO_CIRCUIT;
@@ -80,6 +91,44 @@ fn init() {
     let _ = env_logger::builder().is_test(false).try_init();
 }
 
+// Define the stream decoder parameters.
+fn decode_options() -> DecodeOptions {
+    DecodeOptions {
+        include_len: true,
+        ..Default::default()
+    }
+}
+
+// Read messages from the given stream until a hash response to the request
+// with the given ID is received and return the hashes.
+async fn read_hashes<T>(messages: &mut T, req_id: ReqId) -> Result<Vec<Hash>, Error>
+where
+    T: Stream<Item = Result<Vec<u8>, DecodeError>> + Unpin,
+{
+    loop {
+        let buf = future::timeout(Duration::from_secs(5), messages.next())
+            .await?
+            .expect("stream closed")?;
+        let (_bytes_len, msg) = Message::from_bytes(&buf)?;
+
+        if msg.header.req_id == req_id {
+            if let MessageBody::Response {
+                body: ResponseBody::Hash { hashes },
+            } = msg.body
+            {
+                return Ok(hashes);
+            }
+        }
+    }
+}
+
+// Create and sign a post using the given secret key.
+fn signed(mut post: Post, secret_key: &[u8; 64]) -> Result<Post, Error> {
+    post.sign(secret_key)?;
+
+    Ok(post)
+}
+
 #[async_std::test]
 async fn channel_state_request_response() -> Result<(), Error> {
     init();
@@ -181,13 +230,10 @@ async fn channel_state_request_response() -> Result<(), Error> {
 
     if let MessageBody::Response { body } = msg.body {
         if let ResponseBody::Hash { hashes } = body {
-            // Two post hashes should be returned (one for join / leave and one
-            // for the latest topic).
-            assert_eq!(hashes.len(), 2);
-            // Ensure the first hash matches the hash of the recent leave post.
-            assert_eq!(hashes[0], join_post_hash);
-            // Ensure the second hash matches the hash of the first topic post.
-            assert_eq!(hashes[1], first_topic_hash);
+            // Only the hash of the changed state should be returned.
+            assert_eq!(hashes.len(), 1);
+            // Ensure the hash matches the hash of the first topic post.
+            assert_eq!(hashes[0], first_topic_hash);
         }
     }
 
@@ -219,13 +265,10 @@ async fn channel_state_request_response() -> Result<(), Error> {
 
     if let MessageBody::Response { body } = msg.body {
         if let ResponseBody::Hash { hashes } = body {
-            // Two post hashes should be returned (one for join / leave and one
-            // for the latest topic).
-            assert_eq!(hashes.len(), 2);
-            // Ensure the first hash matches the hash of the recent join post.
-            assert_eq!(hashes[0], join_post_hash);
-            // Ensure the second hash matches the hash of the second topic post.
-            assert_eq!(hashes[1], second_topic_hash);
+            // Only the hash of the changed state should be returned.
+            assert_eq!(hashes.len(), 1);
+            // Ensure the hash matches the hash of the second topic post.
+            assert_eq!(hashes[0], second_topic_hash);
         }
     }
 
@@ -256,16 +299,14 @@ async fn channel_state_request_response() -> Result<(), Error> {
 
     if let MessageBody::Response { body } = msg.body {
         if let ResponseBody::Hash { hashes } = body {
-            // Three post hashes should be returned (one for join / leave, one
-            // for the latest topic and one for the recent delete post).
-            assert_eq!(hashes.len(), 3);
-            // Ensure the first hash matches the hash of the recent join post.
-            assert_eq!(hashes[0], join_post_hash);
-            // Ensure the second hash matches the hash of the first topic post.
-            assert_eq!(hashes[1], first_topic_hash);
-            // Ensure the third hash matches the hash of the recent delete
+            // Two post hashes should be returned (one for the topic which is
+            // once again the latest and one for the recent delete post).
+            assert_eq!(hashes.len(), 2);
+            // Ensure the first hash matches the hash of the first topic post.
+            assert_eq!(hashes[0], first_topic_hash);
+            // Ensure the second hash matches the hash of the recent delete
             // post.
-            assert_eq!(hashes[2], delete_topic_hash);
+            assert_eq!(hashes[1], delete_topic_hash);
         }
     }
 
@@ -291,19 +332,10 @@ async fn channel_state_request_response() -> Result<(), Error> {
 
     if let MessageBody::Response { body } = msg.body {
         if let ResponseBody::Hash { hashes } = body {
-            // Five post hashes should be returned.
-            assert_eq!(hashes.len(), 4);
-            // Ensure the returned hash matches the hash of the recent
-            // join post.
-            assert_eq!(hashes[0], join_post_hash);
-            // Ensure the second hash matches the hash of the first topic post.
-            assert_eq!(hashes[1], first_topic_hash);
-            // Ensure the third hash matches the hash of the recent delete
-            // post.
-            assert_eq!(hashes[2], delete_topic_hash);
-            // Ensure the fourth hash matches the hash of the first name info
-            // post.
-            assert_eq!(hashes[3], first_name_hash);
+            // Only the hash of the changed state should be returned.
+            assert_eq!(hashes.len(), 1);
+            // Ensure the hash matches the hash of the first name info post.
+            assert_eq!(hashes[0], first_name_hash);
         }
     }
 
@@ -329,19 +361,10 @@ async fn channel_state_request_response() -> Result<(), Error> {
 
     if let MessageBody::Response { body } = msg.body {
         if let ResponseBody::Hash { hashes } = body {
-            // Four post hashes should be returned.
-            assert_eq!(hashes.len(), 4);
-            // Ensure the returned hash matches the hash of the recent
-            // join post.
-            assert_eq!(hashes[0], join_post_hash);
-            // Ensure the second hash matches the hash of the first topic post.
-            assert_eq!(hashes[1], first_topic_hash);
-            // Ensure the third hash matches the hash of the recent delete
-            // post.
-            assert_eq!(hashes[2], delete_topic_hash);
-            // Ensure the fourth hash matches the hash of the second name info
-            // post.
-            assert_eq!(hashes[3], second_name_hash);
+            // Only the hash of the changed state should be returned.
+            assert_eq!(hashes.len(), 1);
+            // Ensure the hash matches the hash of the second name info post.
+            assert_eq!(hashes[0], second_name_hash);
         }
     }
 
@@ -365,18 +388,117 @@ async fn channel_state_request_response() -> Result<(), Error> {
 
     if let MessageBody::Response { body } = msg.body {
         if let ResponseBody::Hash { hashes } = body {
-            // Two post hashes should be returned.
-            assert_eq!(hashes.len(), 3);
-            // Ensure the returned hash matches the hash of the recent
-            // leave post.
+            // Only the hash of the changed state should be returned.
+            assert_eq!(hashes.len(), 1);
+            // Ensure the hash matches the hash of the recent leave post.
             assert_eq!(hashes[0], leave_post_hash);
-            // Ensure the second hash matches the hash of the first topic post.
-            assert_eq!(hashes[1], first_topic_hash);
-            // Ensure the third hash matches the hash of the second name info
-            // post.
-            assert_eq!(hashes[2], second_name_hash);
         }
     }
 
     Ok(())
 }
+
+#[async_std::test]
+async fn channel_state_index() -> Result<(), Error> {
+    init();
+
+    let mut cable = CableManager::new(MemoryStore::default());
+    let cable_clone = cable.clone();
+    let channel = "entomology".to_string();
+
+    /* MEMBER */
+
+    let join_hash = cable.post_join(&channel).await?;
+    let name_hash = cable.post_info_name("glyph").await?;
+    task::sleep(Duration::from_millis(5)).await;
+    // A partial update; the name remains part of the profile.
+    let bio_hash = cable
+        .post_info(vec![UserInfo::new("bio", "coleopterist")])
+        .await?;
+    cable.post_topic(&channel, "Insect appreciation").await?;
+    task::sleep(Duration::from_millis(5)).await;
+    let topic_hash = cable.post_topic(&channel, "Beetles only").await?;
+
+    /* EX-MEMBER */
+
+    let (ex_member, ex_member_sk) = MemoryStore::default().get_keypair().await.expect("keypair");
+    let join = signed(
+        Post::join(ex_member, Vec::new(), 1, channel.clone()),
+        &ex_member_sk,
+    )?;
+    cable.store.insert_post(&join).await?;
+    let first_name = signed(
+        Post::info(ex_member, Vec::new(), 2, vec![UserInfo::name("larva")?]),
+        &ex_member_sk,
+    )?;
+    cable.store.insert_post(&first_name).await?;
+    // Only the latest name is part of the channel state.
+    let second_name = signed(
+        Post::info(ex_member, Vec::new(), 3, vec![UserInfo::name("imago")?]),
+        &ex_member_sk,
+    )?;
+    let (ex_member_name_hash, _deleted) = cable.store.insert_post(&second_name).await?;
+    let leave = signed(
+        Post::leave(ex_member, Vec::new(), 4, channel.clone()),
+        &ex_member_sk,
+    )?;
+    let (leave_hash, _deleted) = cable.store.insert_post(&leave).await?;
+
+    /* NON-MEMBERS */
+
+    let (stranger, stranger_sk) = MemoryStore::default().get_keypair().await.expect("keypair");
+    let info = signed(
+        Post::info(stranger, Vec::new(), 1, vec![UserInfo::name("moth")?]),
+        &stranger_sk,
+    )?;
+    cable.store.insert_post(&info).await?;
+
+    let (lurker, lurker_sk) = MemoryStore::default().get_keypair().await.expect("keypair");
+    let text = signed(
+        Post::text(lurker, Vec::new(), 1, channel.clone(), "hello".to_string()),
+        &lurker_sk,
+    )?;
+    cable.store.insert_post(&text).await?;
+
+    // Deploy a TCP listener and pass inbound streams to the cable manager.
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    info!("Deployed TCP server on {}", addr);
+
+    task::spawn(async move {
+        let mut incoming = listener.incoming();
+        while let Some(Ok(stream)) = incoming.next().await {
+            let cable = cable_clone.clone();
+            task::spawn(async move {
+                let _ = cable.listen(stream).await;
+            });
+        }
+    });
+
+    let mut stream = TcpStream::connect(addr).await?;
+    let mut messages = decode_with_options(stream.clone(), decode_options());
+
+    /* REQUEST */
+
+    let (_req_id, req_id) = cable.new_req_id().await?;
+    let request = Message::channel_state_request(CIRCUIT_ID, req_id, 0, channel.clone(), 0);
+    stream.write_all(&request.to_bytes()?).await?;
+
+    let mut hashes = read_hashes(&mut messages, req_id).await?;
+    let mut expected = vec![
+        join_hash,
+        leave_hash,
+        topic_hash,
+        name_hash,
+        bio_hash,
+        ex_member_name_hash,
+    ];
+    hashes.sort();
+    expected.sort();
+    assert_eq!(hashes, expected);
+
+    // The request is concluded with an empty hash response.
+    assert!(read_hashes(&mut messages, req_id).await?.is_empty());
+
+    Ok(())
+}
@@ -17,11 +17,13 @@
 //!
 //! 3) Send a channel time range request for the "myco" channel with a start
 //! time before the three posts were published and an end time of `now()`.
-//! Ensure that all three hashes are returned in the response.
+//! Ensure that all three hashes are returned in the response, followed by a
+//! response with no hashes to conclude the request.
 //!
 //! 4) Send a channel time range request for the "myco" channel with a start
 //! time before the three posts were published, an end time of `now()` and a
-//! limit of 2. Ensure that only two hashes are returned in the response.
+//! limit of 2. Ensure that only two hashes are returned in the response,
+//! followed by a response with no hashes to conclude the request.
 //!
 //! 5) Publish a post to the "books" channel.
 //!
@@ -32,6 +34,15 @@
 //! 7) Publish a second post to the "books" channel.
 //!
 //! 8) Ensure that a hash response is received with two hashes.
+//!
+//! 9) Insert four posts with known timestamps into a `MemoryStore` and a
+//! `SledStore`. Ensure that a limit selects the latest posts, that hashes are
+//! returned newest first, that posts are returned in chronological order and
+//! that both ends of the time range are inclusive.
+//!
+//! 10) Send channel time range requests with a limit and with bounds matching
+//! the post timestamps. Ensure that the hashes of the latest posts are
+//! returned, including those published at the start and end times.
 
 use std::{thread, time::Duration};
 
@@ -43,13 +54,14 @@ use async_std::{
 use cable::{
     constants::{HASH_RESPONSE, NO_CIRCUIT},
     message::{MessageBody, ResponseBody},
-    ChannelOptions, Error, Message,
+    ChannelOptions, Error, Hash, Message, Post, ReqId,
 };
 use desert::{FromBytes, ToBytes};
-use futures::{AsyncReadExt, AsyncWriteExt};
+use futures::{AsyncWriteExt, Stream};
+use length_prefixed_stream::{decode_with_options, DecodeError, DecodeOptions};
 use log::info;
 
-use cable_core::{CableManager, MemoryStore};
+use cable_core::{CableManager, MemoryStore, SledStore, Store};
 
 // The circuit_id field is not currently in use; set to all zeros.
 const CIRCUIT_ID: [u8; 4] = NO_CIRCUIT;
@@ -73,6 +85,141 @@ fn now() -> Result<u64, Error> {
     Ok(time)
 }
 
+// Define the stream decoder parameters.
+fn decode_options() -> DecodeOptions {
+    DecodeOptions {
+        include_len: true,
+        ..Default::default()
+    }
+}
+
+// Read the next message from the given stream.
+async fn read_message<T>(messages: &mut T) -> Result<Message, Error>
+where
+    T: Stream<Item = Result<Vec<u8>, DecodeError>> + Unpin,
+{
+    let buf = messages.next().await.expect("stream closed")?;
+    let (_bytes_len, msg) = Message::from_bytes(&buf)?;
+
+    Ok(msg)
+}
+
+// Ensure that the given message is a hash response with no hashes, concluding
+// the request with the given ID.
+fn assert_concluded(msg: &Message, req_id: ReqId) {
+    assert_eq!(msg.message_type(), HASH_RESPONSE);
+    assert_eq!(msg.header.req_id, req_id);
+
+    if let MessageBody::Response {
+        body: ResponseBody::Hash { hashes },
+    } = &msg.body
+    {
+        assert!(hashes.is_empty());
+    }
+}
+
+// Create four signed text posts in the "myco" channel with timestamps 1000,
+// 2000, 3000 and 4000, returning the posts and their hashes.
+async fn timestamped_posts() -> Result<(Vec<Post>, Vec<Hash>), Error> {
+    let (public_key, secret_key) = MemoryStore::default().get_keypair().await.expect("keypair");
+
+    let mut posts = Vec::new();
+    let mut hashes = Vec::new();
+    for (i, text) in ["spore", "hypha", "mycelium", "sporocarp"]
+        .iter()
+        .enumerate()
+    {
+        let timestamp = (i as u64 + 1) * 1000;
+        let mut post = Post::text(
+            public_key,
+            Vec::new(),
+            timestamp,
+            "myco".into(),
+            text.to_string(),
+        );
+        post.sign(&secret_key)?;
+        hashes.push(post.hash()?);
+        posts.push(post);
+    }
+
+    Ok((posts, hashes))
+}
+
+// Ensure that the given store applies the limit and the inclusive time range
+// of the given channel options, returning hashes newest first and posts in
+// chronological order.
+async fn assert_latest_posts<S: Store>(store: &S, hashes: &[Hash]) -> Result<(), Error> {
+    let get_hashes = |opts: ChannelOptions| async move {
+        store
+            .get_post_hashes(&opts)
+            .await
+            .collect::<Result<Vec<Hash>, Error>>()
+            .await
+    };
+
+    // The latest two posts, newest first.
+    assert_eq!(
+        get_hashes(ChannelOptions::new("myco", 0, 0, 2)).await?,
+        vec![hashes[3], hashes[2]]
+    );
+    // Both ends of the time range are inclusive.
+    assert_eq!(
+        get_hashes(ChannelOptions::new("myco", 2000, 3000, 0)).await?,
+        vec![hashes[2], hashes[1]]
+    );
+    assert_eq!(
+        get_hashes(ChannelOptions::new("myco", 4000, 4000, 0)).await?,
+        vec![hashes[3]]
+    );
+    // A start time later than the end time matches no posts.
+    assert!(get_hashes(ChannelOptions::new("myco", 3000, 2000, 0))
+        .await?
+        .is_empty());
+
+    // The latest two posts in the range, in chronological order.
+    let posts = store
+        .get_posts(&ChannelOptions::new("myco", 1000, 4000, 2))
+        .await
+        .collect::<Result<Vec<Post>, Error>>()
+        .await?;
+    let post_hashes = posts
+        .iter()
+        .map(|post| post.hash())
+        .collect::<Result<Vec<Hash>, Error>>()?;
+    assert_eq!(post_hashes, vec![hashes[2], hashes[3]]);
+
+    Ok(())
+}
+
+// Send a channel time range request with the given options and return the
+// hashes of the response, ensuring that the request is concluded.
+async fn request_hashes<T>(
+    stream: &mut TcpStream,
+    messages: &mut T,
+    req_id: ReqId,
+    opts: ChannelOptions,
+) -> Result<Vec<Hash>, Error>
+where
+    T: Stream<Item = Result<Vec<u8>, DecodeError>> + Unpin,
+{
+    let request = Message::channel_time_range_request(CIRCUIT_ID, req_id, 0, opts);
+    stream.write_all(&request.to_bytes()?).await?;
+
+    let msg = read_message(messages).await?;
+    assert_eq!(msg.header.req_id, req_id);
+    let hashes = match msg.body {
+        MessageBody::Response {
+            body: ResponseBody::Hash { hashes },
+        } => hashes,
+        _ => panic!("expected a hash response"),
+    };
+
+    let msg = read_message(messages).await?;
+    assert_concluded(&msg, req_id);
+
+    Ok(hashes)
+}
+
 #[async_std::test]
 async fn channel_time_range_request_response() -> Result<(), Error> {
     init();
@@ -108,6 +255,9 @@ async fn channel_time_range_request_response() -> Result<(), Error> {
     let mut stream = TcpStream::connect(addr).await?;
     info!("Connected to TCP server on {}", addr);
 
+    // Decode the length-prefixed messages received from the stream.
+    let mut messages = decode_with_options(stream.clone(), decode_options());
+
     // Create a timestamp for later use.
     let time_before_posts_were_published = now()?;
 
@@ -130,6 +280,10 @@ async fn channel_time_range_request_response() -> Result<(), Error> {
     // Generate a novel request ID.
     let (_req_id, req_id_bytes) = cable.new_req_id().await?;
 
+    // Sleep briefly to ensure the time range, which includes both the start
+    // and end time, begins after the posts were published.
+    thread::sleep(Duration::from_millis(2));
+
     // Channel time range request parameters.
     //
     // These parameters should result in zero post hashes being returned, due
@@ -150,11 +304,9 @@ async fn channel_time_range_request_response() -> Result<(), Error> {
     thread::sleep(five_millis);
 
     // Read the response from the stream.
-    let mut res_bytes = [0u8; 1024];
-    let _n = stream.read(&mut res_bytes).await?;
+    let msg = read_message(&mut messages).await?;
 
     // Ensure that a hash response was returned by the listening peer.
-    let (_bytes_len, msg) = Message::from_bytes(&res_bytes)?;
     assert_eq!(msg.message_type(), HASH_RESPONSE);
 
     if let MessageBody::Response { body } = msg.body {
@@ -193,11 +345,9 @@ async fn channel_time_range_request_response() -> Result<(), Error> {
     thread::sleep(five_millis);
 
     // Read the response from the stream.
-    let mut res_bytes = [0u8; 1024];
-    let _n = stream.read(&mut res_bytes).await?;
+    let msg = read_message(&mut messages).await?;
 
     // Ensure that a hash response was returned by the listening peer.
-    let (_bytes_len, msg) = Message::from_bytes(&res_bytes)?;
     assert_eq!(msg.message_type(), HASH_RESPONSE);
 
     if let MessageBody::Response { body } = msg.body {
@@ -207,6 +357,10 @@ async fn channel_time_range_request_response() -> Result<(), Error> {
         }
     }
 
+    // Ensure that the request was concluded.
+    let msg = read_message(&mut messages).await?;
+    assert_concluded(&msg, req_id_bytes);
+
     /* THIRD REQUEST */
 
     // Generate a novel request ID.
@@ -232,11 +386,9 @@ async fn channel_time_range_request_response() -> Result<(), Error> {
     thread::sleep(five_millis);
 
     // Read the response from the stream.
-    let mut res_bytes = [0u8; 1024];
-    let _n = stream.read(&mut res_bytes).await?;
+    let msg = read_message(&mut messages).await?;
 
     // Ensure that a hash response was returned by the listening peer.
-    let (_bytes_len, msg) = Message::from_bytes(&res_bytes)?;
     assert_eq!(msg.message_type(), HASH_RESPONSE);
 
     if let MessageBody::Response { body } = msg.body {
@@ -246,6 +398,10 @@ async fn channel_time_range_request_response() -> Result<(), Error> {
         }
     }
 
+    // Ensure that the request was concluded.
+    let msg = read_message(&mut messages).await?;
+    assert_concluded(&msg, req_id_bytes);
+
     /* FOURTH REQUEST */
 
     // Publish a post to the "books" channel.
@@ -281,11 +437,9 @@ async fn channel_time_range_request_response() -> Result<(), Error> {
     thread::sleep(five_millis);
 
     // Read the response from the stream.
-    let mut res_bytes = [0u8; 1024];
-    let _n = stream.read(&mut res_bytes).await?;
+    let msg = read_message(&mut messages).await?;
 
     // Ensure that a hash response was returned by the listening peer.
-    let (_bytes_len, msg) = Message::from_bytes(&res_bytes)?;
     assert_eq!(msg.message_type(), HASH_RESPONSE);
 
     if let MessageBody::Response { body } = msg.body {
@@ -305,11 +459,9 @@ async fn channel_time_range_request_response() -> Result<(), Error> {
     thread::sleep(five_millis);
 
     // Read the response from the stream.
-    let mut res_bytes = [0u8; 1024];
-    let _n = stream.read(&mut res_bytes).await?;
+    let msg = read_message(&mut messages).await?;
 
     // Ensure that a hash response was returned by the listening peer.
-    let (_bytes_len, msg) = Message::from_bytes(&res_bytes)?;
     assert_eq!(msg.message_type(), HASH_RESPONSE);
 
     if let MessageBody::Response { body } = msg.body {
@@ -321,3 +473,69 @@ async fn channel_time_range_request_response() -> Result<(), Error> {
 
     Ok(())
 }
+
+#[async_std::test]
+async fn channel_time_range_latest() -> Result<(), Error> {
+    init();
+
+    let (posts, hashes) = timestamped_posts().await?;
+
+    /* STORE */
+
+    let mut store = MemoryStore::default();
+    for post in &posts {
+        store.insert_post(post).await?;
+    }
+    assert_latest_posts(&store, &hashes).await?;
+
+    let dir = tempfile::tempdir()?;
+    let mut store = SledStore::open(dir.path())?;
+    for post in &posts {
+        store.insert_post(post).await?;
+    }
+    assert_latest_posts(&store, &hashes).await?;
+
+    /* REQUEST */
+
+    let mut cable = CableManager::new(MemoryStore::default());
+    for post in &posts {
+        cable.store.insert_post(post).await?;
+    }
+    let cable_clone = cable.clone();
+
+    // Deploy a TCP listener and pass inbound streams to the cable manager.
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    info!("Deployed TCP server on {}", addr);
+
+    task::spawn(async move {
+        let mut incoming = listener.incoming();
+        while let Some(Ok(stream)) = incoming.next().await {
+            let cable = cable_clone.clone();
+            task::spawn(async move {
+                let _ = cable.listen(stream).await;
+            });
+        }
+    });
+
+    let mut stream = TcpStream::connect(addr).await?;
+    let mut messages = decode_with_options(stream.clone(), decode_options());
+
+    // The latest two posts are returned, newest first.
+    let (_req_id, req_id) = cable.new_req_id().await?;
+    let opts = ChannelOptions::new("myco", 1000, 4000, 2);
+    assert_eq!(
+        request_hashes(&mut stream, &mut messages, req_id, opts).await?,
+        vec![hashes[3], hashes[2]]
+    );
+
+    // Posts published at the start and end times are included.
+    let (_req_id, req_id) = cable.new_req_id().await?;
+    let opts = ChannelOptions::new("myco", 1000, 2000, 10);
+    assert_eq!(
+        request_hashes(&mut stream, &mut messages, req_id, opts).await?,
+        vec![hashes[1], hashes[0]]
+    );
+
+    Ok(())
+}
@@ -0,0 +1,172 @@
+//! Test the detection of duplicate requests using the bounded cache of
+//! handled requests.
+//!
+//! Run the test with debug logging enabled in a terminal:
+//!
+//! `RUST_LOG=debug cargo test dedup`
+//!
+//! An outline of the actions taken in this test:
+//!
+//! 1) Create a cable manager which retains at most two handled request IDs
+//! and publish a post to the "myco" channel.
+//!
+//! 2) Send a channel list request and ensure that a channel list response is
+//! returned.
+//!
+//! 3) Send the same channel list request again, followed by a second channel
+//! list request. Ensure that only the second request is answered and that the
+//! duplicate is reported in the metrics.
+//!
+//! 4) Send a third channel list request, evicting the first request ID from
+//! the cache. Send the first channel list request again and ensure that it is
+//! answered.
+
+use std::time::Duration;
+
+use async_std::{
+    future,
+    net::{TcpListener, TcpStream},
+    stream::StreamExt,
+    task,
+};
+use cable::{
+    constants::{CHANNEL_LIST_RESPONSE, NO_CIRCUIT},
+    Error, Message, ReqId,
+};
+use desert::{FromBytes, ToBytes};
+use futures::{AsyncWriteExt, Stream};
+use length_prefixed_stream::{decode_with_options, DecodeError, DecodeOptions};
+use log::info;
+
+use cable_core::{CableManager, ManagerConfig, MemoryStore, RequestConfig};
+
+const TTL: u8 = 0;
+
+// Initialise the logger in test mode.
+//
+// Set `is_test()` to `false` if you wish to see logging output during the
+// test run.
+fn init() {
+    let _ = env_logger::builder().is_test(true).try_init();
+}
+
+// Define the stream decoder parameters.
+fn decode_options() -> DecodeOptions {
+    DecodeOptions {
+        include_len: true,
+        ..Default::default()
+    }
+}
+
+// Read the next message from the given stream and ensure that it is a
+// channel list response to the request with the given ID.
+async fn expect_response<T>(messages: &mut T, req_id: ReqId) -> Result<(), Error>
+where
+    T: Stream<Item = Result<Vec<u8>, DecodeError>> + Unpin,
+{
+    let buf = future::timeout(Duration::from_secs(5), messages.next())
+        .await?
+        .expect("stream closed")?;
+    let (_bytes_len, msg) = Message::from_bytes(&buf)?;
+
+    assert_eq!(msg.message_type(), CHANNEL_LIST_RESPONSE);
+    assert_eq!(msg.header.req_id, req_id);
+
+    Ok(())
+}
+
+// Wait for the given number of request IDs to be held in the cache of handled
+// requests.
+async fn wait_cached(cable: &CableManager<MemoryStore>, cached: usize) -> Result<(), Error> {
+    future::timeout(Duration::from_secs(5), async {
+        while cable.dedup_metrics().await.cached != cached {
+            task::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await?;
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn dedup() -> Result<(), Error> {
+    init();
+
+    let config = ManagerConfig {
+        requests: RequestConfig {
+            handled_capacity: 2,
+            ..RequestConfig::default()
+        },
+        ..ManagerConfig::default()
+    };
+    let mut cable = CableManager::with_config(MemoryStore::default(), config);
+    let cable_clone = cable.clone();
+
+    cable.post_text("myco", "hyphal fusion").await?;
+
+    // Deploy a TCP listener and pass inbound streams to the cable manager.
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    info!("Deployed TCP server on {}", addr);
+
+    task::spawn(async move {
+        let mut incoming = listener.incoming();
+        while let Some(Ok(stream)) = incoming.next().await {
+            let cable = cable_clone.clone();
+            task::spawn(async move {
+                let _ = cable.listen(stream).await;
+            });
+        }
+    });
+
+    let mut stream = TcpStream::connect(addr).await?;
+    let mut messages = decode_with_options(stream.clone(), decode_options());
+
+    let (_req_id, req_id_1) = cable.new_req_id().await?;
+    let (_req_id, req_id_2) = cable.new_req_id().await?;
+    let (_req_id, req_id_3) = cable.new_req_id().await?;
+    let request_1 = Message::channel_list_request(NO_CIRCUIT, req_id_1, TTL, 0, 0).to_bytes()?;
+    let request_2 = Message::channel_list_request(NO_CIRCUIT, req_id_2, TTL, 0, 0).to_bytes()?;
+    let request_3 = Message::channel_list_request(NO_CIRCUIT, req_id_3, TTL, 0, 0).to_bytes()?;
+
+    /* FIRST REQUEST */
+
+    stream.write_all(&request_1).await?;
+    expect_response(&mut messages, req_id_1).await?;
+    wait_cached(&cable, 1).await?;
+
+    /* DUPLICATE REQUEST */
+
+    // The duplicate is dropped; the next response answers the second request.
+    stream.write_all(&request_1).await?;
+    stream.write_all(&request_2).await?;
+    expect_response(&mut messages, req_id_2).await?;
+    wait_cached(&cable, 2).await?;
+
+    let metrics = cable.dedup_metrics().await;
+    assert_eq!(metrics.duplicates_dropped, 1);
+    assert_eq!(metrics.evicted, 0);
+
+    /* EVICTED REQUEST */
+
+    stream.write_all(&request_3).await?;
+    expect_response(&mut messages, req_id_3).await?;
+
+    // The first request ID has been evicted from the cache, allowing the
+    // request to be handled once more.
+    future::timeout(Duration::from_secs(5), async {
+        while cable.dedup_metrics().await.evicted != 1 {
+            task::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await?;
+
+    stream.write_all(&request_1).await?;
+    expect_response(&mut messages, req_id_1).await?;
+
+    let metrics = cable.dedup_metrics().await;
+    assert_eq!(metrics.duplicates_dropped, 1);
+    assert!(metrics.cached <= 2);
+
+    Ok(())
+}
@@ -0,0 +1,98 @@
+//! Test discovery of peers on the local network, followed by automatic
+//! connection and handshake.
+//!
+//! Run the test with debug logging enabled in a terminal:
+//!
+//! `RUST_LOG=debug cargo test discovery`
+//!
+//! An outline of the actions taken in this test:
+//!
+//! 1) Publish a text post to the "myco" channel using the first manager and
+//! open the "myco" channel using the second manager.
+//!
+//! 2) Run LAN discovery for both managers on the loopback interface, each with
+//! a TCP listener on an OS-assigned port.
+//!
+//! 3) Ensure that the second manager receives the text post, indicating that
+//! the peers discovered each other, connected and completed the handshake.
+//! Ensure that only a single connection was made between the peers.
+//!
+//! 4) Ensure that announcements of a different cabal are ignored.
+
+use std::{net::Ipv4Addr, time::Duration};
+
+use async_std::{future, net::TcpListener, stream::StreamExt, task};
+use cable::{ChannelOptions, Error};
+
+use cable_core::{discovery_key, CableManager, Discovery, LanConfig, LanDiscovery, MemoryStore};
+
+const CABAL_KEY: [u8; 32] = [3; 32];
+
+// Initialise the logger in test mode.
+//
+// Set `is_test()` to `false` if you wish to see logging output during the
+// test run.
+fn init() {
+    let _ = env_logger::builder().is_test(true).try_init();
+}
+
+// Create a LAN discovery configuration using the loopback interface and the
+// given UDP port.
+fn loopback_config(port: u16) -> LanConfig {
+    LanConfig {
+        port,
+        interface: Ipv4Addr::LOCALHOST,
+        interval: Duration::from_millis(100),
+        ..LanConfig::default()
+    }
+}
+
+#[async_std::test]
+async fn discovery() -> Result<(), Error> {
+    init();
+
+    // Select an available UDP port for announcements.
+    let port = std::net::UdpSocket::bind("127.0.0.1:0")?
+        .local_addr()?
+        .port();
+
+    let mut cable_a = CableManager::new(MemoryStore::default());
+    let cable_b = CableManager::new(MemoryStore::default());
+
+    let post_hash = cable_a.post_text("myco", "spore print").await?;
+
+    let opts = ChannelOptions::new("myco", 0, 0, 10);
+    let mut cable_b_clone = cable_b.clone();
+    let mut post_stream = cable_b_clone.open_channel(&opts).await?;
+
+    for cable in [cable_a.clone(), cable_b.clone()] {
+        let discovery = LanDiscovery::new(loopback_config(port))?;
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        task::spawn(async move {
+            let _ = cable.discover(&discovery, CABAL_KEY, listener).await;
+        });
+    }
+
+    // Wait for the post to be received.
+    let post = future::timeout(Duration::from_secs(5), post_stream.next())
+        .await?
+        .expect("post stream ended")?;
+    assert_eq!(post.hash()?, post_hash);
+
+    // Only one of the peers dialed the other.
+    assert_eq!(cable_a.get_peer_ids().await.len(), 1);
+    assert_eq!(cable_b.get_peer_ids().await.len(), 1);
+
+    // Announcements of a different cabal are not reported.
+    let discovery = LanDiscovery::new(loopback_config(port))?;
+    let discovered = discovery.join(discovery_key(&[4; 32])?, 1).await?;
+    assert!(
+        future::timeout(Duration::from_millis(500), discovered.recv())
+            .await
+            .is_err()
+    );
+    discovery.leave(&discovery_key(&[4; 32])?).await?;
+    assert!(discovered.recv().await.is_err());
+
+    Ok(())
+}
@@ -0,0 +1,206 @@
+//! Test the events emitted by the cable manager.
+//!
+//! Run the test with debug logging enabled in a terminal:
+//!
+//! `RUST_LOG=debug cargo test events`
+//!
+//! An outline of the actions taken in this test:
+//!
+//! 1) Publish join, topic and text posts to the "myco" channel using the
+//! remote manager.
+//!
+//! 2) Subscribe to the events of the local manager, open the "myco" channel
+//! using both managers and connect them via TCP.
+//!
+//! 3) Ensure the local manager emits events for the connected peer, the
+//! received request and hashes, the stored posts, the discovered channel, the
+//! channel topic and the channel member.
+//!
+//! 4) Publish a delete post for the remote text post using the local manager
+//! and ensure that no post deleted event is emitted, since the local peer is
+//! not the author. Publish and delete a local text post and ensure that a post
+//! deleted event is emitted.
+//!
+//! 5) Disconnect the remote peer and ensure that a peer disconnected event is
+//! emitted.
+
+use std::time::Duration;
+
+use async_std::{
+    channel::Receiver,
+    future,
+    net::{TcpListener, TcpStream},
+    stream::StreamExt,
+    task,
+};
+use cable::{ChannelOptions, Error};
+use log::info;
+
+use cable_core::{CableEvent, CableManager, MemoryStore};
+
+// Initialise the logger in test mode.
+//
+// Set `is_test()` to `false` if you wish to see logging output during the
+// test run.
+fn init() {
+    let _ = env_logger::builder().is_test(true).try_init();
+}
+
+// Wait for an event matching the given predicate, skipping all other events.
+async fn expect_event<F>(events: &Receiver<CableEvent>, predicate: F) -> Result<CableEvent, Error>
+where
+    F: Fn(&CableEvent) -> bool,
+{
+    let event = future::timeout(Duration::from_secs(5), async {
+        loop {
+            let event = events.recv().await.expect("event channel closed");
+            if predicate(&event) {
+                return event;
+            }
+        }
+    })
+    .await?;
+
+    Ok(event)
+}
+
+#[async_std::test]
+async fn events() -> Result<(), Error> {
+    init();
+
+    let mut cable = CableManager::new(MemoryStore::default());
+    let mut remote_cable = CableManager::new(MemoryStore::default());
+    let remote_public_key = remote_cable.get_public_key().await?;
+
+    // Publish posts using the remote manager.
+    remote_cable.post_join("myco").await?;
+    remote_cable.post_topic("myco", "mycelial networks").await?;
+    let text_hash = remote_cable.post_text("myco", "anastomosis").await?;
+
+    let events = cable.events().await;
+
+    // Open the channel using both managers.
+    let opts = ChannelOptions::new("myco", 0, 0, 10);
+    let mut cable_clone = cable.clone();
+    let _post_stream = cable_clone.open_channel(&opts).await?;
+    let mut remote_cable_clone = remote_cable.clone();
+    let _remote_post_stream = remote_cable_clone.open_channel(&opts).await?;
+
+    // Deploy a TCP listener for the remote manager and connect.
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    info!("Deployed TCP server on {}", addr);
+
+    let remote_cable_clone = remote_cable.clone();
+    task::spawn(async move {
+        let mut incoming = listener.incoming();
+        while let Some(Ok(stream)) = incoming.next().await {
+            let cable = remote_cable_clone.clone();
+            task::spawn(async move {
+                let _ = cable.listen(stream).await;
+            });
+        }
+    });
+
+    let stream = TcpStream::connect(addr).await?;
+    let cable_clone = cable.clone();
+    task::spawn(async move {
+        let _ = cable_clone.listen(stream).await;
+    });
+
+    let event = expect_event(&events, |event| {
+        matches!(event, CableEvent::PeerConnected { .. })
+    })
+    .await?;
+    let peer_id = match event {
+        CableEvent::PeerConnected {
+            peer_id,
+            public_key,
+        } => {
+            // No handshake was performed.
+            assert_eq!(public_key, None);
+            peer_id
+        }
+        _ => unreachable!(),
+    };
+
+    // Events describing the exchange of requests and posts may arrive in any
+    // order, since requests are handled concurrently.
+    let mut request_received = false;
+    let mut hashes_received = false;
+    let mut post_stored = false;
+    let mut channel_discovered = false;
+    let mut topic_changed = false;
+    let mut member_joined = false;
+    future::timeout(Duration::from_secs(5), async {
+        while !(request_received
+            && hashes_received
+            && post_stored
+            && channel_discovered
+            && topic_changed
+            && member_joined)
+        {
+            match events.recv().await.expect("event channel closed") {
+                CableEvent::RequestReceived { peer_id: id, .. } => {
+                    assert_eq!(id, peer_id);
+                    request_received = true;
+                }
+                CableEvent::HashesReceived { hashes, .. } => {
+                    hashes_received |= hashes.contains(&text_hash);
+                }
+                CableEvent::PostStored { hash, .. } => {
+                    post_stored |= hash == text_hash;
+                }
+                CableEvent::ChannelDiscovered { channel } => {
+                    assert_eq!(channel, "myco");
+                    channel_discovered = true;
+                }
+                CableEvent::TopicChanged {
+                    channel,
+                    topic,
+                    public_key,
+                } => {
+                    assert_eq!(channel, "myco");
+                    assert_eq!(topic, "mycelial networks");
+                    assert_eq!(public_key, remote_public_key);
+                    topic_changed = true;
+                }
+                CableEvent::MemberJoined {
+                    channel,
+                    public_key,
+                } => {
+                    assert_eq!(channel, "myco");
+                    assert_eq!(public_key, remote_public_key);
+                    member_joined = true;
+                }
+                _ => {}
+            }
+        }
+    })
+    .await?;
+
+    // Deleting a post of another author has no effect.
+    cable.post_delete(vec![text_hash]).await?;
+
+    // Delete a post of the local peer.
+    let local_text_hash = cable.post_text("myco", "hyphal fusion").await?;
+    cable.post_delete(vec![local_text_hash]).await?;
+    expect_event(&events, |event| match event {
+        CableEvent::PostDeleted { hash } => {
+            assert_ne!(*hash, text_hash, "unauthorised deletion was reported");
+            *hash == local_text_hash
+        }
+        _ => false,
+    })
+    .await?;
+
+    // Disconnect the remote peer.
+    cable.disconnect(peer_id).await;
+    expect_event(
+        &events,
+        |event| matches!(event, CableEvent::PeerDisconnected { peer_id: id } if *id == peer_id),
+    )
+    .await?;
+
+    Ok(())
+}
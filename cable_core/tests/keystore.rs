@@ -0,0 +1,106 @@
+//! Test encrypted keypair persistence, seed import / export and construction
+//! of a cable manager with an existing keypair.
+//!
+//! Run the test with debug logging enabled in a terminal:
+//!
+//! `RUST_LOG=debug cargo test keystore`
+//!
+//! An outline of the actions taken in this test:
+//!
+//! 1) Create a keystore in a temporary directory, generating and saving a new
+//! keypair. Ensure that the keystore file is only accessible by the owner.
+//!
+//! 2) Load the keypair using the correct passphrase and ensure it matches the
+//! generated keypair. Ensure that loading with an incorrect passphrase fails.
+//!
+//! 3) Export the keypair as a hex seed, import it again and ensure that the
+//! imported keypair matches.
+//!
+//! 4) Create a cable manager with the loaded keypair and ensure that the
+//! public key of the manager matches that of the keypair.
+//!
+//! 5) Create a cable manager backed by a `SledStore` with the loaded keypair
+//! and publish a post. Close the manager and the store, open the database
+//! and ensure that the secret key is not contained in any of its trees.
+
+use std::fs;
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+
+use cable::Error;
+
+use cable_core::{export_seed, import_seed, CableManager, Keystore, MemoryStore, SledStore};
+
+#[async_std::test]
+async fn keystore() -> Result<(), Error> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("identity").join("keystore");
+    let keystore = Keystore::new(&path);
+    assert!(!keystore.exists());
+
+    // Generate and save a new keypair.
+    let keypair = keystore.load_or_create("mycelium")?;
+    assert!(keystore.exists());
+
+    // The keystore file is only accessible by the owner and no temporary
+    // file remains.
+    #[cfg(unix)]
+    assert_eq!(fs::metadata(&path)?.permissions().mode() & 0o777, 0o600);
+    assert_eq!(fs::read_dir(path.parent().unwrap())?.count(), 1);
+
+    // Load the saved keypair.
+    assert_eq!(keystore.load("mycelium")?, keypair);
+    assert_eq!(keystore.load_or_create("mycelium")?, keypair);
+    assert!(keystore.load("hyphae").is_err());
+
+    // Export and reimport the keypair.
+    let seed = export_seed(&keypair);
+    assert_eq!(seed.len(), 64);
+    assert_eq!(import_seed(&seed)?, keypair);
+    assert!(import_seed("not a seed").is_err());
+    assert!(import_seed(&seed[..62]).is_err());
+
+    // Overwrite the keystore with a new passphrase.
+    keystore.save(&keypair, "rhizomorph")?;
+    assert_eq!(keystore.load("rhizomorph")?, keypair);
+
+    // Run a cable manager with the existing identity.
+    let store = MemoryStore::default();
+    let mut cable = CableManager::with_keypair(store, keypair).await;
+    assert_eq!(cable.get_public_key().await?, keypair.0);
+    assert_eq!(cable.get_secret_key().await?, keypair.1);
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn keystore_keypair_not_stored() -> Result<(), Error> {
+    let dir = tempfile::tempdir()?;
+    let keystore = Keystore::new(dir.path().join("keystore"));
+    let keypair = keystore.load_or_create("mycelium")?;
+    let (public_key, secret_key) = keypair;
+
+    // Run a cable manager backed by a persistent store with the identity.
+    let db_path = dir.path().join("db");
+    let mut cable = CableManager::with_keypair(SledStore::open(&db_path)?, keypair).await;
+    assert_eq!(cable.get_public_key().await?, public_key);
+    cable.post_text("myco", "hyphal fusion").await?;
+    cable.close().await?.close().await?;
+
+    // Neither the secret key nor the seed from which it is derived are
+    // written to any tree of the database.
+    let db = sled::open(&db_path)?;
+    let mut entries = 0;
+    for tree_name in db.tree_names() {
+        for entry in db.open_tree(tree_name)?.iter() {
+            let (key, value) = entry?;
+            for bytes in [&key[..], &value[..]] {
+                assert!(!bytes.windows(32).any(|window| window == &secret_key[..32]));
+            }
+            entries += 1;
+        }
+    }
+    assert!(entries > 0);
+
+    Ok(())
+}
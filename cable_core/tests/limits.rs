@@ -0,0 +1,344 @@
+//! Test the limits on the resources consumed by a remote peer.
+//!
+//! Run the test with debug logging enabled in a terminal:
+//!
+//! `RUST_LOG=debug cargo test limits`
+//!
+//! An outline of the actions taken in this test:
+//!
+//! 1) Create a cable manager which returns at most two hashes per response,
+//! keeps at most one live request per peer, handles a burst of two channel
+//! list requests and disconnects peers after three violations. Publish three
+//! posts to the "myco" channel.
+//!
+//! 2) Send a live channel time range request without a limit. Ensure that
+//! only two hashes are returned.
+//!
+//! 3) Send a second live channel time range request. Ensure that two hashes
+//! are returned, followed by a response with no hashes to conclude the
+//! request, since the live request limit has been reached.
+//!
+//! 4) Send three channel list requests. Ensure that only two are answered.
+//!
+//! 5) Send a fourth channel list request. Ensure that the peer is
+//! disconnected.
+//!
+//! 6) Connect to a second cable manager over an encrypted stream and send
+//! channel list requests until the rate limit is exceeded. Ensure that the
+//! peer receives an end-of-stream marker and that the listener returns
+//! while the stream is still open.
+//!
+//! 7) Connect to a third cable manager, which handles a single message at a
+//! time, and send channel list requests with a skip exceeding the limit and
+//! a limit exceeding the number of known channels. Ensure that each request
+//! is answered with the channels in the clamped range.
+
+use std::time::Duration;
+
+use async_std::{
+    future,
+    net::{TcpListener, TcpStream},
+    stream::StreamExt,
+    task,
+};
+use cable::{
+    constants::{CHANNEL_LIST_RESPONSE, HASH_RESPONSE, NO_CIRCUIT},
+    message::{MessageBody, ResponseBody},
+    ChannelOptions, Error, Message, ReqId,
+};
+use desert::{FromBytes, ToBytes};
+use futures::{AsyncWriteExt, Stream};
+use length_prefixed_stream::{decode_with_options, DecodeError, DecodeOptions};
+use log::info;
+
+use cable_handshake::{async_std::handshake, Version};
+
+use cable_core::{CableManager, LimitConfig, ManagerConfig, MemoryStore, RateLimit, Role, Store};
+
+const TTL: u8 = 0;
+const CABAL_KEY: [u8; 32] = [7; 32];
+
+// Initialise the logger in test mode.
+//
+// Set `is_test()` to `false` if you wish to see logging output during the
+// test run.
+fn init() {
+    let _ = env_logger::builder().is_test(true).try_init();
+}
+
+// Define the stream decoder parameters.
+fn decode_options() -> DecodeOptions {
+    DecodeOptions {
+        include_len: true,
+        ..Default::default()
+    }
+}
+
+// Read the next message from the given stream, returning `None` once the
+// stream has been closed.
+async fn read_message<T>(messages: &mut T) -> Result<Option<Message>, Error>
+where
+    T: Stream<Item = Result<Vec<u8>, DecodeError>> + Unpin,
+{
+    match future::timeout(Duration::from_secs(5), messages.next()).await? {
+        Some(buf) => {
+            let (_bytes_len, msg) = Message::from_bytes(&buf?)?;
+            Ok(Some(msg))
+        }
+        None => Ok(None),
+    }
+}
+
+// Read the next message and return the hashes of the hash response to the
+// request with the given ID.
+async fn read_hashes<T>(messages: &mut T, req_id: ReqId) -> Result<Vec<[u8; 32]>, Error>
+where
+    T: Stream<Item = Result<Vec<u8>, DecodeError>> + Unpin,
+{
+    let msg = read_message(messages).await?.expect("stream closed");
+    assert_eq!(msg.message_type(), HASH_RESPONSE);
+    assert_eq!(msg.header.req_id, req_id);
+
+    match msg.body {
+        MessageBody::Response {
+            body: ResponseBody::Hash { hashes },
+        } => Ok(hashes),
+        _ => unreachable!(),
+    }
+}
+
+#[async_std::test]
+async fn limits() -> Result<(), Error> {
+    init();
+
+    let config = ManagerConfig {
+        limits: LimitConfig {
+            channel_list: RateLimit::new(2, 0.1),
+            max_live_requests: 1,
+            max_hashes: 2,
+            max_violations: 3,
+            ..LimitConfig::default()
+        },
+        ..ManagerConfig::default()
+    };
+    let mut cable = CableManager::with_config(MemoryStore::default(), config);
+    let cable_clone = cable.clone();
+
+    cable.post_text("myco", "hyphal fusion").await?;
+    cable.post_text("myco", "anastomosis").await?;
+    cable.post_text("myco", "mycelial networks").await?;
+
+    // Deploy a TCP listener and pass inbound streams to the cable manager.
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    info!("Deployed TCP server on {}", addr);
+
+    task::spawn(async move {
+        let mut incoming = listener.incoming();
+        while let Some(Ok(stream)) = incoming.next().await {
+            let cable = cable_clone.clone();
+            task::spawn(async move {
+                let _ = cable.listen(stream).await;
+            });
+        }
+    });
+
+    let mut stream = TcpStream::connect(addr).await?;
+    let mut messages = decode_with_options(stream.clone(), decode_options());
+
+    /* MAXIMUM HASHES */
+
+    let (_req_id, req_id) = cable.new_req_id().await?;
+    let opts = ChannelOptions::new("myco", 0, 0, 0);
+    let request = Message::channel_time_range_request(NO_CIRCUIT, req_id, TTL, opts);
+    stream.write_all(&request.to_bytes()?).await?;
+
+    assert_eq!(read_hashes(&mut messages, req_id).await?.len(), 2);
+
+    /* MAXIMUM LIVE REQUESTS */
+
+    let (_req_id, req_id) = cable.new_req_id().await?;
+    let opts = ChannelOptions::new("myco", 0, 0, 0);
+    let request = Message::channel_time_range_request(NO_CIRCUIT, req_id, TTL, opts);
+    stream.write_all(&request.to_bytes()?).await?;
+
+    // The request is not kept alive; it is concluded once the known hashes
+    // have been sent.
+    assert_eq!(read_hashes(&mut messages, req_id).await?.len(), 2);
+    assert!(read_hashes(&mut messages, req_id).await?.is_empty());
+
+    /* RATE LIMIT */
+
+    let mut req_ids = Vec::new();
+    for _ in 0..3 {
+        let (_req_id, req_id) = cable.new_req_id().await?;
+        let request = Message::channel_list_request(NO_CIRCUIT, req_id, TTL, 0, 0);
+        stream.write_all(&request.to_bytes()?).await?;
+        req_ids.push(req_id);
+    }
+
+    // Only two requests are answered; the third exceeds the rate limit.
+    // Requests are handled concurrently and may therefore be answered in any
+    // order.
+    for _ in 0..2 {
+        let msg = read_message(&mut messages).await?.expect("stream closed");
+        assert_eq!(msg.message_type(), CHANNEL_LIST_RESPONSE);
+        assert!(req_ids.contains(&msg.header.req_id));
+    }
+
+    // The peer is disconnected once the rate limit is exceeded again.
+    let (_req_id, req_id) = cable.new_req_id().await?;
+    let request = Message::channel_list_request(NO_CIRCUIT, req_id, TTL, 0, 0);
+    stream.write_all(&request.to_bytes()?).await?;
+
+    assert!(read_message(&mut messages).await?.is_none());
+    assert!(cable.get_peer_ids().await.is_empty());
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn limits_encrypted() -> Result<(), Error> {
+    init();
+
+    let config = ManagerConfig {
+        limits: LimitConfig {
+            channel_list: RateLimit::new(2, 0.1),
+            max_violations: 1,
+            ..LimitConfig::default()
+        },
+        ..ManagerConfig::default()
+    };
+    let cable = CableManager::with_config(MemoryStore::default(), config);
+
+    // Deploy a TCP listener and pass the first inbound stream to the cable
+    // manager, retaining the handle of the listener task.
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    info!("Deployed TCP server on {}", addr);
+
+    let cable_clone = cable.clone();
+    let listener_handle = task::spawn(async move {
+        let (stream, _addr) = listener.accept().await?;
+        cable_clone
+            .listen_secure(stream, CABAL_KEY, Role::Responder)
+            .await
+    });
+
+    // Perform the handshake as a raw peer.
+    let (_public_key, private_key) = MemoryStore::default().get_keypair().await.expect("keypair");
+    let mut stream = TcpStream::connect(addr).await?;
+    let mut peer =
+        handshake::client(&mut stream, Version::init(1, 0), CABAL_KEY, private_key).await?;
+
+    future::timeout(Duration::from_secs(5), async {
+        while cable.get_peer_ids().await.is_empty() {
+            task::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await?;
+
+    // The third request exceeds the rate limit.
+    for _ in 0..3 {
+        let (_req_id, req_id) = cable.new_req_id().await?;
+        let request = Message::channel_list_request(NO_CIRCUIT, req_id, TTL, 0, 0);
+        peer.write_message_to_async_stream(&mut stream, &request.to_bytes()?)
+            .await?;
+    }
+
+    // Read messages until the end-of-stream marker is received.
+    future::timeout(Duration::from_secs(5), async {
+        loop {
+            let msg = peer.read_message_from_async_stream(&mut stream).await?;
+            if msg.is_empty() {
+                return Result::<(), Error>::Ok(());
+            }
+        }
+    })
+    .await??;
+
+    // The listener stops reading from the stream even though the peer has
+    // not closed it.
+    future::timeout(Duration::from_secs(5), listener_handle).await??;
+    assert!(cable.get_peer_ids().await.is_empty());
+
+    Ok(())
+}
+
+// Send a channel list request with the given skip and limit and return the
+// channels of the response.
+async fn list_channels<T>(
+    cable: &CableManager<MemoryStore>,
+    stream: &mut TcpStream,
+    messages: &mut T,
+    skip: u64,
+    limit: u64,
+) -> Result<Vec<String>, Error>
+where
+    T: Stream<Item = Result<Vec<u8>, DecodeError>> + Unpin,
+{
+    let (_req_id, req_id) = cable.new_req_id().await?;
+    let request = Message::channel_list_request(NO_CIRCUIT, req_id, TTL, skip, limit);
+    stream.write_all(&request.to_bytes()?).await?;
+
+    let msg = read_message(messages).await?.expect("stream closed");
+    assert_eq!(msg.message_type(), CHANNEL_LIST_RESPONSE);
+    assert_eq!(msg.header.req_id, req_id);
+
+    match msg.body {
+        MessageBody::Response {
+            body: ResponseBody::ChannelList { channels },
+        } => Ok(channels),
+        _ => unreachable!(),
+    }
+}
+
+#[async_std::test]
+async fn limits_channel_list_range() -> Result<(), Error> {
+    init();
+
+    // Handle a single message at a time, such that the connection stalls if
+    // a handler slot is not freed.
+    let config = ManagerConfig {
+        limits: LimitConfig {
+            max_concurrent_messages: 1,
+            ..LimitConfig::default()
+        },
+        ..ManagerConfig::default()
+    };
+    let mut cable = CableManager::with_config(MemoryStore::default(), config);
+    let cable_clone = cable.clone();
+
+    cable.post_join("myco").await?;
+    cable.post_join("fungi").await?;
+
+    // Deploy a TCP listener and pass the inbound stream to the cable manager.
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+
+    task::spawn(async move {
+        if let Some(Ok(stream)) = listener.incoming().next().await {
+            let _ = cable_clone.listen(stream).await;
+        }
+    });
+
+    let mut stream = TcpStream::connect(addr).await?;
+    let mut messages = decode_with_options(stream.clone(), decode_options());
+
+    // A skip exceeding both the limit and the number of known channels.
+    let channels = list_channels(&cable, &mut stream, &mut messages, 5, 1).await?;
+    assert!(channels.is_empty());
+
+    // A skip exceeding the limit.
+    let channels = list_channels(&cable, &mut stream, &mut messages, 2, 1).await?;
+    assert!(channels.is_empty());
+
+    // A limit exceeding the number of known channels.
+    let channels = list_channels(&cable, &mut stream, &mut messages, 1, 10).await?;
+    assert_eq!(channels.len(), 1);
+
+    let channels = list_channels(&cable, &mut stream, &mut messages, 0, 0).await?;
+    assert_eq!(channels.len(), 2);
+
+    Ok(())
+}
@@ -0,0 +1,183 @@
+//! Test the local moderation of authors: hiding and blocking public keys.
+//!
+//! Run the test with debug logging enabled in a terminal:
+//!
+//! `RUST_LOG=debug cargo test moderation`
+//!
+//! An outline of the actions taken in this test:
+//!
+//! 1) Publish join and text posts to the "myco" channel using the remote
+//! manager. Hide the remote author using the local manager, open the "myco"
+//! channel using both managers and connect them via TCP.
+//!
+//! 2) Ensure that the posts of the remote author are stored but not emitted on
+//! the post stream of the local manager.
+//!
+//! 3) Block the remote author. Ensure that the posts of the remote author have
+//! been purged from the store and are no longer wanted.
+//!
+//! 4) Publish a second text post using the remote manager. Ensure that it is
+//! not stored by the local manager and is no longer wanted.
+//!
+//! 5) Unblock the remote author. Ensure that the purged posts are wanted
+//! once more.
+//!
+//! 6) Block an author using a manager backed by a `SledStore`, reopen the
+//! store and ensure that the author is still blocked.
+
+use std::time::Duration;
+
+use async_std::{
+    future,
+    net::{TcpListener, TcpStream},
+    stream::StreamExt,
+    task,
+};
+use cable::{ChannelOptions, Error};
+use log::info;
+
+use cable_core::{CableManager, MemoryStore, Moderation, SledStore, Store};
+
+// Initialise the logger in test mode.
+//
+// Set `is_test()` to `false` if you wish to see logging output during the
+// test run.
+fn init() {
+    let _ = env_logger::builder().is_test(true).try_init();
+}
+
+#[async_std::test]
+async fn moderation() -> Result<(), Error> {
+    init();
+
+    let mut cable = CableManager::new(MemoryStore::default());
+    let mut remote_cable = CableManager::new(MemoryStore::default());
+    let remote_public_key = remote_cable.get_public_key().await?;
+
+    // Publish posts using the remote manager.
+    let join_hash = remote_cable.post_join("myco").await?;
+    let text_hash = remote_cable.post_text("myco", "hyphal fusion").await?;
+
+    // Hide the remote author.
+    cable.hide(&remote_public_key).await;
+    assert_eq!(
+        cable.get_moderation(&remote_public_key).await,
+        Some(Moderation::Hide)
+    );
+
+    // Open the channel using both managers.
+    let opts = ChannelOptions::new("myco", 0, 0, 10);
+    let mut cable_clone = cable.clone();
+    let mut post_stream = cable_clone.open_channel(&opts).await?;
+    let mut remote_cable_clone = remote_cable.clone();
+    let _remote_post_stream = remote_cable_clone.open_channel(&opts).await?;
+
+    // Deploy a TCP listener for the remote manager and connect.
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    info!("Deployed TCP server on {}", addr);
+
+    let remote_cable_clone = remote_cable.clone();
+    task::spawn(async move {
+        let mut incoming = listener.incoming();
+        while let Some(Ok(stream)) = incoming.next().await {
+            let cable = remote_cable_clone.clone();
+            task::spawn(async move {
+                let _ = cable.listen(stream).await;
+            });
+        }
+    });
+
+    let stream = TcpStream::connect(addr).await?;
+    let cable_clone = cable.clone();
+    task::spawn(async move {
+        let _ = cable_clone.listen(stream).await;
+    });
+
+    /* HIDE */
+
+    // The posts of the hidden author are stored.
+    future::timeout(Duration::from_secs(5), async {
+        while !cable.store.want(&[join_hash, text_hash]).await.is_empty() {
+            task::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await?;
+
+    // Only the posts of the local author are emitted.
+    let local_hash = cable.post_text("myco", "anastomosis").await?;
+    future::timeout(Duration::from_secs(5), async {
+        loop {
+            let post = post_stream.next().await.expect("stream closed")?;
+            assert_ne!(post.get_public_key(), remote_public_key);
+            if post.hash()? == local_hash {
+                break;
+            }
+        }
+
+        Result::<(), Error>::Ok(())
+    })
+    .await??;
+
+    /* BLOCK */
+
+    cable.block(&remote_public_key).await;
+    assert_eq!(
+        cable.get_moderation(&remote_public_key).await,
+        Some(Moderation::Block)
+    );
+
+    // The posts of the blocked author have been purged and are not wanted.
+    assert!(cable.store.get_post_payload(&join_hash).await.is_none());
+    assert!(cable.store.get_post_payload(&text_hash).await.is_none());
+    assert!(cable.store.want(&[join_hash, text_hash]).await.is_empty());
+    assert!(
+        !cable
+            .store
+            .is_channel_member(&"myco".to_string(), &remote_public_key)
+            .await
+    );
+
+    // New posts by the blocked author are not stored and are not wanted once
+    // they have been received.
+    let text_hash_2 = remote_cable.post_text("myco", "mycelial networks").await?;
+    future::timeout(Duration::from_secs(5), async {
+        while !cable.store.want(&[text_hash_2]).await.is_empty() {
+            task::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await?;
+    assert!(cable.store.get_post_payload(&text_hash_2).await.is_none());
+
+    /* UNBLOCK */
+
+    cable.unblock(&remote_public_key).await;
+    assert_eq!(cable.get_moderation(&remote_public_key).await, None);
+    assert_eq!(
+        cable.store.want(&[join_hash, text_hash, text_hash_2]).await,
+        vec![join_hash, text_hash, text_hash_2]
+    );
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn moderation_persistence() -> Result<(), Error> {
+    init();
+
+    let dir = tempfile::tempdir()?;
+    let public_key = [7; 32];
+
+    let mut cable = CableManager::new(SledStore::open(dir.path())?);
+    cable.block(&public_key).await;
+    cable.close().await?.close().await?;
+
+    // Reopen the store from the same directory.
+    let store = SledStore::open(dir.path())?;
+    assert_eq!(
+        store.get_moderated_keys().await,
+        vec![(public_key, Moderation::Block)]
+    );
+
+    Ok(())
+}
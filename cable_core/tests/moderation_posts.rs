@@ -0,0 +1,302 @@
+//! Test the publication, propagation and honouring of moderation posts.
+//!
+//! Run the test with debug logging enabled in a terminal:
+//!
+//! `RUST_LOG=debug cargo test moderation_posts`
+//!
+//! An outline of the actions taken in this test:
+//!
+//! 1) Publish join and text posts to the "myco" channel using the remote
+//! manager. Open the "myco" channel using both managers and connect them via
+//! TCP.
+//!
+//! 2) Drop the first text post using the local manager. Ensure that the
+//! moderation post is received and stored by the remote manager.
+//!
+//! 3) Ensure that the dropped post is not emitted on the post stream of the
+//! local manager.
+//!
+//! 4) Connect to the local manager as a raw TCP peer. Ensure that the hash of
+//! the dropped post is omitted from channel time range responses and that the
+//! dropped post is omitted from post responses.
+//!
+//! 5) Derive a moderation state from a set of moderation posts. Ensure that
+//! only the posts of authors holding the required roles are honoured.
+
+use std::time::Duration;
+
+use async_std::{
+    future,
+    net::{TcpListener, TcpStream},
+    stream::StreamExt,
+    task,
+};
+use cable::{
+    constants::{
+        DROP_POST, HASH_RESPONSE, HIDE_USER, MODERATOR_ROLE, NO_CIRCUIT, POST_RESPONSE,
+        UNHIDE_USER, USER_ROLE,
+    },
+    message::{MessageBody, ResponseBody},
+    ChannelOptions, Error, Message, Post, ReqId,
+};
+use desert::{FromBytes, ToBytes};
+use futures::{AsyncWriteExt, Stream};
+use length_prefixed_stream::{decode_with_options, DecodeError, DecodeOptions};
+use log::info;
+
+use cable_core::{CableManager, MemoryStore, ModerationState, Store};
+
+const TTL: u8 = 0;
+
+// Initialise the logger in test mode.
+//
+// Set `is_test()` to `false` if you wish to see logging output during the
+// test run.
+fn init() {
+    let _ = env_logger::builder().is_test(true).try_init();
+}
+
+// Define the stream decoder parameters.
+fn decode_options() -> DecodeOptions {
+    DecodeOptions {
+        include_len: true,
+        ..Default::default()
+    }
+}
+
+// Read messages from the given stream until a response to the request with
+// the given ID is received, skipping any requests sent by the manager.
+async fn read_response<T>(messages: &mut T, req_id: ReqId) -> Result<Message, Error>
+where
+    T: Stream<Item = Result<Vec<u8>, DecodeError>> + Unpin,
+{
+    loop {
+        let buf = future::timeout(Duration::from_secs(5), messages.next())
+            .await?
+            .expect("stream closed")?;
+        let (_bytes_len, msg) = Message::from_bytes(&buf)?;
+
+        if msg.header.req_id == req_id {
+            return Ok(msg);
+        }
+    }
+}
+
+#[async_std::test]
+async fn moderation_posts() -> Result<(), Error> {
+    init();
+
+    let mut cable = CableManager::new(MemoryStore::default());
+    let mut remote_cable = CableManager::new(MemoryStore::default());
+
+    // Publish posts using the remote manager.
+    remote_cable.post_join("myco").await?;
+    let text_hash_1 = remote_cable.post_text("myco", "hyphal fusion").await?;
+    let text_hash_2 = remote_cable.post_text("myco", "anastomosis").await?;
+
+    // Open the channel using both managers.
+    let opts = ChannelOptions::new("myco", 0, 0, 10);
+    let mut cable_clone = cable.clone();
+    let mut post_stream = cable_clone.open_channel(&opts).await?;
+    let mut remote_cable_clone = remote_cable.clone();
+    let _remote_post_stream = remote_cable_clone.open_channel(&opts).await?;
+
+    // Deploy a TCP listener for the local manager and connect.
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    info!("Deployed TCP server on {}", addr);
+
+    let cable_clone = cable.clone();
+    task::spawn(async move {
+        let mut incoming = listener.incoming();
+        while let Some(Ok(stream)) = incoming.next().await {
+            let cable = cable_clone.clone();
+            task::spawn(async move {
+                let _ = cable.listen(stream).await;
+            });
+        }
+    });
+
+    let stream = TcpStream::connect(addr).await?;
+    let remote_cable_clone = remote_cable.clone();
+    task::spawn(async move {
+        let _ = remote_cable_clone.listen(stream).await;
+    });
+
+    // Wait for the posts of the remote author to be stored.
+    future::timeout(Duration::from_secs(5), async {
+        while cable.store.get_post_payload(&text_hash_2).await.is_none() {
+            task::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await?;
+
+    /* DROP */
+
+    let drop_hash = cable
+        .post_moderation("myco", vec![text_hash_1], DROP_POST, "spam")
+        .await?;
+    assert!(cable.moderation_state().await.is_post_dropped(&text_hash_1));
+
+    // The moderation post is sent to the remote manager in response to the
+    // live channel state request.
+    future::timeout(Duration::from_secs(5), async {
+        while remote_cable
+            .store
+            .get_post_payload(&drop_hash)
+            .await
+            .is_none()
+        {
+            task::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await?;
+    assert_eq!(
+        remote_cable
+            .store
+            .get_moderation_hashes(&"myco".to_string())
+            .await,
+        Some(vec![drop_hash])
+    );
+
+    /* SHOW */
+
+    // The dropped post is not emitted.
+    let local_hash = cable.post_text("myco", "mycelial networks").await?;
+    future::timeout(Duration::from_secs(5), async {
+        loop {
+            let post = post_stream.next().await.expect("stream closed")?;
+            let hash = post.hash()?;
+            assert_ne!(hash, text_hash_1);
+            if hash == local_hash {
+                break;
+            }
+        }
+
+        Result::<(), Error>::Ok(())
+    })
+    .await??;
+
+    /* SERVE */
+
+    let mut stream = TcpStream::connect(addr).await?;
+    let mut messages = decode_with_options(stream.clone(), decode_options());
+
+    // The hash of the dropped post is omitted from the hash response.
+    let (_req_id, req_id) = cable.new_req_id().await?;
+    let opts = ChannelOptions::new("myco", 0, u64::MAX, 0);
+    let request = Message::channel_time_range_request(NO_CIRCUIT, req_id, TTL, opts);
+    stream.write_all(&request.to_bytes()?).await?;
+
+    let msg = read_response(&mut messages, req_id).await?;
+    assert_eq!(msg.message_type(), HASH_RESPONSE);
+    if let MessageBody::Response {
+        body: ResponseBody::Hash { hashes },
+    } = msg.body
+    {
+        assert!(!hashes.contains(&text_hash_1));
+        assert!(hashes.contains(&text_hash_2));
+    }
+
+    // The dropped post is omitted from the post response.
+    let (_req_id, req_id) = cable.new_req_id().await?;
+    let request = Message::post_request(NO_CIRCUIT, req_id, TTL, vec![text_hash_1, text_hash_2]);
+    stream.write_all(&request.to_bytes()?).await?;
+
+    let msg = read_response(&mut messages, req_id).await?;
+    assert_eq!(msg.message_type(), POST_RESPONSE);
+    if let MessageBody::Response {
+        body: ResponseBody::Post { posts },
+    } = msg.body
+    {
+        assert_eq!(posts.len(), 1);
+        let (_s, post) = Post::from_bytes(&posts[0])?;
+        assert_eq!(post.hash()?, text_hash_2);
+    }
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn moderation_authority() -> Result<(), Error> {
+    init();
+
+    let admin = [1; 32];
+    let moderator = [2; 32];
+    let user = [3; 32];
+    let myco = "myco".to_string();
+    let other = "other".to_string();
+
+    let posts = vec![
+        // Not honoured; the author does not hold a role.
+        Post::moderation(
+            moderator,
+            Vec::new(),
+            1,
+            myco.clone(),
+            vec![user],
+            HIDE_USER,
+            String::new(),
+        ),
+        // Assign the moderator role for the "myco" channel.
+        Post::role(
+            admin,
+            Vec::new(),
+            2,
+            myco.clone(),
+            moderator,
+            MODERATOR_ROLE,
+            String::new(),
+        ),
+        Post::moderation(
+            moderator,
+            Vec::new(),
+            3,
+            myco.clone(),
+            vec![user],
+            HIDE_USER,
+            String::new(),
+        ),
+        // Not honoured; blocks require a role for the whole cabal.
+        Post::block(
+            moderator,
+            Vec::new(),
+            4,
+            vec![user],
+            true,
+            false,
+            String::new(),
+        ),
+        // Revoke the moderator role.
+        Post::role(
+            admin,
+            Vec::new(),
+            5,
+            myco.clone(),
+            moderator,
+            USER_ROLE,
+            String::new(),
+        ),
+        // Not honoured; the role has been revoked.
+        Post::moderation(
+            moderator,
+            Vec::new(),
+            6,
+            myco.clone(),
+            vec![user],
+            UNHIDE_USER,
+            String::new(),
+        ),
+    ];
+
+    // Posts are applied in order of timestamp, regardless of the order given.
+    let state = ModerationState::new(Some(admin), posts.into_iter().rev().collect());
+
+    assert!(state.is_admin(&myco, &admin));
+    assert!(!state.is_moderator(&myco, &moderator));
+    assert!(state.is_user_hidden(&myco, &user));
+    assert!(!state.is_user_hidden(&other, &user));
+    assert!(!state.is_user_blocked(&user));
+
+    Ok(())
+}
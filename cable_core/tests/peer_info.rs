@@ -0,0 +1,144 @@
+//! Test the publication and indexing of `post/info` posts with arbitrary keys.
+//!
+//! Run the test with debug logging enabled in a terminal:
+//!
+//! `RUST_LOG=debug cargo test peer_info`
+//!
+//! An outline of the actions taken in this test:
+//!
+//! 1) Publish an info post with several key-value pairs. Ensure that the full
+//! profile is returned, sorted by key.
+//!
+//! 2) Publish a second info post updating a single key. Ensure that the
+//! updated value is returned and that all other values are retained.
+//!
+//! 3) Delete the second info post. Ensure that the previous value is returned.
+//!
+//! 4) Attempt to publish an info post with an invalid `accept-role` value.
+//! Ensure that it is rejected.
+//!
+//! 5) Publish an info post using a manager backed by a `SledStore`, reopen
+//! the store and ensure that the profile is retained.
+
+use std::time::Duration;
+
+use async_std::task;
+use cable::{Error, UserInfo};
+
+use cable_core::{CableManager, MemoryStore, SledStore, Store};
+
+// Initialise the logger in test mode.
+//
+// Set `is_test()` to `false` if you wish to see logging output during the
+// test run.
+fn init() {
+    let _ = env_logger::builder().is_test(true).try_init();
+}
+
+#[async_std::test]
+async fn peer_info() -> Result<(), Error> {
+    init();
+
+    let mut cable = CableManager::new(MemoryStore::default());
+    let public_key = cable.get_public_key().await?;
+
+    /* PUBLISH */
+
+    cable
+        .post_info(vec![
+            UserInfo::name("glyph")?,
+            UserInfo::new("bio", "mycophile"),
+            UserInfo::accept_role(true),
+        ])
+        .await?;
+
+    assert_eq!(
+        cable.get_peer_info(&public_key).await,
+        vec![
+            UserInfo::new("accept-role", "1"),
+            UserInfo::new("bio", "mycophile"),
+            UserInfo::new("name", "glyph"),
+        ]
+    );
+
+    /* UPDATE */
+
+    // Ensure the second post has a later timestamp.
+    task::sleep(Duration::from_millis(5)).await;
+    let update_hash = cable.post_info(vec![UserInfo::name("sporocarp")?]).await?;
+
+    assert_eq!(
+        cable.get_peer_info(&public_key).await,
+        vec![
+            UserInfo::new("accept-role", "1"),
+            UserInfo::new("bio", "mycophile"),
+            UserInfo::new("name", "sporocarp"),
+        ]
+    );
+    assert_eq!(
+        cable
+            .store
+            .get_peer_name_and_hash(&public_key)
+            .await
+            .map(|(name, _hash)| name),
+        Some("sporocarp".to_string())
+    );
+
+    /* DELETE */
+
+    cable.post_delete(vec![update_hash]).await?;
+
+    assert_eq!(
+        cable.get_peer_info(&public_key).await,
+        vec![
+            UserInfo::new("accept-role", "1"),
+            UserInfo::new("bio", "mycophile"),
+            UserInfo::new("name", "glyph"),
+        ]
+    );
+
+    /* VALIDATE */
+
+    assert!(cable
+        .post_info(vec![UserInfo::new("accept-role", "yes")])
+        .await
+        .is_err());
+    assert!(cable
+        .post_info(vec![UserInfo::new("", "spore")])
+        .await
+        .is_err());
+
+    // An unknown author has an empty profile.
+    assert!(cable.get_peer_info(&[0; 32]).await.is_empty());
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn peer_info_persistence() -> Result<(), Error> {
+    init();
+
+    let dir = tempfile::tempdir()?;
+
+    let mut cable = CableManager::new(SledStore::open(dir.path())?);
+    let public_key = cable.get_public_key().await?;
+    cable
+        .post_info(vec![
+            UserInfo::name("glyph")?,
+            UserInfo::new("bio", "mycophile"),
+        ])
+        .await?;
+    cable.close().await?.close().await?;
+
+    // Reopen the store from the same directory.
+    let store = SledStore::open(dir.path())?;
+    assert_eq!(
+        store.get_peer_info(&public_key).await,
+        Some(vec![
+            UserInfo::new("bio", "mycophile"),
+            UserInfo::new("name", "glyph"),
+        ])
+    );
+
+    Ok(())
+}
@@ -0,0 +1,127 @@
+//! Test multi-hop request forwarding and response routing between three cable
+//! managers connected in a chain (A <-> B <-> C).
+//!
+//! Run the test with debug logging enabled in a terminal:
+//!
+//! `RUST_LOG=debug cargo test request_forwarding`
+//!
+//! An outline of the actions taken in this test:
+//!
+//! 1) Publish a text post to the "myco" channel using manager C.
+//!
+//! 2) Connect manager B to manager C.
+//!
+//! 3) Open the "myco" channel using manager A and connect A to B.
+//!
+//! 4) Ensure that A receives the post published by C; the channel time range
+//! request is forwarded by B to C and the responses are relayed back to A.
+//!
+//! 5) Close the "myco" channel using manager A and publish a second post
+//! using manager C.
+//!
+//! 6) Ensure that A does not receive the second post; the cancel request is
+//! forwarded by B to C.
+
+use std::time::Duration;
+
+use async_std::{
+    future,
+    net::{TcpListener, TcpStream},
+    stream::StreamExt,
+    task,
+};
+use cable::{ChannelOptions, Error};
+use log::info;
+
+use cable_core::{CableManager, MemoryStore, Store};
+
+// Initialise the logger in test mode.
+//
+// Set `is_test()` to `false` if you wish to see logging output during the
+// test run.
+fn init() {
+    let _ = env_logger::builder().is_test(true).try_init();
+}
+
+// Deploy a TCP listener for the given manager and return the address.
+async fn serve(cable: CableManager<MemoryStore>) -> Result<std::net::SocketAddr, Error> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    info!("Deployed TCP server on {}", addr);
+
+    task::spawn(async move {
+        let mut incoming = listener.incoming();
+        while let Some(Ok(stream)) = incoming.next().await {
+            let cable = cable.clone();
+            task::spawn(async move {
+                let _ = cable.listen(stream).await;
+            });
+        }
+    });
+
+    Ok(addr)
+}
+
+// Connect the given manager to the peer listening on the given address.
+async fn connect(
+    cable: CableManager<MemoryStore>,
+    addr: std::net::SocketAddr,
+) -> Result<(), Error> {
+    let stream = TcpStream::connect(addr).await?;
+    task::spawn(async move {
+        let _ = cable.listen(stream).await;
+    });
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn request_forwarding() -> Result<(), Error> {
+    init();
+
+    let cable_a = CableManager::new(MemoryStore::default());
+    let cable_b = CableManager::new(MemoryStore::default());
+    let mut cable_c = CableManager::new(MemoryStore::default());
+
+    // Publish a post using manager C.
+    let post_hash = cable_c.post_text("myco", "mycelial relay").await?;
+
+    // Connect B to C and wait for the connection to be registered.
+    let addr_c = serve(cable_c.clone()).await?;
+    connect(cable_b.clone(), addr_c).await?;
+    future::timeout(Duration::from_secs(5), async {
+        while cable_c.get_peer_ids().await.is_empty() {
+            task::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await?;
+
+    // Open the channel using A and connect A to B.
+    let opts = ChannelOptions::new("myco", 0, 0, 10);
+    let mut cable_a_clone = cable_a.clone();
+    let mut post_stream = cable_a_clone.open_channel(&opts).await?;
+
+    let addr_b = serve(cable_b.clone()).await?;
+    connect(cable_a.clone(), addr_b).await?;
+
+    // Wait for the post published by C to be received by A.
+    let post = future::timeout(Duration::from_secs(5), post_stream.next())
+        .await?
+        .expect("post stream ended")?;
+    assert_eq!(post.hash()?, post_hash);
+    drop(post_stream);
+
+    // Close the channel; the cancel request is forwarded from B to C.
+    cable_a.close_channel(&"myco".to_string()).await?;
+    task::sleep(Duration::from_millis(500)).await;
+
+    // Publish a second post using manager C.
+    let second_post_hash = cable_c.post_text("myco", "severed hyphae").await?;
+    task::sleep(Duration::from_millis(500)).await;
+
+    // Ensure the second post was not received by A.
+    let payloads = cable_a.store.get_post_payloads(&[second_post_hash]).await;
+    assert!(payloads.is_empty());
+
+    Ok(())
+}
@@ -0,0 +1,233 @@
+//! Test the lifecycle of requests: completion of non-live requests once all
+//! peers have responded with a hash response containing no hashes, the
+//! cancellation of live requests and the conclusion of live requests when the
+//! responding peer shuts down.
+//!
+//! Run the test with debug logging enabled in a terminal:
+//!
+//! `RUST_LOG=debug cargo test request_lifecycle`
+//!
+//! An outline of the actions taken in this test:
+//!
+//! 1) Publish two posts to the "myco" channel using the remote manager and
+//! connect the local and remote managers via TCP.
+//!
+//! 2) Send a non-live channel time range request from the local manager and
+//! await completion of the request. Ensure that both posts have been stored.
+//!
+//! 3) Send a live channel time range request and ensure that it is not
+//! completed. Cancel the request and ensure that it is completed.
+//!
+//! 4) Send a second live channel time range request and shut down the remote
+//! manager. Ensure that the request is completed and that the peer is
+//! disconnected.
+//!
+//! 5) Send a non-live channel time range request from a manager without
+//! peers and ensure that awaiting the request resolves without the request
+//! having been concluded.
+//!
+//! 6) Connect a silent peer, represented by a raw TCP stream, and send a
+//! request. Disconnect the silent peer and ensure that awaiting the request
+//! resolves without the request having been concluded.
+
+use std::time::Duration;
+
+use async_std::{
+    future,
+    net::{TcpListener, TcpStream},
+    stream::StreamExt,
+    task,
+};
+use cable::{constants::NO_CIRCUIT, ChannelOptions, Error, Message};
+use log::info;
+
+use cable_core::{CableEvent, CableManager, MemoryStore, RequestOutcome, Store};
+
+const TTL: u8 = 0;
+
+// Initialise the logger in test mode.
+//
+// Set `is_test()` to `false` if you wish to see logging output during the
+// test run.
+fn init() {
+    let _ = env_logger::builder().is_test(true).try_init();
+}
+
+#[async_std::test]
+async fn request_lifecycle() -> Result<(), Error> {
+    init();
+
+    let cable = CableManager::new(MemoryStore::default());
+    let mut remote_cable = CableManager::new(MemoryStore::default());
+
+    // Publish posts using the remote manager.
+    let post_hash_1 = remote_cable.post_text("myco", "hyphal fusion").await?;
+    let post_hash_2 = remote_cable.post_text("myco", "anastomosis").await?;
+
+    // Deploy a TCP listener for the remote manager and connect.
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    info!("Deployed TCP server on {}", addr);
+
+    let remote_cable_clone = remote_cable.clone();
+    task::spawn(async move {
+        let mut incoming = listener.incoming();
+        while let Some(Ok(stream)) = incoming.next().await {
+            let cable = remote_cable_clone.clone();
+            task::spawn(async move {
+                let _ = cable.listen(stream).await;
+            });
+        }
+    });
+
+    let stream = TcpStream::connect(addr).await?;
+    let cable_clone = cable.clone();
+    task::spawn(async move {
+        let _ = cable_clone.listen(stream).await;
+    });
+
+    // Wait for both managers to register the connection.
+    future::timeout(Duration::from_secs(5), async {
+        while cable.get_peer_ids().await.is_empty() || remote_cable.get_peer_ids().await.is_empty()
+        {
+            task::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await?;
+
+    /* NON-LIVE REQUEST */
+
+    let (_req_id, req_id_bytes) = cable.new_req_id().await?;
+    let opts = ChannelOptions::new("myco", 0, u64::MAX, 10);
+    let request = Message::channel_time_range_request(NO_CIRCUIT, req_id_bytes, TTL, opts);
+    let req_id = cable.send_request(request).await?;
+
+    // The request is completed once the remote peer has concluded it.
+    let outcome = future::timeout(Duration::from_secs(5), cable.await_request(&req_id)).await?;
+    assert_eq!(outcome, RequestOutcome::Concluded);
+
+    // Wait for the requested posts to be received and stored.
+    future::timeout(Duration::from_secs(5), async {
+        loop {
+            let posts = cable
+                .store
+                .get_post_payloads(&[post_hash_1, post_hash_2])
+                .await;
+            if posts.len() == 2 {
+                break;
+            }
+            task::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await?;
+
+    /* CANCELLED LIVE REQUEST */
+
+    let (_req_id, req_id_bytes) = cable.new_req_id().await?;
+    let opts = ChannelOptions::new("myco", 0, 0, 10);
+    let request = Message::channel_time_range_request(NO_CIRCUIT, req_id_bytes, TTL, opts);
+    let req_id = cable.send_request(request).await?;
+
+    // A live request is not concluded by the responding peer.
+    assert!(
+        future::timeout(Duration::from_millis(200), cable.await_request(&req_id))
+            .await
+            .is_err()
+    );
+
+    cable.cancel_request(&req_id).await?;
+    let outcome = future::timeout(Duration::from_secs(5), cable.await_request(&req_id)).await?;
+    assert_eq!(outcome, RequestOutcome::Concluded);
+
+    /* LIVE REQUEST CONCLUDED ON SHUTDOWN */
+
+    let events = cable.events().await;
+
+    let (_req_id, req_id_bytes) = cable.new_req_id().await?;
+    let opts = ChannelOptions::new("myco", 0, 0, 10);
+    let request = Message::channel_time_range_request(NO_CIRCUIT, req_id_bytes, TTL, opts);
+    let req_id = cable.send_request(request).await?;
+
+    // Wait for the remote manager to respond before shutting down.
+    future::timeout(Duration::from_secs(5), async {
+        loop {
+            let event = events.recv().await.expect("event channel closed");
+            if matches!(event, CableEvent::HashesReceived { req_id: id, .. } if id == req_id) {
+                break;
+            }
+        }
+    })
+    .await?;
+
+    remote_cable.shutdown().await?;
+
+    let outcome = future::timeout(Duration::from_secs(5), cable.await_request(&req_id)).await?;
+    assert_eq!(outcome, RequestOutcome::Concluded);
+    assert!(remote_cable.get_peer_ids().await.is_empty());
+
+    // The local manager is disconnected once the remote manager has closed
+    // the connection.
+    future::timeout(Duration::from_secs(5), async {
+        while !cable.get_peer_ids().await.is_empty() {
+            task::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await?;
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn request_without_peers() -> Result<(), Error> {
+    init();
+
+    let cable = CableManager::new(MemoryStore::default());
+
+    /* NO PEERS */
+
+    let (_req_id, req_id_bytes) = cable.new_req_id().await?;
+    let opts = ChannelOptions::new("myco", 0, u64::MAX, 10);
+    let request = Message::channel_time_range_request(NO_CIRCUIT, req_id_bytes, TTL, opts);
+    let req_id = cable.send_request(request).await?;
+
+    // No peer is able to conclude the request.
+    let outcome = future::timeout(Duration::from_secs(5), cable.await_request(&req_id)).await?;
+    assert_eq!(outcome, RequestOutcome::NoPeers);
+
+    /* DISCONNECTED PEER */
+
+    // Deploy a TCP listener for the silent peer and connect.
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+
+    let stream = TcpStream::connect(addr).await?;
+    let cable_clone = cable.clone();
+    task::spawn(async move {
+        let _ = cable_clone.listen(stream).await;
+    });
+
+    let (silent_stream, _) = listener.accept().await?;
+
+    // Wait for the manager to register the connection.
+    future::timeout(Duration::from_secs(5), async {
+        while cable.get_peer_ids().await.is_empty() {
+            task::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await?;
+
+    let (_req_id, req_id_bytes) = cable.new_req_id().await?;
+    let opts = ChannelOptions::new("myco", 0, u64::MAX, 10);
+    let request = Message::channel_time_range_request(NO_CIRCUIT, req_id_bytes, TTL, opts);
+    let req_id = cable.send_request(request).await?;
+
+    // The silent peer disconnects without concluding the request.
+    let awaited_cable = cable.clone();
+    let awaited = task::spawn(async move { awaited_cable.await_request(&req_id).await });
+    drop(silent_stream);
+
+    let outcome = future::timeout(Duration::from_secs(5), awaited).await?;
+    assert_eq!(outcome, RequestOutcome::NoPeers);
+
+    Ok(())
+}
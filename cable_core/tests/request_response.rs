@@ -32,9 +32,12 @@
 //! 9) Send a cancel request with the request ID of the initial channel time
 //! range request.
 //!
-//! 10) Publish a third post to the first channel.
+//! 10) Ensure that a hash response with no hashes is returned to conclude the
+//! request.
 //!
-//! 11) Ensure that no hash response is returned.
+//! 11) Publish a third post to the first channel.
+//!
+//! 12) Ensure that no hash response is returned.
 
 use std::{thread, time::Duration};
 
@@ -223,9 +226,9 @@ async fn request_response() -> Result<(), Error> {
         if let ResponseBody::Hash { hashes } = body {
             // Two post hashes should be returned (for channel "tao").
             assert_eq!(hashes.len(), 2);
-            // Ensure the second returned hash matches the hash of the second
-            // text post.
-            assert_eq!(hashes[1], post_hash);
+            // Ensure the first returned hash matches the hash of the second
+            // text post (hashes are returned newest first).
+            assert_eq!(hashes[0], post_hash);
 
             // Generate a novel request ID.
             let (_req_id, req_id_bytes) = cable.new_req_id().await?;
@@ -275,6 +278,23 @@ async fn request_response() -> Result<(), Error> {
     // Sleep briefly to allow time for the cable manager to respond.
     thread::sleep(five_millis);
 
+    // Read the response from the stream.
+    let _n = stream.read(&mut res_bytes).await?;
+
+    // Ensure that a hash response was returned by the listening peer to
+    // conclude the cancelled request.
+    let (_bytes_len, msg) = Message::from_bytes(&res_bytes)?;
+    assert_eq!(msg.message_type(), HASH_RESPONSE);
+    assert_eq!(msg.header.req_id, channel_time_range_req_id_bytes);
+
+    // No post hashes should be returned.
+    assert!(matches!(
+        msg.body,
+        MessageBody::Response {
+            body: ResponseBody::Hash { hashes }
+        } if hashes.is_empty()
+    ));
+
     // Publish a third post to the "tao" channel.
     let _post_hash = cable
         .post_text("tao", "Those who embrace the Tao are soft and strong.")
@@ -0,0 +1,161 @@
+//! Test request deadlines and the retry of unanswered post requests.
+//!
+//! Run the test with debug logging enabled in a terminal:
+//!
+//! `RUST_LOG=debug cargo test request_timeouts`
+//!
+//! An outline of the actions taken in this test:
+//!
+//! 1) Publish a post to the "myco" channel using the remote manager and
+//! connect the local and remote managers via TCP.
+//!
+//! 2) Connect the local manager to a silent peer, represented by a raw TCP
+//! stream, which never responds to requests.
+//!
+//! 3) Send a hash response containing the hash of the post from the silent
+//! peer. Ensure that the local manager requests the post from the silent peer.
+//!
+//! 4) Ensure that the post request is retried with the remote manager once the
+//! deadline has passed and that the post is stored.
+//!
+//! 5) Send a non-live channel time range request from the local manager.
+//! Ensure that awaiting the request resolves once the deadline has passed,
+//! even though the silent peer never concludes the request.
+
+use std::time::Duration;
+
+use async_std::{
+    future,
+    net::{TcpListener, TcpStream},
+    stream::StreamExt,
+    task,
+};
+use cable::{
+    constants::{NO_CIRCUIT, POST_REQUEST},
+    message::{MessageBody, RequestBody},
+    ChannelOptions, Error, Message,
+};
+use desert::{FromBytes, ToBytes};
+use futures::AsyncWriteExt;
+use length_prefixed_stream::{decode_with_options, DecodeOptions};
+use log::info;
+
+use cable_core::{CableManager, ManagerConfig, MemoryStore, RequestConfig, RequestOutcome, Store};
+
+const TTL: u8 = 0;
+
+// Initialise the logger in test mode.
+//
+// Set `is_test()` to `false` if you wish to see logging output during the
+// test run.
+fn init() {
+    let _ = env_logger::builder().is_test(true).try_init();
+}
+
+// Define the stream decoder parameters.
+fn decode_options() -> DecodeOptions {
+    DecodeOptions {
+        include_len: true,
+        ..Default::default()
+    }
+}
+
+#[async_std::test]
+async fn request_timeouts() -> Result<(), Error> {
+    init();
+
+    let config = ManagerConfig {
+        requests: RequestConfig {
+            post_timeout: Duration::from_millis(200),
+            request_timeout: Duration::from_millis(500),
+            sweep_interval: Duration::from_millis(50),
+            ..RequestConfig::default()
+        },
+        ..ManagerConfig::default()
+    };
+    let cable = CableManager::with_config(MemoryStore::default(), config);
+    let mut remote_cable = CableManager::new(MemoryStore::default());
+
+    // Publish a post using the remote manager.
+    let post_hash = remote_cable.post_text("myco", "hyphal fusion").await?;
+
+    // Deploy a TCP listener for the remote manager and connect.
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    info!("Deployed TCP server on {}", addr);
+
+    let remote_cable_clone = remote_cable.clone();
+    task::spawn(async move {
+        let mut incoming = listener.incoming();
+        while let Some(Ok(stream)) = incoming.next().await {
+            let cable = remote_cable_clone.clone();
+            task::spawn(async move {
+                let _ = cable.listen(stream).await;
+            });
+        }
+    });
+
+    let stream = TcpStream::connect(addr).await?;
+    let cable_clone = cable.clone();
+    task::spawn(async move {
+        let _ = cable_clone.listen(stream).await;
+    });
+
+    // Deploy a TCP listener for the silent peer and connect.
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+
+    let stream = TcpStream::connect(addr).await?;
+    let cable_clone = cable.clone();
+    task::spawn(async move {
+        let _ = cable_clone.listen(stream).await;
+    });
+
+    let (mut silent_stream, _) = listener.accept().await?;
+    let mut messages = decode_with_options(silent_stream.clone(), decode_options());
+
+    /* POST REQUEST RETRY */
+
+    // Send the hash of the post from the silent peer.
+    let (_req_id, req_id_bytes) = cable.new_req_id().await?;
+    let hash_response = Message::hash_response(NO_CIRCUIT, req_id_bytes, vec![post_hash]);
+    silent_stream.write_all(&hash_response.to_bytes()?).await?;
+
+    // The local manager requests the post from the silent peer, which never
+    // responds.
+    let buf = future::timeout(Duration::from_secs(5), messages.next())
+        .await?
+        .expect("stream closed")?;
+    let (_bytes_len, msg) = Message::from_bytes(&buf)?;
+    assert_eq!(msg.message_type(), POST_REQUEST);
+    if let MessageBody::Request {
+        body: RequestBody::Post { hashes },
+        ..
+    } = msg.body
+    {
+        assert_eq!(hashes, vec![post_hash]);
+    }
+
+    // Once the deadline has passed, the post is requested from the remote
+    // manager instead.
+    future::timeout(Duration::from_secs(5), async {
+        while cable.store.get_post_payloads(&[post_hash]).await.is_empty() {
+            task::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await?;
+
+    /* REQUEST DEADLINE */
+
+    let (_req_id, req_id_bytes) = cable.new_req_id().await?;
+    let opts = ChannelOptions::new("myco", 0, u64::MAX, 10);
+    let request = Message::channel_time_range_request(NO_CIRCUIT, req_id_bytes, TTL, opts);
+    let req_id = cable.send_request(request).await?;
+
+    // The silent peer never concludes the request; awaiting the request
+    // resolves once the deadline has passed.
+    let outcome = future::timeout(Duration::from_secs(5), cable.await_request(&req_id)).await?;
+    assert_eq!(outcome, RequestOutcome::TimedOut);
+
+    Ok(())
+}
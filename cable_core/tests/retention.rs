@@ -0,0 +1,253 @@
+//! Test the retention policy of stored posts.
+//!
+//! Run the test with debug logging enabled in a terminal:
+//!
+//! `RUST_LOG=debug cargo test retention`
+//!
+//! An outline of the actions taken in this test:
+//!
+//! 1) Insert join, leave, topic, info and text posts with known timestamps
+//! into a `MemoryStore` and a `SledStore`.
+//!
+//! 2) Prune the posts exceeding a maximum number of posts per channel. Ensure
+//! that only the oldest posts are pruned, that the latest topic post is
+//! retained and that the pruned posts are no longer wanted.
+//!
+//! 3) Prune the posts exceeding a maximum age.
+//!
+//! 4) Prune superseded membership, topic and info posts.
+//!
+//! 5) Prune posts until a payload quota of zero bytes is met. Ensure that
+//! only the posts making up the channel state and the delete post remain and
+//! that the channel state is unchanged.
+//!
+//! 6) Apply the retention policy once the TTL of the pruned hashes has passed
+//! and ensure that the pruned posts are wanted again.
+//!
+//! 7) Configure a cable manager with a retention policy, publish two text
+//! posts and ensure that the older post is pruned and no longer wanted.
+
+use std::{collections::HashSet, time::Duration};
+
+use async_std::task;
+
+use cable::{Error, Hash, Post, UserInfo};
+
+use cable_core::{CableManager, ManagerConfig, MemoryStore, RetentionConfig, SledStore, Store};
+
+// Initialise the logger in test mode.
+//
+// Set `is_test()` to `false` if you wish to see logging output during the
+// test run.
+fn init() {
+    let _ = env_logger::builder().is_test(true).try_init();
+}
+
+// Prune the given store according to the given policy, returning the set of
+// pruned hashes.
+async fn prune<S: Store>(store: &mut S, retention: RetentionConfig, now: u64) -> HashSet<Hash> {
+    store.prune(&retention, now).await.into_iter().collect()
+}
+
+// Insert posts into the given store and apply successive retention policies,
+// ensuring that the expected posts are pruned.
+async fn apply_retention<S: Store>(store: &mut S) -> Result<(), Error> {
+    let channel = "myco".to_string();
+    let (public_key, secret_key) = MemoryStore::default().get_keypair().await.expect("keypair");
+
+    let insert = |mut post: Post| {
+        let mut store = store.clone();
+        async move {
+            post.sign(&secret_key)?;
+            store.insert_post(&post).await.map(|(hash, _deleted)| hash)
+        }
+    };
+
+    let first_join = insert(Post::join(public_key, Vec::new(), 1000, channel.clone())).await?;
+    let old_topic = insert(Post::topic(
+        public_key,
+        Vec::new(),
+        1100,
+        channel.clone(),
+        "spores".into(),
+    ))
+    .await?;
+    let old_info = insert(Post::info(
+        public_key,
+        Vec::new(),
+        1200,
+        vec![UserInfo::name("glyph")?],
+    ))
+    .await?;
+    let text_1 = insert(Post::text(
+        public_key,
+        Vec::new(),
+        1300,
+        channel.clone(),
+        "spore".into(),
+    ))
+    .await?;
+    let leave = insert(Post::leave(public_key, Vec::new(), 1500, channel.clone())).await?;
+    let join = insert(Post::join(public_key, Vec::new(), 2000, channel.clone())).await?;
+    let topic = insert(Post::topic(
+        public_key,
+        Vec::new(),
+        2100,
+        channel.clone(),
+        "mycology".into(),
+    ))
+    .await?;
+    let info = insert(Post::info(
+        public_key,
+        Vec::new(),
+        2200,
+        vec![UserInfo::name("sporocarp")?],
+    ))
+    .await?;
+    let text_2 = insert(Post::text(
+        public_key,
+        Vec::new(),
+        2300,
+        channel.clone(),
+        "hypha".into(),
+    ))
+    .await?;
+    let text_3 = insert(Post::text(
+        public_key,
+        Vec::new(),
+        3300,
+        channel.clone(),
+        "mycelium".into(),
+    ))
+    .await?;
+    let text_4 = insert(Post::text(
+        public_key,
+        Vec::new(),
+        4300,
+        channel.clone(),
+        "fruiting body".into(),
+    ))
+    .await?;
+    let deleted_text = insert(Post::text(
+        public_key,
+        Vec::new(),
+        4400,
+        channel.clone(),
+        "spore print".into(),
+    ))
+    .await?;
+    let delete = insert(Post::delete(
+        public_key,
+        Vec::new(),
+        4500,
+        vec![deleted_text],
+    ))
+    .await?;
+
+    /* POST COUNT */
+
+    // The latest topic post counts towards the limit but is retained.
+    let retention = RetentionConfig {
+        max_posts_per_channel: Some(2),
+        ..Default::default()
+    };
+    assert_eq!(
+        prune(store, retention, 6000).await,
+        HashSet::from([text_2, text_1, old_topic])
+    );
+    assert!(store.want(&[text_2, text_1, old_topic]).await.is_empty());
+
+    /* AGE */
+
+    let retention = RetentionConfig {
+        max_age: Some(Duration::from_millis(2000)),
+        ..Default::default()
+    };
+    assert_eq!(prune(store, retention, 6000).await, HashSet::from([text_3]));
+
+    /* SUPERSEDED */
+
+    let retention = RetentionConfig {
+        prune_superseded: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        prune(store, retention, 6000).await,
+        HashSet::from([first_join, leave, old_info])
+    );
+
+    /* QUOTA */
+
+    let retention = RetentionConfig {
+        max_payload_bytes: Some(0),
+        ..Default::default()
+    };
+    assert_eq!(prune(store, retention, 6000).await, HashSet::from([text_4]));
+
+    // Only the channel state and the delete post remain, with the channel
+    // state unchanged.
+    let remaining: HashSet<Hash> = store
+        .get_payload_sizes()
+        .await
+        .into_iter()
+        .map(|(hash, _size)| hash)
+        .collect();
+    assert_eq!(remaining, HashSet::from([join, topic, info, delete]));
+    assert_eq!(store.get_tombstone(&deleted_text).await, Some(public_key));
+    assert_eq!(
+        store.get_channel_topic_and_hash(&channel).await,
+        Some(("mycology".to_string(), topic))
+    );
+    assert!(store.is_channel_member(&channel, &public_key).await);
+    assert_eq!(
+        store.get_peer_info(&public_key).await,
+        Some(vec![UserInfo::name("sporocarp")?])
+    );
+
+    /* PRUNED HASH EXPIRY */
+
+    // Only the hashes pruned at 6000 have been recorded for longer than the
+    // TTL.
+    let retention = RetentionConfig {
+        pruned_hash_ttl: Duration::from_millis(1000),
+        ..Default::default()
+    };
+    assert!(prune(store, retention, 7500).await.is_empty());
+    assert_eq!(store.want(&[text_1, text_4]).await, vec![text_1, text_4]);
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn retention() -> Result<(), Error> {
+    init();
+
+    /* STORES */
+
+    apply_retention(&mut MemoryStore::default()).await?;
+
+    let dir = tempfile::tempdir()?;
+    apply_retention(&mut SledStore::open(dir.path())?).await?;
+
+    /* MANAGER */
+
+    let config = ManagerConfig {
+        retention: RetentionConfig {
+            max_posts_per_channel: Some(1),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut cable = CableManager::with_config(MemoryStore::default(), config);
+    let old_hash = cable.post_text("myco", "spore").await?;
+    // Ensure the posts have distinct timestamps.
+    task::sleep(Duration::from_millis(2)).await;
+    let new_hash = cable.post_text("myco", "hypha").await?;
+
+    assert_eq!(cable.prune().await?, vec![old_hash]);
+    assert!(cable.store.get_post_payload(&old_hash).await.is_none());
+    assert!(cable.store.get_post_payload(&new_hash).await.is_some());
+    assert!(cable.store.want(&[old_hash]).await.is_empty());
+
+    Ok(())
+}
@@ -0,0 +1,142 @@
+//! Test the full-text search index of text posts.
+//!
+//! Run the test with debug logging enabled in a terminal:
+//!
+//! `RUST_LOG=debug cargo test search`
+//!
+//! An outline of the actions taken in this test:
+//!
+//! 1) Ensure that text is split into lowercase terms according to the Unicode
+//! word boundary rules.
+//!
+//! 2) Publish text posts to the "myco" and "books" channels and insert a text
+//! post by a second author. Ensure that queries match every term, that
+//! phrases match consecutive terms only and that results are ranked by
+//! relevance.
+//!
+//! 3) Ensure that results can be filtered by channel and author and that the
+//! number of results can be limited.
+//!
+//! 4) Delete a post and hide the second author. Ensure that their posts are no
+//! longer returned.
+//!
+//! 5) Insert a text post into a `SledStore`, reopen the store and ensure that
+//! the post is found.
+#![cfg(feature = "search")]
+
+use cable::{Error, Hash, Post};
+
+use cable_core::{tokenize, CableManager, MemoryStore, SearchQuery, SledStore, Store};
+
+// Initialise the logger in test mode.
+//
+// Set `is_test()` to `false` if you wish to see logging output during the
+// test run.
+fn init() {
+    let _ = env_logger::builder().is_test(true).try_init();
+}
+
+// Return the hashes of the given posts.
+fn hashes(posts: &[Post]) -> Result<Vec<Hash>, Error> {
+    posts.iter().map(|post| post.hash()).collect()
+}
+
+#[async_std::test]
+async fn search() -> Result<(), Error> {
+    init();
+
+    /* TOKENIZE */
+
+    assert_eq!(
+        tokenize("Hyphal fusion, in Ōsaka! 菌糸"),
+        vec!["hyphal", "fusion", "in", "ōsaka", "菌", "糸"]
+    );
+
+    /* TERMS AND PHRASES */
+
+    let mut cable = CableManager::new(MemoryStore::default());
+    let fusion_hash = cable
+        .post_text("myco", "Hyphal fusion joins the mycelium")
+        .await?;
+    let repeated_hash = cable
+        .post_text("myco", "Fusion of hyphal tips, fusion!")
+        .await?;
+    let books_hash = cable
+        .post_text("books", "A book about hyphal fusion")
+        .await?;
+    let _spore_hash = cable.post_text("myco", "Spore prints").await?;
+
+    // A post by a second author.
+    let (public_key, secret_key) = MemoryStore::default().get_keypair().await.expect("keypair");
+    let mut other = Post::text(
+        public_key,
+        Vec::new(),
+        1000,
+        "myco".into(),
+        "Hyphal fusion observed under the microscope today".into(),
+    );
+    other.sign(&secret_key)?;
+    let (other_hash, _deleted) = cable.store.insert_post(&other).await?;
+
+    // Every term must match; the post mentioning "fusion" twice ranks first.
+    let posts = cable.search(&SearchQuery::new("FUSION hyphal")).await;
+    assert_eq!(posts.len(), 4);
+    assert_eq!(posts[0].hash()?, repeated_hash);
+    assert!(cable
+        .search(&SearchQuery::new("fusion spore"))
+        .await
+        .is_empty());
+
+    // Phrases must match consecutive terms.
+    let mut posts = hashes(&cable.search(&SearchQuery::new("\"hyphal fusion\"")).await)?;
+    posts.sort();
+    let mut expected = vec![fusion_hash, books_hash, other_hash];
+    expected.sort();
+    assert_eq!(posts, expected);
+
+    /* FILTERS */
+
+    let mut query = SearchQuery::new("fusion");
+    query.channel = Some("books".to_string());
+    assert_eq!(hashes(&cable.search(&query).await)?, vec![books_hash]);
+
+    let mut query = SearchQuery::new("fusion");
+    query.author = Some(public_key);
+    assert_eq!(hashes(&cable.search(&query).await)?, vec![other_hash]);
+
+    let mut query = SearchQuery::new("fusion");
+    query.limit = 2;
+    assert_eq!(cable.search(&query).await.len(), 2);
+
+    /* REMOVAL */
+
+    cable.post_delete(vec![fusion_hash]).await?;
+    cable.hide(&public_key).await;
+
+    let mut posts = hashes(&cable.search(&SearchQuery::new("hyphal fusion")).await)?;
+    posts.sort();
+    let mut expected = vec![repeated_hash, books_hash];
+    expected.sort();
+    assert_eq!(posts, expected);
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn search_persistence() -> Result<(), Error> {
+    init();
+
+    let dir = tempfile::tempdir()?;
+
+    let mut cable = CableManager::new(SledStore::open(dir.path())?);
+    let text_hash = cable.post_text("myco", "Ganoderma neo-japonicum").await?;
+    cable.close().await?.close().await?;
+
+    // Reopen the store from the same directory; the index is rebuilt.
+    let store = SledStore::open(dir.path())?;
+    let results = store.search(&SearchQuery::new("ganoderma")).await;
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].hash, text_hash);
+
+    Ok(())
+}
@@ -0,0 +1,296 @@
+//! Test message exchange between two cable managers over an authenticated and
+//! encrypted stream.
+//!
+//! Create two cable managers, connect them via TCP and perform a handshake
+//! before exchanging requests and responses.
+//!
+//! Run the test with debug logging enabled in a terminal:
+//!
+//! `RUST_LOG=debug cargo test secure_listen`
+//!
+//! An outline of the actions taken in this test:
+//!
+//! 1) Publish a text post to the "myco" channel using the first manager. Both
+//! managers enable the identity payload extension of the handshake.
+//!
+//! 2) Open the "myco" channel using the second manager.
+//!
+//! 3) Connect the managers, with the first manager acting as the handshake
+//! responder and the second as the handshake initiator.
+//!
+//! 4) Ensure that the second manager receives the text post and that each
+//! manager has received the public key of the other during the handshake.
+//!
+//! 5) Attempt to connect with a different cabal key and ensure that the
+//! handshake fails.
+//!
+//! 6) Complete a handshake with a manager limiting the size of received
+//! messages and announce a message exceeding the limit. Ensure that the
+//! listener returns an error without waiting for the message payload.
+//!
+//! 7) Complete a handshake with a manager as responder and then as initiator,
+//! with the handshake extensions disabled, using the plain handshake client
+//! and server. Ensure that the managers register the peer without a public
+//! key and that a manager enabling the identity payload extension is unable
+//! to complete a handshake with the plain client.
+
+use std::time::Duration;
+
+use async_std::{
+    future,
+    net::{TcpListener, TcpStream},
+    stream::StreamExt,
+    task,
+};
+use cable::{post::PostBody, ChannelOptions, Error};
+use cable_handshake::{async_std::handshake, HandshakeError, Version};
+use futures::AsyncWriteExt;
+use log::info;
+
+use cable_core::{CableManager, Extensions, LimitConfig, ManagerConfig, MemoryStore, Role, Store};
+
+const CABAL_KEY: [u8; 32] = [7; 32];
+
+// Initialise the logger in test mode.
+//
+// Set `is_test()` to `false` if you wish to see logging output during the
+// test run.
+fn init() {
+    let _ = env_logger::builder().is_test(true).try_init();
+}
+
+#[async_std::test]
+async fn secure_listen() -> Result<(), Error> {
+    init();
+
+    let config = ManagerConfig {
+        handshake_extensions: Extensions {
+            identity_payload: true,
+        },
+        ..ManagerConfig::default()
+    };
+    let mut responder = CableManager::with_config(MemoryStore::default(), config.clone());
+    let initiator = CableManager::with_config(MemoryStore::default(), config);
+
+    // Publish a post using the handshake responder.
+    let post_hash = responder.post_text("myco", "hyphal fusion").await?;
+
+    // Deploy a TCP listener.
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    info!("Deployed TCP server on {}", addr);
+
+    let responder_clone = responder.clone();
+    task::spawn(async move {
+        // Listen for incoming TCP connections and pass any inbound streams to
+        // the cable manager.
+        let mut incoming = listener.incoming();
+        while let Some(Ok(stream)) = incoming.next().await {
+            let cable = responder_clone.clone();
+            task::spawn(async move {
+                let _ = cable
+                    .listen_secure(stream, CABAL_KEY, Role::Responder)
+                    .await;
+            });
+        }
+    });
+
+    // Open the channel before connecting; the outbound requests are sent to
+    // the peer once the handshake is complete.
+    let opts = ChannelOptions::new("myco", 0, 0, 10);
+    let mut initiator_clone = initiator.clone();
+    let mut post_stream = initiator_clone.open_channel(&opts).await?;
+
+    let stream = TcpStream::connect(addr).await?;
+    let initiator_c = initiator.clone();
+    task::spawn(async move {
+        let _ = initiator_c
+            .listen_secure(stream, CABAL_KEY, Role::Initiator)
+            .await;
+    });
+
+    // Wait for the post to be received.
+    let post = future::timeout(Duration::from_secs(5), post_stream.next())
+        .await?
+        .expect("post stream ended")?;
+    assert_eq!(post.hash()?, post_hash);
+    if let PostBody::Text { channel, text } = post.body {
+        assert_eq!(channel, "myco");
+        assert_eq!(text, "hyphal fusion");
+    } else {
+        panic!("expected a text post");
+    }
+
+    // Ensure each manager knows the ed25519 public key of the connected peer.
+    let peer_ids = initiator.get_peer_ids().await;
+    assert_eq!(peer_ids.len(), 1);
+    assert_eq!(
+        initiator.get_peer_public_key(peer_ids[0]).await,
+        Some(responder.get_public_key().await?)
+    );
+    let peer_ids = responder.get_peer_ids().await;
+    assert_eq!(peer_ids.len(), 1);
+    assert_eq!(
+        responder.get_peer_public_key(peer_ids[0]).await,
+        Some(initiator.clone().get_public_key().await?)
+    );
+
+    // A peer with a different cabal key is unable to complete the handshake.
+    let stream = TcpStream::connect(addr).await?;
+    let res = future::timeout(
+        Duration::from_secs(5),
+        initiator.listen_secure(stream, [8; 32], Role::Initiator),
+    )
+    .await?;
+    assert!(res.is_err());
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn secure_listen_frame_limit() -> Result<(), Error> {
+    init();
+
+    let config = ManagerConfig {
+        limits: LimitConfig {
+            max_frame_size: 1024,
+            ..LimitConfig::default()
+        },
+        ..ManagerConfig::default()
+    };
+    let cable = CableManager::with_config(MemoryStore::default(), config);
+
+    // Deploy a TCP listener and pass the first inbound stream to the cable
+    // manager, retaining the handle of the listener task.
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    info!("Deployed TCP server on {}", addr);
+
+    let listener_handle = task::spawn(async move {
+        let (stream, _addr) = listener.accept().await?;
+        cable
+            .listen_secure(stream, CABAL_KEY, Role::Responder)
+            .await
+    });
+
+    // Perform the handshake as a raw peer.
+    let (_public_key, private_key) = MemoryStore::default().get_keypair().await.expect("keypair");
+    let mut stream = TcpStream::connect(addr).await?;
+    let _peer = handshake::client(&mut stream, Version::init(1, 0), CABAL_KEY, private_key).await?;
+
+    // Announce a message exceeding the limit, without sending the payload.
+    stream.write_all(&1025u32.to_le_bytes()).await?;
+
+    let err = future::timeout(Duration::from_secs(5), listener_handle)
+        .await?
+        .expect_err("message exceeding the limit was accepted");
+    assert_eq!(
+        err.downcast_ref::<HandshakeError>(),
+        Some(&HandshakeError::MessageTooLong {
+            len: 1025,
+            max_len: 1024
+        })
+    );
+
+    Ok(())
+}
+
+// Wait until the given manager has registered a connected peer and return
+// the public key of the peer, if known.
+async fn connected_public_key(
+    cable: &CableManager<MemoryStore>,
+) -> Result<Option<[u8; 32]>, Error> {
+    let peer_id = future::timeout(Duration::from_secs(5), async {
+        loop {
+            if let Some(peer_id) = cable.get_peer_ids().await.first() {
+                return *peer_id;
+            }
+            task::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await?;
+
+    Ok(cable.get_peer_public_key(peer_id).await)
+}
+
+#[async_std::test]
+async fn secure_listen_plain_handshake() -> Result<(), Error> {
+    init();
+
+    let (_public_key, private_key) = MemoryStore::default().get_keypair().await.expect("keypair");
+
+    /* RESPONDER */
+
+    let responder = CableManager::new(MemoryStore::default());
+
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let responder_clone = responder.clone();
+    task::spawn(async move {
+        let mut incoming = listener.incoming();
+        while let Some(Ok(stream)) = incoming.next().await {
+            let cable = responder_clone.clone();
+            task::spawn(async move {
+                let _ = cable
+                    .listen_secure(stream, CABAL_KEY, Role::Responder)
+                    .await;
+            });
+        }
+    });
+
+    let mut stream = TcpStream::connect(addr).await?;
+    let peer = handshake::client(&mut stream, Version::init(1, 0), CABAL_KEY, private_key).await?;
+    assert_eq!(peer.get_remote_public_key(), None);
+    assert_eq!(connected_public_key(&responder).await?, None);
+
+    /* INITIATOR */
+
+    let initiator = CableManager::new(MemoryStore::default());
+
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let stream = TcpStream::connect(listener.local_addr()?).await?;
+    let initiator_clone = initiator.clone();
+    task::spawn(async move {
+        let _ = initiator_clone
+            .listen_secure(stream, CABAL_KEY, Role::Initiator)
+            .await;
+    });
+
+    let (mut stream, _addr) = listener.accept().await?;
+    let peer = handshake::server(&mut stream, Version::init(1, 0), CABAL_KEY, private_key).await?;
+    assert_eq!(peer.get_remote_public_key(), None);
+    assert_eq!(connected_public_key(&initiator).await?, None);
+
+    /* EXTENSION MISMATCH */
+
+    let config = ManagerConfig {
+        handshake_extensions: Extensions {
+            identity_payload: true,
+        },
+        ..ManagerConfig::default()
+    };
+    let cable = CableManager::with_config(MemoryStore::default(), config);
+
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let listener_handle = task::spawn(async move {
+        let (stream, _addr) = listener.accept().await?;
+        cable
+            .listen_secure(stream, CABAL_KEY, Role::Responder)
+            .await
+    });
+
+    let mut stream = TcpStream::connect(addr).await?;
+    let res = future::timeout(
+        Duration::from_secs(5),
+        handshake::client(&mut stream, Version::init(1, 0), CABAL_KEY, private_key),
+    )
+    .await?;
+    assert!(res.is_err());
+    drop(stream);
+
+    let res = future::timeout(Duration::from_secs(5), listener_handle).await?;
+    assert!(res.is_err());
+
+    Ok(())
+}
@@ -0,0 +1,186 @@
+//! Test persistence of the sled-backed store.
+//!
+//! Create a cable manager backed by a `SledStore` in a temporary directory,
+//! publish posts of several types, close the store and then reopen it from
+//! the same directory.
+//!
+//! Run the test with debug logging enabled in a terminal:
+//!
+//! `RUST_LOG=debug cargo test sled_store_persistence`
+//!
+//! An outline of the actions taken in this test:
+//!
+//! 1) Publish a join post, a topic post, three text posts and an info post.
+//!
+//! 2) Publish a delete post with the hash of the third text post.
+//!
+//! 3) Close the manager and the store.
+//!
+//! 4) Reopen the store and ensure that the channels, channel members, channel
+//! topic, peer name, post hashes and post payloads match those which were
+//! stored before the store was closed. Ensure that the deleted post is not
+//! returned and that the keypair was not persisted.
+//!
+//! 5) Connect a manager backed by a `SledStore` to a remote manager via TCP,
+//! close the manager and the store and ensure that the store can be reopened
+//! immediately.
+
+use std::time::Duration;
+
+use async_std::{
+    future,
+    net::{TcpListener, TcpStream},
+    stream::StreamExt,
+    task,
+};
+use cable::{ChannelOptions, Error};
+use log::info;
+
+use cable_core::{CableManager, MemoryStore, SledStore, Store};
+
+// Initialise the logger in test mode.
+//
+// Set `is_test()` to `false` if you wish to see logging output during the
+// test run.
+fn init() {
+    let _ = env_logger::builder().is_test(true).try_init();
+}
+
+#[async_std::test]
+async fn sled_store_persistence() -> Result<(), Error> {
+    init();
+
+    let dir = tempfile::tempdir()?;
+    info!("Opening store in {}", dir.path().display());
+
+    let store = SledStore::open(dir.path())?;
+    let mut cable = CableManager::new(store);
+
+    let public_key = cable.get_public_key().await?;
+
+    // Publish posts of several types.
+    let join_hash = cable.post_join("myco").await?;
+    let topic_hash = cable.post_topic("myco", "mycology").await?;
+    let text_hash_1 = cable.post_text("myco", "hyphal fusion").await?;
+    let text_hash_2 = cable.post_text("myco", "Ganoderma neo-japonicum").await?;
+    let text_hash_3 = cable.post_text("myco", "Daedaleopsis confragosa").await?;
+    let name_hash = cable.post_info_name("glyph").await?;
+
+    // Delete the third text post.
+    let delete_hash = cable.post_delete(vec![text_hash_3]).await?;
+
+    let opts = ChannelOptions::new("myco", 0, 0, 10);
+    let hashes_before: Vec<_> = cable
+        .store
+        .get_post_hashes(&opts)
+        .await
+        .collect::<Result<Vec<_>, Error>>()
+        .await?;
+
+    // Close the manager and the store.
+    cable.close().await?.close().await?;
+
+    // Reopen the store from the same directory.
+    let store = SledStore::open(dir.path())?;
+
+    // The keypair is held in memory only.
+    assert_eq!(store.get_keypair().await, None);
+
+    let channel = "myco".to_string();
+    assert_eq!(store.get_channels().await, Some(vec![channel.clone()]));
+    assert!(store.is_channel_member(&channel, &public_key).await);
+    assert_eq!(
+        store.get_channel_membership_hashes(&channel).await,
+        Some(vec![join_hash])
+    );
+    assert_eq!(
+        store.get_channel_topic_and_hash(&channel).await,
+        Some(("mycology".to_string(), topic_hash))
+    );
+    assert_eq!(
+        store.get_peer_name_and_hash(&public_key).await,
+        Some(("glyph".to_string(), name_hash))
+    );
+    assert_eq!(
+        store.get_delete_hashes(&public_key).await,
+        Some(vec![delete_hash])
+    );
+
+    // Ensure the post hashes match those returned before the store was
+    // closed and that the deleted post is not included.
+    let hashes_after: Vec<_> = store
+        .get_post_hashes(&opts)
+        .await
+        .collect::<Result<Vec<_>, Error>>()
+        .await?;
+    assert_eq!(hashes_before, hashes_after);
+    assert!(hashes_after.contains(&topic_hash));
+    assert!(hashes_after.contains(&text_hash_1));
+    assert!(hashes_after.contains(&text_hash_2));
+    assert!(!hashes_after.contains(&text_hash_3));
+
+    // Ensure the stored posts can be decoded, including the non-channel info
+    // post.
+    let posts: Vec<_> = store
+        .get_posts(&opts)
+        .await
+        .collect::<Result<Vec<_>, Error>>()
+        .await?;
+    assert_eq!(posts.len(), 4);
+
+    // Ensure the post payloads were persisted.
+    assert!(store.get_post_payload(&text_hash_1).await.is_some());
+    assert!(store.get_post_payload(&text_hash_3).await.is_none());
+    // The deleted post is not wanted again.
+    assert!(store.want(&[text_hash_2, text_hash_3]).await.is_empty());
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn sled_store_close() -> Result<(), Error> {
+    init();
+
+    let dir = tempfile::tempdir()?;
+
+    let mut cable = CableManager::new(SledStore::open(dir.path())?);
+    let text_hash = cable.post_text("myco", "hyphal fusion").await?;
+    let remote_cable = CableManager::new(MemoryStore::default());
+
+    // Deploy a TCP listener for the remote manager and connect.
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+
+    let remote_cable_clone = remote_cable.clone();
+    task::spawn(async move {
+        if let Some(Ok(stream)) = listener.incoming().next().await {
+            let _ = remote_cable_clone.listen(stream).await;
+        }
+    });
+
+    let stream = TcpStream::connect(addr).await?;
+    let cable_clone = cable.clone();
+    task::spawn(async move {
+        let _ = cable_clone.listen(stream).await;
+    });
+
+    // Wait for the manager to register the connection.
+    future::timeout(Duration::from_secs(5), async {
+        while cable.get_peer_ids().await.is_empty() {
+            task::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await?;
+
+    // Closing the manager disconnects the peer and waits for the listener
+    // and all spawned tasks to drop their clones of the store.
+    future::timeout(Duration::from_secs(5), async {
+        cable.close().await?.close().await
+    })
+    .await??;
+
+    let store = SledStore::open(dir.path())?;
+    assert!(store.get_post_payload(&text_hash).await.is_some());
+
+    Ok(())
+}
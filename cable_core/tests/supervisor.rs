@@ -0,0 +1,204 @@
+//! Test the connection supervisor: reconnection with backoff, de-duplication
+//! of connections by remote public key, the connection limit and connection
+//! status events.
+//!
+//! Run the test with debug logging enabled in a terminal:
+//!
+//! `RUST_LOG=debug cargo test supervisor`
+//!
+//! An outline of the actions taken in this test:
+//!
+//! 1) Deploy two TCP listeners for the remote manager and add the address of
+//! the first listener to the supervisor of the local manager. Ensure the
+//! connection is established.
+//!
+//! 2) Disconnect the local peer from the remote manager and ensure that the
+//! supervisor reconnects.
+//!
+//! 3) Add the address of the second listener and ensure the connection is
+//! closed as a duplicate, since it leads to the same remote public key.
+//!
+//! 4) Ensure that an inbound connection is rejected once the connection limit
+//! has been reached.
+//!
+//! 5) Remove both addresses and ensure the local manager is disconnected.
+
+use std::{net::SocketAddr, time::Duration};
+
+use async_std::{
+    channel::Receiver,
+    future,
+    net::{TcpListener, TcpStream},
+    stream::StreamExt,
+    task,
+};
+use cable::Error;
+use log::info;
+
+use cable_core::{
+    CableManager, ConnectionError, ConnectionEvent, ConnectionStatus, ConnectionSupervisor,
+    Extensions, ManagerConfig, MemoryStore, Role, SupervisorConfig,
+};
+
+const CABAL_KEY: [u8; 32] = [5; 32];
+
+// Initialise the logger in test mode.
+//
+// Set `is_test()` to `false` if you wish to see logging output during the
+// test run.
+fn init() {
+    let _ = env_logger::builder().is_test(true).try_init();
+}
+
+// Deploy a TCP listener for the given manager and return the address.
+async fn serve(cable: CableManager<MemoryStore>) -> Result<SocketAddr, Error> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    info!("Deployed TCP server on {}", addr);
+
+    task::spawn(async move {
+        let mut incoming = listener.incoming();
+        while let Some(Ok(stream)) = incoming.next().await {
+            let cable = cable.clone();
+            task::spawn(async move {
+                let _ = cable
+                    .listen_secure(stream, CABAL_KEY, Role::Responder)
+                    .await;
+            });
+        }
+    });
+
+    Ok(addr)
+}
+
+// Wait for an event with the given address and a status matching the given
+// predicate, skipping all other events.
+async fn expect_status<F>(
+    events: &Receiver<ConnectionEvent>,
+    addr: SocketAddr,
+    predicate: F,
+) -> Result<ConnectionStatus, Error>
+where
+    F: Fn(&ConnectionStatus) -> bool,
+{
+    let status = future::timeout(Duration::from_secs(5), async {
+        loop {
+            let event = events.recv().await.expect("event channel closed");
+            if event.addr == addr && predicate(&event.status) {
+                return event.status;
+            }
+        }
+    })
+    .await?;
+
+    Ok(status)
+}
+
+#[async_std::test]
+async fn supervisor() -> Result<(), Error> {
+    init();
+
+    // The supervisor identifies connections by the public key of the remote
+    // peer, which requires the identity payload extension of the handshake.
+    let config = ManagerConfig {
+        handshake_extensions: Extensions {
+            identity_payload: true,
+        },
+        ..ManagerConfig::default()
+    };
+    let cable = CableManager::with_config(MemoryStore::default(), config.clone());
+    let remote_cable = CableManager::with_config(MemoryStore::default(), config);
+    let remote_public_key = remote_cable.clone().get_public_key().await?;
+
+    let addr_1 = serve(remote_cable.clone()).await?;
+    let addr_2 = serve(remote_cable.clone()).await?;
+
+    let config = SupervisorConfig {
+        max_connections: 1,
+        initial_backoff: Duration::from_millis(50),
+        max_backoff: Duration::from_millis(200),
+    };
+    let supervisor = ConnectionSupervisor::new(cable.clone(), CABAL_KEY, config);
+    let events = supervisor.events().await;
+
+    // Connect to the first address.
+    supervisor.add_peer(addr_1).await;
+    let status = expect_status(&events, addr_1, |status| {
+        matches!(status, ConnectionStatus::Connected(_))
+    })
+    .await?;
+    assert_eq!(status, ConnectionStatus::Connected(remote_public_key));
+
+    // Disconnect from the remote side and wait for the supervisor to
+    // reconnect.
+    let peer_ids = future::timeout(Duration::from_secs(5), async {
+        loop {
+            let peer_ids = remote_cable.get_peer_ids().await;
+            if !peer_ids.is_empty() {
+                return peer_ids;
+            }
+            task::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await?;
+    assert_eq!(peer_ids.len(), 1);
+    remote_cable.disconnect(peer_ids[0]).await;
+    expect_status(&events, addr_1, |status| {
+        matches!(status, ConnectionStatus::Disconnected(_))
+    })
+    .await?;
+    expect_status(&events, addr_1, |status| {
+        matches!(status, ConnectionStatus::Reconnecting(_))
+    })
+    .await?;
+    expect_status(&events, addr_1, |status| {
+        matches!(status, ConnectionStatus::Connected(_))
+    })
+    .await?;
+
+    // Raise the connection limit by way of a second supervisor sharing the
+    // manager, then connect to the same remote peer via the second address.
+    let config = SupervisorConfig {
+        max_connections: 2,
+        initial_backoff: Duration::from_millis(50),
+        max_backoff: Duration::from_millis(200),
+    };
+    let second_supervisor = ConnectionSupervisor::new(cable.clone(), CABAL_KEY, config);
+    let second_events = second_supervisor.events().await;
+    second_supervisor.add_peer(addr_2).await;
+    let status = expect_status(&second_events, addr_2, |status| {
+        matches!(
+            status,
+            ConnectionStatus::Connected(_) | ConnectionStatus::Duplicate(_)
+        )
+    })
+    .await?;
+    assert_eq!(status, ConnectionStatus::Duplicate(remote_public_key));
+    assert_eq!(cable.get_peer_ids().await.len(), 1);
+
+    // Reject inbound connections once the connection limit is reached.
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let _client = TcpStream::connect(listener.local_addr()?).await?;
+    let (stream, _) = listener.accept().await?;
+    let err = supervisor.accept(stream).await.unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<ConnectionError>(),
+        Some(&ConnectionError::ConnectionLimitReached)
+    );
+
+    // Remove both addresses.
+    second_supervisor.remove_peer(&addr_2).await;
+    expect_status(&second_events, addr_2, |status| {
+        status == &ConnectionStatus::Removed
+    })
+    .await?;
+    supervisor.remove_peer(&addr_1).await;
+    expect_status(&events, addr_1, |status| {
+        status == &ConnectionStatus::Removed
+    })
+    .await?;
+    assert!(supervisor.known_peers().await.is_empty());
+    assert!(cable.get_peer_ids().await.is_empty());
+
+    Ok(())
+}
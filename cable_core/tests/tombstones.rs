@@ -0,0 +1,294 @@
+//! Test the recording of tombstones for deleted posts.
+//!
+//! Run the test with debug logging enabled in a terminal:
+//!
+//! `RUST_LOG=debug cargo test tombstones`
+//!
+//! An outline of the actions taken in this test:
+//!
+//! 1) Create a text post and a delete post referencing it, both by the same
+//! author. Connect to a cable manager as a raw TCP peer.
+//!
+//! 2) Send a hash response containing the hash of the delete post. Respond to
+//! the post request with the delete post and ensure that it is stored.
+//!
+//! 3) Ensure that a pending tombstone is recorded for the text post and that
+//! the text post is still wanted.
+//!
+//! 4) Insert the text post. Ensure that it is refused, that the tombstone is
+//! confirmed and that the text post is no longer wanted.
+//!
+//! 5) Send a delete post by a third party referencing a post by another
+//! author which has not been seen yet. Announce the hash of the referenced
+//! post and ensure that it is requested, stored and that the pending
+//! tombstone is discarded.
+//!
+//! 6) Connect to the manager as a second raw TCP peer and request the delete
+//! post. Ensure that it is served.
+//!
+//! 7) Insert the delete post into a `SledStore`, reopen the store and ensure
+//! that the pending tombstone is retained. Insert the text post and ensure
+//! that it is refused.
+
+use std::time::Duration;
+
+use async_std::{
+    future,
+    net::{TcpListener, TcpStream},
+    stream::StreamExt,
+    task,
+};
+use cable::{
+    constants::{NO_CIRCUIT, POST_REQUEST},
+    message::{MessageBody, RequestBody, ResponseBody},
+    Error, Message, Post, ReqId,
+};
+use desert::{FromBytes, ToBytes};
+use futures::{AsyncWriteExt, Stream};
+use length_prefixed_stream::{decode_with_options, DecodeError, DecodeOptions};
+use log::info;
+
+use cable_core::{CableManager, MemoryStore, SledStore, Store};
+
+const TTL: u8 = 0;
+
+// Initialise the logger in test mode.
+//
+// Set `is_test()` to `false` if you wish to see logging output during the
+// test run.
+fn init() {
+    let _ = env_logger::builder().is_test(true).try_init();
+}
+
+// Define the stream decoder parameters.
+fn decode_options() -> DecodeOptions {
+    DecodeOptions {
+        include_len: true,
+        ..Default::default()
+    }
+}
+
+// Read messages from the given stream until a message of the given type is
+// received.
+async fn read_message_of_type<T>(messages: &mut T, msg_type: u64) -> Result<Message, Error>
+where
+    T: Stream<Item = Result<Vec<u8>, DecodeError>> + Unpin,
+{
+    loop {
+        let buf = future::timeout(Duration::from_secs(5), messages.next())
+            .await?
+            .expect("stream closed")?;
+        let (_bytes_len, msg) = Message::from_bytes(&buf)?;
+
+        if msg.message_type() == msg_type {
+            return Ok(msg);
+        }
+    }
+}
+
+// Read messages from the given stream until a response to the request with
+// the given ID is received.
+async fn read_response<T>(messages: &mut T, req_id: ReqId) -> Result<Message, Error>
+where
+    T: Stream<Item = Result<Vec<u8>, DecodeError>> + Unpin,
+{
+    loop {
+        let buf = future::timeout(Duration::from_secs(5), messages.next())
+            .await?
+            .expect("stream closed")?;
+        let (_bytes_len, msg) = Message::from_bytes(&buf)?;
+
+        if msg.header.req_id == req_id {
+            return Ok(msg);
+        }
+    }
+}
+
+// Announce the hash of the given post to the cable manager, respond to the
+// resulting post request with the post and wait until the post is stored.
+async fn send_post<T>(
+    cable: &CableManager<MemoryStore>,
+    stream: &mut TcpStream,
+    messages: &mut T,
+    post: &Post,
+) -> Result<(), Error>
+where
+    T: Stream<Item = Result<Vec<u8>, DecodeError>> + Unpin,
+{
+    let hash = post.hash()?;
+
+    let (_req_id, req_id) = cable.new_req_id().await?;
+    let response = Message::hash_response(NO_CIRCUIT, req_id, vec![hash]);
+    stream.write_all(&response.to_bytes()?).await?;
+
+    let msg = read_message_of_type(messages, POST_REQUEST).await?;
+    if let MessageBody::Request {
+        body: RequestBody::Post { hashes },
+        ..
+    } = &msg.body
+    {
+        assert_eq!(hashes, &vec![hash]);
+    }
+    let response = Message::post_response(NO_CIRCUIT, msg.header.req_id, vec![post.to_bytes()?]);
+    stream.write_all(&response.to_bytes()?).await?;
+
+    future::timeout(Duration::from_secs(5), async {
+        while cable.store.get_post_payload(&hash).await.is_none() {
+            task::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await?;
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn tombstones() -> Result<(), Error> {
+    init();
+
+    let (public_key, secret_key) = MemoryStore::default().get_keypair().await.expect("keypair");
+
+    // Create a text post and a delete post referencing it.
+    let mut text = Post::text(public_key, Vec::new(), 1000, "myco".into(), "spore".into());
+    text.sign(&secret_key)?;
+    let text_hash = text.hash()?;
+    let mut delete = Post::delete(public_key, Vec::new(), 2000, vec![text_hash]);
+    delete.sign(&secret_key)?;
+    let delete_bytes = delete.to_bytes()?;
+    let delete_hash = delete.hash()?;
+
+    let cable = CableManager::new(MemoryStore::default());
+    let cable_clone = cable.clone();
+
+    // Deploy a TCP listener and pass inbound streams to the cable manager.
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    info!("Deployed TCP server on {}", addr);
+
+    task::spawn(async move {
+        let mut incoming = listener.incoming();
+        while let Some(Ok(stream)) = incoming.next().await {
+            let cable = cable_clone.clone();
+            task::spawn(async move {
+                let _ = cable.listen(stream).await;
+            });
+        }
+    });
+
+    /* RECEIVE */
+
+    let mut stream = TcpStream::connect(addr).await?;
+    let mut messages = decode_with_options(stream.clone(), decode_options());
+
+    // Announce only the hash of the delete post.
+    send_post(&cable, &mut stream, &mut messages, &delete).await?;
+
+    /* PENDING */
+
+    // The deletion is not verified until the text post arrives.
+    let mut store = cable.store.clone();
+    assert_eq!(store.get_tombstone(&text_hash).await, None);
+    assert_eq!(
+        store.get_pending_tombstones(&text_hash).await,
+        Some(vec![(public_key, delete_hash)])
+    );
+    assert_eq!(store.get_delete_hashes(&public_key).await, None);
+    assert_eq!(store.want(&[text_hash]).await, vec![text_hash]);
+
+    /* REFUSE */
+
+    // The deleted post is refused when it arrives later.
+    store.insert_post(&text).await?;
+    assert!(store.get_post_payload(&text_hash).await.is_none());
+    assert_eq!(store.get_tombstone(&text_hash).await, Some(public_key));
+    assert_eq!(store.get_pending_tombstones(&text_hash).await, None);
+    assert_eq!(
+        store.get_delete_hashes(&public_key).await,
+        Some(vec![delete_hash])
+    );
+    assert!(store.want(&[text_hash]).await.is_empty());
+
+    /* UNAUTHORISED */
+
+    // A third party deletes a post by another author which has not been
+    // seen yet.
+    let (other_public_key, other_secret_key) =
+        MemoryStore::default().get_keypair().await.expect("keypair");
+    let mut other_text = Post::text(public_key, Vec::new(), 3000, "myco".into(), "hypha".into());
+    other_text.sign(&secret_key)?;
+    let other_text_hash = other_text.hash()?;
+    let mut other_delete = Post::delete(other_public_key, Vec::new(), 4000, vec![other_text_hash]);
+    other_delete.sign(&other_secret_key)?;
+    let other_delete_hash = other_delete.hash()?;
+
+    send_post(&cable, &mut stream, &mut messages, &other_delete).await?;
+    assert_eq!(
+        store.get_pending_tombstones(&other_text_hash).await,
+        Some(vec![(other_public_key, other_delete_hash)])
+    );
+    assert_eq!(store.want(&[other_text_hash]).await, vec![other_text_hash]);
+
+    // The post is still requested and stored when its hash is announced.
+    send_post(&cable, &mut stream, &mut messages, &other_text).await?;
+    assert_eq!(store.get_tombstone(&other_text_hash).await, None);
+    assert_eq!(store.get_pending_tombstones(&other_text_hash).await, None);
+    assert_eq!(store.get_delete_hashes(&other_public_key).await, None);
+
+    /* SERVE */
+
+    let mut stream = TcpStream::connect(addr).await?;
+    let mut messages = decode_with_options(stream.clone(), decode_options());
+
+    let (_req_id, req_id) = cable.new_req_id().await?;
+    let request = Message::post_request(NO_CIRCUIT, req_id, TTL, vec![delete_hash]);
+    stream.write_all(&request.to_bytes()?).await?;
+
+    // The delete post is served, allowing other peers to converge.
+    let msg = read_response(&mut messages, req_id).await?;
+    if let MessageBody::Response {
+        body: ResponseBody::Post { posts },
+    } = msg.body
+    {
+        assert_eq!(posts, vec![delete_bytes]);
+    } else {
+        panic!("expected a post response");
+    }
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn tombstones_persistence() -> Result<(), Error> {
+    init();
+
+    let dir = tempfile::tempdir()?;
+
+    let mut store = SledStore::open(dir.path())?;
+    let (public_key, secret_key) = MemoryStore::default().get_keypair().await.expect("keypair");
+
+    let mut text = Post::text(public_key, Vec::new(), 1000, "myco".into(), "spore".into());
+    text.sign(&secret_key)?;
+    let text_hash = text.hash()?;
+    let mut delete = Post::delete(public_key, Vec::new(), 2000, vec![text_hash]);
+    delete.sign(&secret_key)?;
+    let delete_hash = delete.hash()?;
+
+    store.insert_post(&delete).await?;
+    store.close().await?;
+
+    // Reopen the store from the same directory.
+    let mut store = SledStore::open(dir.path())?;
+    assert_eq!(
+        store.get_pending_tombstones(&text_hash).await,
+        Some(vec![(public_key, delete_hash)])
+    );
+    assert_eq!(store.want(&[text_hash]).await, vec![text_hash]);
+
+    store.insert_post(&text).await?;
+    assert!(store.get_post_payload(&text_hash).await.is_none());
+    assert_eq!(store.get_tombstone(&text_hash).await, Some(public_key));
+    assert_eq!(store.get_pending_tombstones(&text_hash).await, None);
+    assert!(store.want(&[text_hash]).await.is_empty());
+
+    Ok(())
+}
@@ -0,0 +1,168 @@
+//! Test the TTL configuration of the cable manager and the clamping of
+//! inbound request TTLs.
+//!
+//! Run the test with debug logging enabled in a terminal:
+//!
+//! `RUST_LOG=debug cargo test ttl`
+//!
+//! An outline of the actions taken in this test:
+//!
+//! 1) Ensure the TTL configuration returns the default TTL per request type,
+//! never exceeding the maximum TTL, and applies the dynamic adjustment.
+//!
+//! 2) Create a cable manager with a dynamic TTL configuration and connect two
+//! peers to it via TCP.
+//!
+//! 3) Send a channel list request with a TTL of 200 from the first peer and
+//! ensure that the request is forwarded to the second peer with a TTL of 15.
+//!
+//! 4) Open a channel and ensure the channel time range request received by the
+//! first peer has the TTL defined by the configuration.
+
+use std::time::Duration;
+
+use async_std::{
+    future,
+    net::{TcpListener, TcpStream},
+    stream::StreamExt,
+    task,
+};
+use cable::{
+    constants::{
+        CANCEL_REQUEST, CHANNEL_LIST_REQUEST, CHANNEL_TIME_RANGE_REQUEST, MAX_TTL, NO_CIRCUIT,
+        POST_REQUEST,
+    },
+    message::MessageBody,
+    ChannelOptions, Error, Message,
+};
+use desert::{FromBytes, ToBytes};
+use futures::{AsyncWriteExt, Stream};
+use length_prefixed_stream::{decode_with_options, DecodeError, DecodeOptions};
+use log::info;
+
+use cable_core::{CableManager, ManagerConfig, MemoryStore, TtlConfig};
+
+// Initialise the logger in test mode.
+//
+// Set `is_test()` to `false` if you wish to see logging output during the
+// test run.
+fn init() {
+    let _ = env_logger::builder().is_test(true).try_init();
+}
+
+// Define the stream decoder parameters.
+fn decode_options() -> DecodeOptions {
+    DecodeOptions {
+        include_len: true,
+        ..Default::default()
+    }
+}
+
+// Read messages from the given stream until a request of the given type is
+// received, returning the TTL of the request.
+async fn read_request_ttl<T>(messages: &mut T, msg_type: u64) -> Result<u8, Error>
+where
+    T: Stream<Item = Result<Vec<u8>, DecodeError>> + Unpin,
+{
+    while let Some(buf) = messages.next().await {
+        let (_, msg) = Message::from_bytes(&buf?)?;
+        if msg.message_type() == msg_type {
+            if let MessageBody::Request { ttl, .. } = msg.body {
+                return Ok(ttl);
+            }
+        }
+    }
+
+    panic!("stream ended before a matching request was received");
+}
+
+#[async_std::test]
+async fn ttl_config() -> Result<(), Error> {
+    let ttl_config = TtlConfig::default();
+    assert_eq!(ttl_config.ttl(POST_REQUEST, 0), 1);
+    assert_eq!(ttl_config.ttl(CANCEL_REQUEST, 3), 1);
+
+    // The TTL never exceeds the maximum.
+    let ttl_config = TtlConfig::uniform(40);
+    assert_eq!(ttl_config.ttl(CHANNEL_LIST_REQUEST, 0), MAX_TTL);
+
+    // Increase the TTL when few peers are connected.
+    let ttl_config = TtlConfig {
+        channel_time_range: 2,
+        ..TtlConfig::default()
+    }
+    .with_dynamic(|ttl, peer_count| if peer_count < 3 { ttl * 4 } else { ttl });
+    assert_eq!(ttl_config.ttl(CHANNEL_TIME_RANGE_REQUEST, 2), 8);
+    assert_eq!(ttl_config.ttl(CHANNEL_TIME_RANGE_REQUEST, 3), 2);
+    assert_eq!(ttl_config.ttl(POST_REQUEST, 1), 4);
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn ttl_forwarding() -> Result<(), Error> {
+    init();
+
+    let config = ManagerConfig {
+        ttl: TtlConfig {
+            channel_time_range: 2,
+            ..TtlConfig::default()
+        }
+        .with_dynamic(|ttl, peer_count| ttl + peer_count as u8),
+        ..ManagerConfig::default()
+    };
+    let mut cable = CableManager::with_config(MemoryStore::default(), config);
+
+    // Deploy a TCP listener.
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    info!("Deployed TCP server on {}", addr);
+
+    let cable_clone = cable.clone();
+    task::spawn(async move {
+        let mut incoming = listener.incoming();
+        while let Some(Ok(stream)) = incoming.next().await {
+            let cable = cable_clone.clone();
+            task::spawn(async move {
+                let _ = cable.listen(stream).await;
+            });
+        }
+    });
+
+    // Connect two peers and wait for both connections to be registered.
+    let mut stream_1 = TcpStream::connect(addr).await?;
+    let mut messages_1 = decode_with_options(stream_1.clone(), decode_options());
+    let stream_2 = TcpStream::connect(addr).await?;
+    let mut messages_2 = decode_with_options(stream_2, decode_options());
+    future::timeout(Duration::from_secs(5), async {
+        while cable.get_peer_ids().await.len() < 2 {
+            task::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await?;
+
+    // Send a request with an excessive TTL from the first peer.
+    let (_req_id, req_id_bytes) = cable.new_req_id().await?;
+    let request = Message::channel_list_request(NO_CIRCUIT, req_id_bytes, 200, 0, 0);
+    stream_1.write_all(&request.to_bytes()?).await?;
+
+    // The request is forwarded with a clamped and decremented TTL.
+    let ttl = future::timeout(
+        Duration::from_secs(5),
+        read_request_ttl(&mut messages_2, CHANNEL_LIST_REQUEST),
+    )
+    .await??;
+    assert_eq!(ttl, MAX_TTL - 1);
+
+    // The TTL of outbound requests is defined by the configuration.
+    let opts = ChannelOptions::new("myco", 0, 0, 10);
+    let _post_stream = cable.open_channel(&opts).await?;
+    let ttl = future::timeout(
+        Duration::from_secs(5),
+        read_request_ttl(&mut messages_1, CHANNEL_TIME_RANGE_REQUEST),
+    )
+    .await??;
+    assert_eq!(ttl, 4);
+
+    Ok(())
+}
@@ -0,0 +1,184 @@
+//! Test the storage and relaying of posts of unrecognized types.
+//!
+//! Run the test with debug logging enabled in a terminal:
+//!
+//! `RUST_LOG=debug cargo test unrecognized_posts`
+//!
+//! An outline of the actions taken in this test:
+//!
+//! 1) Create a signed post of an unrecognized type and a cable manager.
+//! Connect to the manager as a raw TCP peer.
+//!
+//! 2) Send a hash response containing the hash of the post. Ensure that the
+//! manager requests the post and respond with the encoded post.
+//!
+//! 3) Ensure that the encoded post is stored unchanged.
+//!
+//! 4) Connect to the manager as a second raw TCP peer and request the post.
+//! Ensure that the encoded post is served unchanged.
+
+use std::time::Duration;
+
+use async_std::{
+    future,
+    net::{TcpListener, TcpStream},
+    stream::StreamExt,
+    task,
+};
+use cable::{
+    constants::{NO_CIRCUIT, POST_REQUEST},
+    message::{MessageBody, RequestBody, ResponseBody},
+    post::{PostBody, PostHeader},
+    Error, Message, Post, ReqId,
+};
+use desert::{FromBytes, ToBytes};
+use futures::{AsyncWriteExt, Stream};
+use length_prefixed_stream::{decode_with_options, DecodeError, DecodeOptions};
+use log::info;
+
+use cable_core::{CableManager, MemoryStore, Store};
+
+const TTL: u8 = 0;
+
+// Initialise the logger in test mode.
+//
+// Set `is_test()` to `false` if you wish to see logging output during the
+// test run.
+fn init() {
+    let _ = env_logger::builder().is_test(true).try_init();
+}
+
+// Define the stream decoder parameters.
+fn decode_options() -> DecodeOptions {
+    DecodeOptions {
+        include_len: true,
+        ..Default::default()
+    }
+}
+
+// Read messages from the given stream until a message of the given type is
+// received.
+async fn read_message_of_type<T>(messages: &mut T, msg_type: u64) -> Result<Message, Error>
+where
+    T: Stream<Item = Result<Vec<u8>, DecodeError>> + Unpin,
+{
+    loop {
+        let buf = future::timeout(Duration::from_secs(5), messages.next())
+            .await?
+            .expect("stream closed")?;
+        let (_bytes_len, msg) = Message::from_bytes(&buf)?;
+
+        if msg.message_type() == msg_type {
+            return Ok(msg);
+        }
+    }
+}
+
+// Read messages from the given stream until a response to the request with
+// the given ID is received.
+async fn read_response<T>(messages: &mut T, req_id: ReqId) -> Result<Message, Error>
+where
+    T: Stream<Item = Result<Vec<u8>, DecodeError>> + Unpin,
+{
+    loop {
+        let buf = future::timeout(Duration::from_secs(5), messages.next())
+            .await?
+            .expect("stream closed")?;
+        let (_bytes_len, msg) = Message::from_bytes(&buf)?;
+
+        if msg.header.req_id == req_id {
+            return Ok(msg);
+        }
+    }
+}
+
+#[async_std::test]
+async fn unrecognized_posts() -> Result<(), Error> {
+    init();
+
+    // Create and sign a post of an unrecognized type.
+    let (public_key, secret_key) = MemoryStore::default().get_keypair().await.expect("keypair");
+    let header = PostHeader::new(public_key, [0; 64], Vec::new(), 42, 80);
+    let body = PostBody::Unrecognized {
+        post_type: 42,
+        body: b"\x05spore\x01".to_vec(),
+    };
+    let mut post = Post::new(header, body);
+    post.sign(&secret_key)?;
+    let post_bytes = post.to_bytes()?;
+    let post_hash = post.hash()?;
+
+    let cable = CableManager::new(MemoryStore::default());
+    let cable_clone = cable.clone();
+
+    // Deploy a TCP listener and pass inbound streams to the cable manager.
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    info!("Deployed TCP server on {}", addr);
+
+    task::spawn(async move {
+        let mut incoming = listener.incoming();
+        while let Some(Ok(stream)) = incoming.next().await {
+            let cable = cable_clone.clone();
+            task::spawn(async move {
+                let _ = cable.listen(stream).await;
+            });
+        }
+    });
+
+    /* RECEIVE */
+
+    let mut stream = TcpStream::connect(addr).await?;
+    let mut messages = decode_with_options(stream.clone(), decode_options());
+
+    // Announce the hash of the post.
+    let (_req_id, req_id) = cable.new_req_id().await?;
+    let response = Message::hash_response(NO_CIRCUIT, req_id, vec![post_hash]);
+    stream.write_all(&response.to_bytes()?).await?;
+
+    // Respond to the post request with the encoded post.
+    let msg = read_message_of_type(&mut messages, POST_REQUEST).await?;
+    if let MessageBody::Request {
+        body: RequestBody::Post { hashes },
+        ..
+    } = &msg.body
+    {
+        assert_eq!(hashes, &vec![post_hash]);
+    }
+    let response = Message::post_response(NO_CIRCUIT, msg.header.req_id, vec![post_bytes.clone()]);
+    stream.write_all(&response.to_bytes()?).await?;
+
+    // The encoded post is stored unchanged.
+    future::timeout(Duration::from_secs(5), async {
+        while cable.store.get_post_payload(&post_hash).await.is_none() {
+            task::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await?;
+    assert_eq!(
+        cable.store.get_post_payload(&post_hash).await,
+        Some(post_bytes.clone())
+    );
+
+    /* SERVE */
+
+    let mut stream = TcpStream::connect(addr).await?;
+    let mut messages = decode_with_options(stream.clone(), decode_options());
+
+    let (_req_id, req_id) = cable.new_req_id().await?;
+    let request = Message::post_request(NO_CIRCUIT, req_id, TTL, vec![post_hash]);
+    stream.write_all(&request.to_bytes()?).await?;
+
+    // The encoded post is served unchanged.
+    let msg = read_response(&mut messages, req_id).await?;
+    if let MessageBody::Response {
+        body: ResponseBody::Post { posts },
+    } = msg.body
+    {
+        assert_eq!(posts, vec![post_bytes]);
+    } else {
+        panic!("expected a post response");
+    }
+
+    Ok(())
+}
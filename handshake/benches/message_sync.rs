@@ -15,21 +15,23 @@ use std::{
 
 use cable_handshake::{sync::handshake, Result, Version};
 use criterion::{criterion_group, criterion_main, Criterion};
-use snow::Builder as NoiseBuilder;
+use sodiumoxide::crypto::sign;
 
 const SOCKET_PATH: &str = "/tmp/handshake.sock";
 
-fn setup() -> Result<(Version, Version, [u8; 32], Vec<u8>, Vec<u8>)> {
+/// An ed25519 secret key.
+type PrivateKey = [u8; 64];
+
+fn setup() -> Result<(Version, Version, [u8; 32], PrivateKey, PrivateKey)> {
     let client_version = Version::init(1, 0);
     let server_version = Version::init(1, 0);
 
     let psk: [u8; 32] = [1; 32];
 
-    let builder = NoiseBuilder::new("Noise_XXpsk0_25519_ChaChaPoly_BLAKE2b".parse()?);
-    let client_keypair = builder.generate_keypair()?;
-    let server_keypair = builder.generate_keypair()?;
-    let client_private_key = client_keypair.private;
-    let server_private_key = server_keypair.private;
+    let (_client_public_key, client_secret_key) = sign::gen_keypair();
+    let (_server_public_key, server_secret_key) = sign::gen_keypair();
+    let client_private_key = client_secret_key.as_ref().try_into()?;
+    let server_private_key = server_secret_key.as_ref().try_into()?;
 
     Ok((
         client_version,
@@ -44,8 +46,8 @@ fn message(
     client_version: Version,
     server_version: Version,
     psk: [u8; 32],
-    client_private_key: Vec<u8>,
-    server_private_key: Vec<u8>,
+    client_private_key: PrivateKey,
+    server_private_key: PrivateKey,
 ) -> Result<()> {
     // Deploy a Unix socket listener.
     let listener = UnixListener::bind(SOCKET_PATH)?;
@@ -109,8 +111,8 @@ fn criterion_benchmark(c: &mut Criterion) {
                 client_version.clone(),
                 server_version.clone(),
                 psk.clone(),
-                client_private_key.clone(),
-                server_private_key.clone(),
+                client_private_key,
+                server_private_key,
             )
         })
     });
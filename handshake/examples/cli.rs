@@ -9,7 +9,7 @@ use std::{env, fs, io::Write};
 
 use cable_handshake::{sync::handshake, Result, Version};
 use io_streams::StreamDuplexer;
-use snow::Builder as NoiseBuilder;
+use sodiumoxide::crypto::sign;
 
 fn help() {
     println!(
@@ -23,14 +23,13 @@ fn help() {
     );
 }
 
-fn setup() -> Result<(Version, Vec<u8>, StreamDuplexer)> {
+fn setup() -> Result<(Version, [u8; 64], StreamDuplexer)> {
     // Define handshake version.
     let version = Version::init(1, 0);
 
     // Generate keypair.
-    let builder = NoiseBuilder::new("Noise_XXpsk0_25519_ChaChaPoly_BLAKE2b".parse()?);
-    let keypair = builder.generate_keypair()?;
-    let private_key = keypair.private;
+    let (_public_key, secret_key) = sign::gen_keypair();
+    let private_key = secret_key.as_ref().try_into()?;
 
     // Construct a duplex stream.
     let stream = StreamDuplexer::stdin_stdout()?;
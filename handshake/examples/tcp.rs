@@ -11,7 +11,7 @@ use std::{
 };
 
 use cable_handshake::{sync::handshake, Result, Version};
-use snow::Builder as NoiseBuilder;
+use sodiumoxide::crypto::sign;
 
 fn help() {
     println!(
@@ -24,7 +24,7 @@ tcp {{-s|--server}}
     );
 }
 
-fn setup() -> Result<(Version, [u8; 32], Vec<u8>)> {
+fn setup() -> Result<(Version, [u8; 32], [u8; 64])> {
     // Define handshake version.
     let version = Version::init(1, 0);
 
@@ -32,9 +32,8 @@ fn setup() -> Result<(Version, [u8; 32], Vec<u8>)> {
     let psk: [u8; 32] = [1; 32];
 
     // Generate keypair.
-    let builder = NoiseBuilder::new("Noise_XXpsk0_25519_ChaChaPoly_BLAKE2b".parse()?);
-    let keypair = builder.generate_keypair()?;
-    let private_key = keypair.private;
+    let (_public_key, secret_key) = sign::gen_keypair();
+    let private_key = secret_key.as_ref().try_into()?;
 
     Ok((version, psk, private_key))
 }
@@ -62,8 +61,8 @@ fn run_client() -> Result<()> {
     println!("Initiating handshake...");
     let mut encrypted = handshake::client(&mut stream, version, psk, private_key)?;
 
-    // Return the public key of the remote peer (as bytes).
-    if let Some(key) = encrypted.get_remote_public_key() {
+    // Return the static key of the remote peer (as bytes).
+    if let Some(key) = encrypted.get_remote_static_key() {
         println!("Completed handshake with {:?}", key)
     }
 
@@ -12,7 +12,7 @@ use std::{
 };
 
 use cable_handshake::{sync::handshake, Result, Version};
-use snow::Builder as NoiseBuilder;
+use sodiumoxide::crypto::sign;
 
 const SOCKET_PATH: &str = "/tmp/handshake.sock";
 
@@ -27,7 +27,7 @@ unix {{-s|--server}}
     );
 }
 
-fn setup() -> Result<(Version, [u8; 32], Vec<u8>)> {
+fn setup() -> Result<(Version, [u8; 32], [u8; 64])> {
     // Define handshake version.
     let version = Version::init(1, 0);
 
@@ -35,9 +35,8 @@ fn setup() -> Result<(Version, [u8; 32], Vec<u8>)> {
     let psk: [u8; 32] = [1; 32];
 
     // Generate keypair.
-    let builder = NoiseBuilder::new("Noise_XXpsk0_25519_ChaChaPoly_BLAKE2b".parse()?);
-    let keypair = builder.generate_keypair()?;
-    let private_key = keypair.private;
+    let (_public_key, secret_key) = sign::gen_keypair();
+    let private_key = secret_key.as_ref().try_into()?;
 
     Ok((version, psk, private_key))
 }
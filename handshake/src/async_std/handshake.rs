@@ -5,23 +5,38 @@
 use futures_util::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
 use crate::{
-    constants::{
-        EPHEMERAL_AND_STATIC_KEY_BYTES_LEN, EPHEMERAL_KEY_BYTES_LEN, STATIC_KEY_BYTES_LEN,
-        VERSION_BYTES_LEN,
-    },
-    Handshake, HandshakeComplete, Result, Version,
+    constants::{EPHEMERAL_KEY_BYTES_LEN, VERSION_BYTES_LEN},
+    Extensions, Handshake, HandshakeComplete, HandshakeError, Result, Version,
 };
 
 /// Initiate the handshake over an asynchronous stream and run to completion.
+///
+/// The `private_key` is the ed25519 secret key of the cable keypair belonging
+/// to the local peer.
 pub async fn client<T: AsyncRead + AsyncWrite + Unpin>(
     stream: &mut T,
     version: Version,
     psk: [u8; 32],
-    private_key: Vec<u8>,
+    private_key: [u8; 64],
+) -> Result<Handshake<HandshakeComplete>> {
+    client_with_extensions(stream, version, psk, private_key, Extensions::default()).await
+}
+
+/// Initiate the handshake over an asynchronous stream with the given protocol
+/// extensions enabled and run to completion.
+///
+/// The handshake can only be completed if the server enables the same
+/// extensions.
+pub async fn client_with_extensions<T: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut T,
+    version: Version,
+    psk: [u8; 32],
+    private_key: [u8; 64],
+    extensions: Extensions,
 ) -> Result<Handshake<HandshakeComplete>> {
     let mut buf = [0; 256];
 
-    let handshake = Handshake::new_client(version, psk, private_key);
+    let handshake = Handshake::new_client(version, psk, private_key, extensions);
 
     // Send version.
     let send_buf = &mut buf[..VERSION_BYTES_LEN];
@@ -42,12 +57,12 @@ pub async fn client<T: AsyncRead + AsyncWrite + Unpin>(
     stream.write_all(send_buf).await?;
 
     // Receive ephemeral and static keys.
-    let recv_buf = &mut buf[..EPHEMERAL_AND_STATIC_KEY_BYTES_LEN];
+    let recv_buf = &mut buf[..extensions.ephemeral_and_static_key_len()];
     stream.read_exact(recv_buf).await?;
     let handshake = handshake.recv_server_ephemeral_and_static_key(recv_buf)?;
 
     // Send static key.
-    let send_buf = &mut buf[..STATIC_KEY_BYTES_LEN];
+    let send_buf = &mut buf[..extensions.static_key_len()];
     let handshake = handshake.send_client_static_key(send_buf)?;
     stream.write_all(send_buf).await?;
 
@@ -57,15 +72,54 @@ pub async fn client<T: AsyncRead + AsyncWrite + Unpin>(
 }
 
 /// Respond to a handshake over an asynchronous stream and run to completion.
+///
+/// The `private_key` is the ed25519 secret key of the cable keypair belonging
+/// to the local peer.
 pub async fn server<T: AsyncRead + AsyncWrite + Unpin>(
     stream: &mut T,
     version: Version,
     psk: [u8; 32],
-    private_key: Vec<u8>,
+    private_key: [u8; 64],
 ) -> Result<Handshake<HandshakeComplete>> {
+    server_with_extensions(stream, version, psk, private_key, Extensions::default()).await
+}
+
+/// Respond to a handshake over an asynchronous stream with the given protocol
+/// extensions enabled and run to completion.
+///
+/// The handshake can only be completed if the client enables the same
+/// extensions.
+pub async fn server_with_extensions<T: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut T,
+    version: Version,
+    psk: [u8; 32],
+    private_key: [u8; 64],
+    extensions: Extensions,
+) -> Result<Handshake<HandshakeComplete>> {
+    let (_index, handshake) =
+        server_with_keys(stream, version, &[(psk, private_key)], extensions).await?;
+
+    Ok(handshake)
+}
+
+/// Respond to a handshake over an asynchronous stream using whichever of the given
+/// PSK and private key pairs matches the PSK of the client, and run to
+/// completion.
+///
+/// Returns the index of the selected pair along with the completed handshake.
+/// This allows a single server to accept connections for several cabals, each
+/// with its own cabal key (PSK) and keypair. The given protocol extensions
+/// are enabled for the handshake.
+pub async fn server_with_keys<T: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut T,
+    version: Version,
+    keys: &[([u8; 32], [u8; 64])],
+    extensions: Extensions,
+) -> Result<(usize, Handshake<HandshakeComplete>)> {
     let mut buf = [0; 256];
 
-    let handshake = Handshake::new_server(version, psk, private_key);
+    let (psk, private_key) = keys.first().ok_or(HandshakeError::UnrecognizedPsk)?;
+    let handshake = Handshake::new_server(version, *psk, *private_key, extensions);
 
     // Receive version.
     let recv_buf = &mut buf[..VERSION_BYTES_LEN];
@@ -77,25 +131,23 @@ pub async fn server<T: AsyncRead + AsyncWrite + Unpin>(
     let handshake = handshake.send_server_version(send_buf)?;
     stream.write_all(send_buf).await?;
 
-    // Build Noise state machine.
-    let handshake = handshake.build_server_noise_state_machine()?;
-
-    // Receive ephemeral key.
+    // Receive ephemeral key, building a Noise state machine for each of the
+    // given keys until one is found which matches the client PSK.
     let recv_buf = &mut buf[..EPHEMERAL_KEY_BYTES_LEN];
     stream.read_exact(recv_buf).await?;
-    let handshake = handshake.recv_client_ephemeral_key(recv_buf)?;
+    let (index, handshake) = handshake.recv_client_ephemeral_key_with_keys(keys, recv_buf)?;
 
     // Send ephemeral and static keys.
-    let send_buf = &mut buf[..EPHEMERAL_AND_STATIC_KEY_BYTES_LEN];
+    let send_buf = &mut buf[..extensions.ephemeral_and_static_key_len()];
     let handshake = handshake.send_server_ephemeral_and_static_key(send_buf)?;
     stream.write_all(send_buf).await?;
 
     // Receive static key.
-    let recv_buf = &mut buf[..STATIC_KEY_BYTES_LEN];
+    let recv_buf = &mut buf[..extensions.static_key_len()];
     stream.read_exact(recv_buf).await?;
     let handshake = handshake.recv_client_static_key(recv_buf)?;
 
     let handshake = handshake.init_server_transport_mode()?;
 
-    Ok(handshake)
+    Ok((index, handshake))
 }
@@ -12,6 +12,17 @@ use std::{
 pub enum HandshakeError {
     /// The received major server version does not match that of the client.
     IncompatibleServerVersion { received: u8, expected: u8 },
+    /// The given private key is not a valid ed25519 private key.
+    InvalidPrivateKey,
+    /// The public key received from the remote peer is not a valid ed25519
+    /// public key or does not match the remote static key.
+    InvalidRemotePublicKey,
+    /// The length specifier of a received message exceeds the maximum
+    /// message length.
+    MessageTooLong { len: u32, max_len: u32 },
+    /// The ephemeral key received from the client could not be decrypted
+    /// using any of the PSKs known to the server.
+    UnrecognizedPsk,
 }
 
 impl Error for HandshakeError {}
@@ -26,6 +37,25 @@ impl Display for HandshakeError {
                     received, expected
                 )
             }
+            HandshakeError::InvalidPrivateKey => {
+                write!(f, "Private key is not a valid ed25519 private key")
+            }
+            HandshakeError::InvalidRemotePublicKey => {
+                write!(
+                    f,
+                    "Received public key is invalid or does not match the remote static key"
+                )
+            }
+            HandshakeError::MessageTooLong { len, max_len } => {
+                write!(
+                    f,
+                    "Received message length `{}` exceeds maximum message length `{}`",
+                    len, max_len
+                )
+            }
+            HandshakeError::UnrecognizedPsk => {
+                write!(f, "Received ephemeral key does not match any known PSK")
+            }
         }
     }
 }
@@ -19,10 +19,11 @@ use snow::{
     Builder as NoiseBuilder, HandshakeState as NoiseHandshakeState,
     TransportState as NoiseTransportState,
 };
+use sodiumoxide::crypto::sign::{self, ed25519};
 
 use constants::{
-    EPHEMERAL_AND_STATIC_KEY_BYTES_LEN, EPHEMERAL_KEY_BYTES_LEN, PUBLIC_KEY_BYTES_LEN,
-    STATIC_KEY_BYTES_LEN,
+    EPHEMERAL_AND_STATIC_KEY_BYTES_LEN, EPHEMERAL_KEY_BYTES_LEN, IDENTITY_PAYLOAD_BYTES_LEN,
+    PUBLIC_KEY_BYTES_LEN, STATIC_KEY_BYTES_LEN,
 };
 
 pub use crate::{error::HandshakeError, version::Version};
@@ -32,21 +33,46 @@ pub type Result<T> = std::result::Result<T, Error>;
 
 /// The initialization data of a handshake that exists in every state of the
 /// handshake.
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct HandshakeBase {
     /// The handshake protocol version.
     version: Version,
     /// The pre-shared key (aka. the "cabal key").
     psk: [u8; 32],
-    // TODO: Could this rather be a sized array?
-    //private_key: [u8; 64],
-    /// The private key of the cabal keypair belonging to the handshaker.
-    private_key: Vec<u8>,
-    /// The public key of the remote peer with whom the handshake has
+    /// The ed25519 private key of the cabal keypair belonging to the
+    /// handshaker.
+    private_key: [u8; 64],
+    /// The protocol extensions enabled by the handshaker.
+    extensions: Extensions,
+    /// The ed25519 public key of the remote peer with whom the handshake has
     /// been conducted.
+    ///
+    /// Only known if the identity payload extension is enabled.
     pub remote_public_key: Option<[u8; PUBLIC_KEY_BYTES_LEN]>,
 }
 
+impl HandshakeBase {
+    /// Return the payload to be sent along with the static key of the
+    /// handshaker.
+    fn payload(&self) -> &[u8] {
+        if self.extensions.identity_payload {
+            public_key(&self.private_key)
+        } else {
+            &[]
+        }
+    }
+
+    /// Process the payload received along with the static key of the remote
+    /// peer.
+    fn recv_payload(&mut self, payload: &[u8], remote_static: Option<&[u8]>) -> Result<()> {
+        if self.extensions.identity_payload {
+            self.remote_public_key = Some(verify_remote_public_key(payload, remote_static)?);
+        }
+
+        Ok(())
+    }
+}
+
 /// The `Handshake` type maintains the different states that happen in each
 /// step of the handshake, allowing it to advance to completion.
 ///
@@ -58,12 +84,55 @@ pub struct Handshake<S: State> {
 }
 
 /// The role taken by a peer during the handshake.
-#[derive(Debug)]
-enum Role {
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Role {
+    /// The peer initiating the handshake (the client).
     Initiator,
+    /// The peer responding to the handshake (the server).
     Responder,
 }
 
+/// Optional extensions of the handshake protocol.
+///
+/// Extensions change the messages exchanged during the handshake and are not
+/// part of the specification. An extension must therefore be enabled by both
+/// peers; a peer enabling an extension is unable to complete a handshake with
+/// a peer which does not. All extensions are disabled by default, in which
+/// case the handshake conforms to the specification.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Extensions {
+    /// Send the ed25519 public key of the handshaker as the (encrypted)
+    /// payload of its static key message. The received public key is verified
+    /// against the static key of the remote peer and made available via
+    /// `get_remote_public_key()`.
+    ///
+    /// This extends the second and third handshake messages by 32 bytes each.
+    pub identity_payload: bool,
+}
+
+impl Extensions {
+    /// Return the size of the payload sent along with each static key.
+    fn payload_len(&self) -> usize {
+        if self.identity_payload {
+            IDENTITY_PAYLOAD_BYTES_LEN
+        } else {
+            0
+        }
+    }
+
+    /// Return the size of the message containing the ephemeral and static
+    /// keys of the server.
+    fn ephemeral_and_static_key_len(&self) -> usize {
+        EPHEMERAL_AND_STATIC_KEY_BYTES_LEN + self.payload_len()
+    }
+
+    /// Return the size of the message containing the static key of the
+    /// client.
+    fn static_key_len(&self) -> usize {
+        STATIC_KEY_BYTES_LEN + self.payload_len()
+    }
+}
+
 // Client states. The client acts as the handshake initiator.
 
 /// The client state that can send the version.
@@ -174,13 +243,63 @@ impl State for ServerInitTransportMode {}
 
 impl State for HandshakeComplete {}
 
+/// Convert the given ed25519 private key to the equivalent X25519 private key
+/// for use as the Noise static key.
+fn to_x25519_private_key(private_key: &[u8; 64]) -> Result<Vec<u8>> {
+    ed25519::SecretKey::from_slice(private_key)
+        .and_then(|sk| sign::to_curve25519_sk(&sk).ok())
+        .map(|sk| sk.as_ref().to_vec())
+        .ok_or_else(|| HandshakeError::InvalidPrivateKey.into())
+}
+
+/// Return the ed25519 public key embedded in the given ed25519 private key.
+fn public_key(private_key: &[u8; 64]) -> &[u8] {
+    &private_key[32..]
+}
+
+/// Query whether the given ed25519 public key corresponds to the given static
+/// key of a remote peer, once converted to X25519.
+fn is_static_key_of(public_key: &[u8], remote_static: Option<&[u8]>) -> bool {
+    let static_key =
+        ed25519::PublicKey::from_slice(public_key).and_then(|pk| sign::to_curve25519_pk(&pk).ok());
+
+    match (static_key, remote_static) {
+        (Some(static_key), Some(remote_static)) => static_key.as_ref() == remote_static,
+        _ => false,
+    }
+}
+
+/// Verify that the ed25519 public key received in the handshake payload
+/// corresponds to the static key of the remote peer, returning the ed25519
+/// public key.
+fn verify_remote_public_key(
+    payload: &[u8],
+    remote_static: Option<&[u8]>,
+) -> Result<[u8; PUBLIC_KEY_BYTES_LEN]> {
+    let remote_public_key: [u8; PUBLIC_KEY_BYTES_LEN] = payload
+        .try_into()
+        .map_err(|_| HandshakeError::InvalidRemotePublicKey)?;
+
+    if !is_static_key_of(&remote_public_key, remote_static) {
+        warn!("Received public key does not match remote static key");
+        return Err(HandshakeError::InvalidRemotePublicKey.into());
+    }
+
+    Ok(remote_public_key)
+}
+
 /// Initialise the Noise handshake state machine according to the given role,
 /// ie. initiator or responder.
+///
+/// The given ed25519 private key is converted to X25519 for use as the Noise
+/// static key.
 fn build_noise_state_machine(
     role: Role,
     psk: [u8; 32],
-    private_key: Vec<u8>,
+    private_key: &[u8; 64],
 ) -> Result<NoiseHandshakeState> {
+    let private_key = to_x25519_private_key(private_key)?;
+
     let handshake_state = match role {
         Role::Initiator => NoiseBuilder::new("Noise_XXpsk0_25519_ChaChaPoly_BLAKE2b".parse()?)
             .local_private_key(&private_key)
@@ -204,12 +323,14 @@ impl Handshake<ClientSendVersion> {
     fn new_client(
         version: Version,
         psk: [u8; 32],
-        private_key: Vec<u8>,
+        private_key: [u8; 64],
+        extensions: Extensions,
     ) -> Handshake<ClientSendVersion> {
         let base = HandshakeBase {
             version,
             psk,
             private_key,
+            extensions,
             remote_public_key: None,
         };
         let state = ClientSendVersion;
@@ -264,12 +385,8 @@ impl Handshake<ClientBuildNoiseStateMachine> {
     /// Build the Noise handshake state machine for the client with the PSK and
     /// private key.
     fn build_client_noise_state_machine(self) -> Result<Handshake<ClientSendEphemeralKey>> {
-        let noise_state_machine = build_noise_state_machine(
-            Role::Initiator,
-            self.base.psk,
-            // TODO: Can we avoid this clone?
-            self.base.private_key.clone(),
-        )?;
+        let noise_state_machine =
+            build_noise_state_machine(Role::Initiator, self.base.psk, &self.base.private_key)?;
 
         let state = ClientSendEphemeralKey(noise_state_machine);
         let handshake = Handshake {
@@ -311,17 +428,15 @@ impl Handshake<ClientRecvEphemeralAndStaticKey> {
         mut self,
         recv_buf: &mut [u8],
     ) -> Result<Handshake<ClientSendStaticKey>> {
-        let mut read_buf = [0u8; EPHEMERAL_AND_STATIC_KEY_BYTES_LEN];
+        let mut read_buf = [0u8; EPHEMERAL_AND_STATIC_KEY_BYTES_LEN + IDENTITY_PAYLOAD_BYTES_LEN];
 
-        // Receive the ephemeral and static keys from the server.
-        self.state.0.read_message(recv_buf, &mut read_buf)?;
+        // Receive the ephemeral and static keys from the server, along with
+        // the payload of the server.
+        let len = self.state.0.read_message(recv_buf, &mut read_buf)?;
 
-        // Set the value of the server's public key.
-        self.base.remote_public_key = match self.state.0.get_remote_static() {
-            // Convert the key from a slice (`&[u8]`) to a sized array.
-            Some(key) => Some(key.try_into()?),
-            None => None,
-        };
+        // Set the value of the server's public key (if sent as payload).
+        self.base
+            .recv_payload(&read_buf[..len], self.state.0.get_remote_static())?;
 
         let state = ClientSendStaticKey(self.state.0);
         let handshake = Handshake {
@@ -339,10 +454,14 @@ impl Handshake<ClientSendStaticKey> {
         mut self,
         send_buf: &mut [u8],
     ) -> Result<Handshake<ClientInitTransportMode>> {
-        let mut write_buf = [0u8; STATIC_KEY_BYTES_LEN];
+        let mut write_buf = [0u8; STATIC_KEY_BYTES_LEN + IDENTITY_PAYLOAD_BYTES_LEN];
 
-        // Send the client static key to the server.
-        let len = self.state.0.write_message(&[], &mut write_buf)?;
+        // Send the client static key to the server, along with the payload of
+        // the client.
+        let len = self
+            .state
+            .0
+            .write_message(self.base.payload(), &mut write_buf)?;
 
         concat_into!(send_buf, &write_buf[..len]);
 
@@ -378,12 +497,14 @@ impl Handshake<ServerRecvVersion> {
     fn new_server(
         version: Version,
         psk: [u8; 32],
-        private_key: Vec<u8>,
+        private_key: [u8; 64],
+        extensions: Extensions,
     ) -> Handshake<ServerRecvVersion> {
         let base = HandshakeBase {
             version,
             psk,
             private_key,
+            extensions,
             remote_public_key: None,
         };
         let state = ServerRecvVersion;
@@ -433,11 +554,8 @@ impl Handshake<ServerBuildNoiseStateMachine> {
     /// Build the Noise handshake state machine for the server with the PSK and
     /// private key.
     fn build_server_noise_state_machine(self) -> Result<Handshake<ServerRecvEphemeralKey>> {
-        let noise_state_machine = build_noise_state_machine(
-            Role::Responder,
-            self.base.psk,
-            self.base.private_key.clone(),
-        )?;
+        let noise_state_machine =
+            build_noise_state_machine(Role::Responder, self.base.psk, &self.base.private_key)?;
 
         let state = ServerRecvEphemeralKey(noise_state_machine);
         let handshake = Handshake {
@@ -449,6 +567,44 @@ impl Handshake<ServerBuildNoiseStateMachine> {
     }
 }
 
+impl Handshake<ServerBuildNoiseStateMachine> {
+    /// Build a Noise handshake state machine for each of the given PSK and
+    /// private key pairs in turn, attempting to receive the client ephemeral
+    /// key with each one. Advance to the next server state using the first
+    /// pair for which the ephemeral key message can be decrypted, returning
+    /// the index of that pair.
+    ///
+    /// This allows a server to respond to handshakes for several cabals
+    /// without knowing in advance which cabal key the client is using.
+    fn recv_client_ephemeral_key_with_keys(
+        self,
+        keys: &[([u8; 32], [u8; 64])],
+        recv_buf: &mut [u8],
+    ) -> Result<(usize, Handshake<ServerSendEphemeralAndStaticKey>)> {
+        for (index, (psk, private_key)) in keys.iter().enumerate() {
+            let handshake = Handshake {
+                base: HandshakeBase {
+                    psk: *psk,
+                    private_key: *private_key,
+                    ..self.base.clone()
+                },
+                state: ServerBuildNoiseStateMachine,
+            };
+
+            let handshake = handshake.build_server_noise_state_machine()?;
+
+            // The message can only be decrypted using the PSK of the client.
+            if let Ok(handshake) = handshake.recv_client_ephemeral_key(recv_buf) {
+                return Ok((index, handshake));
+            }
+        }
+
+        warn!("Received ephemeral key encrypted with an unrecognized PSK");
+
+        Err(HandshakeError::UnrecognizedPsk.into())
+    }
+}
+
 impl Handshake<ServerRecvEphemeralKey> {
     /// Receive the ephemeral key from the client and advance to the next server state.
     fn recv_client_ephemeral_key(
@@ -479,8 +635,12 @@ impl Handshake<ServerSendEphemeralAndStaticKey> {
     ) -> Result<Handshake<ServerRecvStaticKey>> {
         let mut write_buf = [0u8; 1024];
 
-        // Send the ephemeral and static keys to the client.
-        let len = self.state.0.write_message(&[], &mut write_buf)?;
+        // Send the ephemeral and static keys to the client, along with the
+        // payload of the server.
+        let len = self
+            .state
+            .0
+            .write_message(self.base.payload(), &mut write_buf)?;
 
         concat_into!(send_buf, &write_buf[..len]);
 
@@ -503,15 +663,13 @@ impl Handshake<ServerRecvStaticKey> {
     ) -> Result<Handshake<ServerInitTransportMode>> {
         let mut read_buf = [0u8; 1024];
 
-        // Receive the static key to the client.
-        self.state.0.read_message(recv_buf, &mut read_buf)?;
+        // Receive the static key from the client, along with the payload of
+        // the client.
+        let len = self.state.0.read_message(recv_buf, &mut read_buf)?;
 
-        // Set the value of the client's static key.
-        self.base.remote_public_key = match self.state.0.get_remote_static() {
-            // Convert the key from a slice (`&[u8]`) to a sized array.
-            Some(key) => Some(key.try_into()?),
-            None => None,
-        };
+        // Set the value of the client's public key (if sent as payload).
+        self.base
+            .recv_payload(&read_buf[..len], self.state.0.get_remote_static())?;
 
         let state = ServerInitTransportMode(self.state.0);
         let handshake = Handshake {
@@ -540,32 +698,28 @@ impl Handshake<ServerInitTransportMode> {
 
 #[cfg(test)]
 mod tests {
-    use constants::{
-        EPHEMERAL_AND_STATIC_KEY_BYTES_LEN, EPHEMERAL_KEY_BYTES_LEN, STATIC_KEY_BYTES_LEN,
-        VERSION_BYTES_LEN,
-    };
+    use constants::{EPHEMERAL_KEY_BYTES_LEN, VERSION_BYTES_LEN};
 
     use super::*;
 
     fn init_handshakers(
         client_version: (u8, u8),
         server_version: (u8, u8),
+        extensions: Extensions,
     ) -> Result<(Handshake<ClientSendVersion>, Handshake<ServerRecvVersion>)> {
         let psk: [u8; 32] = [1; 32];
 
-        let builder = NoiseBuilder::new("Noise_XXpsk0_25519_ChaChaPoly_BLAKE2b".parse()?);
+        let (_pk, client_private_key) = sign::gen_keypair();
+        let client_private_key = client_private_key.as_ref().try_into()?;
 
-        let client_keypair = builder.generate_keypair()?;
-        let client_private_key = client_keypair.private;
-
-        let server_keypair = builder.generate_keypair()?;
-        let server_private_key = server_keypair.private;
+        let (_pk, server_private_key) = sign::gen_keypair();
+        let server_private_key = server_private_key.as_ref().try_into()?;
 
         let client_version = Version::init(client_version.0, client_version.1);
         let server_version = Version::init(server_version.0, server_version.1);
 
-        let hs_client = Handshake::new_client(client_version, psk, client_private_key);
-        let hs_server = Handshake::new_server(server_version, psk, server_private_key);
+        let hs_client = Handshake::new_client(client_version, psk, client_private_key, extensions);
+        let hs_server = Handshake::new_server(server_version, psk, server_private_key, extensions);
 
         Ok((hs_client, hs_server))
     }
@@ -584,7 +738,7 @@ mod tests {
 
     #[test]
     fn version_exchange_success() -> Result<()> {
-        let (hs_client, hs_server) = init_handshakers((1, 0), (1, 0))?;
+        let (hs_client, hs_server) = init_handshakers((1, 0), (1, 0), Extensions::default())?;
 
         let mut buf = [0; 8];
 
@@ -605,7 +759,7 @@ mod tests {
 
     #[test]
     fn version_exchange_failure() -> Result<()> {
-        let (hs_client, hs_server) = init_handshakers((3, 7), (1, 0))?;
+        let (hs_client, hs_server) = init_handshakers((3, 7), (1, 0), Extensions::default())?;
 
         let mut buf = [0; 8];
 
@@ -637,8 +791,17 @@ mod tests {
 
     #[test]
     fn handshake() -> Result<()> {
+        run_handshake(Extensions::default())?;
+        run_handshake(Extensions {
+            identity_payload: true,
+        })?;
+
+        Ok(())
+    }
+
+    fn run_handshake(extensions: Extensions) -> Result<()> {
         // Build the handshake client and server.
-        let (hs_client, hs_server) = init_handshakers((1, 0), (1, 0))?;
+        let (hs_client, hs_server) = init_handshakers((1, 0), (1, 0), extensions)?;
 
         // Define a shared buffer for sending and receiving messages.
         let mut buf = [0; 1024];
@@ -679,7 +842,7 @@ mod tests {
         // Send and receive server ephemeral and static keys.
         let (hs_client, hs_server) = {
             let hs_server = hs_server.send_server_ephemeral_and_static_key(&mut buf)?;
-            let mut client_buf = &mut buf[..EPHEMERAL_AND_STATIC_KEY_BYTES_LEN];
+            let mut client_buf = &mut buf[..extensions.ephemeral_and_static_key_len()];
             let hs_client = hs_client.recv_server_ephemeral_and_static_key(&mut client_buf)?;
             (hs_client, hs_server)
         };
@@ -687,7 +850,7 @@ mod tests {
         // Send and receive client static key.
         let (hs_client, hs_server) = {
             let hs_client = hs_client.send_client_static_key(&mut buf)?;
-            let mut server_buf = &mut buf[..STATIC_KEY_BYTES_LEN];
+            let mut server_buf = &mut buf[..extensions.static_key_len()];
             let hs_server = hs_server.recv_client_static_key(&mut server_buf)?;
             (hs_client, hs_server)
         };
@@ -696,6 +859,24 @@ mod tests {
         let mut hs_client = hs_client.init_client_transport_mode()?;
         let mut hs_server = hs_server.init_server_transport_mode()?;
 
+        let client_public_key: [u8; 32] = public_key(&hs_client.base.private_key).try_into()?;
+        let server_public_key: [u8; 32] = public_key(&hs_server.base.private_key).try_into()?;
+
+        // Ensure each peer recognises the ed25519 public key of the other.
+        assert!(hs_client.is_remote_public_key(&server_public_key));
+        assert!(hs_server.is_remote_public_key(&client_public_key));
+        assert!(!hs_client.is_remote_public_key(&client_public_key));
+
+        // The ed25519 public key of each peer is only received with the
+        // identity payload extension.
+        if extensions.identity_payload {
+            assert_eq!(hs_client.get_remote_public_key(), Some(server_public_key));
+            assert_eq!(hs_server.get_remote_public_key(), Some(client_public_key));
+        } else {
+            assert_eq!(hs_client.get_remote_public_key(), None);
+            assert_eq!(hs_server.get_remote_public_key(), None);
+        }
+
         // Write an encrypted message.
         let msg_text = b"An impeccably polite pangolin";
         let (write_len, encrypted_msg) = hs_client.write_message(msg_text)?;
@@ -707,4 +888,57 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn ephemeral_key_psk_selection() -> Result<()> {
+        let (_pk, server_private_key) = sign::gen_keypair();
+        let server_private_key: [u8; 64] = server_private_key.as_ref().try_into()?;
+
+        // The client uses the second of the server PSKs.
+        let server_keys = [([1; 32], server_private_key), ([2; 32], server_private_key)];
+        let unknown_keys = [([3; 32], server_private_key)];
+
+        for (keys, expected_index) in [(&server_keys[..], Some(1)), (&unknown_keys[..], None)] {
+            let (_pk, client_private_key) = sign::gen_keypair();
+            let hs_client = Handshake::new_client(
+                Version::init(1, 0),
+                [2; 32],
+                client_private_key.as_ref().try_into()?,
+                Extensions::default(),
+            );
+            let hs_server = Handshake::new_server(
+                Version::init(1, 0),
+                keys[0].0,
+                keys[0].1,
+                Extensions::default(),
+            );
+
+            let mut buf = [0; 1024];
+
+            let client_buf = &mut buf[..VERSION_BYTES_LEN];
+            let hs_client = hs_client.send_client_version(client_buf)?;
+            let server_buf = &mut buf[..VERSION_BYTES_LEN];
+            let hs_server = hs_server.recv_client_version(server_buf)?;
+            let server_buf = &mut buf[..VERSION_BYTES_LEN];
+            let hs_server = hs_server.send_server_version(server_buf)?;
+            let client_buf = &mut buf[..VERSION_BYTES_LEN];
+            let hs_client = hs_client.recv_server_version(client_buf)?;
+
+            let hs_client = hs_client.build_client_noise_state_machine()?;
+            hs_client.send_client_ephemeral_key(&mut buf)?;
+
+            let server_buf = &mut buf[..EPHEMERAL_KEY_BYTES_LEN];
+            let res = hs_server.recv_client_ephemeral_key_with_keys(keys, server_buf);
+
+            match expected_index {
+                Some(index) => assert_eq!(res?.0, index),
+                None => {
+                    let err = res.unwrap_err().downcast::<HandshakeError>().unwrap();
+                    assert_eq!(*err, HandshakeError::UnrecognizedPsk);
+                }
+            }
+        }
+
+        Ok(())
+    }
 }
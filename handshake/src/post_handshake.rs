@@ -12,7 +12,63 @@ use std::{
 
 use futures_util::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
-use crate::{constants::PUBLIC_KEY_BYTES_LEN, Handshake, HandshakeComplete, Result};
+use crate::{
+    constants::PUBLIC_KEY_BYTES_LEN, is_static_key_of, Handshake, HandshakeComplete,
+    HandshakeError, Result,
+};
+
+/// Read the length specifier and encrypted payload of a message from the
+/// given asynchronous stream, without decrypting the payload.
+///
+/// A message with a length specifier greater than `max_len` is refused before
+/// any memory is allocated for the payload.
+///
+/// An `Ok` return value containing a `Vec` of zero length and capacity
+/// indicates receipt of an end-of-stream marker.
+pub async fn read_encrypted_message_from_async_stream<T: AsyncRead + Unpin>(
+    stream: &mut T,
+    max_len: u32,
+) -> Result<Vec<u8>> {
+    // Read four bytes describing the length of the incoming message.
+    let mut len_buf = [0; 4];
+    stream.read_exact(&mut len_buf).await?;
+    let msg_len = u32::from_le_bytes(len_buf);
+
+    if msg_len == 0 {
+        // Return a 0 capacity vector to indicate end-of-stream.
+        //
+        // This does not result in an allocation.
+        return Ok(Vec::with_capacity(0));
+    }
+
+    if msg_len > max_len {
+        return Err(HandshakeError::MessageTooLong {
+            len: msg_len,
+            max_len,
+        }
+        .into());
+    }
+
+    // Read the encrypted bytes of the incoming message.
+    let mut recv_buf = vec![0u8; msg_len as usize];
+    stream.read_exact(&mut recv_buf[..]).await?;
+
+    Ok(recv_buf)
+}
+
+/// Write the length specifier and the given encrypted payload of a message to
+/// the given asynchronous stream.
+pub async fn write_encrypted_message_to_async_stream<T: AsyncWrite + Unpin>(
+    stream: &mut T,
+    encrypted_msg: &[u8],
+) -> Result<()> {
+    let encrypted_msg_len = u32::try_from(encrypted_msg.len())?;
+
+    stream.write_all(&encrypted_msg_len.to_le_bytes()).await?;
+    stream.write_all(encrypted_msg).await?;
+
+    Ok(())
+}
 
 impl Handshake<HandshakeComplete> {
     /// Read an encrypted message from the receive buffer, decrypt and write it
@@ -105,24 +161,29 @@ impl Handshake<HandshakeComplete> {
         &mut self,
         stream: &mut T,
     ) -> Result<Vec<u8>> {
-        // Read four bytes describing the length of the incoming message.
-        let mut len_buf = [0; 4];
-        stream.read_exact(&mut len_buf).await?;
-        let msg_len = u32::from_le_bytes(len_buf);
+        self.read_message_from_async_stream_with_limit(stream, u32::MAX)
+            .await
+    }
 
-        if msg_len == 0 {
-            // Return a 0 capacity vector to indicate end-of-stream.
-            //
-            // This does not result in an allocation.
-            return Ok(Vec::with_capacity(0));
-        }
+    /// Read an encrypted message of no more than `max_len` encrypted bytes
+    /// from the given asynchronous stream and return it as a byte vector.
+    ///
+    /// A message with a length specifier greater than `max_len` is refused
+    /// before any memory is allocated for the payload. See
+    /// `read_message_from_async_stream()`.
+    pub async fn read_message_from_async_stream_with_limit<T: AsyncRead + AsyncWrite + Unpin>(
+        &mut self,
+        stream: &mut T,
+        max_len: u32,
+    ) -> Result<Vec<u8>> {
+        let recv_buf = read_encrypted_message_from_async_stream(stream, max_len).await?;
 
-        // Read the encrypted bytes of the incoming message.
-        let mut recv_buf = vec![0u8; msg_len as usize];
-        stream.read_exact(&mut recv_buf[..]).await?;
+        if recv_buf.is_empty() {
+            return Ok(recv_buf);
+        }
 
         // Decrypt and return the entire message.
-        let msg = self.read_message(&recv_buf, msg_len)?;
+        let msg = self.read_message(&recv_buf, recv_buf.len() as u32)?;
 
         Ok(msg)
     }
@@ -222,10 +283,7 @@ impl Handshake<HandshakeComplete> {
         msg: &[u8],
     ) -> Result<usize> {
         let (bytes_written, encrypted_msg) = self.write_message(msg)?;
-        let encrypted_msg_len = &bytes_written.to_le_bytes()[..4];
-
-        stream.write_all(encrypted_msg_len).await?;
-        stream.write_all(&encrypted_msg).await?;
+        write_encrypted_message_to_async_stream(stream, &encrypted_msg).await?;
 
         Ok(bytes_written)
     }
@@ -242,8 +300,27 @@ impl Handshake<HandshakeComplete> {
         Ok(())
     }
 
-    /// Return the public key of the remote peer.
+    /// Return the ed25519 public key of the remote peer.
+    ///
+    /// The public key is only known if the identity payload extension was
+    /// enabled for the handshake; otherwise, use `is_remote_public_key()` to
+    /// check a claimed public key against the static key of the remote peer.
     pub fn get_remote_public_key(&self) -> Option<[u8; PUBLIC_KEY_BYTES_LEN]> {
         self.base.remote_public_key
     }
+
+    /// Return the X25519 static key of the remote peer.
+    pub fn get_remote_static_key(&self) -> Option<[u8; PUBLIC_KEY_BYTES_LEN]> {
+        self.state
+            .0
+            .get_remote_static()
+            .and_then(|key| key.try_into().ok())
+    }
+
+    /// Query whether the given ed25519 public key belongs to the remote peer,
+    /// ie. whether it corresponds to the static key of the remote peer once
+    /// converted to X25519.
+    pub fn is_remote_public_key(&self, public_key: &[u8; PUBLIC_KEY_BYTES_LEN]) -> bool {
+        is_static_key_of(public_key, self.state.0.get_remote_static())
+    }
 }
@@ -5,23 +5,38 @@
 use std::io::{Read, Write};
 
 use crate::{
-    constants::{
-        EPHEMERAL_AND_STATIC_KEY_BYTES_LEN, EPHEMERAL_KEY_BYTES_LEN, STATIC_KEY_BYTES_LEN,
-        VERSION_BYTES_LEN,
-    },
-    Handshake, HandshakeComplete, Result, Version,
+    constants::{EPHEMERAL_KEY_BYTES_LEN, VERSION_BYTES_LEN},
+    Extensions, Handshake, HandshakeComplete, HandshakeError, Result, Version,
 };
 
 /// Initiate the handshake over a synchronous stream and run to completion.
+///
+/// The `private_key` is the ed25519 secret key of the cable keypair belonging
+/// to the local peer.
 pub fn client<T: Read + Write>(
     stream: &mut T,
     version: Version,
     psk: [u8; 32],
-    private_key: Vec<u8>,
+    private_key: [u8; 64],
+) -> Result<Handshake<HandshakeComplete>> {
+    client_with_extensions(stream, version, psk, private_key, Extensions::default())
+}
+
+/// Initiate the handshake over a synchronous stream with the given protocol
+/// extensions enabled and run to completion.
+///
+/// The handshake can only be completed if the server enables the same
+/// extensions.
+pub fn client_with_extensions<T: Read + Write>(
+    stream: &mut T,
+    version: Version,
+    psk: [u8; 32],
+    private_key: [u8; 64],
+    extensions: Extensions,
 ) -> Result<Handshake<HandshakeComplete>> {
     let mut buf = [0; 256];
 
-    let handshake = Handshake::new_client(version, psk, private_key);
+    let handshake = Handshake::new_client(version, psk, private_key, extensions);
 
     // Send version.
     let send_buf = &mut buf[..VERSION_BYTES_LEN];
@@ -42,12 +57,12 @@ pub fn client<T: Read + Write>(
     stream.write_all(send_buf)?;
 
     // Receive ephemeral and static keys.
-    let recv_buf = &mut buf[..EPHEMERAL_AND_STATIC_KEY_BYTES_LEN];
+    let recv_buf = &mut buf[..extensions.ephemeral_and_static_key_len()];
     stream.read_exact(recv_buf)?;
     let handshake = handshake.recv_server_ephemeral_and_static_key(recv_buf)?;
 
     // Send static key.
-    let send_buf = &mut buf[..STATIC_KEY_BYTES_LEN];
+    let send_buf = &mut buf[..extensions.static_key_len()];
     let handshake = handshake.send_client_static_key(send_buf)?;
     stream.write_all(send_buf)?;
 
@@ -57,15 +72,53 @@ pub fn client<T: Read + Write>(
 }
 
 /// Respond to a handshake over a synchronous stream and run to completion.
+///
+/// The `private_key` is the ed25519 secret key of the cable keypair belonging
+/// to the local peer.
 pub fn server<T: Read + Write>(
     stream: &mut T,
     version: Version,
     psk: [u8; 32],
-    private_key: Vec<u8>,
+    private_key: [u8; 64],
 ) -> Result<Handshake<HandshakeComplete>> {
+    server_with_extensions(stream, version, psk, private_key, Extensions::default())
+}
+
+/// Respond to a handshake over a synchronous stream with the given protocol
+/// extensions enabled and run to completion.
+///
+/// The handshake can only be completed if the client enables the same
+/// extensions.
+pub fn server_with_extensions<T: Read + Write>(
+    stream: &mut T,
+    version: Version,
+    psk: [u8; 32],
+    private_key: [u8; 64],
+    extensions: Extensions,
+) -> Result<Handshake<HandshakeComplete>> {
+    let (_index, handshake) = server_with_keys(stream, version, &[(psk, private_key)], extensions)?;
+
+    Ok(handshake)
+}
+
+/// Respond to a handshake over a synchronous stream using whichever of the given
+/// PSK and private key pairs matches the PSK of the client, and run to
+/// completion.
+///
+/// Returns the index of the selected pair along with the completed handshake.
+/// This allows a single server to accept connections for several cabals, each
+/// with its own cabal key (PSK) and keypair. The given protocol extensions
+/// are enabled for the handshake.
+pub fn server_with_keys<T: Read + Write>(
+    stream: &mut T,
+    version: Version,
+    keys: &[([u8; 32], [u8; 64])],
+    extensions: Extensions,
+) -> Result<(usize, Handshake<HandshakeComplete>)> {
     let mut buf = [0; 256];
 
-    let handshake = Handshake::new_server(version, psk, private_key);
+    let (psk, private_key) = keys.first().ok_or(HandshakeError::UnrecognizedPsk)?;
+    let handshake = Handshake::new_server(version, *psk, *private_key, extensions);
 
     // Receive version.
     let recv_buf = &mut buf[..VERSION_BYTES_LEN];
@@ -77,25 +130,23 @@ pub fn server<T: Read + Write>(
     let handshake = handshake.send_server_version(send_buf)?;
     stream.write_all(send_buf)?;
 
-    // Build Noise state machine.
-    let handshake = handshake.build_server_noise_state_machine()?;
-
-    // Receive ephemeral key.
+    // Receive ephemeral key, building a Noise state machine for each of the
+    // given keys until one is found which matches the client PSK.
     let recv_buf = &mut buf[..EPHEMERAL_KEY_BYTES_LEN];
     stream.read_exact(recv_buf)?;
-    let handshake = handshake.recv_client_ephemeral_key(recv_buf)?;
+    let (index, handshake) = handshake.recv_client_ephemeral_key_with_keys(keys, recv_buf)?;
 
     // Send ephemeral and static keys.
-    let send_buf = &mut buf[..EPHEMERAL_AND_STATIC_KEY_BYTES_LEN];
+    let send_buf = &mut buf[..extensions.ephemeral_and_static_key_len()];
     let handshake = handshake.send_server_ephemeral_and_static_key(send_buf)?;
     stream.write_all(send_buf)?;
 
     // Receive static key.
-    let recv_buf = &mut buf[..STATIC_KEY_BYTES_LEN];
+    let recv_buf = &mut buf[..extensions.static_key_len()];
     stream.read_exact(recv_buf)?;
     let handshake = handshake.recv_client_static_key(recv_buf)?;
 
     let handshake = handshake.init_server_transport_mode()?;
 
-    Ok(handshake)
+    Ok((index, handshake))
 }
@@ -2,15 +2,16 @@
 //
 // SPDX-License-Identifier: LGPL-3.0-or-later
 
-//! Test the synchronous handshake and (de)fragmented message exchange.
+//! Test the synchronous handshake with the identity payload extension enabled
+//! and (de)fragmented message exchange.
 
 use std::{
     net::{TcpListener, TcpStream},
     thread,
 };
 
-use cable_handshake::{sync::handshake, Result, Version};
-use snow::Builder as NoiseBuilder;
+use cable_handshake::{sync::handshake, Extensions, Result, Version};
+use sodiumoxide::crypto::sign;
 
 #[test]
 fn sync_handshake_works() -> Result<()> {
@@ -20,12 +21,16 @@ fn sync_handshake_works() -> Result<()> {
 
     let psk: [u8; 32] = [1; 32];
 
+    // Exchange ed25519 public keys during the handshake.
+    let extensions = Extensions {
+        identity_payload: true,
+    };
+
     // Generate keypairs.
-    let builder = NoiseBuilder::new("Noise_XXpsk0_25519_ChaChaPoly_BLAKE2b".parse()?);
-    let client_keypair = builder.generate_keypair()?;
-    let server_keypair = builder.generate_keypair()?;
-    let client_private_key = client_keypair.private;
-    let server_private_key = server_keypair.private;
+    let (client_public_key, client_secret_key) = sign::gen_keypair();
+    let (server_public_key, server_secret_key) = sign::gen_keypair();
+    let client_private_key = client_secret_key.as_ref().try_into()?;
+    let server_private_key = server_secret_key.as_ref().try_into()?;
 
     // Deploy a TCP listener.
     //
@@ -47,8 +52,17 @@ fn sync_handshake_works() -> Result<()> {
             let mut stream = stream.unwrap();
 
             // Perform the handshake.
-            let mut encrypted =
-                handshake::server(&mut stream, server_version, psk, server_private_key).unwrap();
+            let mut encrypted = handshake::server_with_extensions(
+                &mut stream,
+                server_version,
+                psk,
+                server_private_key,
+                extensions,
+            )
+            .unwrap();
+
+            // Ensure the ed25519 public key of the client was received.
+            assert_eq!(encrypted.get_remote_public_key(), Some(client_public_key.0));
 
             // Read a short encrypted message.
             let msg = encrypted.read_message_from_stream(&mut stream).unwrap();
@@ -74,7 +88,16 @@ fn sync_handshake_works() -> Result<()> {
     let mut stream = TcpStream::connect(addr)?;
 
     // Perform the handshake.
-    let mut encrypted = handshake::client(&mut stream, client_version, psk, client_private_key)?;
+    let mut encrypted = handshake::client_with_extensions(
+        &mut stream,
+        client_version,
+        psk,
+        client_private_key,
+        extensions,
+    )?;
+
+    // Ensure the ed25519 public key of the server was received.
+    assert_eq!(encrypted.get_remote_public_key(), Some(server_public_key.0));
 
     // Write a short encrypted message.
     encrypted.write_message_to_stream(&mut stream, msg_1)?;